@@ -647,6 +647,30 @@ impl BuildPlan {
         locked: bool,
         offline: bool,
         ipfs_node: IPFSNode,
+    ) -> Result<Self> {
+        Self::from_lock_and_manifests_inner(lock_path, manifests, locked, offline, ipfs_node, true)
+    }
+
+    /// Create a new build plan in the same manner as [BuildPlan::from_lock_and_manifests], but
+    /// without ever writing the lock file.
+    ///
+    /// An outdated or missing lock is only resolved in memory, leaving the lock on disk as it was.
+    pub fn from_lock_and_manifests_without_writing(
+        lock_path: &Path,
+        manifests: &MemberManifestFiles,
+        offline: bool,
+        ipfs_node: IPFSNode,
+    ) -> Result<Self> {
+        Self::from_lock_and_manifests_inner(lock_path, manifests, false, offline, ipfs_node, false)
+    }
+
+    fn from_lock_and_manifests_inner(
+        lock_path: &Path,
+        manifests: &MemberManifestFiles,
+        locked: bool,
+        offline: bool,
+        ipfs_node: IPFSNode,
+        write_lock: bool,
     ) -> Result<Self> {
         // Check toolchain version
         validate_version(manifests)?;
@@ -720,6 +744,9 @@ impl BuildPlan {
                     cause,
                 );
             }
+            if !write_lock {
+                return Ok(plan);
+            }
             info!("  Creating a new `Forc.lock` file. (Cause: {})", cause);
             let member_names = manifests
                 .iter()
@@ -2594,6 +2621,29 @@ pub fn check(
     include_tests: bool,
     engines: &Engines,
     retrigger_compilation: Option<Arc<AtomicBool>>,
+) -> anyhow::Result<Vec<(Option<Programs>, Handler)>> {
+    let profile = BuildProfile {
+        terse: terse_mode,
+        ..BuildProfile::debug()
+    };
+    check_with_profile(
+        plan,
+        build_target,
+        &profile,
+        include_tests,
+        engines,
+        retrigger_compilation,
+    )
+}
+
+/// Like [check], but compiles every package with the given build profile rather than `debug`.
+pub fn check_with_profile(
+    plan: &BuildPlan,
+    build_target: BuildTarget,
+    profile: &BuildProfile,
+    include_tests: bool,
+    engines: &Engines,
+    retrigger_compilation: Option<Arc<AtomicBool>>,
 ) -> anyhow::Result<Vec<(Option<Programs>, Handler)>> {
     let mut lib_namespace_map = Default::default();
     let mut source_map = SourceMap::new();
@@ -2627,20 +2677,15 @@ pub fn check(
         )
         .expect("failed to create dependency namespace");
 
-        let profile = BuildProfile {
-            terse: terse_mode,
-            ..BuildProfile::debug()
-        };
-
         let build_config = sway_build_config(
             manifest.dir(),
             &manifest.entry_path(),
             build_target,
-            &profile,
+            profile,
         )?
         .with_include_tests(include_tests);
 
-        let input = manifest.entry_string()?;
+        let input = engines.se().read_source(&manifest.entry_path())?;
         let handler = Handler::default();
         let programs_res = sway_core::compile_to_ast(
             &handler,
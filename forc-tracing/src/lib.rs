@@ -6,7 +6,9 @@ use std::{env, io};
 use tracing::{Level, Metadata};
 use tracing_subscriber::{
     filter::{EnvFilter, LevelFilter},
-    fmt::MakeWriter,
+    fmt::{self, MakeWriter},
+    prelude::*,
+    reload, Registry,
 };
 
 /// Prints a warning message to stdout with the yellow prefix "warning: ".
@@ -98,10 +100,16 @@ pub struct TracingSubscriberOptions {
     pub writer_mode: Option<TracingWriterMode>,
 }
 
+/// A handle to the filter of the subscriber installed by [init_tracing_subscriber], which can be used
+/// to change the level of the subscriber at runtime.
+pub type TracingFilterHandle = reload::Handle<EnvFilter, Registry>;
+
 /// A subscriber built from default `tracing_subscriber::fmt::SubscriberBuilder` such that it would match directly using `println!` throughout the repo.
 ///
 /// `RUST_LOG` environment variable can be used to set different minimum level for the subscriber, default is `INFO`.
-pub fn init_tracing_subscriber(options: TracingSubscriberOptions) {
+///
+/// Returns a handle to the filter of the subscriber, so that its level can be changed without installing a new one.
+pub fn init_tracing_subscriber(options: TracingSubscriberOptions) -> TracingFilterHandle {
     let env_filter = match env::var_os(LOG_FILTER) {
         Some(_) => EnvFilter::try_from_default_env().expect("Invalid `RUST_LOG` provided"),
         None => EnvFilter::new("info"),
@@ -125,8 +133,14 @@ pub fn init_tracing_subscriber(options: TracingSubscriberOptions) {
             })
         });
 
-    let builder = tracing_subscriber::fmt::Subscriber::builder()
-        .with_env_filter(env_filter)
+    // If log level, verbosity, or silent mode is set, it overrides the RUST_LOG setting
+    let filter = match level_filter {
+        Some(level_filter) => EnvFilter::default().add_directive(level_filter.into()),
+        None => env_filter,
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let fmt_layer = fmt::layer()
         .with_ansi(true)
         .with_level(false)
         .with_file(false)
@@ -137,10 +151,9 @@ pub fn init_tracing_subscriber(options: TracingSubscriberOptions) {
             writer_mode: options.writer_mode.unwrap_or(TracingWriterMode::Stdio),
         });
 
-    // If log level, verbosity, or silent mode is set, it overrides the RUST_LOG setting
-    if let Some(level_filter) = level_filter {
-        builder.with_max_level(level_filter).init();
-    } else {
-        builder.init();
-    }
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .init();
+    handle
 }
@@ -85,6 +85,11 @@ where
         None
     }
 
+    /// Returns the number of values in the slab.
+    pub fn count(&self) -> usize {
+        self.inner.read().unwrap().len()
+    }
+
     pub fn get(&self, index: usize) -> Arc<T> {
         let inner = self.inner.read().unwrap();
         inner[&index].clone()
@@ -229,6 +229,36 @@ decl_engine_clear_module!(
     type_alias_slab, ty::TyTypeAliasDecl;
 );
 
+macro_rules! decl_engine_size {
+    ($($slab:ident, $decl:ty);* $(;)?) => {
+        impl DeclEngine {
+            /// Returns the number of declarations in the decl engine.
+            pub fn decl_count(&self) -> usize {
+                0 $(+ self.$slab.count())*
+            }
+
+            /// Returns an estimate of the memory held by the declarations, which only counts their inline size.
+            pub fn approximate_size(&self) -> usize {
+                0 $(+ self.$slab.count() * std::mem::size_of::<$decl>())*
+            }
+        }
+    };
+}
+
+decl_engine_size!(
+    function_slab, ty::TyFunctionDecl;
+    trait_slab, ty::TyTraitDecl;
+    trait_fn_slab, ty::TyTraitFn;
+    trait_type_slab, ty::TyTraitType;
+    impl_trait_slab, ty::TyImplTrait;
+    struct_slab, ty::TyStructDecl;
+    storage_slab, ty::TyStorageDecl;
+    abi_slab, ty::TyAbiDecl;
+    constant_slab, ty::TyConstantDecl;
+    enum_slab, ty::TyEnumDecl;
+    type_alias_slab, ty::TyTypeAliasDecl;
+);
+
 impl DeclEngine {
     /// Given a [DeclRef] `index`, finds all the parents of `index` and all the
     /// recursive parents of those parents, and so on. Does not perform
@@ -69,6 +69,9 @@ pub mod fuel_prelude {
 pub use build_config::ExperimentalFlags;
 pub use engine_threading::Engines;
 
+/// The version of the compiler.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// Given an input `Arc<str>` and an optional [BuildConfig], parse the input into a [lexed::LexedProgram] and [parsed::ParseProgram].
 ///
 /// # Example
@@ -251,8 +254,8 @@ fn parse_submodules(
         // Read the source code from the dependency.
         // If we cannot, record as an error, but continue with other files.
         let submod_path = Arc::new(module_path(module_dir, module_name, submod));
-        let submod_str: Arc<str> = match std::fs::read_to_string(&*submod_path) {
-            Ok(s) => Arc::from(s),
+        let submod_str: Arc<str> = match engines.se().read_source(&submod_path) {
+            Ok(s) => s,
             Err(e) => {
                 handler.emit_err(CompileError::FileCouldNotBeRead {
                     span: submod.name.span(),
@@ -387,9 +390,14 @@ fn parse_module_tree(
     };
 
     // Let's prime the cache with the module dependency and hash data.
-    let modified_time = std::fs::metadata(path.as_path())
-        .ok()
-        .and_then(|m| m.modified().ok());
+    // Overridden sources don't match the file on disk, so they don't get a modification time.
+    let modified_time = if engines.se().has_source_override(path.as_path()) {
+        None
+    } else {
+        std::fs::metadata(path.as_path())
+            .ok()
+            .and_then(|m| m.modified().ok())
+    };
     let dependencies = submodules.into_iter().map(|s| s.path).collect::<Vec<_>>();
     let parsed_module_tree = ParsedModuleTree {
         tree_type: kind,
@@ -425,8 +433,10 @@ fn is_parse_module_cache_up_to_date(
             // Let's check if we can re-use the dependency information
             // we got from the cache, which is only true if the file hasn't been
             // modified since or if its hash is the same.
-            let cache_up_to_date = entry.modified_time == modified_time || {
-                let src = std::fs::read_to_string(path.as_path()).unwrap();
+            // The modification time says nothing about overridden sources, so always compare their hash.
+            let has_override = engines.se().has_source_override(path.as_path());
+            let cache_up_to_date = (!has_override && entry.modified_time == modified_time) || {
+                let src = engines.se().read_source(path.as_path()).unwrap();
 
                 let mut hasher = DefaultHasher::new();
                 src.hash(&mut hasher);
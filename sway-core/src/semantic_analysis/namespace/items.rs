@@ -303,6 +303,18 @@ impl Items {
             .collect::<Vec<_>>()
     }
 
+    pub fn get_methods_for_decl(
+        &self,
+        engines: &Engines,
+        ty_decl: &TyDecl,
+    ) -> Vec<DeclRefFunction> {
+        let handler = Handler::default();
+        ty_decl
+            .return_type(&handler, engines)
+            .map(|type_id| self.get_methods_for_type(engines, type_id))
+            .unwrap_or_default()
+    }
+
     pub(crate) fn has_storage_declared(&self) -> bool {
         self.declared_storage.is_some()
     }
@@ -68,6 +68,16 @@ impl TypeEngine {
         }
     }
 
+    /// Returns the number of types in the type engine.
+    pub fn type_count(&self) -> usize {
+        self.slab.count()
+    }
+
+    /// Returns an estimate of the memory held by the types, which only counts their inline size.
+    pub fn approximate_size(&self) -> usize {
+        self.slab.count() * std::mem::size_of::<TypeSourceInfo>()
+    }
+
     /// Removes all data associated with `module_id` from the type engine.
     pub fn clear_module(&mut self, module_id: &ModuleId) {
         self.slab.retain(|_, tsi| match tsi.source_id {
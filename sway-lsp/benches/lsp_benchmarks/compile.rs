@@ -1,25 +1,28 @@
 use criterion::{black_box, criterion_group, Criterion};
 use lsp_types::Url;
 use sway_core::Engines;
-use sway_lsp::core::session;
+use sway_lsp::{config::BuildConfig, core::session, utils::position_encoding::PositionEncoding};
 
 const NUM_DID_CHANGE_ITERATIONS: usize = 10;
 
 fn benchmarks(c: &mut Criterion) {
     // Load the test project
     let uri = Url::from_file_path(super::benchmark_dir().join("src/main.sw")).unwrap();
+    let build_config = BuildConfig::default();
     c.bench_function("compile", |b| {
         b.iter(|| {
             let engines = Engines::default();
-            let _ = black_box(session::compile(&uri, &engines, None).unwrap());
+            let _ = black_box(session::compile(&uri, &engines, &build_config, None).unwrap());
         })
     });
 
     c.bench_function("traverse", |b| {
         let engines = Engines::default();
-        let results = black_box(session::compile(&uri, &engines, None).unwrap());
+        let results = black_box(session::compile(&uri, &engines, &build_config, None).unwrap());
         b.iter(|| {
-            let _ = black_box(session::traverse(results.clone(), &engines).unwrap());
+            let _ = black_box(
+                session::traverse(results.clone(), &engines, PositionEncoding::default()).unwrap(),
+            );
         })
     });
 
@@ -27,7 +30,7 @@ fn benchmarks(c: &mut Criterion) {
         let engines = Engines::default();
         b.iter(|| {
             for _ in 0..NUM_DID_CHANGE_ITERATIONS {
-                let _ = black_box(session::compile(&uri, &engines, None).unwrap());
+                let _ = black_box(session::compile(&uri, &engines, &build_config, None).unwrap());
             }
         })
     });
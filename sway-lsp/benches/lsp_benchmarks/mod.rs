@@ -4,7 +4,10 @@ pub mod token_map;
 
 use lsp_types::Url;
 use std::{path::PathBuf, sync::Arc};
-use sway_lsp::core::session::{self, ParseResult, Session};
+use sway_lsp::{
+    config::BuildConfig,
+    core::session::{self, ParseResult, Session},
+};
 
 pub async fn compile_test_project() -> (Url, Arc<Session>) {
     let session = Session::new();
@@ -13,7 +16,15 @@ pub async fn compile_test_project() -> (Url, Arc<Session>) {
     session.handle_open_file(&uri).await;
     // Compile the project and write the parse result to the session
     let mut parse_result = ParseResult::default();
-    session::parse_project(&uri, &session.engines.read(), None, &mut parse_result).unwrap();
+    session::parse_project(
+        &uri,
+        &session.engines.read(),
+        session.position_encoding(),
+        &BuildConfig::default(),
+        None,
+        &mut parse_result,
+    )
+    .unwrap();
     session.write_parse_result(&mut parse_result);
     (uri, Arc::new(session))
 }
@@ -1,7 +1,7 @@
 use criterion::{black_box, criterion_group, Criterion};
 use lsp_types::{
-    CompletionResponse, DocumentSymbolResponse, Position, Range, TextDocumentContentChangeEvent,
-    TextDocumentIdentifier,
+    CompletionResponse, DocumentSymbolResponse, FormattingOptions, Position, Range,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier,
 };
 use sway_lsp::{capabilities, lsp_ext::OnEnterParams, utils::keyword_docs::KeywordDocs};
 use tokio::runtime::Runtime;
@@ -28,10 +28,11 @@ fn benchmarks(c: &mut Criterion) {
     });
 
     c.bench_function("completion", |b| {
-        let position = Position::new(1698, 28);
+        let receiver = Position::new(1698, 26);
+        let range = Range::new(Position::new(1698, 28), Position::new(1698, 28));
         b.iter(|| {
             session
-                .completion_items(&uri, position, ".")
+                .member_completion_items(&uri, receiver, range, false)
                 .map(CompletionResponse::Array)
         })
     });
@@ -99,7 +100,12 @@ fn benchmarks(c: &mut Criterion) {
         b.iter(|| capabilities::on_enter::on_enter(&config.on_enter, &session, &uri, &params))
     });
 
-    c.bench_function("format", |b| b.iter(|| session.format_text(&uri)));
+    let options = FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+        ..Default::default()
+    };
+    c.bench_function("format", |b| b.iter(|| session.format_text(&uri, &options)));
 }
 
 criterion_group! {
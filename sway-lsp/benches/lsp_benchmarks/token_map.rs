@@ -1,5 +1,8 @@
-use criterion::{black_box, criterion_group, Criterion};
+use criterion::{black_box, criterion_group, BatchSize, Criterion};
 use lsp_types::Position;
+use parking_lot::RwLock;
+use std::sync::Arc;
+use sway_lsp::core::token_map::TokenMap;
 use tokio::runtime::Runtime;
 
 fn benchmarks(c: &mut Criterion) {
@@ -25,9 +28,13 @@ fn benchmarks(c: &mut Criterion) {
 
     c.bench_function("tokens_at_position", |b| {
         b.iter(|| {
-            session
-                .token_map()
-                .tokens_at_position(engines.se(), &uri, position, None)
+            session.token_map().tokens_at_position(
+                engines.se(),
+                &uri,
+                position,
+                None,
+                session.position_encoding(),
+            )
         })
     });
 
@@ -37,11 +44,45 @@ fn benchmarks(c: &mut Criterion) {
 
     c.bench_function("parent_decl_at_position", |b| {
         b.iter(|| {
-            session
-                .token_map()
-                .parent_decl_at_position(engines.se(), &uri, position)
+            session.token_map().parent_decl_at_position(
+                engines.se(),
+                &uri,
+                position,
+                session.position_encoding(),
+            )
         })
     });
+
+    // Compares updating the token map after a compilation in place with clearing and rebuilding it.
+    let token_map = TokenMap::new();
+    token_map.update_from(&session.token_map());
+
+    c.bench_function("token_map_update_from", |b| {
+        b.iter(|| token_map.update_from(black_box(&session.token_map())))
+    });
+
+    c.bench_function("token_map_rebuild", |b| {
+        b.iter(|| {
+            token_map.clear();
+            session.token_map().iter().for_each(|(ident, token)| {
+                token_map.insert(ident, token);
+            });
+        })
+    });
+
+    // With `tokenMapWriteStrategy: "swap"`, the map is built off to the side and readers only wait for the swap.
+    let swapped = RwLock::new(Arc::new(TokenMap::new()));
+    c.bench_function("token_map_swap", |b| {
+        b.iter_batched(
+            || {
+                let new = TokenMap::new();
+                new.update_from(&session.token_map());
+                Arc::new(new)
+            },
+            |new| std::mem::replace(&mut *swapped.write(), new),
+            BatchSize::LargeInput,
+        )
+    });
 }
 
 criterion_group! {
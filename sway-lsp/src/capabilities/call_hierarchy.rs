@@ -0,0 +1,192 @@
+use crate::core::{
+    session::Session,
+    token::{get_range_from_span, Token, TokenIdent, TypedAstToken},
+    token_map::TokenMapExt,
+};
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Position, Range,
+    SymbolKind, Url,
+};
+use std::{collections::HashMap, sync::Arc};
+use sway_core::language::ty;
+
+/// Returns the [CallHierarchyItem] of the function declared or called at the given position.
+pub fn prepare_call_hierarchy(
+    session: Arc<Session>,
+    url: &Url,
+    position: Position,
+) -> Option<Vec<CallHierarchyItem>> {
+    let (_, token) = session.token_at_position(url, position)?;
+    let (decl_ident, _, decl) = function_declaration(&session, &token)?;
+    let item = call_hierarchy_item(&session, &decl_ident, &decl)?;
+    Some(vec![item])
+}
+
+/// Returns the functions that call the function of the given [CallHierarchyItem],
+/// along with the ranges of the call sites.
+pub fn incoming_calls(
+    session: Arc<Session>,
+    url: &Url,
+    item: &CallHierarchyItem,
+) -> Option<Vec<CallHierarchyIncomingCall>> {
+    let (_, token) = session.token_at_position(url, item.selection_range.start)?;
+    let (decl_ident, decl_token, _) = function_declaration(&session, &token)?;
+    let engines = session.engines.read();
+    let encoding = session.position_encoding();
+
+    let mut callers: HashMap<TokenIdent, (ty::TyFunctionDecl, Vec<Range>)> = HashMap::new();
+    session
+        .token_map()
+        .iter()
+        .all_references_of_token(&decl_token, &engines, encoding)
+        .filter(|(ident, _)| *ident != decl_ident)
+        .for_each(|(ident, _)| {
+            let Some(ref_url) = ident
+                .path
+                .as_ref()
+                .and_then(|path| Url::from_file_path(path).ok())
+            else {
+                return;
+            };
+            let caller = session
+                .token_map()
+                .tokens_at_position(
+                    engines.se(),
+                    &ref_url,
+                    ident.range.start,
+                    Some(true),
+                    encoding,
+                )
+                .into_iter()
+                .find_map(|(caller_ident, caller_token)| match caller_token.typed {
+                    Some(TypedAstToken::TypedFunctionDeclaration(caller_decl)) => {
+                        Some((caller_ident, caller_decl))
+                    }
+                    _ => None,
+                });
+            if let Some((caller_ident, caller_decl)) = caller {
+                callers
+                    .entry(caller_ident)
+                    .or_insert_with(|| (caller_decl, vec![]))
+                    .1
+                    .push(ident.range);
+            }
+        });
+    drop(engines);
+
+    let mut calls = callers
+        .into_iter()
+        .filter_map(|(caller_ident, (caller_decl, mut from_ranges))| {
+            from_ranges.sort_by_key(|range| range.start);
+            Some(CallHierarchyIncomingCall {
+                from: call_hierarchy_item(&session, &caller_ident, &caller_decl)?,
+                from_ranges,
+            })
+        })
+        .filter(|call| !session.is_excluded_from_workspace(&call.from.uri))
+        .collect::<Vec<_>>();
+    calls.sort_by(|a, b| sort_key(&a.from).cmp(&sort_key(&b.from)));
+    Some(calls)
+}
+
+/// Returns the functions that are called from the body of the function of the given
+/// [CallHierarchyItem], along with the ranges of the call sites.
+pub fn outgoing_calls(
+    session: Arc<Session>,
+    url: &Url,
+    item: &CallHierarchyItem,
+) -> Option<Vec<CallHierarchyOutgoingCall>> {
+    let (_, token) = session.token_at_position(url, item.selection_range.start)?;
+    let (_, _, decl) = function_declaration(&session, &token)?;
+    let body_range = get_range_from_span(&decl.span, session.position_encoding());
+
+    let mut callees: HashMap<TokenIdent, (ty::TyFunctionDecl, Vec<Range>)> = HashMap::new();
+    session
+        .token_map()
+        .tokens_for_file(url)
+        .filter(|(ident, token)| {
+            ident.range.start >= body_range.start
+                && ident.range.end <= body_range.end
+                && matches!(
+                    &token.typed,
+                    Some(TypedAstToken::TypedExpression(ty::TyExpression {
+                        expression: ty::TyExpressionVariant::FunctionApplication { .. },
+                        ..
+                    }))
+                )
+        })
+        .for_each(|(ident, token)| {
+            if let Some((callee_ident, _, callee_decl)) = function_declaration(&session, &token) {
+                callees
+                    .entry(callee_ident)
+                    .or_insert_with(|| (callee_decl, vec![]))
+                    .1
+                    .push(ident.range);
+            }
+        });
+
+    let mut calls = callees
+        .into_iter()
+        .filter_map(|(callee_ident, (callee_decl, mut from_ranges))| {
+            from_ranges.sort_by_key(|range| range.start);
+            Some(CallHierarchyOutgoingCall {
+                to: call_hierarchy_item(&session, &callee_ident, &callee_decl)?,
+                from_ranges,
+            })
+        })
+        .filter(|call| !session.is_excluded_from_workspace(&call.to.uri))
+        .collect::<Vec<_>>();
+    calls.sort_by(|a, b| sort_key(&a.to).cmp(&sort_key(&b.to)));
+    Some(calls)
+}
+
+/// Returns the [TokenIdent], [Token] and [ty::TyFunctionDecl] of the function declaration
+/// that the given token declares or refers to.
+fn function_declaration(
+    session: &Session,
+    token: &Token,
+) -> Option<(TokenIdent, Token, ty::TyFunctionDecl)> {
+    let decl_ident =
+        token.declared_token_ident(&session.engines.read(), session.position_encoding())?;
+    let decl_token = session
+        .token_map()
+        .try_get(&decl_ident)
+        .try_unwrap()
+        .map(|item| item.value().clone())?;
+    match &decl_token.typed {
+        Some(TypedAstToken::TypedFunctionDeclaration(decl)) => {
+            let decl = decl.clone();
+            Some((decl_ident, decl_token, decl))
+        }
+        _ => None,
+    }
+}
+
+/// Builds a [CallHierarchyItem] for the function declaration.
+///
+/// Returns `None` if the function is declared outside of the user's workspace, e.g. in a dependency.
+fn call_hierarchy_item(
+    session: &Session,
+    decl_ident: &TokenIdent,
+    decl: &ty::TyFunctionDecl,
+) -> Option<CallHierarchyItem> {
+    let url = Url::from_file_path(decl_ident.path.as_ref()?).ok()?;
+    if !session.sync.is_path_in_temp_workspace(&url) {
+        return None;
+    }
+    let uri = session.sync.temp_to_workspace_url(&url).ok()?;
+    Some(CallHierarchyItem {
+        name: decl.name.to_string(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: None,
+        uri,
+        range: get_range_from_span(&decl.span, session.position_encoding()),
+        selection_range: decl_ident.range,
+        data: None,
+    })
+}
+
+fn sort_key(item: &CallHierarchyItem) -> (String, Position) {
+    (item.uri.to_string(), item.selection_range.start)
+}
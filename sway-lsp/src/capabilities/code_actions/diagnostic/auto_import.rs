@@ -3,11 +3,15 @@ use crate::{
         code_actions::{CodeActionContext, CODE_ACTION_IMPORT_TITLE},
         diagnostic::DiagnosticData,
     },
-    core::token::{get_range_from_span, AstToken, SymbolKind, TypedAstToken},
+    core::{
+        token::{get_range_from_span, AstToken, SymbolKind, Token, TypedAstToken},
+        token_map::TokenMap,
+    },
+    utils::position_encoding::PositionEncoding,
 };
 use lsp_types::{
     CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, Position, Range, TextEdit,
-    WorkspaceEdit,
+    Url, WorkspaceEdit,
 };
 use serde_json::Value;
 use std::{
@@ -15,12 +19,17 @@ use std::{
     collections::{BTreeSet, HashMap},
     iter,
 };
-use sway_core::language::{
-    parsed::ImportType,
-    ty::{
-        TyConstantDecl, TyDecl, TyFunctionDecl, TyIncludeStatement, TyTypeAliasDecl, TyUseStatement,
+use sway_core::{
+    language::{
+        parsed::ImportType,
+        ty::{
+            TyConstantDecl, TyDecl, TyFunctionDecl, TyIncludeStatement, TyTypeAliasDecl,
+            TyUseStatement,
+        },
+        CallPath,
     },
-    CallPath,
+    namespace::Namespace,
+    Engines,
 };
 use sway_types::{Ident, Spanned};
 
@@ -36,33 +45,12 @@ pub(crate) fn import_code_action(
     let call_paths = get_call_paths_for_name(ctx, &symbol_name)?;
 
     // Collect the tokens we need to determine where to insert the import statement.
-    let mut use_statements = Vec::<TyUseStatement>::new();
-    let mut include_statements = Vec::<TyIncludeStatement>::new();
-    let mut program_type_keyword = None;
-
-    ctx.tokens
-        .tokens_for_file(ctx.temp_uri)
-        .for_each(|(_, token)| {
-            if let Some(TypedAstToken::TypedUseStatement(use_stmt)) = token.typed {
-                use_statements.push(use_stmt);
-            } else if let Some(TypedAstToken::TypedIncludeStatement(include_stmt)) = token.typed {
-                include_statements.push(include_stmt);
-            } else if token.kind == SymbolKind::ProgramTypeKeyword {
-                if let AstToken::Keyword(ident) = token.parsed {
-                    program_type_keyword = Some(ident);
-                }
-            }
-        });
+    let imports = FileImports::of_file(ctx.tokens, ctx.temp_uri, ctx.encoding);
 
     // Create a list of code actions, one for each potential call path.
     let actions = call_paths
         .map(|call_path| {
-            let text_edit = get_text_edit(
-                &call_path,
-                &use_statements,
-                &include_statements,
-                &program_type_keyword,
-            );
+            let text_edit = imports.text_edit(&call_path);
             let changes = HashMap::from([(ctx.uri.clone(), vec![text_edit])]);
 
             CodeActionOrCommand::CodeAction(LspCodeAction {
@@ -95,50 +83,124 @@ pub(crate) fn get_call_paths_for_name<'s>(
     let mut call_paths = ctx
         .tokens
         .tokens_for_name(symbol_name)
-        .filter_map(move |(_, token)| {
-            // If the typed token is a declaration, then we can import it.
-            match token.typed.as_ref() {
-                Some(TypedAstToken::TypedDeclaration(ty_decl)) => {
-                    return match ty_decl {
-                        TyDecl::StructDecl(decl) => {
-                            let struct_decl = ctx.engines.de().get_struct(&decl.decl_id);
-                            let call_path = struct_decl.call_path.to_import_path(&namespace);
-                            Some(call_path)
-                        }
-                        TyDecl::EnumDecl(decl) => {
-                            let enum_decl = ctx.engines.de().get_enum(&decl.decl_id);
-                            let call_path = enum_decl.call_path.to_import_path(&namespace);
-                            Some(call_path)
-                        }
-                        TyDecl::TraitDecl(decl) => {
-                            let trait_decl = ctx.engines.de().get_trait(&decl.decl_id);
-                            let call_path = trait_decl.call_path.to_import_path(&namespace);
-                            Some(call_path)
-                        }
-                        _ => None,
-                    };
-                }
-                Some(TypedAstToken::TypedFunctionDeclaration(TyFunctionDecl {
-                    call_path, ..
-                }))
-                | Some(TypedAstToken::TypedConstantDeclaration(TyConstantDecl {
-                    call_path, ..
-                }))
-                | Some(TypedAstToken::TypedTypeAliasDeclaration(TyTypeAliasDecl {
-                    call_path,
-                    ..
-                })) => {
-                    let call_path = call_path.to_import_path(&namespace);
-                    Some(call_path)
-                }
-                _ => None,
-            }
-        })
+        .filter_map(move |(_, token)| import_path(ctx.engines, &namespace, &token))
         .collect::<Vec<_>>();
     call_paths.sort();
     Some(call_paths.into_iter())
 }
 
+/// Returns the [CallPath] that the declaration of the token can be imported by, if it's a declaration.
+pub(crate) fn import_path(
+    engines: &Engines,
+    namespace: &Namespace,
+    token: &Token,
+) -> Option<CallPath> {
+    // If the typed token is a declaration, then we can import it.
+    match token.typed.as_ref() {
+        Some(TypedAstToken::TypedDeclaration(ty_decl)) => match ty_decl {
+            TyDecl::StructDecl(decl) => {
+                let struct_decl = engines.de().get_struct(&decl.decl_id);
+                Some(struct_decl.call_path.to_import_path(namespace))
+            }
+            TyDecl::EnumDecl(decl) => {
+                let enum_decl = engines.de().get_enum(&decl.decl_id);
+                Some(enum_decl.call_path.to_import_path(namespace))
+            }
+            TyDecl::TraitDecl(decl) => {
+                let trait_decl = engines.de().get_trait(&decl.decl_id);
+                Some(trait_decl.call_path.to_import_path(namespace))
+            }
+            _ => None,
+        },
+        Some(TypedAstToken::TypedFunctionDeclaration(TyFunctionDecl { call_path, .. }))
+        | Some(TypedAstToken::TypedConstantDeclaration(TyConstantDecl { call_path, .. }))
+        | Some(TypedAstToken::TypedTypeAliasDeclaration(TyTypeAliasDecl { call_path, .. })) => {
+            Some(call_path.to_import_path(namespace))
+        }
+        _ => None,
+    }
+}
+
+/// The statements of a file that determine where an import statement is inserted,
+/// and which names are already imported.
+pub(crate) struct FileImports {
+    use_statements: Vec<TyUseStatement>,
+    include_statements: Vec<TyIncludeStatement>,
+    program_type_keyword: Option<Ident>,
+    encoding: PositionEncoding,
+}
+
+impl FileImports {
+    /// Collects the statements of the file at `uri` from its tokens.
+    pub(crate) fn of_file(tokens: &TokenMap, uri: &Url, encoding: PositionEncoding) -> Self {
+        let mut imports = FileImports {
+            use_statements: vec![],
+            include_statements: vec![],
+            program_type_keyword: None,
+            encoding,
+        };
+        tokens.tokens_for_file(uri).for_each(|(_, token)| {
+            if let Some(TypedAstToken::TypedUseStatement(use_stmt)) = token.typed {
+                imports.use_statements.push(use_stmt);
+            } else if let Some(TypedAstToken::TypedIncludeStatement(include_stmt)) = token.typed {
+                imports.include_statements.push(include_stmt);
+            } else if token.kind == SymbolKind::ProgramTypeKeyword {
+                if let AstToken::Keyword(ident) = token.parsed {
+                    imports.program_type_keyword = Some(ident);
+                }
+            }
+        });
+        imports
+    }
+
+    /// Returns a [TextEdit] to insert an import statement for the [CallPath], as described in [get_text_edit].
+    pub(crate) fn text_edit(&self, call_path: &CallPath) -> TextEdit {
+        get_text_edit(
+            call_path,
+            &self.use_statements,
+            &self.include_statements,
+            &self.program_type_keyword,
+            self.encoding,
+        )
+    }
+
+    /// Returns whether the [CallPath] is already imported by its name, or by a glob import of its module.
+    pub(crate) fn imports(&self, call_path: &CallPath) -> bool {
+        self.use_statements.iter().any(|stmt| {
+            is_same_path(&stmt.call_path, &call_path.prefixes)
+                && match &stmt.import_type {
+                    ImportType::Star => true,
+                    ImportType::Item(name) => {
+                        stmt.alias.is_none() && name.as_str() == call_path.suffix.as_str()
+                    }
+                    ImportType::SelfImport(_) => false,
+                }
+        })
+    }
+
+    /// Returns whether another item is imported by the name of the [CallPath], which importing it would conflict with.
+    pub(crate) fn conflicts_with(&self, call_path: &CallPath) -> bool {
+        self.use_statements.iter().any(|stmt| {
+            let name = match (&stmt.alias, &stmt.import_type) {
+                (Some(alias), _) => alias,
+                (None, ImportType::Item(name)) => name,
+                _ => return false,
+            };
+            let is_same_item =
+                stmt.alias.is_none() && is_same_path(&stmt.call_path, &call_path.prefixes);
+            name.as_str() == call_path.suffix.as_str() && !is_same_item
+        })
+    }
+}
+
+fn is_same_path(path: &[Ident], other: &[Ident]) -> bool {
+    path.len() == other.len()
+        && path
+            .iter()
+            .zip(other)
+            .all(|(ident, other)| ident.as_str() == other.as_str())
+}
+
 /// Returns a [TextEdit] to insert an import statement for the given [CallPath] in the appropriate location in the file.
 ///
 /// To determine where to insert the import statement in the file, we try these options and do
@@ -154,13 +216,15 @@ fn get_text_edit(
     use_statements: &[TyUseStatement],
     include_statements: &[TyIncludeStatement],
     program_type_keyword: &Option<Ident>,
+    encoding: PositionEncoding,
 ) -> TextEdit {
-    get_text_edit_for_group(call_path, use_statements)
-        .or_else(|| get_text_edit_in_use_block(call_path, use_statements))
+    get_text_edit_for_group(call_path, use_statements, encoding)
+        .or_else(|| get_text_edit_in_use_block(call_path, use_statements, encoding))
         .unwrap_or(get_text_edit_fallback(
             call_path,
             include_statements,
             program_type_keyword,
+            encoding,
         ))
 }
 
@@ -169,6 +233,7 @@ fn get_text_edit(
 fn get_text_edit_for_group(
     call_path: &CallPath,
     use_statements: &[TyUseStatement],
+    encoding: PositionEncoding,
 ) -> Option<TextEdit> {
     let group_statements = use_statements.iter().filter(|use_stmt| {
         call_path
@@ -216,7 +281,7 @@ fn get_text_edit_for_group(
             .join("::");
 
         TextEdit {
-            range: get_range_from_span(&span.clone()),
+            range: get_range_from_span(&span.clone(), encoding),
             new_text: format!("use {}::{{{}}};", prefix_string, suffix_string),
         }
     })
@@ -227,6 +292,7 @@ fn get_text_edit_for_group(
 fn get_text_edit_in_use_block(
     call_path: &CallPath,
     use_statements: &[TyUseStatement],
+    encoding: PositionEncoding,
 ) -> Option<TextEdit> {
     let after_statement = use_statements.iter().reduce(|acc, curr| {
         if call_path.span().as_str().cmp(curr.span().as_str()) == Ordering::Greater
@@ -237,7 +303,7 @@ fn get_text_edit_in_use_block(
         acc
     })?;
 
-    let after_range = get_range_from_span(&after_statement.span());
+    let after_range = get_range_from_span(&after_statement.span(), encoding);
     let range_line = if call_path
         .span()
         .as_str()
@@ -261,6 +327,7 @@ fn get_text_edit_fallback(
     call_path: &CallPath,
     include_statements: &[TyIncludeStatement],
     program_type_keyword: &Option<Ident>,
+    encoding: PositionEncoding,
 ) -> TextEdit {
     let range_line = include_statements
         .iter()
@@ -271,11 +338,11 @@ fn get_text_edit_fallback(
             }
             acc
         })
-        .map(|span| get_range_from_span(&span).end.line + 1)
+        .map(|span| get_range_from_span(&span, encoding).end.line + 1)
         .unwrap_or(
             program_type_keyword
                 .clone()
-                .map(|keyword| get_range_from_span(&keyword.span()).end.line + 1)
+                .map(|keyword| get_range_from_span(&keyword.span(), encoding).end.line + 1)
                 .unwrap_or(1),
         );
     TextEdit {
@@ -381,6 +448,7 @@ use b:c:*;
             &use_statements,
             &include_statements,
             &program_type_keyword,
+            PositionEncoding::Utf16,
         );
         assert_text_edit(text_edit, expected_range, expected_text);
     }
@@ -410,6 +478,7 @@ use b:c:*;
             &use_statements,
             &include_statements,
             &program_type_keyword,
+            PositionEncoding::Utf16,
         );
         assert_text_edit(text_edit, expected_range, expected_text);
     }
@@ -447,6 +516,7 @@ use b:c:{D, F};
             &use_statements,
             &include_statements,
             &program_type_keyword,
+            PositionEncoding::Utf16,
         );
         assert_text_edit(text_edit, expected_range, expected_text);
     }
@@ -475,6 +545,7 @@ pub mod zz_module;
             &use_statements,
             &include_statements,
             &program_type_keyword,
+            PositionEncoding::Utf16,
         );
         assert_text_edit(text_edit, expected_range, expected_text);
     }
@@ -499,6 +570,7 @@ const HI: u8 = 0;
             &use_statements,
             &include_statements,
             &program_type_keyword,
+            PositionEncoding::Utf16,
         );
         assert_text_edit(text_edit, expected_range, expected_text);
     }
@@ -0,0 +1,186 @@
+use crate::{
+    capabilities::{
+        code_actions::{
+            common::generate_impl::TAB, CodeActionContext, CODE_ACTION_CREATE_FUNCTION_TITLE,
+        },
+        diagnostic::DiagnosticData,
+    },
+    core::token::{get_range_from_span, AstToken, TypedAstToken},
+    utils::position_encoding::PositionEncoding,
+};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, Position, Range, TextEdit,
+    WorkspaceEdit,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use sway_core::{
+    language::{
+        parsed::{Declaration, Expression, ExpressionKind, FunctionApplicationExpression},
+        Literal,
+    },
+    TypeInfo,
+};
+use sway_types::Span;
+
+/// Returns a [CodeActionOrCommand] that creates a stub of a function that is called but doesn't exist.
+///
+/// The types of the parameters are those of the arguments where they are known, and generic type parameters
+/// otherwise. The return type is only known if the result of the call is assigned to a variable with a type
+/// ascription. The stub is inserted after the item that contains the call.
+pub(crate) fn create_function_code_action(
+    ctx: &CodeActionContext,
+    diagnostics: &mut impl Iterator<Item = (Range, DiagnosticData)>,
+) -> Option<Vec<CodeActionOrCommand>> {
+    // Find a diagnostic that has the attached metadata indicating that a symbol couldn't be found,
+    // where the symbol is the name of a function that is called.
+    let (fn_name, call, range) = diagnostics.find_map(|(range, diag)| {
+        let name = diag.unknown_symbol_name?;
+        let (ident, token) = ctx.tokens.token_at_position(ctx.temp_uri, range.start)?;
+        match token.parsed {
+            AstToken::FunctionApplicationExpression(call)
+                if ident.name == name && call.call_path_binding.inner.prefixes.is_empty() =>
+            {
+                Some((name, call, ident.range))
+            }
+            _ => None,
+        }
+    })?;
+
+    let insertion_line = enclosing_item_end_line(ctx, &range)?;
+    let text_edit = TextEdit {
+        range: Range {
+            start: Position {
+                line: insertion_line,
+                character: 0,
+            },
+            end: Position {
+                line: insertion_line,
+                character: 0,
+            },
+        },
+        new_text: function_stub(ctx, &fn_name, &call, &range),
+    };
+    let changes = HashMap::from([(ctx.uri.clone(), vec![text_edit])]);
+
+    Some(vec![CodeActionOrCommand::CodeAction(LspCodeAction {
+        title: format!("{} `{}`", CODE_ACTION_CREATE_FUNCTION_TITLE, fn_name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        data: Some(Value::String(ctx.uri.to_string())),
+        ..Default::default()
+    })])
+}
+
+/// Returns the text of a stub of the function `fn_name` that `call` calls, with an empty line before it.
+fn function_stub(
+    ctx: &CodeActionContext,
+    fn_name: &str,
+    call: &FunctionApplicationExpression,
+    range: &Range,
+) -> String {
+    let mut type_parameters = vec![];
+    let params = call
+        .arguments
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| {
+            let name = argument_name(arg).unwrap_or_else(|| format!("arg{index}"));
+            let type_name = argument_type(ctx, arg).unwrap_or_else(|| {
+                let type_parameter = format!("T{index}");
+                type_parameters.push(type_parameter.clone());
+                type_parameter
+            });
+            format!("{name}: {type_name}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let type_parameters = if type_parameters.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", type_parameters.join(", "))
+    };
+    let return_type = return_type(ctx, range)
+        .map(|type_name| format!(" -> {type_name}"))
+        .unwrap_or_default();
+    format!(
+        "\nfn {fn_name}{type_parameters}({params}){return_type} {{\n{TAB}// TODO: implement `{fn_name}`.\n{TAB}revert(0)\n}}\n"
+    )
+}
+
+/// Returns the name of the variable that is passed as the argument, if it is one.
+fn argument_name(arg: &Expression) -> Option<String> {
+    match &arg.kind {
+        ExpressionKind::Variable(name) | ExpressionKind::AmbiguousVariableExpression(name) => {
+            Some(name.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type of the argument, if it is a literal or its type is known from the typed token map.
+fn argument_type(ctx: &CodeActionContext, arg: &Expression) -> Option<String> {
+    if let ExpressionKind::Literal(literal) = &arg.kind {
+        return Some(literal_type(literal));
+    }
+    let range = get_range_from_span(&arg.span, ctx.encoding);
+    let (_, token) = ctx.tokens.token_at_position(ctx.temp_uri, range.start)?;
+    let Some(TypedAstToken::TypedExpression(expr)) = token.typed else {
+        return None;
+    };
+    match &*ctx.engines.te().get(expr.return_type) {
+        TypeInfo::Unknown | TypeInfo::UnknownGeneric { .. } | TypeInfo::ErrorRecovery(_) => None,
+        _ => Some(ctx.engines.help_out(expr.return_type).to_string()),
+    }
+}
+
+fn literal_type(literal: &Literal) -> String {
+    match literal {
+        Literal::U8(_) => "u8".into(),
+        Literal::U16(_) => "u16".into(),
+        Literal::U32(_) => "u32".into(),
+        Literal::U64(_) | Literal::Numeric(_) => "u64".into(),
+        Literal::U256(_) => "u256".into(),
+        Literal::String(value) => format!("str[{}]", value.as_str().len()),
+        Literal::Boolean(_) => "bool".into(),
+        Literal::B256(_) => "b256".into(),
+    }
+}
+
+/// Returns the type ascription of the variable whose initializer is the call at `range`.
+fn return_type(ctx: &CodeActionContext, range: &Range) -> Option<String> {
+    ctx.tokens
+        .tokens_for_file(ctx.temp_uri)
+        .find_map(|(_, token)| match token.parsed {
+            AstToken::Declaration(Declaration::VariableDeclaration(decl))
+                if decl.type_ascription.call_path_tree.is_some()
+                    && contains(&decl.body.span, range, ctx.encoding) =>
+            {
+                Some(decl.type_ascription.span.as_str().to_string())
+            }
+            _ => None,
+        })
+}
+
+/// Returns the line after the outermost function or `impl` block that contains the call at `range`.
+fn enclosing_item_end_line(ctx: &CodeActionContext, range: &Range) -> Option<u32> {
+    ctx.tokens
+        .tokens_for_file(ctx.temp_uri)
+        .filter_map(|(_, token)| match token.parsed {
+            AstToken::Declaration(Declaration::FunctionDeclaration(decl)) => Some(decl.span),
+            AstToken::Declaration(Declaration::ImplSelf(decl)) => Some(decl.block_span),
+            AstToken::Declaration(Declaration::ImplTrait(decl)) => Some(decl.block_span),
+            _ => None,
+        })
+        .filter(|span| contains(span, range, ctx.encoding))
+        .map(|span| get_range_from_span(&span, ctx.encoding).end.line + 1)
+        .max()
+}
+
+fn contains(span: &Span, range: &Range, encoding: PositionEncoding) -> bool {
+    let span_range = get_range_from_span(span, encoding);
+    span_range.start <= range.start && range.end <= span_range.end
+}
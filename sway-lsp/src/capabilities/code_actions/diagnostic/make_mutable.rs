@@ -0,0 +1,63 @@
+use crate::{
+    capabilities::{
+        code_actions::{CodeActionContext, CODE_ACTION_MAKE_MUTABLE_TITLE},
+        diagnostic::DiagnosticData,
+    },
+    core::token::AstToken,
+};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, Range, TextEdit,
+    WorkspaceEdit,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use sway_core::language::parsed::Declaration;
+
+/// Returns a [CodeActionOrCommand] that inserts `mut` into the declaration of a variable
+/// that is assigned to but isn't declared as mutable.
+pub(crate) fn make_mutable_code_action(
+    ctx: &CodeActionContext,
+    diagnostics: &mut impl Iterator<Item = (Range, DiagnosticData)>,
+) -> Option<Vec<CodeActionOrCommand>> {
+    // Find a diagnostic that has the attached metadata indicating that an immutable variable was reassigned.
+    let (variable_name, range) = diagnostics.find_map(|(range, diag)| {
+        let name = diag.immutable_variable_name?;
+        Some((name, range))
+    })?;
+
+    // The declaration is the closest immutable variable declaration with the same name
+    // that comes before the reassignment.
+    let decl_ident = ctx
+        .tokens
+        .tokens_for_file(ctx.temp_uri)
+        .filter(|(ident, token)| {
+            ident.name == variable_name
+                && ident.range.start < range.start
+                && matches!(
+                    &token.parsed,
+                    AstToken::Declaration(Declaration::VariableDeclaration(decl)) if !decl.is_mutable
+                )
+        })
+        .map(|(ident, _)| ident)
+        .max_by_key(|ident| ident.range.start)?;
+
+    let text_edit = TextEdit {
+        range: Range {
+            start: decl_ident.range.start,
+            end: decl_ident.range.start,
+        },
+        new_text: "mut ".to_string(),
+    };
+    let changes = HashMap::from([(ctx.uri.clone(), vec![text_edit])]);
+
+    Some(vec![CodeActionOrCommand::CodeAction(LspCodeAction {
+        title: format!("{} `{}`", CODE_ACTION_MAKE_MUTABLE_TITLE, variable_name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        data: Some(Value::String(ctx.uri.to_string())),
+        ..Default::default()
+    })])
+}
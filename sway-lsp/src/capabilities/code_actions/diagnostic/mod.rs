@@ -1,29 +1,89 @@
-mod auto_import;
+pub(crate) mod auto_import;
+mod create_function;
+mod make_mutable;
 mod qualify;
+mod related_information;
+mod remove_storage_field;
+mod remove_unused_variable;
 
-use crate::capabilities::{code_actions::CodeActionContext, diagnostic::DiagnosticData};
-use lsp_types::CodeActionOrCommand;
+use crate::{
+    capabilities::{code_actions::CodeActionContext, diagnostic::DiagnosticData},
+    config::FixAllCategory,
+};
+use lsp_types::{CodeActionOrCommand, Range};
 
 use self::auto_import::import_code_action;
+use self::create_function::create_function_code_action;
+use self::make_mutable::make_mutable_code_action;
 use self::qualify::qualify_code_action;
+use self::related_information::go_to_related_code_action;
+use self::remove_storage_field::remove_storage_field_code_action;
+use self::remove_unused_variable::remove_unused_variable_code_action;
 
 /// Returns a list of [CodeActionOrCommand] based on the relavent compiler diagnostics.
 pub(crate) fn code_actions(ctx: &CodeActionContext) -> Option<Vec<CodeActionOrCommand>> {
-    // Find diagnostics that have attached metadata.
-    let diagnostics_with_data = ctx.diagnostics.iter().filter_map(|diag| {
-        if let Some(data) = diag.clone().data {
-            if let Ok(data) = serde_json::from_value::<DiagnosticData>(data) {
-                return Some((diag.range, data));
-            }
-        }
-        None
-    });
+    let diagnostics_with_data = diagnostics_with_data(ctx);
 
     import_code_action(ctx, &mut diagnostics_with_data.clone())
         .into_iter()
         .chain(qualify_code_action(ctx, &mut diagnostics_with_data.clone()))
+        .chain(create_function_code_action(
+            ctx,
+            &mut diagnostics_with_data.clone(),
+        ))
+        .chain(make_mutable_code_action(
+            ctx,
+            &mut diagnostics_with_data.clone(),
+        ))
+        .chain(remove_storage_field_code_action(
+            ctx,
+            &mut diagnostics_with_data.clone(),
+        ))
+        .chain(remove_unused_variable_code_action(ctx))
+        .chain(go_to_related_code_action(ctx))
         .reduce(|mut combined, mut curr| {
             combined.append(&mut curr);
             combined
         })
 }
+
+/// Returns the quick fixes of the diagnostics of `ctx` that are in one of the `categories`.
+///
+/// Only the fixes that don't ask the user to choose between alternatives, like which path to import, can be
+/// applied by `source.fixAll`.
+pub(crate) fn fix_all_code_actions(
+    ctx: &CodeActionContext,
+    categories: &[FixAllCategory],
+) -> Vec<CodeActionOrCommand> {
+    let diagnostics_with_data = diagnostics_with_data(ctx);
+    let mut actions = vec![];
+    if categories.contains(&FixAllCategory::UnusedVariables) {
+        actions.extend(remove_unused_variable_code_action(ctx).unwrap_or_default());
+    }
+    if categories.contains(&FixAllCategory::UnusedStorageFields) {
+        actions.extend(
+            remove_storage_field_code_action(ctx, &mut diagnostics_with_data.clone())
+                .unwrap_or_default(),
+        );
+    }
+    if categories.contains(&FixAllCategory::MakeMutable) {
+        actions.extend(
+            make_mutable_code_action(ctx, &mut diagnostics_with_data.clone()).unwrap_or_default(),
+        );
+    }
+    actions
+}
+
+/// Returns the diagnostics of `ctx` that have attached metadata, along with it.
+fn diagnostics_with_data<'a>(
+    ctx: &'a CodeActionContext,
+) -> impl Iterator<Item = (Range, DiagnosticData)> + Clone + 'a {
+    ctx.diagnostics.iter().filter_map(|diag| {
+        if let Some(data) = diag.clone().data {
+            if let Ok(data) = serde_json::from_value::<DiagnosticData>(data) {
+                return Some((diag.range, data));
+            }
+        }
+        None
+    })
+}
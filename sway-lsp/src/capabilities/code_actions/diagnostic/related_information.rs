@@ -0,0 +1,55 @@
+use crate::{
+    capabilities::code_actions::{CodeActionContext, CODE_ACTION_GO_TO_DEFINITION_TITLE},
+    utils::markup::GO_TO_COMMAND,
+};
+use lsp_types::{CodeAction as LspCodeAction, CodeActionOrCommand, Command};
+use serde_json::json;
+
+/// Returns a list of [CodeActionOrCommand] that navigate to the related locations of the diagnostics,
+/// such as the first definition of a name that is defined multiple times.
+pub(crate) fn go_to_related_code_action(
+    ctx: &CodeActionContext,
+) -> Option<Vec<CodeActionOrCommand>> {
+    let actions = ctx
+        .diagnostics
+        .iter()
+        .flat_map(|diagnostic| {
+            diagnostic
+                .related_information
+                .iter()
+                .flatten()
+                .map(move |info| {
+                    let file_name = info
+                        .location
+                        .uri
+                        .path_segments()
+                        .and_then(|mut segments| segments.next_back())
+                        .unwrap_or_default();
+                    let title = format!(
+                        "{} ({}:{})",
+                        CODE_ACTION_GO_TO_DEFINITION_TITLE,
+                        file_name,
+                        info.location.range.start.line + 1
+                    );
+                    CodeActionOrCommand::CodeAction(LspCodeAction {
+                        title: title.clone(),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        command: Some(Command {
+                            title,
+                            command: GO_TO_COMMAND.to_string(),
+                            arguments: Some(vec![
+                                json!({ "uri": info.location.uri, "range": info.location.range }),
+                            ]),
+                        }),
+                        ..Default::default()
+                    })
+                })
+        })
+        .collect::<Vec<_>>();
+
+    if !actions.is_empty() {
+        return Some(actions);
+    }
+
+    None
+}
@@ -0,0 +1,39 @@
+use crate::capabilities::{
+    code_actions::{CodeActionContext, CODE_ACTION_REMOVE_STORAGE_FIELD_TITLE},
+    diagnostic::DiagnosticData,
+};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, Range, TextEdit,
+    WorkspaceEdit,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Returns a [CodeActionOrCommand] that removes a storage field that is never accessed.
+pub(crate) fn remove_storage_field_code_action(
+    ctx: &CodeActionContext,
+    diagnostics: &mut impl Iterator<Item = (Range, DiagnosticData)>,
+) -> Option<Vec<CodeActionOrCommand>> {
+    // Find a diagnostic that has the attached metadata indicating that a storage field is unused.
+    let field = diagnostics.find_map(|(_, diag)| diag.unused_storage_field)?;
+
+    let text_edit = TextEdit {
+        range: field.removal_range,
+        new_text: String::new(),
+    };
+    let changes = HashMap::from([(ctx.uri.clone(), vec![text_edit])]);
+
+    Some(vec![CodeActionOrCommand::CodeAction(LspCodeAction {
+        title: format!(
+            "{} `{}`",
+            CODE_ACTION_REMOVE_STORAGE_FIELD_TITLE, field.name
+        ),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        data: Some(Value::String(ctx.uri.to_string())),
+        ..Default::default()
+    })])
+}
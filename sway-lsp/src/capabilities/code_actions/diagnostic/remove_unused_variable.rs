@@ -0,0 +1,115 @@
+use crate::{
+    capabilities::code_actions::{CodeActionContext, CODE_ACTION_REMOVE_UNUSED_VARIABLE_TITLE},
+    core::token::AstToken,
+    utils::position_encoding::PositionEncoding,
+};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, NumberOrString, Position,
+    Range, TextEdit, WorkspaceEdit,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use sway_core::language::parsed::Declaration;
+use sway_types::{Span, Spanned};
+
+/// The code of the compiler's warnings for declarations that are never used.
+const DEAD_CODE: &str = "dead_code";
+
+/// Returns a [CodeActionOrCommand] that removes the `let` statement of each variable that is never used.
+///
+/// Only statements that are on lines of their own are removed, along with those lines.
+pub(crate) fn remove_unused_variable_code_action(
+    ctx: &CodeActionContext,
+) -> Option<Vec<CodeActionOrCommand>> {
+    let actions = ctx
+        .diagnostics
+        .iter()
+        .filter(|diag| diag.code == Some(NumberOrString::String(DEAD_CODE.to_string())))
+        .filter_map(|diag| {
+            let (ident, token) = ctx
+                .tokens
+                .token_at_position(ctx.temp_uri, diag.range.start)?;
+            let AstToken::Declaration(Declaration::VariableDeclaration(decl)) = &token.parsed
+            else {
+                return None;
+            };
+            let text_edit = TextEdit {
+                range: statement_lines(&decl.name.span(), &decl.body.span(), ctx.encoding)?,
+                new_text: String::new(),
+            };
+            let changes = HashMap::from([(ctx.uri.clone(), vec![text_edit])]);
+
+            Some(CodeActionOrCommand::CodeAction(LspCodeAction {
+                title: format!(
+                    "{} `{}`",
+                    CODE_ACTION_REMOVE_UNUSED_VARIABLE_TITLE, ident.name
+                ),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                data: Some(Value::String(ctx.uri.to_string())),
+                ..Default::default()
+            }))
+        })
+        .collect::<Vec<_>>();
+    (!actions.is_empty()).then_some(actions)
+}
+
+/// Returns the range of the whole lines of the `let` statement that declares `name` as `body`,
+/// or `None` if the statement shares its lines with other code.
+fn statement_lines(name: &Span, body: &Span, encoding: PositionEncoding) -> Option<Range> {
+    let src = name.src();
+    let line_start = src[..name.start()].rfind('\n').map_or(0, |i| i + 1);
+    if !matches!(src[line_start..name.start()].trim(), "let" | "let mut") {
+        return None;
+    }
+    let line_end = src[body.end()..]
+        .find('\n')
+        .map_or(src.len(), |i| body.end() + i + 1);
+    if src[body.end()..line_end].trim() != ";" {
+        return None;
+    }
+    let line_of = |offset: usize| src[..offset].matches('\n').count() as u32;
+    let end = if line_end == src.len() && !src.ends_with('\n') {
+        let last_line_start = src.rfind('\n').map_or(0, |i| i + 1);
+        Position::new(line_of(line_end), encoding.len(&src[last_line_start..]))
+    } else {
+        Position::new(line_of(line_end), 0)
+    };
+    Some(Range::new(Position::new(line_of(line_start), 0), end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(src: &str, name: &str, body: &str) -> (Span, Span) {
+        let src: std::sync::Arc<str> = src.into();
+        let span = |text: &str| {
+            let start = src.find(text).unwrap();
+            Span::new(src.clone(), start, start + text.len(), None).unwrap()
+        };
+        (span(name), span(body))
+    }
+
+    #[test]
+    fn statement_lines_covers_the_whole_statement() {
+        let (name, body) = spans(
+            "fn main() {\n    let mut x = foo(\n        1,\n    );\n}\n",
+            "x",
+            "foo(\n        1,\n    )",
+        );
+        assert_eq!(
+            statement_lines(&name, &body, PositionEncoding::Utf16),
+            Some(Range::new(Position::new(1, 0), Position::new(4, 0)))
+        );
+    }
+
+    #[test]
+    fn statement_lines_skips_statements_that_share_a_line() {
+        let (name, body) = spans("fn main() {\n    let x = 1; let y = 2;\n}\n", "x", "1");
+        assert_eq!(statement_lines(&name, &body, PositionEncoding::Utf16), None);
+    }
+}
@@ -0,0 +1,143 @@
+use super::{diagnostic, organize_imports, CodeActionContext, CODE_ACTION_FIX_ALL_TITLE};
+use crate::{config::FixAllCategory, core::session::Session, server_state};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Returns the code action that applies every fix of the file in the categories of `codeAction.fixAll` at once.
+///
+/// The fixes are those of the file's diagnostics from the last compilation, regardless of the range that the
+/// code action is requested for. A fix whose edits overlap those of an earlier fix is skipped, as applying
+/// both would corrupt the file. It can be applied by requesting the code action again.
+pub(crate) fn code_action(
+    session: &Session,
+    uri: &Url,
+    temp_uri: &Url,
+) -> Option<CodeActionOrCommand> {
+    let categories = session.config.read().code_action.fix_all.clone();
+    if categories.is_empty() {
+        return None;
+    }
+    let mut fixes = vec![];
+    if categories.contains(&FixAllCategory::OrganizeImports) {
+        fixes.extend(organize_imports::code_action(session, uri, temp_uri));
+    }
+    let diagnostics = server_state::diagnostics(temp_uri, session);
+    let engines = session.engines.read();
+    let namespace = session.namespace();
+    for diagnostic in diagnostics {
+        let Some((_, token)) = session
+            .token_map()
+            .token_at_position(temp_uri, diagnostic.range.start)
+        else {
+            continue;
+        };
+        // Each diagnostic gets a context of its own, as some fixes only look at the first diagnostic they apply to.
+        let diagnostics = vec![diagnostic];
+        let ctx = CodeActionContext {
+            engines: &engines,
+            tokens: &session.token_map(),
+            token: &token,
+            uri,
+            temp_uri,
+            diagnostics: &diagnostics,
+            namespace: &namespace,
+            encoding: session.position_encoding(),
+        };
+        fixes.extend(diagnostic::fix_all_code_actions(&ctx, &categories));
+    }
+
+    let edits = non_overlapping_edits(
+        fixes
+            .into_iter()
+            .filter_map(|fix| match fix {
+                CodeActionOrCommand::CodeAction(LspCodeAction {
+                    edit:
+                        Some(WorkspaceEdit {
+                            changes: Some(mut changes),
+                            ..
+                        }),
+                    ..
+                }) => changes.remove(uri),
+                _ => None,
+            })
+            .collect(),
+    );
+    if edits.is_empty() {
+        return None;
+    }
+    let changes = HashMap::from([(uri.clone(), edits)]);
+    Some(CodeActionOrCommand::CodeAction(LspCodeAction {
+        title: CODE_ACTION_FIX_ALL_TITLE.to_string(),
+        kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        data: Some(Value::String(uri.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Returns the edits of the `fixes` that don't overlap those of an earlier fix, sorted by their position.
+///
+/// The edits of a fix are kept or skipped together, as a fix that is applied partially may not compile.
+fn non_overlapping_edits(fixes: Vec<Vec<TextEdit>>) -> Vec<TextEdit> {
+    let mut accepted: Vec<TextEdit> = vec![];
+    for edits in fixes {
+        let conflicts = edits.iter().any(|edit| {
+            accepted
+                .iter()
+                .any(|accepted| overlaps(&edit.range, &accepted.range))
+        });
+        if !conflicts {
+            accepted.extend(edits);
+        }
+    }
+    accepted.sort_by_key(|edit| edit.range.start);
+    accepted
+}
+
+/// Whether applying edits of both ranges would be ambiguous. Edits that only touch at their ends don't overlap,
+/// but two insertions at the same position do, as their order is unknown.
+fn overlaps(a: &Range, b: &Range) -> bool {
+    (a.start < b.end && b.start < a.end) || a.start == b.start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn overlapping_fixes_are_skipped() {
+        let remove_line = edit((3, 0), (4, 0), "");
+        let make_mutable = edit((3, 8), (3, 8), "mut ");
+        let remove_other_line = edit((5, 0), (6, 0), "");
+        let insert_before_line = edit((3, 0), (3, 0), "// ");
+        let imports = vec![edit((0, 0), (1, 0), "use std::hash::*;\n")];
+        let edits = non_overlapping_edits(vec![
+            vec![remove_other_line.clone()],
+            imports.clone(),
+            vec![remove_line.clone()],
+            vec![make_mutable],
+            vec![insert_before_line],
+            // Skipped as a whole, as its second edit overlaps the first fix.
+            vec![edit((2, 0), (2, 0), "x"), edit((5, 4), (5, 4), "y")],
+        ]);
+        assert_eq!(
+            edits,
+            vec![imports[0].clone(), remove_line, remove_other_line]
+        );
+    }
+}
@@ -4,18 +4,24 @@ pub mod constant_decl;
 pub mod diagnostic;
 pub mod enum_decl;
 pub mod enum_variant;
+pub mod fix_all;
 pub mod function_decl;
+pub mod organize_imports;
+pub mod qualify_path;
 pub mod storage_field;
 pub mod struct_decl;
 pub mod struct_field;
 pub mod trait_fn;
 
-use crate::core::{
-    session::Session,
-    token::{Token, TypedAstToken},
-    token_map::TokenMap,
-};
 pub use crate::error::DocumentError;
+use crate::{
+    core::{
+        session::Session,
+        token::{Token, TypedAstToken},
+        token_map::TokenMap,
+    },
+    utils::position_encoding::PositionEncoding,
+};
 use lsp_types::{
     CodeAction as LspCodeAction, CodeActionDisabled, CodeActionKind, CodeActionOrCommand,
     CodeActionResponse, Diagnostic, Position, Range, TextEdit, Url, WorkspaceEdit,
@@ -30,6 +36,14 @@ pub(crate) const CODE_ACTION_NEW_TITLE: &str = "Generate `new`";
 pub(crate) const CODE_ACTION_DOC_TITLE: &str = "Generate a documentation template";
 pub(crate) const CODE_ACTION_IMPORT_TITLE: &str = "Import";
 pub(crate) const CODE_ACTION_QUALIFY_TITLE: &str = "Qualify as";
+pub(crate) const CODE_ACTION_CREATE_FUNCTION_TITLE: &str = "Create function";
+pub(crate) const CODE_ACTION_GO_TO_DEFINITION_TITLE: &str = "Go to definition";
+pub(crate) const CODE_ACTION_MAKE_MUTABLE_TITLE: &str = "Make mutable";
+pub(crate) const CODE_ACTION_REMOVE_STORAGE_FIELD_TITLE: &str = "Remove unused storage field";
+pub(crate) const CODE_ACTION_REMOVE_UNUSED_VARIABLE_TITLE: &str = "Remove unused variable";
+pub(crate) const CODE_ACTION_ORGANIZE_IMPORTS_TITLE: &str = "Organize imports";
+pub(crate) const CODE_ACTION_FIX_ALL_TITLE: &str = "Fix all auto-fixable problems";
+pub(crate) const CODE_ACTION_FULLY_QUALIFY_TITLE: &str = "Use the fully-qualified path";
 
 #[derive(Clone)]
 pub(crate) struct CodeActionContext<'a> {
@@ -40,6 +54,7 @@ pub(crate) struct CodeActionContext<'a> {
     temp_uri: &'a Url,
     diagnostics: &'a Vec<Diagnostic>,
     namespace: &'a Option<Namespace>,
+    encoding: PositionEncoding,
 }
 
 pub fn code_actions(
@@ -49,18 +64,23 @@ pub fn code_actions(
     temp_uri: &Url,
     diagnostics: &Vec<Diagnostic>,
 ) -> Option<CodeActionResponse> {
-    let (_, token) = session
-        .token_map()
-        .token_at_position(temp_uri, range.start)?;
+    // Organizing the imports doesn't depend on the token under the cursor.
+    let source_actions: Vec<_> = organize_imports::code_action(&session, uri, temp_uri)
+        .into_iter()
+        .collect();
+    let Some((_, token)) = session.token_at_position(temp_uri, range.start) else {
+        return (!source_actions.is_empty()).then_some(source_actions);
+    };
 
     let ctx = CodeActionContext {
         engines: &session.engines.read(),
-        tokens: session.token_map(),
+        tokens: &session.token_map(),
         token: &token,
         uri,
         temp_uri,
         diagnostics,
         namespace: &session.namespace(),
+        encoding: session.position_encoding(),
     };
 
     let actions_by_type = token
@@ -93,9 +113,55 @@ pub fn code_actions(
         })
         .unwrap_or_default();
 
+    let actions_by_usage = qualify_path::code_actions(&ctx).unwrap_or_default();
     let actions_by_diagnostic = diagnostic::code_actions(&ctx).unwrap_or_default();
 
-    Some([actions_by_type, actions_by_diagnostic].concat())
+    Some(
+        [
+            actions_by_type,
+            actions_by_usage,
+            actions_by_diagnostic,
+            source_actions,
+        ]
+        .concat(),
+    )
+}
+
+/// Returns the `actions` whose kind is one of the `only` kinds or a subkind of them,
+/// e.g. `source.organizeImports` for `source`.
+pub fn filter_by_kind(
+    actions: CodeActionResponse,
+    only: Option<&[CodeActionKind]>,
+) -> CodeActionResponse {
+    let Some(only) = only else {
+        return actions;
+    };
+    actions
+        .into_iter()
+        .filter(|action| match action {
+            CodeActionOrCommand::CodeAction(LspCodeAction {
+                kind: Some(kind), ..
+            }) => only.iter().any(|only| is_kind_or_subkind(kind, only)),
+            _ => false,
+        })
+        .collect()
+}
+
+/// Whether the `only` kinds of a request ask for `source.fixAll`. It's only offered when asked for, e.g. by
+/// the client's actions on save, as it applies to the whole file rather than to the range of the request.
+pub fn requests_fix_all(only: Option<&[CodeActionKind]>) -> bool {
+    only.is_some_and(|only| {
+        only.iter()
+            .any(|only| is_kind_or_subkind(&CodeActionKind::SOURCE_FIX_ALL, only))
+    })
+}
+
+fn is_kind_or_subkind(kind: &CodeActionKind, of: &CodeActionKind) -> bool {
+    kind == of
+        || kind
+            .as_str()
+            .strip_prefix(of.as_str())
+            .is_some_and(|rest| rest.starts_with('.'))
 }
 
 pub(crate) trait CodeAction<'a, T: Spanned> {
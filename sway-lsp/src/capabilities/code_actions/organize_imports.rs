@@ -0,0 +1,293 @@
+use super::CODE_ACTION_ORGANIZE_IMPORTS_TITLE;
+use crate::core::{
+    session::Session,
+    token::{get_range_from_span, AstToken, SymbolKind, TypedAstToken},
+};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, TextEdit, Url, WorkspaceEdit,
+};
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+use sway_ast::{ItemKind, UseTree};
+use sway_core::language::parsed::ImportType;
+use sway_error::handler::Handler;
+use sway_types::{Span, Spanned};
+
+const SELF_IMPORT: &str = "self";
+const GLOB_IMPORT: &str = "*";
+
+/// A `use` statement that imports a single name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Import {
+    path: Vec<String>,
+    is_absolute: bool,
+    is_pub: bool,
+    leaf: Leaf,
+}
+
+/// The last segment of an [Import], which is `self`, `*` or a name with an optional alias.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Leaf {
+    name: String,
+    alias: Option<String>,
+}
+
+impl Leaf {
+    /// `self` comes before the names of a group, and globs after them.
+    fn sort_key(&self) -> (u8, &str, Option<&str>) {
+        let rank = match self.name.as_str() {
+            SELF_IMPORT => 0,
+            GLOB_IMPORT => 2,
+            _ => 1,
+        };
+        (rank, &self.name, self.alias.as_deref())
+    }
+}
+
+impl Ord for Leaf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Leaf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for Leaf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.alias {
+            Some(alias) => write!(f, "{} as {}", self.name, alias),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Returns the code action that sorts and merges the `use` statements at the top of the file,
+/// and removes the ones that are never used.
+///
+/// The file is left alone if its imports have comments or attributes, or are interleaved
+/// with other items, as they couldn't be moved around without losing them.
+pub(crate) fn code_action(
+    session: &Session,
+    uri: &Url,
+    temp_uri: &Url,
+) -> Option<CodeActionOrCommand> {
+    let text: Arc<str> = session.get_text_document(temp_uri).ok()?.get_text().into();
+    let module = sway_parse::parse_file(&Handler::default(), text.clone(), None).ok()?;
+    let items = &module.value.items;
+    let use_indices: Vec<usize> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| matches!(item.value, ItemKind::Use(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let (first, last) = (*use_indices.first()?, *use_indices.last()?);
+    if last - first + 1 != use_indices.len() {
+        return None;
+    }
+
+    let mut imports = vec![];
+    for item in &items[first..=last] {
+        let ItemKind::Use(item_use) = &item.value else {
+            return None;
+        };
+        if !item.attribute_list.is_empty() {
+            return None;
+        }
+        let mut leaves = vec![];
+        flatten_use_tree(&item_use.tree, vec![], &mut leaves)?;
+        imports.extend(leaves.into_iter().map(|(path, leaf)| Import {
+            path,
+            is_absolute: item_use.root_import.is_some(),
+            is_pub: item_use.visibility.is_some(),
+            leaf,
+        }));
+    }
+    let block = Span::join(items[first].span(), items[last].span());
+    if block.as_str().contains("//") || block.as_str().contains("/*") {
+        return None;
+    }
+
+    let unused = unused_imports(session, temp_uri);
+    imports.retain(|import| {
+        import.is_pub || !unused.contains(&(import.path.clone(), import.leaf.clone()))
+    });
+    let new_text = organized_imports(imports);
+    if new_text == block.as_str() {
+        return None;
+    }
+
+    let text_edit = TextEdit {
+        range: get_range_from_span(&block, session.position_encoding()),
+        new_text,
+    };
+    let changes = HashMap::from([(uri.clone(), vec![text_edit])]);
+    Some(CodeActionOrCommand::CodeAction(LspCodeAction {
+        title: CODE_ACTION_ORGANIZE_IMPORTS_TITLE.to_string(),
+        kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        data: Some(Value::String(uri.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Collects the imports of `tree` into `imports` by their path, or returns `None` if it doesn't parse.
+fn flatten_use_tree(
+    tree: &UseTree,
+    path: Vec<String>,
+    imports: &mut Vec<(Vec<String>, Leaf)>,
+) -> Option<()> {
+    let leaf = |name: &str, alias: Option<String>| Leaf {
+        name: name.to_string(),
+        alias,
+    };
+    match tree {
+        UseTree::Group { imports: group } => {
+            for tree in group.get() {
+                flatten_use_tree(tree, path.clone(), imports)?;
+            }
+        }
+        UseTree::Name { name } => imports.push((path, leaf(name.as_str(), None))),
+        UseTree::Rename { name, alias, .. } => {
+            imports.push((path, leaf(name.as_str(), Some(alias.to_string()))))
+        }
+        UseTree::Glob { .. } => imports.push((path, leaf(GLOB_IMPORT, None))),
+        UseTree::Path { prefix, suffix, .. } => {
+            let mut path = path;
+            path.push(prefix.to_string());
+            flatten_use_tree(suffix, path, imports)?;
+        }
+        UseTree::Error { .. } => return None,
+    }
+    Some(())
+}
+
+/// Returns the imported items of the file whose name isn't used anywhere else in it.
+///
+/// Traits are never reported, as their methods can be called without naming them.
+fn unused_imports(session: &Session, temp_uri: &Url) -> HashSet<(Vec<String>, Leaf)> {
+    let encoding = session.position_encoding();
+    let tokens: Vec<_> = session.token_map().tokens_for_file(temp_uri).collect();
+    let used_names: HashSet<&str> = tokens
+        .iter()
+        .filter(|(_, token)| {
+            !matches!(token.parsed, AstToken::UseStatement(_))
+                && !matches!(token.typed, Some(TypedAstToken::TypedUseStatement(_)))
+        })
+        .map(|(ident, _)| ident.name.as_str())
+        .collect();
+
+    tokens
+        .iter()
+        .filter_map(|(ident, token)| {
+            let Some(TypedAstToken::TypedUseStatement(use_stmt)) = &token.typed else {
+                return None;
+            };
+            let ImportType::Item(item) = &use_stmt.import_type else {
+                return None;
+            };
+            // The path and alias of the statement have tokens of their own.
+            if ident.range != get_range_from_span(&item.span(), encoding)
+                || matches!(token.kind, SymbolKind::Trait | SymbolKind::Unknown)
+            {
+                return None;
+            }
+            let name = use_stmt.alias.as_ref().unwrap_or(item);
+            if used_names.contains(name.as_str()) {
+                return None;
+            }
+            let path = use_stmt.call_path.iter().map(|ident| ident.to_string());
+            let leaf = Leaf {
+                name: item.to_string(),
+                alias: use_stmt.alias.as_ref().map(|alias| alias.to_string()),
+            };
+            Some((path.collect(), leaf))
+        })
+        .collect()
+}
+
+/// Returns the `use` statements of `imports`, sorted by path with one statement for each path.
+fn organized_imports(imports: Vec<Import>) -> String {
+    let mut groups: BTreeMap<(Vec<String>, bool, bool), BTreeSet<Leaf>> = BTreeMap::new();
+    for import in imports {
+        groups
+            .entry((import.path, import.is_absolute, import.is_pub))
+            .or_default()
+            .insert(import.leaf);
+    }
+    let mut statements = vec![];
+    for ((path, is_absolute, is_pub), leaves) in groups {
+        let prefix = format!(
+            "{}use {}{}",
+            if is_pub { "pub " } else { "" },
+            if is_absolute { "::" } else { "" },
+            path.iter()
+                .map(|segment| format!("{segment}::"))
+                .collect::<String>()
+        );
+        let leaves: Vec<String> = leaves.iter().map(Leaf::to_string).collect();
+        match leaves.as_slice() {
+            [leaf] if leaf != SELF_IMPORT || path.is_empty() => {
+                statements.push(format!("{prefix}{leaf};"))
+            }
+            // A group needs a path, so top-level imports get a statement each.
+            _ if path.is_empty() => {
+                statements.extend(leaves.iter().map(|leaf| format!("{prefix}{leaf};")))
+            }
+            _ => statements.push(format!("{prefix}{{{}}};", leaves.join(", "))),
+        }
+    }
+    statements.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imports(src: &str) -> Vec<Import> {
+        let module = sway_parse::parse_file(&Handler::default(), Arc::from(src), None).unwrap();
+        let mut imports = vec![];
+        for item in &module.value.items {
+            let ItemKind::Use(item_use) = &item.value else {
+                continue;
+            };
+            let mut leaves = vec![];
+            flatten_use_tree(&item_use.tree, vec![], &mut leaves).unwrap();
+            imports.extend(leaves.into_iter().map(|(path, leaf)| Import {
+                path,
+                is_absolute: item_use.root_import.is_some(),
+                is_pub: item_use.visibility.is_some(),
+                leaf,
+            }));
+        }
+        imports
+    }
+
+    #[test]
+    fn imports_are_sorted_and_merged_by_path() {
+        let src = "library;\n\nuse std::hash::sha256;\nuse core::ops::*;\nuse std::hash::{Hash, self};\nuse std::hash::Hash;\n";
+        assert_eq!(
+            organized_imports(imports(src)),
+            "use core::ops::*;\nuse std::hash::{self, Hash, sha256};"
+        );
+    }
+
+    #[test]
+    fn visibility_and_aliases_are_kept() {
+        let src = "library;\n\npub use ::foo::bar;\nuse ::foo::baz as qux;\nuse foo::{self};\nuse a;\nuse b;\n";
+        assert_eq!(
+            organized_imports(imports(src)),
+            "use a;\nuse b;\nuse foo::{self};\nuse ::foo::baz as qux;\npub use ::foo::bar;"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    capabilities::code_actions::{CodeActionContext, CODE_ACTION_FULLY_QUALIFY_TITLE},
+    core::token::{get_range_from_span, SymbolKind, TypedAstToken},
+    utils::position_encoding::PositionEncoding,
+};
+use lsp_types::{
+    CodeAction as LspCodeAction, CodeActionKind, CodeActionOrCommand, Position, Range, TextEdit,
+    WorkspaceEdit,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use sway_core::language::{parsed::ImportType, ty::TyUseStatement};
+use sway_types::Spanned;
+
+/// Returns a [CodeActionOrCommand] that replaces the imported symbol under the cursor with its
+/// fully-qualified path, everywhere it's used in the file, and removes its import.
+///
+/// It's only offered if exactly one `use` statement of the file imports the name of the symbol.
+/// The import of a trait is kept, as its methods can only be called while it's in scope.
+pub(crate) fn code_actions(ctx: &CodeActionContext) -> Option<Vec<CodeActionOrCommand>> {
+    if matches!(ctx.token.typed, Some(TypedAstToken::TypedUseStatement(_))) {
+        return None;
+    }
+    let decl_ident = ctx.token.declared_token_ident(ctx.engines, ctx.encoding)?;
+    let use_statements = use_statements(ctx);
+    let mut imports = use_statements.iter().filter(|(_, use_stmt)| {
+        matches!(&use_stmt.import_type, ImportType::Item(item) if item.as_str() == decl_ident.name)
+    });
+    let (name, use_stmt) = imports.next()?;
+    if imports.next().is_some() {
+        return None;
+    }
+    let ImportType::Item(item) = &use_stmt.import_type else {
+        return None;
+    };
+    let path = format!(
+        "{}{}::{}",
+        if use_stmt.is_absolute { "::" } else { "" },
+        use_stmt
+            .call_path
+            .iter()
+            .map(|ident| ident.as_str())
+            .collect::<Vec<_>>()
+            .join("::"),
+        item
+    );
+
+    let src = use_stmt.span.src();
+    let mut text_edits: Vec<TextEdit> = ctx
+        .tokens
+        .tokens_for_file(ctx.temp_uri)
+        .filter(|(ident, token)| {
+            ident.name == *name
+                && !matches!(token.typed, Some(TypedAstToken::TypedUseStatement(_)))
+                && token
+                    .declared_token_ident(ctx.engines, ctx.encoding)
+                    .as_ref()
+                    == Some(&decl_ident)
+                && !is_qualified(src, &ident.range, ctx.encoding)
+        })
+        .map(|(ident, _)| TextEdit {
+            range: ident.range,
+            new_text: path.clone(),
+        })
+        .collect();
+    if text_edits.is_empty() {
+        return None;
+    }
+    if ctx.token.kind != SymbolKind::Trait {
+        text_edits.extend(remove_import_edit(&use_statements, use_stmt, ctx.encoding));
+    }
+    text_edits.sort_by_key(|edit| edit.range.start);
+    let changes = HashMap::from([(ctx.uri.clone(), text_edits)]);
+
+    Some(vec![CodeActionOrCommand::CodeAction(LspCodeAction {
+        title: format!("{} `{}`", CODE_ACTION_FULLY_QUALIFY_TITLE, path),
+        kind: Some(CodeActionKind::REFACTOR),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        data: Some(Value::String(ctx.uri.to_string())),
+        ..Default::default()
+    })])
+}
+
+/// Returns each import of the file's `use` statements, with the name it's imported as.
+///
+/// The statements of a group like `use a::{b, c};` share the span of the whole group.
+fn use_statements(ctx: &CodeActionContext) -> Vec<(String, TyUseStatement)> {
+    let mut use_statements: Vec<(String, TyUseStatement)> = vec![];
+    for (_, token) in ctx.tokens.tokens_for_file(ctx.temp_uri) {
+        let Some(TypedAstToken::TypedUseStatement(use_stmt)) = token.typed else {
+            continue;
+        };
+        let name = match (&use_stmt.alias, &use_stmt.import_type) {
+            (Some(alias), _) => alias.to_string(),
+            (None, ImportType::Item(item)) => item.to_string(),
+            (None, ImportType::SelfImport(_)) => "self".to_string(),
+            (None, ImportType::Star) => "*".to_string(),
+        };
+        let is_duplicate = use_statements.iter().any(|(other_name, other)| {
+            *other_name == name
+                && other.span == use_stmt.span
+                && other.call_path == use_stmt.call_path
+        });
+        if !is_duplicate {
+            use_statements.push((name, use_stmt));
+        }
+    }
+    use_statements
+}
+
+/// Returns the [TextEdit] that removes `use_stmt` from its `use` statement, or the whole statement
+/// if it's the only import in it.
+///
+/// Groups with nested paths are left alone, as they can't be rewritten from their imports.
+fn remove_import_edit(
+    use_statements: &[(String, TyUseStatement)],
+    use_stmt: &TyUseStatement,
+    encoding: PositionEncoding,
+) -> Option<TextEdit> {
+    let siblings: Vec<_> = use_statements
+        .iter()
+        .filter(|(_, other)| other.span == use_stmt.span && other != use_stmt)
+        .collect();
+    let range = get_range_from_span(&use_stmt.span, encoding);
+    if siblings.is_empty() {
+        return Some(TextEdit {
+            range: Range::new(
+                Position::new(range.start.line, 0),
+                Position::new(range.end.line + 1, 0),
+            ),
+            new_text: String::new(),
+        });
+    }
+    if siblings
+        .iter()
+        .any(|(_, other)| other.call_path != use_stmt.call_path)
+    {
+        return None;
+    }
+    let leaves: Vec<String> = siblings
+        .iter()
+        .map(|(name, other)| match (&other.alias, &other.import_type) {
+            (Some(_), ImportType::Item(item)) => format!("{} as {}", item, name),
+            _ => name.clone(),
+        })
+        .collect();
+    let prefix = format!(
+        "{}use {}{}::",
+        if use_stmt.span.as_str().starts_with("pub") {
+            "pub "
+        } else {
+            ""
+        },
+        if use_stmt.is_absolute { "::" } else { "" },
+        use_stmt
+            .call_path
+            .iter()
+            .map(|ident| ident.as_str())
+            .collect::<Vec<_>>()
+            .join("::")
+    );
+    let new_text = match leaves.as_slice() {
+        [leaf] if leaf != "self" => format!("{prefix}{leaf};"),
+        _ => format!("{prefix}{{{}}};", leaves.join(", ")),
+    };
+    Some(TextEdit { range, new_text })
+}
+
+/// Returns true if the identifier at `range` of `src` already follows a path, like `b` in `a::b`.
+fn is_qualified(src: &str, range: &Range, encoding: PositionEncoding) -> bool {
+    let Some(line) = src.lines().nth(range.start.line as usize) else {
+        return false;
+    };
+    let start = encoding.byte_offset(line, range.start.character);
+    line[..start].trim_end().ends_with("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_qualified_checks_for_a_path_before_the_identifier() {
+        let src = "fn main() {\n    let p = items::Point {};\n    let q = Point {};\n}\n";
+        assert!(is_qualified(
+            src,
+            &Range::new(Position::new(1, 19), Position::new(1, 24)),
+            PositionEncoding::Utf16
+        ));
+        assert!(!is_qualified(
+            src,
+            &Range::new(Position::new(2, 12), Position::new(2, 17)),
+            PositionEncoding::Utf16
+        ));
+    }
+}
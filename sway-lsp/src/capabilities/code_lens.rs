@@ -1,8 +1,25 @@
 use std::{path::PathBuf, sync::Arc};
 
-use lsp_types::{CodeLens, Url};
+use lsp_types::{CodeLens, Command, Position, Url};
+use serde::{Deserialize, Serialize};
 
-use crate::core::session::Session;
+use crate::{
+    capabilities::references::get_references,
+    core::{session::Session, token::SymbolKind},
+};
+
+/// The command defined in the client that shows the references of a reference count lens.
+const SHOW_REFERENCES_COMMAND: &str = "sway.showReferences";
+
+/// The `data` of a reference count lens, which is used to find the declaration to count
+/// the references of once the lens is resolved.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReferencesLensData {
+    /// The workspace url of the file of the declaration.
+    uri: Url,
+    position: Position,
+}
 
 pub fn code_lens(session: &Arc<Session>, url: &Url) -> Vec<CodeLens> {
     let url_path = PathBuf::from(url.path());
@@ -21,7 +38,85 @@ pub fn code_lens(session: &Arc<Session>, url: &Url) -> Vec<CodeLens> {
                 .collect()
         })
         .unwrap_or_default();
+    if session.config.read().code_lens.references {
+        result.extend(reference_lenses(session, url));
+    }
     // Sort the results
     result.sort_by(|a, b| a.range.start.line.cmp(&b.range.start.line));
     result
 }
+
+/// Returns an unresolved lens for each function, struct and trait declared in the file.
+///
+/// The references are only counted once the lens is resolved, so that only the lenses the user
+/// actually sees require a scan of the token map.
+fn reference_lenses(session: &Session, url: &Url) -> Vec<CodeLens> {
+    let Ok(workspace_url) = session.sync.temp_to_workspace_url(url) else {
+        return vec![];
+    };
+    let engines = session.engines.read();
+    let encoding = session.position_encoding();
+    session
+        .token_map()
+        .tokens_for_file(url)
+        .filter(|(ident, token)| {
+            matches!(
+                token.kind,
+                SymbolKind::Function | SymbolKind::Struct | SymbolKind::Trait
+            ) && token.declared_token_ident(&engines, encoding).as_ref() == Some(ident)
+        })
+        .map(|(ident, _)| {
+            let data = ReferencesLensData {
+                uri: workspace_url.clone(),
+                position: ident.range.start,
+            };
+            CodeLens {
+                range: ident.range,
+                command: None,
+                data: serde_json::to_value(data).ok(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the workspace url of the file of the declaration of a reference count lens.
+///
+/// Returns `None` if the lens isn't a reference count lens.
+pub fn references_lens_uri(code_lens: &CodeLens) -> Option<Url> {
+    let data = serde_json::from_value::<ReferencesLensData>(code_lens.data.clone()?).ok()?;
+    Some(data.uri)
+}
+
+/// Counts the references of the declaration of a reference count lens, given the temp `url` of its file.
+///
+/// The lens gets a command that shows the references, which is left out if the declaration can't be found.
+pub fn resolve_references_lens(
+    session: Arc<Session>,
+    url: Url,
+    mut code_lens: CodeLens,
+) -> CodeLens {
+    let Some(data) = code_lens
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value::<ReferencesLensData>(data).ok())
+    else {
+        return code_lens;
+    };
+    let Some(locations) = get_references(session, url, data.position, false) else {
+        return code_lens;
+    };
+    let title = match locations.len() {
+        1 => "1 reference".to_string(),
+        count => format!("{count} references"),
+    };
+    code_lens.command = Some(Command {
+        title,
+        command: SHOW_REFERENCES_COMMAND.to_string(),
+        arguments: Some(vec![
+            serde_json::json!(data.uri),
+            serde_json::json!(data.position),
+            serde_json::json!(locations),
+        ]),
+    });
+    code_lens
+}
@@ -1,32 +1,219 @@
-use crate::core::token::TokenIdent;
+use crate::{
+    capabilities::{
+        code_actions::diagnostic::auto_import::{import_path, FileImports},
+        hover::fn_signature,
+    },
+    core::{
+        token::{get_range_from_span, SymbolKind, Token, TokenIdent, TypedAstToken},
+        token_map::TokenMap,
+    },
+    utils::{
+        document::get_url_from_span, keyword_docs::KeywordDocs, position_encoding::PositionEncoding,
+    },
+};
+use dashmap::{mapref::one::Ref, try_result::TryResult};
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit, Position,
-    Range, TextEdit,
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionTextEdit,
+    Documentation, InsertTextFormat, MarkupContent, MarkupKind, Position, Range, TextEdit, Url,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashSet, fs, path::Path};
 use sway_core::{
-    language::ty::{TyAstNodeContent, TyDecl, TyFunctionDecl},
-    namespace::Items,
+    language::{
+        parsed::{
+            AstNode, AstNodeContent, CodeBlock, Declaration, Expression, ExpressionKind,
+            FunctionDeclaration, ImplItem, ParseModule,
+        },
+        ty::{self, TyAstNodeContent, TyDecl, TyFunctionDecl},
+    },
+    namespace::{Items, Namespace},
     Engines, TypeId, TypeInfo,
 };
+use sway_types::{Ident, SourceEngine, Span, Spanned};
+
+/// What the name at the cursor is completed with, based on the text before it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompletionContext {
+    /// The fields and methods of the expression whose last character is at the position, e.g. `foo.`.
+    Member(Position),
+    /// The variants and associated functions of the type whose name ends at the position, e.g. `MyEnum::`.
+    Path(Position),
+    /// The names that are in scope, and the keywords.
+    Scope,
+}
+
+/// Returns the context of the completion at `position` in `text`, and the range of the partially
+/// typed name before the cursor, which is replaced by the completion.
+pub fn completion_context(text: &str, position: Position) -> (CompletionContext, Range) {
+    let line = text
+        .split('\n')
+        .nth(position.line as usize)
+        .unwrap_or_default()
+        .chars()
+        .take(position.character as usize)
+        .collect::<Vec<_>>();
+    let mut start = line.len();
+    while start > 0 && (line[start - 1].is_alphanumeric() || line[start - 1] == '_') {
+        start -= 1;
+    }
+    let range = Range::new(Position::new(position.line, start as u32), position);
+    let context = match &line[..start] {
+        [.., c, '.'] if !c.is_whitespace() => {
+            CompletionContext::Member(Position::new(position.line, start as u32 - 2))
+        }
+        [.., c, ':', ':'] if c.is_alphanumeric() || *c == '_' => {
+            CompletionContext::Path(Position::new(position.line, start as u32 - 3))
+        }
+        _ => CompletionContext::Scope,
+    };
+    (context, range)
+}
+
+/// The `data` of a completion item, which is used to fill in its documentation, and the signature of a function,
+/// once the item is resolved.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum CompletionItemData {
+    /// A keyword, which is documented by the keyword docs.
+    Keyword,
+    /// A declaration, from the byte offset `start` to `end` of the file at `uri` as it was compiled.
+    Declaration { uri: Url, start: usize, end: usize },
+}
+
+/// Returns the `data` of a completion item for the declaration at `span`.
+fn declaration_data(source_engine: &SourceEngine, span: &Span) -> Option<Value> {
+    let data = CompletionItemData::Declaration {
+        uri: get_url_from_span(source_engine, span).ok()?,
+        start: span.start(),
+        end: span.end(),
+    };
+    serde_json::to_value(data).ok()
+}
+
+/// Fills in the documentation of a completion item, and the signature of a function, which are left out of
+/// the completion response so that it stays small in files with many names in scope.
+///
+/// The declaration is read from the file it was compiled from, so that it can be resolved without waiting for
+/// a compilation. Items without `data`, or whose declaration can't be read, are returned as-is.
+pub fn resolve_completion_item(
+    keyword_docs: &KeywordDocs,
+    mut item: CompletionItem,
+) -> CompletionItem {
+    let Some(data) = item
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value::<CompletionItemData>(data).ok())
+    else {
+        return item;
+    };
+    let documentation = match data {
+        CompletionItemData::Keyword => keyword_docs.get(&item.label).cloned(),
+        CompletionItemData::Declaration { uri, start, end } => {
+            let Some(text) = uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| fs::read_to_string(path).ok())
+            else {
+                return item;
+            };
+            let Some(declaration) = text.get(start..end) else {
+                return item;
+            };
+            let is_function = matches!(
+                item.kind,
+                Some(CompletionItemKind::FUNCTION | CompletionItemKind::METHOD)
+            );
+            if is_function && item.detail.is_none() {
+                item.detail = Some(fn_signature(declaration));
+            }
+            doc_comment_before(&text, start)
+        }
+    };
+    item.documentation = documentation.map(|value| {
+        Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        })
+    });
+    item
+}
+
+/// Returns the doc comments on the lines before the line of `start` in `text`, skipping the other attributes
+/// of the declaration, with a line for each comment as in the hover.
+fn doc_comment_before(text: &str, start: usize) -> Option<String> {
+    let mut comments = text
+        .get(..start)?
+        .rsplit('\n')
+        .skip(1)
+        .map(str::trim_start)
+        .take_while(|line| line.starts_with("///") || line.starts_with("#["))
+        .filter_map(|line| line.strip_prefix("///"))
+        .collect::<Vec<_>>();
+    if comments.is_empty() {
+        return None;
+    }
+    comments.reverse();
+    Some(
+        comments
+            .iter()
+            .map(|comment| format!("{comment}\n"))
+            .collect(),
+    )
+}
+
+/// Looks up the typed tokens of the names that are declared in a parsed module.
+pub(crate) struct TypedNames<'a> {
+    pub(crate) token_map: &'a TokenMap,
+    pub(crate) engines: &'a Engines,
+    pub(crate) encoding: PositionEncoding,
+}
+
+impl TypedNames<'_> {
+    /// Returns the name of the type of the variable, parameter or constant, if it's known.
+    fn type_name(&self, ident: &Ident) -> Option<String> {
+        let token = self.token(ident)?;
+        let type_id = token.value().type_id()?;
+        Some(self.engines.help_out(type_id).to_string())
+    }
+
+    /// Returns the declaration of the function, if it has been typed.
+    fn function(&self, ident: &Ident) -> Option<TyFunctionDecl> {
+        match self.token(ident)?.value().typed.clone()? {
+            TypedAstToken::TypedFunctionDeclaration(fn_decl) => Some(fn_decl),
+            _ => None,
+        }
+    }
+
+    fn token(&self, ident: &Ident) -> Option<Ref<TokenIdent, Token>> {
+        self.token_map
+            .try_get(&TokenIdent::new(ident, self.engines.se(), self.encoding))
+            .try_unwrap()
+    }
+}
 
 pub(crate) fn to_completion_items(
     namespace: &Items,
     engines: &Engines,
     ident_to_complete: &TokenIdent,
     fn_decl: &TyFunctionDecl,
-    position: Position,
+    range: Range,
+    snippets: bool,
 ) -> Vec<CompletionItem> {
     type_id_of_raw_ident(engines, namespace, &ident_to_complete.name, fn_decl)
-        .map(|type_id| completion_items_for_type_id(engines, namespace, type_id, position))
+        .map(|type_id| completion_items_for_type_id(engines, namespace, type_id, range, snippets))
         .unwrap_or_default()
 }
 
 /// Gathers the given [TypeId] struct's fields and methods and builds completion items.
-fn completion_items_for_type_id(
+///
+/// If `snippets` is set, the methods are inserted as snippets with a placeholder for each argument.
+pub(crate) fn completion_items_for_type_id(
     engines: &Engines,
     namespace: &Items,
     type_id: TypeId,
-    position: Position,
+    range: Range,
+    snippets: bool,
 ) -> Vec<CompletionItem> {
     let mut completion_items = vec![];
     let type_info = engines.te().get(type_id);
@@ -36,10 +223,12 @@ fn completion_items_for_type_id(
             let item = CompletionItem {
                 kind: Some(CompletionItemKind::FIELD),
                 label: field.name.as_str().to_string(),
+                detail: Some(field.type_argument.span.as_str().to_string()),
                 label_details: Some(CompletionItemLabelDetails {
                     description: Some(field.type_argument.span.clone().str()),
                     detail: None,
                 }),
+                data: declaration_data(engines.se(), &field.span),
                 ..Default::default()
             };
             completion_items.push(item);
@@ -52,34 +241,21 @@ fn completion_items_for_type_id(
 
         // Only show methods that take `self` as the first parameter.
         if params.first().map(|p| p.is_self()).unwrap_or(false) {
-            let params_short = match params.is_empty() {
-                true => "()".to_string(),
-                false => "(…)".to_string(),
-            };
-            let params_edit_str = params
-                .iter()
-                .filter_map(|p| {
-                    if p.is_self() {
-                        return None;
-                    }
-                    Some(p.name.as_str())
-                })
-                .collect::<Vec<&str>>()
-                .join(", ");
+            let (text_edit, insert_text_format) = call_text_edit(&fn_decl, range, snippets);
             let item = CompletionItem {
                 kind: Some(CompletionItemKind::METHOD),
-                label: format!("{}{}", method.name().clone().as_str(), params_short),
-                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
-                    range: Range {
-                        start: position,
-                        end: position,
-                    },
-                    new_text: format!("{}({})", method.name().clone().as_str(), params_edit_str),
-                })),
+                label: method.name().clone().as_str().to_string(),
+                text_edit: Some(text_edit),
+                insert_text_format,
                 label_details: Some(CompletionItemLabelDetails {
-                    description: Some(fn_signature_string(engines, &fn_decl, &type_id)),
-                    detail: None,
+                    detail: Some(fn_params_string(engines, &fn_decl, &type_id)),
+                    description: Some(replace_self_with_type_str(
+                        engines,
+                        fn_decl.return_type.clone().span.str(),
+                        &type_id,
+                    )),
                 }),
+                data: declaration_data(engines.se(), &fn_decl.span),
                 ..Default::default()
             };
             completion_items.push(item);
@@ -89,8 +265,418 @@ fn completion_items_for_type_id(
     completion_items
 }
 
-/// Returns the [String] of the shortened function signature to display in the completion item's label details.
-fn fn_signature_string(
+/// Gathers the variants of the enum and the associated functions of the type declared by
+/// `ty_decl`, i.e. those that don't take `self`, and builds completion items.
+pub(crate) fn completion_items_for_path(
+    engines: &Engines,
+    namespace: &Items,
+    ty_decl: &TyDecl,
+    range: Range,
+    snippets: bool,
+) -> Vec<CompletionItem> {
+    let mut completion_items = vec![];
+    if let TyDecl::EnumDecl(ty::EnumDecl { decl_id, .. }) = ty_decl {
+        let enum_decl = engines.de().get_enum(decl_id);
+        for variant in enum_decl.variants.iter() {
+            completion_items.push(CompletionItem {
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                label: variant.name.as_str().to_string(),
+                detail: Some(engines.help_out(variant.type_argument.type_id).to_string()),
+                data: declaration_data(engines.se(), &variant.span),
+                ..Default::default()
+            });
+        }
+    }
+
+    for method in namespace.get_methods_for_decl(engines, ty_decl) {
+        let fn_decl = engines.de().get_function(&method.id().clone());
+        if fn_decl.parameters.first().is_some_and(|p| p.is_self()) {
+            continue;
+        }
+        let (text_edit, insert_text_format) = call_text_edit(&fn_decl, range, snippets);
+        completion_items.push(CompletionItem {
+            kind: Some(CompletionItemKind::FUNCTION),
+            label: method.name().as_str().to_string(),
+            text_edit: Some(text_edit),
+            insert_text_format,
+            data: declaration_data(engines.se(), &fn_decl.span),
+            ..Default::default()
+        });
+    }
+    completion_items
+}
+
+/// Builds the completion items for the names that are in scope at `position` in the `module`:
+/// its items, and the parameters and variables of the enclosing function that are declared before the position.
+///
+/// Later declarations shadow earlier ones with the same name. If `snippets` is set, the functions
+/// whose declarations have been typed are inserted as snippets, and the others by their name.
+pub(crate) fn completion_items_for_scope(
+    module: &ParseModule,
+    position: Position,
+    range: Range,
+    names: &TypedNames,
+    snippets: bool,
+) -> Vec<CompletionItem> {
+    let nodes = &module.tree.root_nodes;
+    let mut completion_items = nodes
+        .iter()
+        .filter_map(|node| module_item(node, names, range, snippets))
+        .collect::<Vec<_>>();
+
+    if let Some(fn_decl) = enclosing_fn(nodes, position, names.encoding) {
+        for param in &fn_decl.parameters {
+            let detail = names
+                .type_name(&param.name)
+                .unwrap_or_else(|| param.type_argument.span.as_str().to_string());
+            completion_items.push(variable_item(&param.name, Some(detail)));
+        }
+        block_variables(&fn_decl.body, position, names, &mut completion_items);
+    }
+
+    let mut seen = HashSet::new();
+    let mut completion_items = completion_items
+        .into_iter()
+        .rev()
+        .filter(|item| seen.insert(item.label.clone()))
+        .collect::<Vec<_>>();
+    completion_items.reverse();
+    completion_items
+}
+
+/// Builds the completion items for the declarations whose names start with `prefix` but aren't in scope, which import
+/// them once they're accepted. The items are sorted by the paths they're imported by.
+///
+/// Names that are already imported need no edit. Names that are declared in the module, or that another import
+/// brings into scope, are left out so that the import can't conflict with them.
+pub(crate) fn completion_items_for_imports(
+    token_map: &TokenMap,
+    engines: &Engines,
+    namespace: &Namespace,
+    imports: &FileImports,
+    prefix: &str,
+    in_scope: &HashSet<String>,
+) -> Vec<CompletionItem> {
+    let mut seen = HashSet::new();
+    let mut completion_items = token_map
+        .iter()
+        .filter(|item| item.key().name.starts_with(prefix) && !in_scope.contains(&item.key().name))
+        .filter_map(|item| {
+            let token = item.value();
+            let (kind, span) = match (&token.kind, &token.typed) {
+                // Methods and the functions of traits are called through their type, not imported.
+                (SymbolKind::Function, Some(TypedAstToken::TypedFunctionDeclaration(fn_decl)))
+                    if fn_decl.implementing_type.is_none() && !fn_decl.is_trait_method_dummy =>
+                {
+                    (CompletionItemKind::FUNCTION, Some(fn_decl.span.clone()))
+                }
+                (SymbolKind::Function, _) => return None,
+                (SymbolKind::Struct | SymbolKind::TypeAlias, _) => {
+                    (CompletionItemKind::STRUCT, None)
+                }
+                (SymbolKind::Enum, _) => (CompletionItemKind::ENUM, None),
+                (SymbolKind::Trait, _) => (CompletionItemKind::INTERFACE, None),
+                (SymbolKind::Const, _) => (CompletionItemKind::CONSTANT, None),
+                _ => return None,
+            };
+            let call_path = import_path(engines, namespace, token)?;
+            // The same declaration can be found more than once, e.g. through its uses in other modules.
+            if call_path.prefixes.is_empty()
+                || imports.conflicts_with(&call_path)
+                || !seen.insert(call_path.to_string())
+            {
+                return None;
+            }
+            let span = span.unwrap_or_else(|| call_path.suffix.span());
+            let item = CompletionItem {
+                kind: Some(kind),
+                label: call_path.suffix.to_string(),
+                label_details: Some(CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some(call_path.to_string()),
+                }),
+                additional_text_edits: (!imports.imports(&call_path))
+                    .then(|| vec![imports.text_edit(&call_path)]),
+                data: declaration_data(engines.se(), &span),
+                ..Default::default()
+            };
+            Some((call_path.to_string(), item))
+        })
+        .collect::<Vec<_>>();
+    completion_items.sort_by(|(a, _), (b, _)| a.cmp(b));
+    completion_items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Returns the name that is partially typed in the `range` of `text`, which is on a single line.
+pub(crate) fn typed_prefix(text: &str, range: Range) -> String {
+    text.split('\n')
+        .nth(range.start.line as usize)
+        .unwrap_or_default()
+        .chars()
+        .skip(range.start.character as usize)
+        .take((range.end.character - range.start.character) as usize)
+        .collect()
+}
+
+/// Builds a completion item for each of the documented keywords, in alphabetical order.
+///
+/// The documentation is filled in once the item is resolved.
+pub(crate) fn completion_items_for_keywords(keyword_docs: &KeywordDocs) -> Vec<CompletionItem> {
+    let mut completion_items = keyword_docs
+        .keys()
+        .map(|keyword| CompletionItem {
+            kind: Some(CompletionItemKind::KEYWORD),
+            label: keyword.clone(),
+            data: serde_json::to_value(CompletionItemData::Keyword).ok(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    completion_items.sort_by(|a, b| a.label.cmp(&b.label));
+    completion_items
+}
+
+/// Returns the module of the program, or one of its submodules, that is in the file at `path`.
+pub(crate) fn parse_module_of_file<'a>(
+    module: &'a ParseModule,
+    source_engine: &SourceEngine,
+    path: &Path,
+) -> Option<&'a ParseModule> {
+    if module
+        .span
+        .source_id()
+        .is_some_and(|source_id| source_engine.get_path(source_id) == path)
+    {
+        return Some(module);
+    }
+    module
+        .submodules
+        .iter()
+        .find_map(|(_, submodule)| parse_module_of_file(&submodule.module, source_engine, path))
+}
+
+/// Returns the edit that inserts a call to the function, with its parameters other than `self` as arguments,
+/// and the format of the inserted text.
+///
+/// If `snippets` is set, each argument is a placeholder, with the tab stops in the order of the parameters,
+/// and the cursor ends up after the call.
+fn call_text_edit(
+    fn_decl: &TyFunctionDecl,
+    range: Range,
+    snippets: bool,
+) -> (CompletionTextEdit, Option<InsertTextFormat>) {
+    let params = fn_decl
+        .parameters
+        .iter()
+        .filter(|p| !p.is_self())
+        .map(|p| p.name.as_str());
+    let (new_text, insert_text_format) = if snippets {
+        let args = params
+            .enumerate()
+            .map(|(i, name)| format!("${{{}:{}}}", i + 1, name))
+            .collect::<Vec<String>>()
+            .join(", ");
+        (
+            format!("{}({})$0", fn_decl.name.as_str(), args),
+            Some(InsertTextFormat::SNIPPET),
+        )
+    } else {
+        let args = params.collect::<Vec<&str>>().join(", ");
+        (format!("{}({})", fn_decl.name.as_str(), args), None)
+    };
+    let text_edit = CompletionTextEdit::Edit(TextEdit { range, new_text });
+    (text_edit, insert_text_format)
+}
+
+/// Builds the completion item for the item that the module-level node declares, if any.
+fn module_item(
+    node: &AstNode,
+    names: &TypedNames,
+    range: Range,
+    snippets: bool,
+) -> Option<CompletionItem> {
+    let AstNodeContent::Declaration(decl) = &node.content else {
+        return None;
+    };
+    // The signature of a function is filled in once the item is resolved, from the span of its declaration.
+    // The other items are resolved from their name, which is on the line of the declaration.
+    let (name, kind, detail, span) = match decl {
+        Declaration::FunctionDeclaration(decl) => (
+            &decl.name,
+            CompletionItemKind::FUNCTION,
+            None,
+            decl.span.clone(),
+        ),
+        Declaration::StructDeclaration(decl) => (
+            &decl.name,
+            CompletionItemKind::STRUCT,
+            None,
+            decl.name.span(),
+        ),
+        Declaration::EnumDeclaration(decl) => {
+            (&decl.name, CompletionItemKind::ENUM, None, decl.name.span())
+        }
+        Declaration::TraitDeclaration(decl) => (
+            &decl.name,
+            CompletionItemKind::INTERFACE,
+            None,
+            decl.name.span(),
+        ),
+        Declaration::AbiDeclaration(decl) => (
+            &decl.name,
+            CompletionItemKind::INTERFACE,
+            None,
+            decl.name.span(),
+        ),
+        Declaration::TypeAliasDeclaration(decl) => (
+            &decl.name,
+            CompletionItemKind::STRUCT,
+            Some(decl.ty.span.as_str().to_string()),
+            decl.name.span(),
+        ),
+        Declaration::ConstantDeclaration(decl) => (
+            &decl.name,
+            CompletionItemKind::CONSTANT,
+            names.type_name(&decl.name),
+            decl.name.span(),
+        ),
+        _ => return None,
+    };
+    let mut item = CompletionItem {
+        kind: Some(kind),
+        label: name.as_str().to_string(),
+        detail,
+        data: declaration_data(names.engines.se(), &span),
+        ..Default::default()
+    };
+    // Without the typed declaration, the parameters aren't known, so only the name is inserted.
+    if let Some(fn_decl) = snippets.then(|| names.function(name)).flatten() {
+        let (text_edit, insert_text_format) = call_text_edit(&fn_decl, range, true);
+        item.text_edit = Some(text_edit);
+        item.insert_text_format = insert_text_format;
+    }
+    Some(item)
+}
+
+fn variable_item(name: &Ident, detail: Option<String>) -> CompletionItem {
+    CompletionItem {
+        kind: Some(CompletionItemKind::VARIABLE),
+        label: name.as_str().to_string(),
+        detail,
+        ..Default::default()
+    }
+}
+
+/// Returns the function whose body contains `position`, including the methods of `impl` blocks,
+/// traits and ABIs.
+pub(crate) fn enclosing_fn<'a>(
+    nodes: &'a [AstNode],
+    position: Position,
+    encoding: PositionEncoding,
+) -> Option<&'a FunctionDeclaration> {
+    let node = nodes
+        .iter()
+        .find(|node| span_contains(&node.span, position, encoding))?;
+    let AstNodeContent::Declaration(decl) = &node.content else {
+        return None;
+    };
+    let find_method = |methods: &'a [FunctionDeclaration]| {
+        methods
+            .iter()
+            .find(|method| span_contains(&method.span, position, encoding))
+    };
+    match decl {
+        Declaration::FunctionDeclaration(decl) => Some(decl),
+        Declaration::ImplSelf(decl) => impl_method(&decl.items, position, encoding),
+        Declaration::ImplTrait(decl) => impl_method(&decl.items, position, encoding),
+        Declaration::TraitDeclaration(decl) => find_method(&decl.methods),
+        Declaration::AbiDeclaration(decl) => find_method(&decl.methods),
+        _ => None,
+    }
+}
+
+fn impl_method(
+    items: &[ImplItem],
+    position: Position,
+    encoding: PositionEncoding,
+) -> Option<&FunctionDeclaration> {
+    items.iter().find_map(|item| match item {
+        ImplItem::Fn(decl) if span_contains(&decl.span, position, encoding) => Some(decl),
+        _ => None,
+    })
+}
+
+/// Adds the variables that are declared in the `block` before `position`, and in the nested
+/// blocks that contain it, to the completion items.
+fn block_variables(
+    block: &CodeBlock,
+    position: Position,
+    names: &TypedNames,
+    completion_items: &mut Vec<CompletionItem>,
+) {
+    if !span_contains(&block.span(), position, names.encoding) {
+        return;
+    }
+    for node in &block.contents {
+        let range = get_range_from_span(&node.span, names.encoding);
+        if range.start >= position {
+            break;
+        }
+        match &node.content {
+            // A variable isn't in scope in its own initializer.
+            AstNodeContent::Declaration(Declaration::VariableDeclaration(decl)) => {
+                if range.end <= position {
+                    completion_items.push(variable_item(&decl.name, names.type_name(&decl.name)));
+                } else {
+                    expression_variables(&decl.body, position, names, completion_items);
+                }
+            }
+            AstNodeContent::Expression(expr) | AstNodeContent::ImplicitReturnExpression(expr) => {
+                expression_variables(expr, position, names, completion_items);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Adds the variables of the blocks in the expression that contain `position` to the completion items.
+fn expression_variables(
+    expr: &Expression,
+    position: Position,
+    names: &TypedNames,
+    completion_items: &mut Vec<CompletionItem>,
+) {
+    if !span_contains(&expr.span, position, names.encoding) {
+        return;
+    }
+    match &expr.kind {
+        ExpressionKind::CodeBlock(block) => {
+            block_variables(block, position, names, completion_items)
+        }
+        ExpressionKind::If(if_expr) => {
+            for branch in std::iter::once(&if_expr.then).chain(&if_expr.r#else) {
+                expression_variables(branch, position, names, completion_items);
+            }
+        }
+        ExpressionKind::Match(match_expr) => {
+            for branch in &match_expr.branches {
+                expression_variables(&branch.result, position, names, completion_items);
+            }
+        }
+        ExpressionKind::WhileLoop(while_loop) => {
+            block_variables(&while_loop.body, position, names, completion_items)
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn span_contains(span: &Span, position: Position, encoding: PositionEncoding) -> bool {
+    let range = get_range_from_span(span, encoding);
+    range.start <= position && position <= range.end
+}
+
+/// Returns the [String] of the method's parameters, excluding `self`, to display after the
+/// label of the completion item, e.g. `(foo: MyStruct)`.
+fn fn_params_string(
     engines: &Engines,
     fn_decl: &TyFunctionDecl,
     parent_type_id: &TypeId,
@@ -98,20 +684,21 @@ fn fn_signature_string(
     let params_str = fn_decl
         .parameters
         .iter()
+        .filter(|p| !p.is_self())
         .map(|p| {
-            replace_self_with_type_str(engines, p.type_argument.clone().span.str(), parent_type_id)
+            format!(
+                "{}: {}",
+                p.name.as_str(),
+                replace_self_with_type_str(
+                    engines,
+                    p.type_argument.clone().span.str(),
+                    parent_type_id
+                )
+            )
         })
         .collect::<Vec<String>>()
         .join(", ");
-    format!(
-        "fn({}) -> {}",
-        params_str,
-        replace_self_with_type_str(
-            engines,
-            fn_decl.return_type.clone().span.str(),
-            parent_type_id
-        )
-    )
+    format!("({params_str})")
 }
 
 /// Given a [String] representing a type, replaces `Self` with the display name of the type.
@@ -204,3 +791,76 @@ fn type_id_of_local_ident(ident_name: &str, fn_decl: &TyFunctionDecl) -> Option<
             })
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context_at(text: &str, line: u32, character: u32) -> (CompletionContext, Range) {
+        completion_context(text, Position::new(line, character))
+    }
+
+    #[test]
+    fn completion_context_after_dot() {
+        let text = "fn main() {\n    foo.ba\n}";
+        assert_eq!(
+            context_at(text, 1, 8),
+            (
+                CompletionContext::Member(Position::new(1, 6)),
+                Range::new(Position::new(1, 8), Position::new(1, 8))
+            )
+        );
+        assert_eq!(
+            context_at(text, 1, 10),
+            (
+                CompletionContext::Member(Position::new(1, 6)),
+                Range::new(Position::new(1, 8), Position::new(1, 10))
+            )
+        );
+    }
+
+    #[test]
+    fn completion_context_after_path_separator() {
+        let text = "    let x = MyEnum::Va";
+        assert_eq!(
+            context_at(text, 0, 22),
+            (
+                CompletionContext::Path(Position::new(0, 17)),
+                Range::new(Position::new(0, 20), Position::new(0, 22))
+            )
+        );
+    }
+
+    #[test]
+    fn doc_comments_before_a_declaration() {
+        let text = "/// Adds `a` and `b`.\n///\n/// Panics on overflow.\n#[inline(always)]\npub fn add(a: u64, b: u64) -> u64 {\n    a + b\n}\n";
+        let start = text.find("pub fn").unwrap();
+        assert_eq!(
+            doc_comment_before(text, start).unwrap(),
+            " Adds `a` and `b`.\n\n Panics on overflow.\n"
+        );
+        assert_eq!(
+            fn_signature(&text[start..]),
+            "pub fn add(a: u64, b: u64) -> u64"
+        );
+
+        // Comments that aren't doc comments, or are separated from the declaration, aren't documentation.
+        let text = "/// Not this.\n\n// Nor this.\nstruct Foo {}\n";
+        assert_eq!(doc_comment_before(text, text.find("Foo").unwrap()), None);
+    }
+
+    #[test]
+    fn completion_context_in_scope() {
+        let text = "    let x = fo";
+        assert_eq!(
+            context_at(text, 0, 14),
+            (
+                CompletionContext::Scope,
+                Range::new(Position::new(0, 12), Position::new(0, 14))
+            )
+        );
+        // Type ascriptions and leading `::` aren't paths.
+        assert_eq!(context_at("let x: ", 0, 7).0, CompletionContext::Scope);
+        assert_eq!(context_at("use ::", 0, 6).0, CompletionContext::Scope);
+    }
+}
@@ -1,50 +1,183 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag, Position, Range};
+use crate::{
+    core::{
+        token::{get_range_from_span, TypedAstToken},
+        token_map::{TokenMap, TokenMapExt},
+    },
+    utils::position_encoding::PositionEncoding,
+};
+
+use dashmap::DashMap;
+use forc_pkg::PackageManifestFile;
+use forc_util::kebab_to_snake_case;
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag,
+    DocumentDiagnosticReport, FullDocumentDiagnosticReport, Location, NumberOrString, Position,
+    Range, RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport,
+    UnchangedDocumentDiagnosticReport, Url, WorkspaceDocumentDiagnosticReport,
+    WorkspaceFullDocumentDiagnosticReport, WorkspaceUnchangedDocumentDiagnosticReport,
+};
 use serde::{Deserialize, Serialize};
+use sway_core::Engines;
+use sway_error::diagnostic::ToDiagnostic;
 use sway_error::warning::CompileWarning;
 use sway_error::{error::CompileError, warning::Warning};
-use sway_types::{LineCol, SourceEngine, Spanned};
+use sway_types::{SourceEngine, Spanned};
 
 pub(crate) type DiagnosticMap = HashMap<PathBuf, Diagnostics>;
 
+/// The diagnostics last returned for each file to a client that pulls them, by workspace url,
+/// so that it can be told when they haven't changed.
+#[derive(Debug, Default)]
+pub(crate) struct PulledDiagnostics {
+    reports: DashMap<Url, (String, Vec<Diagnostic>)>,
+    next_result_id: AtomicU64,
+}
+
+impl PulledDiagnostics {
+    /// Returns the report of the diagnostics of `uri`, which is unchanged if they're the same as the ones
+    /// last returned with `previous_result_id`. Otherwise, they're returned with a new result id.
+    pub(crate) fn report(
+        &self,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+        diagnostics: Vec<Diagnostic>,
+    ) -> DocumentDiagnosticReport {
+        let mut entry = self.reports.entry(uri.clone()).or_default();
+        let (result_id, last_diagnostics) = &mut *entry;
+        if Some(result_id.as_str()) == previous_result_id && *last_diagnostics == diagnostics {
+            return DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                    result_id: result_id.clone(),
+                },
+            });
+        }
+        *result_id = self
+            .next_result_id
+            .fetch_add(1, Ordering::SeqCst)
+            .to_string();
+        *last_diagnostics = diagnostics.clone();
+        DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                result_id: Some(result_id.clone()),
+                items: diagnostics,
+            },
+        })
+    }
+
+    /// Returns the report of the diagnostics of `uri` like [PulledDiagnostics::report], for a `workspace/diagnostic` request.
+    pub(crate) fn workspace_report(
+        &self,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+        diagnostics: Vec<Diagnostic>,
+    ) -> WorkspaceDocumentDiagnosticReport {
+        match self.report(uri, previous_result_id, diagnostics) {
+            DocumentDiagnosticReport::Full(report) => {
+                WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                    uri: uri.clone(),
+                    version: None,
+                    full_document_diagnostic_report: report.full_document_diagnostic_report,
+                })
+            }
+            DocumentDiagnosticReport::Unchanged(report) => {
+                WorkspaceDocumentDiagnosticReport::Unchanged(
+                    WorkspaceUnchangedDocumentDiagnosticReport {
+                        uri: uri.clone(),
+                        version: None,
+                        unchanged_document_diagnostic_report: report
+                            .unchanged_document_diagnostic_report,
+                    },
+                )
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Diagnostics {
     pub warnings: Vec<Diagnostic>,
     pub errors: Vec<Diagnostic>,
 }
 
-fn get_error_diagnostic(error: &CompileError) -> Diagnostic {
+fn get_error_diagnostic(
+    error: &CompileError,
+    source_engine: &SourceEngine,
+    encoding: PositionEncoding,
+) -> Diagnostic {
     let data = serde_json::to_value(DiagnosticData::try_from(error.clone()).ok()).ok();
 
     Diagnostic {
-        range: get_range(error.span().line_col()),
+        range: get_range_from_span(&error.span(), encoding),
         severity: Some(DiagnosticSeverity::ERROR),
         message: format!("{error}"),
+        related_information: get_related_information(error, source_engine, encoding),
         data,
         ..Default::default()
     }
 }
 
-fn get_warning_diagnostic(warning: &CompileWarning) -> Diagnostic {
+fn get_warning_diagnostic(
+    warning: &CompileWarning,
+    source_engine: &SourceEngine,
+    encoding: PositionEncoding,
+) -> Diagnostic {
     Diagnostic {
-        range: get_range(warning.span().line_col()),
+        range: get_range_from_span(&warning.span(), encoding),
         severity: Some(DiagnosticSeverity::WARNING),
         message: warning.to_friendly_warning_string(),
+        code: Some(NumberOrString::String(
+            get_warning_code(&warning.warning_content).to_string(),
+        )),
         tags: get_warning_diagnostic_tags(&warning.warning_content),
+        related_information: get_related_information(warning, source_engine, encoding),
         ..Default::default()
     }
 }
 
+/// Returns the locations of the compiler's hints that point to source code other than the
+/// issue itself, e.g. the first definition of a name that is defined multiple times.
+///
+/// The locations are in the temp directory the project is compiled in.
+fn get_related_information(
+    item: &impl ToDiagnostic,
+    source_engine: &SourceEngine,
+    encoding: PositionEncoding,
+) -> Option<Vec<DiagnosticRelatedInformation>> {
+    let diagnostic = item.to_diagnostic(source_engine);
+    let issue_span = diagnostic.issue().span();
+    let related_information = diagnostic
+        .labels()
+        .into_iter()
+        .filter(|label| label.span() != issue_span)
+        .filter_map(|label| {
+            let uri = Url::from_file_path(label.source_path()?.as_path_buf()).ok()?;
+            Some(DiagnosticRelatedInformation {
+                location: Location {
+                    uri,
+                    range: get_range_from_span(label.span(), encoding),
+                },
+                message: label.friendly_text().to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    (!related_information.is_empty()).then_some(related_information)
+}
+
 pub fn get_diagnostics(
     warnings: &[CompileWarning],
     errors: &[CompileError],
     source_engine: &SourceEngine,
+    encoding: PositionEncoding,
 ) -> DiagnosticMap {
     let mut diagnostics = DiagnosticMap::new();
     for warning in warnings {
-        let diagnostic = get_warning_diagnostic(warning);
+        let diagnostic = get_warning_diagnostic(warning, source_engine, encoding);
         if let Some(source_id) = warning.span().source_id() {
             let path = source_engine.get_path(source_id);
             diagnostics
@@ -55,7 +188,7 @@ pub fn get_diagnostics(
         }
     }
     for error in errors {
-        let diagnostic = get_error_diagnostic(error);
+        let diagnostic = get_error_diagnostic(error, source_engine, encoding);
         if let Some(source_id) = error.span().source_id() {
             let path = source_engine.get_path(source_id);
             diagnostics.entry(path).or_default().errors.push(diagnostic);
@@ -65,11 +198,283 @@ pub fn get_diagnostics(
     diagnostics
 }
 
-fn get_range((start, end): (LineCol, LineCol)) -> Range {
-    let pos = |lc: LineCol| Position::new(lc.line as u32 - 1, lc.col as u32 - 1);
-    let start = pos(start);
-    let end = pos(end);
-    Range { start, end }
+/// Returns the path of the module defined in the file at `path`, e.g. `my_lib::utils::math` for `src/utils/math.sw`.
+///
+/// The entry file of the package is its root module, which is named after the package.
+pub(crate) fn module_path(path: &Path) -> Option<String> {
+    let manifest = PackageManifestFile::from_dir(path).ok()?;
+    let entry_path = manifest.entry_path();
+    let mut segments = vec![kebab_to_snake_case(manifest.project_name())];
+    if path != entry_path {
+        let relative_path = path.strip_prefix(entry_path.parent()?).ok()?;
+        segments.extend(
+            relative_path
+                .with_extension("")
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string()),
+        );
+    }
+    Some(segments.join("::"))
+}
+
+/// Prefixes the message of each diagnostic with the path of the module they were reported in, e.g. `[my_lib::utils] ...`.
+pub(crate) fn prefix_module_path(path: &Path, diagnostics: &mut [Diagnostic]) {
+    let Some(module_path) = module_path(path) else {
+        return;
+    };
+    for diagnostic in diagnostics {
+        diagnostic.message = format!("[{module_path}] {}", diagnostic.message);
+    }
+}
+
+/// Sets the source of the diagnostics that don't have one to `label`, which is shown next to them by editors.
+/// An empty label leaves them without a source.
+pub(crate) fn set_source(diagnostics: &mut [Diagnostic], label: &str) {
+    if label.is_empty() {
+        return;
+    }
+    for diagnostic in diagnostics {
+        diagnostic.source.get_or_insert_with(|| label.to_string());
+    }
+}
+
+/// Removes the diagnostics that have the same range, code and message as an earlier one, e.g. those that are
+/// reported once for each instantiation of a generic function. The first of each is kept, in its place.
+pub(crate) fn deduplicate(diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+    diagnostics.retain(|diagnostic| {
+        let code = diagnostic.code.as_ref().map(|code| match code {
+            NumberOrString::Number(number) => number.to_string(),
+            NumberOrString::String(string) => string.clone(),
+        });
+        seen.insert((diagnostic.range, code, diagnostic.message.clone()))
+    });
+}
+
+/// Returns the diagnostics of a failed compilation for the files that it reported errors in, and those of the
+/// last successful compilation for the rest, so that the previously known issues of the files that the failure
+/// didn't affect stay visible. See [DiagnosticConfig::retain_on_failure].
+///
+/// [DiagnosticConfig::retain_on_failure]: crate::config::DiagnosticConfig::retain_on_failure
+pub(crate) fn retain_unaffected_files(
+    last_successful: &DiagnosticMap,
+    failed: DiagnosticMap,
+) -> DiagnosticMap {
+    let mut diagnostics: DiagnosticMap = last_successful
+        .iter()
+        .filter(|(path, _)| {
+            failed
+                .get(*path)
+                .map_or(true, |diagnostics| diagnostics.errors.is_empty())
+        })
+        .map(|(path, diagnostics)| (path.clone(), diagnostics.clone()))
+        .collect();
+    diagnostics.extend(
+        failed
+            .into_iter()
+            .filter(|(_, diagnostics)| !diagnostics.errors.is_empty()),
+    );
+    diagnostics
+}
+
+/// The diagnostics of a project, as written to the file set in `diagnostic.snapshot` and
+/// returned by `sway/exportDiagnostics`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsSnapshot {
+    pub files: Vec<FileDiagnostics>,
+}
+
+/// The diagnostics of a single file of a [DiagnosticsSnapshot].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiagnostics {
+    /// The path of the file, relative to the project's `Forc.toml`.
+    pub path: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The most diagnostics that are kept of each compilation for `sway/diagnosticsDiff`, to bound the memory they take.
+pub(crate) const MAX_RETAINED_DIAGNOSTICS: usize = 10_000;
+
+/// The diagnostics of the last two compilations of a session, for `sway/diagnosticsDiff`.
+#[derive(Debug, Default)]
+pub struct DiagnosticsHistory {
+    previous: Option<DiagnosticsSnapshot>,
+    latest: Option<DiagnosticsSnapshot>,
+}
+
+impl DiagnosticsHistory {
+    /// Records the diagnostics of a new compilation, keeping at most [MAX_RETAINED_DIAGNOSTICS] of them.
+    pub fn record(&mut self, mut snapshot: DiagnosticsSnapshot) {
+        let mut remaining = MAX_RETAINED_DIAGNOSTICS;
+        snapshot.files.retain_mut(|file| {
+            file.diagnostics.truncate(remaining);
+            remaining -= file.diagnostics.len();
+            !file.diagnostics.is_empty()
+        });
+        self.previous = self.latest.replace(snapshot);
+    }
+
+    /// Returns the diagnostics that the latest compilation added and removed compared to the one before it,
+    /// or `None` if the session hasn't been compiled twice yet.
+    pub fn diff(&self) -> Option<DiagnosticsDiff> {
+        let (previous, latest) = (self.previous.as_ref()?, self.latest.as_ref()?);
+        Some(DiagnosticsDiff {
+            added: missing_diagnostics(latest, previous),
+            removed: missing_diagnostics(previous, latest),
+        })
+    }
+}
+
+/// The diagnostics that were added and removed between two compilations, as returned by `sway/diagnosticsDiff`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsDiff {
+    pub added: Vec<FileDiagnostics>,
+    pub removed: Vec<FileDiagnostics>,
+}
+
+/// What tells two diagnostics apart when diffing them: their file, range, code and message.
+type DiagnosticKey<'a> = (&'a Path, Range, Option<String>, &'a str);
+
+fn diagnostic_key<'a>(path: &'a Path, diagnostic: &'a Diagnostic) -> DiagnosticKey<'a> {
+    let code = diagnostic.code.as_ref().map(|code| match code {
+        NumberOrString::Number(number) => number.to_string(),
+        NumberOrString::String(string) => string.clone(),
+    });
+    (path, diagnostic.range, code, &diagnostic.message)
+}
+
+/// Returns the diagnostics of `snapshot` that aren't in `other`, counting duplicates.
+fn missing_diagnostics<'a>(
+    snapshot: &'a DiagnosticsSnapshot,
+    other: &'a DiagnosticsSnapshot,
+) -> Vec<FileDiagnostics> {
+    let mut counts: HashMap<DiagnosticKey<'a>, usize> = HashMap::new();
+    for file in &other.files {
+        for diagnostic in &file.diagnostics {
+            *counts
+                .entry(diagnostic_key(&file.path, diagnostic))
+                .or_default() += 1;
+        }
+    }
+    snapshot
+        .files
+        .iter()
+        .filter_map(|file| {
+            let diagnostics: Vec<Diagnostic> = file
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| {
+                    match counts.get_mut(&diagnostic_key(&file.path, diagnostic)) {
+                        Some(count) if *count > 0 => {
+                            *count -= 1;
+                            false
+                        }
+                        _ => true,
+                    }
+                })
+                .cloned()
+                .collect();
+            (!diagnostics.is_empty()).then(|| FileDiagnostics {
+                path: file.path.clone(),
+                diagnostics,
+            })
+        })
+        .collect()
+}
+
+/// Returns a warning for each storage field that is never read or written anywhere in the project.
+pub(crate) fn get_unused_storage_field_diagnostics(
+    token_map: &TokenMap,
+    engines: &Engines,
+    encoding: PositionEncoding,
+) -> HashMap<PathBuf, Vec<Diagnostic>> {
+    let mut diagnostics: HashMap<PathBuf, Vec<Diagnostic>> = HashMap::new();
+    let storage_fields = token_map
+        .iter()
+        .filter_map(|(ident, token)| match &token.typed {
+            Some(TypedAstToken::TypedStorageField(field)) => {
+                Some((ident, token.clone(), field.clone()))
+            }
+            _ => None,
+        });
+    for (ident, token, field) in storage_fields {
+        let Some(path) = ident.path.clone() else {
+            continue;
+        };
+        let is_accessed = token_map
+            .iter()
+            .all_references_of_token(&token, engines, encoding)
+            .any(|(reference, _)| reference != ident);
+        if is_accessed {
+            continue;
+        }
+        // Remove the whole lines of the field, including its trailing comma.
+        let field_range = get_range_from_span(&field.span(), encoding);
+        let removal_range = Range {
+            start: Position::new(field_range.start.line, 0),
+            end: Position::new(field_range.end.line + 1, 0),
+        };
+        let data = DiagnosticData {
+            unused_storage_field: Some(UnusedStorageField {
+                name: ident.name.clone(),
+                removal_range,
+            }),
+            ..Default::default()
+        };
+        diagnostics.entry(path).or_default().push(Diagnostic {
+            range: ident.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String("unused_storage_field".to_string())),
+            message: format!("Storage field `{}` is never read or written.", ident.name),
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            data: serde_json::to_value(data).ok(),
+            ..Default::default()
+        });
+    }
+    diagnostics
+}
+
+/// Returns the code of the warning's category, which can be used to disable the warning
+/// with the `diagnostic.disabledCodes` setting.
+fn get_warning_code(warning: &Warning) -> &'static str {
+    match warning {
+        Warning::NonClassCaseStructName { .. }
+        | Warning::NonClassCaseTypeParameter { .. }
+        | Warning::NonClassCaseTraitName { .. }
+        | Warning::NonClassCaseEnumName { .. }
+        | Warning::NonClassCaseEnumVariantName { .. }
+        | Warning::NonSnakeCaseStructFieldName { .. }
+        | Warning::NonSnakeCaseFunctionName { .. }
+        | Warning::NonScreamingSnakeCaseConstName { .. } => "naming_convention",
+        Warning::UnusedReturnValue { .. } => "unused_return_value",
+        Warning::SimilarMethodFound { .. } => "similar_method",
+        Warning::ShadowsOtherSymbol { .. }
+        | Warning::UninitializedAsmRegShadowsVariable { .. }
+        | Warning::ShadowingReservedRegister { .. } => "shadowing",
+        Warning::OverridingTraitImplementation => "overriding_trait_implementation",
+        Warning::StructFieldNeverRead
+        | Warning::DeadDeclaration
+        | Warning::DeadEnumDeclaration
+        | Warning::DeadEnumVariant { .. }
+        | Warning::DeadFunctionDeclaration
+        | Warning::DeadMethod
+        | Warning::DeadStorageDeclaration
+        | Warning::DeadStorageDeclarationForFunction { .. }
+        | Warning::DeadStructDeclaration
+        | Warning::DeadTrait => "dead_code",
+        Warning::UnreachableCode | Warning::MatchExpressionUnreachableArm { .. } => {
+            "unreachable_code"
+        }
+        Warning::UnrecognizedAttribute { .. }
+        | Warning::AttributeExpectedNumberOfArguments { .. }
+        | Warning::UnexpectedAttributeArgumentValue { .. } => "attribute",
+        Warning::EffectAfterInteraction { .. } => "effect_after_interaction",
+        Warning::ModulePrivacyDisabled => "module_privacy_disabled",
+        Warning::UsingDeprecated { .. } => "deprecated",
+    }
 }
 
 fn get_warning_diagnostic_tags(warning: &Warning) -> Option<Vec<DiagnosticTag>> {
@@ -95,6 +500,15 @@ fn get_warning_diagnostic_tags(warning: &Warning) -> Option<Vec<DiagnosticTag>>
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DiagnosticData {
     pub unknown_symbol_name: Option<String>,
+    pub immutable_variable_name: Option<String>,
+    pub unused_storage_field: Option<UnusedStorageField>,
+}
+
+/// A storage field that is never accessed, along with the range to remove to delete it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnusedStorageField {
+    pub name: String,
+    pub removal_range: Range,
 }
 
 impl TryFrom<CompileWarning> for DiagnosticData {
@@ -112,14 +526,169 @@ impl TryFrom<CompileError> for DiagnosticData {
         match value {
             CompileError::SymbolNotFound { name, .. } => Ok(DiagnosticData {
                 unknown_symbol_name: Some(name.to_string()),
+                ..Default::default()
             }),
             CompileError::TraitNotFound { name, .. } => Ok(DiagnosticData {
                 unknown_symbol_name: Some(name),
+                ..Default::default()
             }),
             CompileError::UnknownVariable { var_name, .. } => Ok(DiagnosticData {
                 unknown_symbol_name: Some(var_name.to_string()),
+                ..Default::default()
+            }),
+            CompileError::AssignmentToNonMutable { name, .. } => Ok(DiagnosticData {
+                immutable_variable_name: Some(name.to_string()),
+                ..Default::default()
             }),
             _ => anyhow::bail!("Not implemented"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_diagnostics_are_removed_in_order() {
+        let diagnostic = |line, code: &str, message: &str| Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 4)),
+            code: Some(NumberOrString::String(code.to_string())),
+            message: message.to_string(),
+            ..Default::default()
+        };
+        let mut diagnostics = vec![
+            diagnostic(1, "dead_code", "unused"),
+            diagnostic(2, "dead_code", "unused"),
+            diagnostic(1, "dead_code", "unused"),
+            diagnostic(1, "dead_code", "never read"),
+            diagnostic(2, "dead_code", "unused"),
+        ];
+        deduplicate(&mut diagnostics);
+        assert_eq!(
+            diagnostics,
+            vec![
+                diagnostic(1, "dead_code", "unused"),
+                diagnostic(2, "dead_code", "unused"),
+                diagnostic(1, "dead_code", "never read"),
+            ]
+        );
+    }
+
+    #[test]
+    fn failures_only_replace_the_diagnostics_of_files_with_errors() {
+        let diagnostic = |message: &str| Diagnostic {
+            message: message.to_string(),
+            ..Default::default()
+        };
+        let last_successful = DiagnosticMap::from([
+            (
+                PathBuf::from("src/main.sw"),
+                Diagnostics {
+                    warnings: vec![diagnostic("unused variable")],
+                    errors: vec![],
+                },
+            ),
+            (
+                PathBuf::from("src/lib.sw"),
+                Diagnostics {
+                    warnings: vec![diagnostic("dead code")],
+                    errors: vec![diagnostic("type mismatch")],
+                },
+            ),
+        ]);
+        let failed = DiagnosticMap::from([
+            (
+                PathBuf::from("src/main.sw"),
+                Diagnostics {
+                    warnings: vec![],
+                    errors: vec![diagnostic("expected `;`")],
+                },
+            ),
+            (
+                PathBuf::from("src/other.sw"),
+                Diagnostics {
+                    warnings: vec![diagnostic("unreachable")],
+                    errors: vec![],
+                },
+            ),
+        ]);
+        let diagnostics = retain_unaffected_files(&last_successful, failed);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[Path::new("src/main.sw")].errors,
+            vec![diagnostic("expected `;`")]
+        );
+        assert!(diagnostics[Path::new("src/main.sw")].warnings.is_empty());
+        assert_eq!(
+            diagnostics[Path::new("src/lib.sw")].errors,
+            vec![diagnostic("type mismatch")]
+        );
+    }
+
+    #[test]
+    fn pulled_diagnostics_are_unchanged_until_they_differ() {
+        let pulled = PulledDiagnostics::default();
+        let uri = Url::parse("file:///project/src/main.sw").unwrap();
+        let warning = Diagnostic {
+            message: "unused".to_string(),
+            ..Default::default()
+        };
+        let DocumentDiagnosticReport::Full(first) =
+            pulled.report(&uri, None, vec![warning.clone()])
+        else {
+            panic!("expected a full report");
+        };
+        let result_id = first.full_document_diagnostic_report.result_id.unwrap();
+
+        assert!(matches!(
+            pulled.report(&uri, Some(&result_id), vec![warning.clone()]),
+            DocumentDiagnosticReport::Unchanged(_)
+        ));
+        // A client without the last result gets the full report again.
+        assert!(matches!(
+            pulled.report(&uri, Some("stale"), vec![warning]),
+            DocumentDiagnosticReport::Full(_)
+        ));
+        let DocumentDiagnosticReport::Full(cleared) = pulled.report(&uri, Some(&result_id), vec![])
+        else {
+            panic!("expected a full report");
+        };
+        assert!(cleared.full_document_diagnostic_report.items.is_empty());
+        assert_ne!(
+            cleared.full_document_diagnostic_report.result_id,
+            Some(result_id)
+        );
+    }
+
+    #[test]
+    fn diff_lists_added_and_removed_diagnostics() {
+        let diagnostic = |line, message: &str| Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+            message: message.to_string(),
+            ..Default::default()
+        };
+        let snapshot = |diagnostics| DiagnosticsSnapshot {
+            files: vec![FileDiagnostics {
+                path: PathBuf::from("src/main.sw"),
+                diagnostics,
+            }],
+        };
+        let mut history = DiagnosticsHistory::default();
+        history.record(snapshot(vec![diagnostic(1, "a"), diagnostic(2, "b")]));
+        assert!(history.diff().is_none());
+
+        history.record(snapshot(vec![
+            diagnostic(2, "b"),
+            diagnostic(3, "c"),
+            diagnostic(3, "c"),
+        ]));
+        let diff = history.diff().unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(
+            diff.added[0].diagnostics,
+            vec![diagnostic(3, "c"), diagnostic(3, "c")]
+        );
+        assert_eq!(diff.removed[0].diagnostics, vec![diagnostic(1, "a")]);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::core::{
+    session::Session,
+    token::{get_range_from_span, AstToken},
+};
+use forc_pkg::PackageManifestFile;
+use lsp_types::{DocumentLink, Url};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use sway_core::language::parsed::UseStatement;
+use sway_types::{constants::DEFAULT_FILE_EXTENSION, Ident, Spanned};
+
+/// Returns a link for each module name in the `mod` and `use` statements of the file,
+/// targeting the file of the module it names.
+///
+/// The first segment of a `use` path can also name a `path` dependency of the project, which then
+/// links to the dependency's entry file. Names that can't be resolved to an existing file don't get a link.
+pub fn document_links(session: Arc<Session>, url: &Url) -> Option<Vec<DocumentLink>> {
+    let module = url.to_file_path().ok()?;
+    let manifest = PackageManifestFile::from_dir(session.sync.temp_dir().ok()?).ok()?;
+    let entry = manifest.entry_path();
+    let encoding = session.position_encoding();
+    let mut links: Vec<DocumentLink> = session
+        .token_map()
+        .tokens_for_file(url)
+        .filter_map(|(ident, token)| {
+            let target = match &token.parsed {
+                AstToken::IncludeStatement(_) | AstToken::ModuleName => {
+                    submodule_path(&entry, &module, &ident.name)?
+                }
+                AstToken::UseStatement(use_statement) => {
+                    let len = use_statement.call_path.iter().position(|prefix| {
+                        get_range_from_span(&prefix.span(), encoding) == ident.range
+                    })? + 1;
+                    use_path_target(&manifest, &module, use_statement, len)?
+                }
+                _ => return None,
+            };
+            let target = Url::from_file_path(target).ok()?;
+            Some(DocumentLink {
+                range: ident.range,
+                target: session.sync.to_workspace_url(target),
+                tooltip: None,
+                data: None,
+            })
+        })
+        .collect();
+    links.sort_by_key(|link| (link.range.start.line, link.range.start.character));
+    Some(links)
+}
+
+/// Returns the file of the module named by the first `len` segments of the path of the `use` statement,
+/// which is in the `module` file.
+fn use_path_target(
+    manifest: &PackageManifestFile,
+    module: &Path,
+    use_statement: &UseStatement,
+    len: usize,
+) -> Option<PathBuf> {
+    let (first, rest) = use_statement.call_path[..len].split_first()?;
+    let project_entry = manifest.entry_path();
+    // Absolute paths start at the root of the project, while relative paths start in the current module,
+    // unless they name a dependency.
+    let (entry, first_module) = if use_statement.is_absolute {
+        let path = submodule_path(&project_entry, &project_entry, first.as_str())?;
+        (project_entry, path)
+    } else if let Some(path) = submodule_path(&project_entry, module, first.as_str()) {
+        (project_entry, path)
+    } else {
+        let dependency = manifest.dep_path(first.as_str())?;
+        let entry = PackageManifestFile::from_dir(dependency).ok()?.entry_path();
+        (entry.clone(), entry)
+    };
+    resolve_submodules(&entry, first_module, rest)
+}
+
+/// Follows the `names` of nested submodules, starting at the `module` file.
+fn resolve_submodules(entry: &Path, module: PathBuf, names: &[Ident]) -> Option<PathBuf> {
+    names.iter().try_fold(module, |module, name| {
+        submodule_path(entry, &module, name.as_str())
+    })
+}
+
+/// Returns the file of the submodule `name` of the `module` file, if it exists.
+///
+/// The submodules of a package's `entry` file are next to it, while those of any other module
+/// are in a directory with the name of the module, e.g. `src/foo/bar.sw` for `mod bar;` in `src/foo.sw`.
+fn submodule_path(entry: &Path, module: &Path, name: &str) -> Option<PathBuf> {
+    let dir = module.parent()?;
+    let dir = if module == entry {
+        dir.to_path_buf()
+    } else {
+        dir.join(module.file_stem()?)
+    };
+    let path = dir.join(name).with_extension(DEFAULT_FILE_EXTENSION);
+    path.is_file().then_some(path)
+}
@@ -1,5 +1,20 @@
-use crate::core::token::{SymbolKind, Token, TokenIdent};
-use lsp_types::{self, Location, SymbolInformation, Url};
+use crate::{
+    capabilities::semantic_tokens::is_declaration,
+    core::{
+        session::Session,
+        token::{get_range_from_span, SymbolKind, Token, TokenIdent},
+    },
+    lsp_ext::KindSymbol,
+    utils::position_encoding::PositionEncoding,
+};
+use lsp_types::{self, DocumentSymbol, Location, Position, SymbolInformation, Url};
+use std::sync::Arc;
+use sway_ast::{
+    attribute::Annotated, CommaToken, ItemFn, ItemImplItem, ItemKind, ItemTraitItem, Module,
+    Punctuated, Submodule, TypeField,
+};
+use sway_error::handler::Handler;
+use sway_types::{Ident, Span, Spanned};
 
 pub fn to_symbol_information<I>(tokens: I, url: Url) -> Vec<SymbolInformation>
 where
@@ -15,6 +30,419 @@ where
     symbols
 }
 
+/// Returns the declarations in the file whose symbol kind is `kind`, sorted by their position, with their
+/// locations in the workspace.
+///
+/// Unlike [document_symbols], these are the declarations of the token map, so the file must have been compiled.
+pub fn symbols_by_kind(
+    session: &Session,
+    url: &Url,
+    kind: lsp_types::SymbolKind,
+) -> Option<Vec<KindSymbol>> {
+    let workspace_url = session.sync.to_workspace_url(url.clone())?;
+    let engines = session.engines.read();
+    let encoding = session.position_encoding();
+    let mut symbols: Vec<_> = session
+        .token_map()
+        .tokens_for_file(url)
+        .filter(|(ident, token)| {
+            symbol_kind(&token.kind) == kind && is_declaration(ident, token, &engines, encoding)
+        })
+        .map(|(ident, _)| KindSymbol {
+            name: ident.name,
+            location: Location::new(workspace_url.clone(), ident.range),
+        })
+        .collect();
+    symbols.sort_by_key(|symbol| symbol.location.range.start);
+    Some(symbols)
+}
+
+/// Whether `name` begins with `_`, which hides it unless the `includeUnderscoreItems` options are set.
+pub fn is_underscore_name(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+/// Removes the symbols whose names begin with `_` from the outline, along with those nested in them.
+pub fn remove_underscore_symbols(symbols: &mut Vec<DocumentSymbol>) {
+    symbols.retain(|symbol| !is_underscore_name(&symbol.name));
+    for symbol in symbols {
+        if let Some(children) = &mut symbol.children {
+            remove_underscore_symbols(children);
+        }
+    }
+}
+
+/// Returns the outline of the file: its items, with the fields of structs, the variants of enums
+/// and the methods of traits, abis and impls nested in them.
+///
+/// The outline is built by parsing the current contents of the file, so it doesn't need the project
+/// to be compiled and is available while the file has type errors.
+pub fn document_symbols(session: Arc<Session>, url: &Url) -> Option<Vec<DocumentSymbol>> {
+    let text = session.get_text_document(url).ok()?.get_text();
+    outline_of_text(&text, session.position_encoding())
+}
+
+/// Returns the outline of a file with the contents `text`, like [document_symbols], or `None` if it doesn't parse.
+pub(crate) fn outline_of_text(
+    text: &str,
+    encoding: PositionEncoding,
+) -> Option<Vec<DocumentSymbol>> {
+    let module = sway_parse::parse_file(&Handler::default(), text.into(), None).ok()?;
+    Some(module_symbols(&module.value, encoding))
+}
+
+/// Returns the innermost item of the outline that contains `position`, along with the name of the item
+/// it's nested in, if any, or `None` if the position is outside of every item.
+///
+/// Fields and enum variants aren't items of their own, so a position in one is in its struct or enum.
+pub fn enclosing_symbol(
+    symbols: &[DocumentSymbol],
+    position: Position,
+) -> Option<(&DocumentSymbol, Option<&str>)> {
+    let symbol = symbols.iter().find(|symbol| {
+        symbol.range.start <= position
+            && position <= symbol.range.end
+            && !matches!(
+                symbol.kind,
+                lsp_types::SymbolKind::FIELD | lsp_types::SymbolKind::ENUM_MEMBER
+            )
+    })?;
+    match symbol
+        .children
+        .as_deref()
+        .and_then(|children| enclosing_symbol(children, position))
+    {
+        Some((child, Some(container_name))) => Some((child, Some(container_name))),
+        Some((child, None)) => Some((child, Some(symbol.name.as_str()))),
+        None => Some((symbol, None)),
+    }
+}
+
+fn module_symbols(module: &Module, encoding: PositionEncoding) -> Vec<DocumentSymbol> {
+    module
+        .items
+        .iter()
+        .filter_map(|item| {
+            let span = item.span();
+            let symbol = match &item.value {
+                ItemKind::Submodule(Submodule { name, .. }) => document_symbol(
+                    name,
+                    lsp_types::SymbolKind::MODULE,
+                    None,
+                    &span,
+                    None,
+                    encoding,
+                ),
+                ItemKind::Struct(item_struct) => document_symbol(
+                    &item_struct.name,
+                    lsp_types::SymbolKind::STRUCT,
+                    None,
+                    &span,
+                    Some(field_symbols(
+                        item_struct.fields.get(),
+                        lsp_types::SymbolKind::FIELD,
+                        encoding,
+                    )),
+                    encoding,
+                ),
+                ItemKind::Enum(item_enum) => document_symbol(
+                    &item_enum.name,
+                    lsp_types::SymbolKind::ENUM,
+                    None,
+                    &span,
+                    Some(field_symbols(
+                        item_enum.fields.get(),
+                        lsp_types::SymbolKind::ENUM_MEMBER,
+                        encoding,
+                    )),
+                    encoding,
+                ),
+                ItemKind::Fn(item_fn) => {
+                    fn_symbol(item_fn, lsp_types::SymbolKind::FUNCTION, &span, encoding)
+                }
+                ItemKind::Trait(item_trait) => {
+                    let mut children = trait_item_symbols(item_trait.trait_items.get(), encoding);
+                    if let Some(trait_defs) = &item_trait.trait_defs_opt {
+                        children.extend(method_symbols(trait_defs.get(), encoding));
+                    }
+                    document_symbol(
+                        &item_trait.name,
+                        lsp_types::SymbolKind::INTERFACE,
+                        None,
+                        &span,
+                        Some(children),
+                        encoding,
+                    )
+                }
+                ItemKind::Abi(item_abi) => {
+                    let mut children = trait_item_symbols(item_abi.abi_items.get(), encoding);
+                    if let Some(abi_defs) = &item_abi.abi_defs_opt {
+                        children.extend(method_symbols(abi_defs.get(), encoding));
+                    }
+                    document_symbol(
+                        &item_abi.name,
+                        lsp_types::SymbolKind::INTERFACE,
+                        None,
+                        &span,
+                        Some(children),
+                        encoding,
+                    )
+                }
+                ItemKind::Impl(item_impl) => {
+                    // Impls have no name, so they're named after what they implement, e.g. `impl Foo for Bar`.
+                    let name = match &item_impl.trait_opt {
+                        Some((trait_name, _)) => format!(
+                            "impl {} for {}",
+                            trait_name.span().as_str(),
+                            item_impl.ty.span().as_str()
+                        ),
+                        None => format!("impl {}", item_impl.ty.span().as_str()),
+                    };
+                    let children = item_impl
+                        .contents
+                        .get()
+                        .iter()
+                        .map(|item| match &item.value {
+                            ItemImplItem::Fn(item_fn) => fn_symbol(
+                                item_fn,
+                                lsp_types::SymbolKind::METHOD,
+                                &item_fn.span(),
+                                encoding,
+                            ),
+                            ItemImplItem::Const(item_const) => document_symbol(
+                                &item_const.name,
+                                lsp_types::SymbolKind::CONSTANT,
+                                None,
+                                &item_const.span(),
+                                None,
+                                encoding,
+                            ),
+                            ItemImplItem::Type(trait_type) => document_symbol(
+                                &trait_type.name,
+                                lsp_types::SymbolKind::TYPE_PARAMETER,
+                                None,
+                                &trait_type.span(),
+                                None,
+                                encoding,
+                            ),
+                        })
+                        .collect();
+                    named_document_symbol(
+                        name,
+                        &item_impl.ty.span(),
+                        lsp_types::SymbolKind::OBJECT,
+                        None,
+                        &span,
+                        Some(children),
+                        encoding,
+                    )
+                }
+                ItemKind::Const(item_const) => document_symbol(
+                    &item_const.name,
+                    lsp_types::SymbolKind::CONSTANT,
+                    item_const
+                        .ty_opt
+                        .as_ref()
+                        .map(|(_, ty)| ty.span().as_str().to_string()),
+                    &span,
+                    None,
+                    encoding,
+                ),
+                ItemKind::Storage(item_storage) => {
+                    let children = item_storage
+                        .fields
+                        .get()
+                        .into_iter()
+                        .map(|field| {
+                            document_symbol(
+                                &field.value.name,
+                                lsp_types::SymbolKind::FIELD,
+                                Some(field.value.ty.span().as_str().to_string()),
+                                &field.value.span(),
+                                None,
+                                encoding,
+                            )
+                        })
+                        .collect();
+                    let keyword = item_storage.storage_token.span();
+                    named_document_symbol(
+                        keyword.as_str().to_string(),
+                        &keyword,
+                        lsp_types::SymbolKind::NAMESPACE,
+                        None,
+                        &span,
+                        Some(children),
+                        encoding,
+                    )
+                }
+                ItemKind::Configurable(item_configurable) => {
+                    let children = item_configurable
+                        .fields
+                        .get()
+                        .into_iter()
+                        .map(|field| {
+                            document_symbol(
+                                &field.value.name,
+                                lsp_types::SymbolKind::CONSTANT,
+                                Some(field.value.ty.span().as_str().to_string()),
+                                &field.value.span(),
+                                None,
+                                encoding,
+                            )
+                        })
+                        .collect();
+                    let keyword = item_configurable.configurable_token.span();
+                    named_document_symbol(
+                        keyword.as_str().to_string(),
+                        &keyword,
+                        lsp_types::SymbolKind::NAMESPACE,
+                        None,
+                        &span,
+                        Some(children),
+                        encoding,
+                    )
+                }
+                ItemKind::TypeAlias(item_type_alias) => document_symbol(
+                    &item_type_alias.name,
+                    lsp_types::SymbolKind::TYPE_PARAMETER,
+                    None,
+                    &span,
+                    None,
+                    encoding,
+                ),
+                ItemKind::Use(_) | ItemKind::Error(_, _) => return None,
+            };
+            Some(symbol)
+        })
+        .collect()
+}
+
+/// Returns the symbols of the fields of a struct, or of the variants of an enum.
+fn field_symbols(
+    fields: &Punctuated<Annotated<TypeField>, CommaToken>,
+    kind: lsp_types::SymbolKind,
+    encoding: PositionEncoding,
+) -> Vec<DocumentSymbol> {
+    fields
+        .into_iter()
+        .map(|field| {
+            document_symbol(
+                &field.value.name,
+                kind,
+                Some(field.value.ty.span().as_str().to_string()),
+                &field.value.span(),
+                None,
+                encoding,
+            )
+        })
+        .collect()
+}
+
+/// Returns the symbols of the declarations in the body of a trait or an abi.
+fn trait_item_symbols(
+    items: &[Annotated<ItemTraitItem>],
+    encoding: PositionEncoding,
+) -> Vec<DocumentSymbol> {
+    items
+        .iter()
+        .filter_map(|item| match &item.value {
+            ItemTraitItem::Fn(fn_signature, _) => Some(document_symbol(
+                &fn_signature.name,
+                lsp_types::SymbolKind::METHOD,
+                None,
+                &fn_signature.span(),
+                None,
+                encoding,
+            )),
+            ItemTraitItem::Const(item_const, _) => Some(document_symbol(
+                &item_const.name,
+                lsp_types::SymbolKind::CONSTANT,
+                None,
+                &item_const.span(),
+                None,
+                encoding,
+            )),
+            ItemTraitItem::Type(trait_type, _) => Some(document_symbol(
+                &trait_type.name,
+                lsp_types::SymbolKind::TYPE_PARAMETER,
+                None,
+                &trait_type.span(),
+                None,
+                encoding,
+            )),
+            ItemTraitItem::Error(_, _) => None,
+        })
+        .collect()
+}
+
+/// Returns the symbols of the methods that a trait or an abi provides.
+fn method_symbols(items: &[Annotated<ItemFn>], encoding: PositionEncoding) -> Vec<DocumentSymbol> {
+    items
+        .iter()
+        .map(|item| {
+            fn_symbol(
+                &item.value,
+                lsp_types::SymbolKind::METHOD,
+                &item.value.span(),
+                encoding,
+            )
+        })
+        .collect()
+}
+
+fn fn_symbol(
+    item_fn: &ItemFn,
+    kind: lsp_types::SymbolKind,
+    span: &Span,
+    encoding: PositionEncoding,
+) -> DocumentSymbol {
+    document_symbol(&item_fn.fn_signature.name, kind, None, span, None, encoding)
+}
+
+/// Builds a symbol whose `range` is the whole `span` of the item and whose `selection_range` is its `name`.
+fn document_symbol(
+    name: &Ident,
+    kind: lsp_types::SymbolKind,
+    detail: Option<String>,
+    span: &Span,
+    children: Option<Vec<DocumentSymbol>>,
+    encoding: PositionEncoding,
+) -> DocumentSymbol {
+    named_document_symbol(
+        name.as_str().to_string(),
+        &name.span(),
+        kind,
+        detail,
+        span,
+        children,
+        encoding,
+    )
+}
+
+/// Builds a symbol for an item that has no name of its own, such as an impl, with `selection_span`
+/// being the part of the item that identifies it.
+#[allow(deprecated)]
+fn named_document_symbol(
+    name: String,
+    selection_span: &Span,
+    kind: lsp_types::SymbolKind,
+    detail: Option<String>,
+    span: &Span,
+    children: Option<Vec<DocumentSymbol>>,
+    encoding: PositionEncoding,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: get_range_from_span(span, encoding),
+        selection_range: get_range_from_span(selection_span, encoding),
+        children,
+    }
+}
+
 /// Given a `token::SymbolKind`, return the `lsp_types::SymbolKind` that corresponds to it.
 pub(crate) fn symbol_kind(symbol_kind: &SymbolKind) -> lsp_types::SymbolKind {
     match symbol_kind {
@@ -58,3 +486,94 @@ fn symbol_info(ident: &TokenIdent, token: &Token, url: Url) -> SymbolInformation
         deprecated: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn outline(symbols: &[DocumentSymbol]) -> Vec<(String, lsp_types::SymbolKind, Vec<String>)> {
+        symbols
+            .iter()
+            .map(|symbol| {
+                let children = symbol
+                    .children
+                    .iter()
+                    .flatten()
+                    .map(|child| child.name.clone())
+                    .collect();
+                (symbol.name.clone(), symbol.kind, children)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn underscore_symbols_are_removed_with_their_members() {
+        let text = "library;\n\nstruct _Hidden {\n    x: u64,\n}\n\nstruct Point {\n    _x: u64,\n    y: u64,\n}\n\nfn _helper() {}\n";
+        let module = sway_parse::parse_file(&Handler::default(), text.into(), None).unwrap();
+        let mut symbols = module_symbols(&module.value, PositionEncoding::Utf16);
+        remove_underscore_symbols(&mut symbols);
+        assert_eq!(
+            outline(&symbols),
+            vec![(
+                "Point".to_string(),
+                lsp_types::SymbolKind::STRUCT,
+                vec!["y".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn module_symbols_nest_members() {
+        // The type error in `len` doesn't matter, as the file is only parsed.
+        let text = "library;\n\nstruct Point {\n    x: u64,\n    y: u64,\n}\n\nenum Color {\n    Red: (),\n}\n\n\
+            impl Point {\n    fn len(self) -> u64 { self.x + true }\n}\n\nfn main() {}\n";
+        let module = sway_parse::parse_file(&Handler::default(), text.into(), None).unwrap();
+        let symbols = module_symbols(&module.value, PositionEncoding::Utf16);
+        assert_eq!(
+            outline(&symbols),
+            vec![
+                (
+                    "Point".to_string(),
+                    lsp_types::SymbolKind::STRUCT,
+                    vec!["x".to_string(), "y".to_string()]
+                ),
+                (
+                    "Color".to_string(),
+                    lsp_types::SymbolKind::ENUM,
+                    vec!["Red".to_string()]
+                ),
+                (
+                    "impl Point".to_string(),
+                    lsp_types::SymbolKind::OBJECT,
+                    vec!["len".to_string()]
+                ),
+                ("main".to_string(), lsp_types::SymbolKind::FUNCTION, vec![]),
+            ]
+        );
+        assert_eq!(
+            symbols[0].selection_range,
+            Range::new(Position::new(2, 7), Position::new(2, 12))
+        );
+        assert_eq!(
+            symbols[0].range,
+            Range::new(Position::new(2, 0), Position::new(5, 1))
+        );
+    }
+
+    #[test]
+    fn enclosing_symbol_is_the_innermost_item() {
+        let text = "library;\n\nstruct Point {\n    x: u64,\n}\n\nimpl Point {\n    fn len(self) -> u64 {\n        self.x\n    }\n}\n";
+        let module = sway_parse::parse_file(&Handler::default(), text.into(), None).unwrap();
+        let symbols = module_symbols(&module.value, PositionEncoding::Utf16);
+        let enclosing = |line, character| {
+            enclosing_symbol(&symbols, Position::new(line, character))
+                .map(|(symbol, container_name)| (symbol.name.as_str(), container_name))
+        };
+        assert_eq!(enclosing(8, 10), Some(("len", Some("impl Point"))));
+        assert_eq!(enclosing(6, 2), Some(("impl Point", None)));
+        // A field is part of its struct.
+        assert_eq!(enclosing(3, 5), Some(("Point", None)));
+        assert_eq!(enclosing(1, 0), None);
+    }
+}
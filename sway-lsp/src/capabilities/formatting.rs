@@ -1,23 +1,279 @@
-use crate::error::LanguageServerError;
-use lsp_types::{Position, Range, TextEdit};
-use std::sync::Arc;
-use swayfmt::Formatter;
+use crate::{error::LanguageServerError, utils::position_encoding::PositionEncoding};
+use lsp_types::{FormattingOptions, Position, Range, TextEdit};
+use std::{path::Path, sync::Arc};
+use sway_types::Spanned;
+use sway_utils::find_parent_dir_with_file;
+use swayfmt::{parse::parse_file, Config, Formatter, FormatterError};
 
+/// The name of the file that configures the formatter of a project.
+const SWAY_FORMAT_FILE_NAME: &str = "swayfmt.toml";
+
+/// Returns the edits that format the text, which are empty if it's already formatted.
 pub fn get_page_text_edit(
     text: Arc<str>,
     formatter: &mut Formatter,
-) -> Result<TextEdit, LanguageServerError> {
+    encoding: PositionEncoding,
+) -> Result<Vec<TextEdit>, LanguageServerError> {
     // we only format if code is correct
     let formatted_code = formatter
         .format(text.clone(), None)
         .map_err(LanguageServerError::FormatError)?;
 
-    let text_lines_count = text.split('\n').count();
-    let num_of_lines = formatted_code.split('\n').count();
-    let line_end = std::cmp::max(num_of_lines, text_lines_count) as u32;
+    Ok(minimal_text_edit(&text, &formatted_code, encoding)
+        .into_iter()
+        .collect())
+}
+
+/// Returns the edits that format the top-level items overlapping the `range` of the text,
+/// which are empty if there are none or they're already formatted.
+///
+/// The formatter only works on whole files, so the whole text is formatted and the formatted items
+/// are matched up with the original ones by their position in the file.
+pub fn get_range_text_edit(
+    text: Arc<str>,
+    range: Range,
+    formatter: &mut Formatter,
+    encoding: PositionEncoding,
+) -> Result<Vec<TextEdit>, LanguageServerError> {
+    let formatted_code: Arc<str> = formatter
+        .format(text.clone(), None)
+        .map_err(LanguageServerError::FormatError)?
+        .into();
+    let item_spans = |code: &Arc<str>| {
+        parse_file(&formatter.source_engine, code.clone(), None)
+            .map(|module| {
+                module
+                    .value
+                    .items
+                    .iter()
+                    .map(|item| {
+                        let span = item.span();
+                        (span.start(), span.end())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|err| LanguageServerError::FormatError(FormatterError::from(err)))
+    };
+    let items = item_spans(&text)?;
+    let formatted_items = item_spans(&formatted_code)?;
+    if items.len() != formatted_items.len() {
+        tracing::warn!("Unable to match the formatted items with the original ones");
+        return Ok(vec![]);
+    }
+
+    let (start, end) = (
+        offset_at(&text, range.start, encoding),
+        offset_at(&text, range.end, encoding),
+    );
+    let mut selected = items
+        .iter()
+        .enumerate()
+        .filter(|(_, (item_start, item_end))| *item_start <= end && *item_end >= start)
+        .map(|(i, _)| i);
+    let Some(first) = selected.next() else {
+        return Ok(vec![]);
+    };
+    let last = selected.last().unwrap_or(first);
+    let partially_formatted = format!(
+        "{}{}{}",
+        &text[..items[first].0],
+        &formatted_code[formatted_items[first].0..formatted_items[last].1],
+        &text[items[last].1..]
+    );
+    Ok(minimal_text_edit(&text, &partially_formatted, encoding)
+        .into_iter()
+        .collect())
+}
+
+/// Returns the formatter for the project in `manifest_dir`.
+///
+/// A project's `swayfmt.toml` takes precedence over the client's [FormattingOptions],
+/// which only set the indentation of projects without one.
+pub fn project_formatter(manifest_dir: &Path, options: &FormattingOptions) -> Formatter {
+    let config = if find_parent_dir_with_file(manifest_dir, SWAY_FORMAT_FILE_NAME).is_some() {
+        Config::from_dir(manifest_dir).unwrap_or_else(|err| {
+            tracing::warn!(
+                "Unable to read the {} of {}: {}",
+                SWAY_FORMAT_FILE_NAME,
+                manifest_dir.display(),
+                err
+            );
+            Config::default()
+        })
+    } else {
+        let mut config = Config::default();
+        if options.tab_size > 0 {
+            config.whitespace.tab_spaces = options.tab_size as usize;
+        }
+        config.whitespace.hard_tabs = !options.insert_spaces;
+        config
+    };
+    Formatter {
+        config,
+        ..Default::default()
+    }
+}
 
-    Ok(TextEdit {
-        range: Range::new(Position::new(0, 0), Position::new(line_end, 0)),
-        new_text: formatted_code,
+/// Returns the edit that replaces the part of `text` that differs from `formatted`,
+/// or `None` if they're the same.
+///
+/// Only the span between the longest common prefix and suffix is replaced, so the client
+/// can keep the cursor and folds of the unchanged parts of the document.
+fn minimal_text_edit(text: &str, formatted: &str, encoding: PositionEncoding) -> Option<TextEdit> {
+    let prefix: usize = text
+        .chars()
+        .zip(formatted.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    if prefix == text.len() && prefix == formatted.len() {
+        return None;
+    }
+    let suffix: usize = text[prefix..]
+        .chars()
+        .rev()
+        .zip(formatted[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    Some(TextEdit {
+        range: Range::new(
+            position_at(text, prefix, encoding),
+            position_at(text, text.len() - suffix, encoding),
+        ),
+        new_text: formatted[prefix..formatted.len() - suffix].to_string(),
     })
 }
+
+/// Returns the [Position] of the byte `offset` in `text`.
+fn position_at(text: &str, offset: usize, encoding: PositionEncoding) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        encoding.len(&before[line_start..]),
+    )
+}
+
+/// Returns the byte offset of the `position` in `text`, clamped to the end of its line.
+pub(crate) fn offset_at(text: &str, position: Position, encoding: PositionEncoding) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    line_start + encoding.byte_offset(line, position.character)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, edit: &TextEdit) -> String {
+        let offset = |position: Position| {
+            let line_start: usize = text
+                .split_inclusive('\n')
+                .take(position.line as usize)
+                .map(str::len)
+                .sum();
+            line_start
+                + text[line_start..]
+                    .chars()
+                    .take(position.character as usize)
+                    .map(char::len_utf8)
+                    .sum::<usize>()
+        };
+        let (start, end) = (offset(edit.range.start), offset(edit.range.end));
+        format!("{}{}{}", &text[..start], edit.new_text, &text[end..])
+    }
+
+    #[test]
+    fn minimal_text_edit_is_none_for_formatted_text() {
+        assert_eq!(
+            minimal_text_edit("fn main() {}\n", "fn main() {}\n", PositionEncoding::Utf16),
+            None
+        );
+    }
+
+    #[test]
+    fn minimal_text_edit_only_replaces_the_changes() {
+        let text = "fn main() {\n  let x = 1;\n}\n";
+        let formatted = "fn main() {\n    let x = 1;\n}\n";
+        let edit = minimal_text_edit(text, formatted, PositionEncoding::Utf16).unwrap();
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 2), Position::new(1, 2))
+        );
+        assert_eq!(edit.new_text, "  ");
+        assert_eq!(apply(text, &edit), formatted);
+    }
+
+    #[test]
+    fn minimal_text_edit_handles_removed_lines() {
+        let text = "fn a() {}\n\n\n\nfn b() {}";
+        let formatted = "fn a() {}\n\nfn b() {}\n";
+        let edit = minimal_text_edit(text, formatted, PositionEncoding::Utf16).unwrap();
+        assert_eq!(apply(text, &edit), formatted);
+    }
+
+    #[test]
+    fn offset_at_is_the_inverse_of_position_at() {
+        let text = "a\nbé c\n";
+        let utf16 = PositionEncoding::Utf16;
+        for offset in [0, 1, 2, 3, 5, 6, 8] {
+            assert_eq!(
+                offset_at(text, position_at(text, offset, utf16), utf16),
+                offset
+            );
+        }
+        assert_eq!(offset_at(text, Position::new(0, 9), utf16), 1);
+        assert_eq!(offset_at(text, Position::new(5, 0), utf16), text.len());
+    }
+
+    #[test]
+    fn range_formatting_only_formats_the_selected_items() {
+        let text = "library;\n\nfn a() {\n  let x = 1;\n}\n\nfn b() {\n  let y = 2;\n}\n";
+        let mut formatter = Formatter::default();
+        // The cursor is in the body of `b`.
+        let range = Range::new(Position::new(7, 3), Position::new(7, 3));
+        let edits = get_range_text_edit(
+            Arc::from(text),
+            range,
+            &mut formatter,
+            PositionEncoding::Utf16,
+        )
+        .unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            apply(text, &edits[0]),
+            "library;\n\nfn a() {\n  let x = 1;\n}\n\nfn b() {\n    let y = 2;\n}\n"
+        );
+
+        // Nothing is formatted if the range is between the items.
+        let range = Range::new(Position::new(5, 0), Position::new(5, 0));
+        let edits = get_range_text_edit(
+            Arc::from(text),
+            range,
+            &mut formatter,
+            PositionEncoding::Utf16,
+        )
+        .unwrap();
+        assert!(edits.is_empty());
+    }
+
+    #[test]
+    fn formatting_options_apply_without_a_project_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = FormattingOptions {
+            tab_size: 2,
+            insert_spaces: false,
+            ..Default::default()
+        };
+        let formatter = project_formatter(dir.path(), &options);
+        assert_eq!(formatter.config.whitespace.tab_spaces, 2);
+        assert!(formatter.config.whitespace.hard_tabs);
+    }
+}
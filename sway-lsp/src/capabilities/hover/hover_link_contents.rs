@@ -77,7 +77,7 @@ impl<'a> HoverLinkContents<'a> {
             if let Ok(url) = converted_url {
                 uri = url;
             }
-            let range = get_range_from_span(span);
+            let range = get_range_from_span(span, self.session.position_encoding());
             self.related_types.push(RelatedType {
                 name,
                 uri,
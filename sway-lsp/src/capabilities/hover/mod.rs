@@ -20,49 +20,71 @@ use sway_types::{Span, Spanned};
 
 use self::hover_link_contents::HoverLinkContents;
 
-/// Extracts the hover information for a token at the current position.
-pub fn hover_data(
-    session: Arc<Session>,
+/// Returns the documentation of the keyword at the current position, if there is one.
+///
+/// This doesn't depend on the types of the program, so it can be answered while the project is compiling.
+pub fn keyword_hover(
+    session: &Session,
     keyword_docs: &KeywordDocs,
-    url: Url,
+    url: &Url,
     position: Position,
 ) -> Option<lsp_types::Hover> {
-    let (ident, token) = session.token_map().token_at_position(&url, position)?;
-    let range = ident.range;
+    let (ident, token) = session.token_at_position(url, position)?;
+    if !is_keyword(&token) {
+        return None;
+    }
+    let name = &ident.name;
+    let documentation = keyword_docs.get(name)?;
+    let prefix = format!("\n```sway\n{name}\n```\n\n---\n\n");
+    let formatted_doc = format!("{prefix}{documentation}");
+    let content = Markup::new().text(&formatted_doc);
+    let contents = lsp_types::HoverContents::Markup(markup_content(content));
+    Some(lsp_types::Hover {
+        contents,
+        range: Some(ident.range),
+    })
+}
 
-    // check if our token is a keyword
-    if matches!(
+fn is_keyword(token: &Token) -> bool {
+    matches!(
         token.kind,
         SymbolKind::BoolLiteral
             | SymbolKind::Keyword
             | SymbolKind::SelfKeyword
             | SymbolKind::ProgramTypeKeyword
-    ) {
-        let name = &ident.name;
-        let documentation = keyword_docs.get(name).unwrap();
-        let prefix = format!("\n```sway\n{name}\n```\n\n---\n\n");
-        let formatted_doc = format!("{prefix}{documentation}");
-        let content = Markup::new().text(&formatted_doc);
-        let contents = lsp_types::HoverContents::Markup(markup_content(content));
-        return Some(lsp_types::Hover {
-            contents,
-            range: Some(range),
-        });
+    )
+}
+
+/// Extracts the hover information for a token at the current position.
+///
+/// For declarations and their uses, this is the signature of the declaration followed by its doc comments.
+pub fn hover_data(
+    session: Arc<Session>,
+    keyword_docs: &KeywordDocs,
+    url: Url,
+    position: Position,
+) -> Option<lsp_types::Hover> {
+    let (ident, token) = session.token_at_position(&url, position)?;
+    let range = ident.range;
+
+    if is_keyword(&token) {
+        return keyword_hover(&session, keyword_docs, &url, position);
     }
 
-    let (decl_ident, decl_token) = match token.declared_token_ident(&session.engines.read()) {
-        Some(decl_ident) => {
-            let decl_token = session
-                .token_map()
-                .try_get(&decl_ident)
-                .try_unwrap()
-                .map(|item| item.value().clone())?;
-            (decl_ident, decl_token)
-        }
-        // The `TypeInfo` of the token does not contain an `Ident`. In this case,
-        // we use the `Ident` of the token itself.
-        None => (ident, token),
-    };
+    let (decl_ident, decl_token) =
+        match token.declared_token_ident(&session.engines.read(), session.position_encoding()) {
+            Some(decl_ident) => {
+                let decl_token = session
+                    .token_map()
+                    .try_get(&decl_ident)
+                    .try_unwrap()
+                    .map(|item| item.value().clone())?;
+                (decl_ident, decl_token)
+            }
+            // The `TypeInfo` of the token does not contain an `Ident`. In this case,
+            // we use the `Ident` of the token itself.
+            None => (ident, token),
+        };
 
     let contents = hover_format(
         session.clone(),
@@ -84,12 +106,17 @@ fn visibility_as_str(visibility: &Visibility) -> &'static str {
 }
 
 /// Expects a span from either a `FunctionDeclaration` or a `TypedFunctionDeclaration`.
-fn extract_fn_signature(span: &Span) -> String {
-    let value = span.as_str();
-    value.split('{').take(1).map(|v| v.trim()).collect()
+pub(crate) fn extract_fn_signature(span: &Span) -> String {
+    fn_signature(span.as_str())
+}
+
+/// Returns the signature of the function declared by `text`, without its body.
+pub(crate) fn fn_signature(text: &str) -> String {
+    text.split('{').take(1).map(|v| v.trim()).collect()
 }
 
-fn format_doc_attributes(token: &Token) -> String {
+/// Returns the doc comment of the declaration, one line per line of the comment.
+pub(crate) fn format_doc_attributes(token: &Token) -> String {
     let mut doc_comment = String::new();
     if let Some(attributes) = doc_comment_attributes(token) {
         doc_comment = attributes.iter().fold("".to_string(), |output, attribute| {
@@ -117,7 +144,7 @@ fn format_variable_hover(is_mutable: bool, type_name: &str, token_name: &str) ->
     format!("let{mutability} {token_name}: {type_name}")
 }
 
-fn markup_content(markup: Markup) -> lsp_types::MarkupContent {
+pub(crate) fn markup_content(markup: Markup) -> lsp_types::MarkupContent {
     let kind = lsp_types::MarkupKind::Markdown;
     let value = markdown::format_docs(markup.as_str());
     lsp_types::MarkupContent { kind, value }
@@ -138,6 +165,7 @@ fn hover_format(
     };
 
     // Used to collect all the information we need to generate links for the hover component.
+    let encoding = session.position_encoding();
     let mut hover_link_contents = HoverLinkContents::new(session, engines);
 
     let sway_block = token
@@ -225,6 +253,7 @@ fn hover_format(
             engines.se(),
             hover_link_contents.related_types,
             hover_link_contents.implementations,
+            encoding,
         );
 
     lsp_types::HoverContents::Markup(markup_content(content))
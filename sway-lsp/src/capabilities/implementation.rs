@@ -0,0 +1,151 @@
+use crate::{
+    core::{
+        session::Session,
+        token::{TokenIdent, TypedAstToken},
+    },
+    utils::position_encoding::PositionEncoding,
+};
+use lsp_types::{Location, Position, Url};
+use std::sync::Arc;
+use sway_core::{
+    decl_engine::InterfaceDeclId,
+    language::ty::{self, TyImplTrait, TyTraitInterfaceItem, TyTraitItem},
+    Engines,
+};
+
+/// Returns the locations of the implementations of the trait, abi or interface method at `position`.
+///
+/// For a trait or abi, these are the `impl` blocks that implement it, and for one of their methods,
+/// the methods of those blocks that implement it. For a method of an `impl` block, it's the method
+/// of the trait or abi that it implements.
+pub fn implementations(session: &Session, uri: &Url, position: Position) -> Option<Vec<Location>> {
+    let engines = session.engines.read();
+    let encoding = session.position_encoding();
+    let token_map = session.token_map();
+    let (ident, token) = session.token_at_position(uri, position)?;
+    let decl_ident = token
+        .declared_token_ident(&engines, encoding)
+        .unwrap_or(ident);
+    let decl_token = token_map
+        .try_get(&decl_ident)
+        .try_unwrap()
+        .map(|item| item.value().clone())
+        .unwrap_or(token);
+
+    let impls = impl_traits(session, &engines);
+    let idents: Vec<TokenIdent> = match decl_token.typed? {
+        TypedAstToken::TypedDeclaration(ty::TyDecl::TraitDecl(_) | ty::TyDecl::AbiDecl(_)) => impls
+            .into_iter()
+            .filter(|(_, impl_trait)| {
+                interface_name(&engines, impl_trait, encoding).as_ref() == Some(&decl_ident)
+            })
+            .map(|(ident, _)| ident)
+            .collect(),
+        TypedAstToken::TypedTraitFn(_) => impls
+            .iter()
+            .filter(|(_, impl_trait)| {
+                interface_fn_names(&engines, impl_trait, encoding).contains(&decl_ident)
+            })
+            .flat_map(|(_, impl_trait)| impl_fn_names(&engines, impl_trait, encoding))
+            .filter(|ident| ident.name == decl_ident.name)
+            .collect(),
+        TypedAstToken::TypedFunctionDeclaration(_) => impls
+            .iter()
+            .filter(|(_, impl_trait)| {
+                impl_fn_names(&engines, impl_trait, encoding).contains(&decl_ident)
+            })
+            .flat_map(|(_, impl_trait)| interface_fn_names(&engines, impl_trait, encoding))
+            .filter(|ident| ident.name == decl_ident.name)
+            .collect(),
+        _ => return None,
+    };
+
+    let locations: Vec<Location> = idents
+        .into_iter()
+        .filter_map(|ident| {
+            let url = Url::from_file_path(ident.path?).ok()?;
+            let url = session.sync.to_workspace_url(url)?;
+            Some(Location::new(url, ident.range))
+        })
+        .collect();
+    (!locations.is_empty()).then_some(locations)
+}
+
+/// Returns the `impl` blocks of traits and abis in the session, with the ident of the name of the
+/// trait or abi in each of them.
+fn impl_traits(session: &Session, engines: &Engines) -> Vec<(TokenIdent, Arc<TyImplTrait>)> {
+    session
+        .token_map()
+        .iter()
+        .filter_map(|(ident, token)| match token.typed? {
+            TypedAstToken::TypedDeclaration(ty::TyDecl::ImplTrait(ty::ImplTrait {
+                decl_id,
+                ..
+            })) => {
+                let impl_trait = engines.de().get_impl_trait(&decl_id);
+                impl_trait
+                    .trait_decl_ref
+                    .is_some()
+                    .then_some((ident, impl_trait))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the ident of the name of the trait or abi that `impl_trait` implements.
+fn interface_name(
+    engines: &Engines,
+    impl_trait: &TyImplTrait,
+    encoding: PositionEncoding,
+) -> Option<TokenIdent> {
+    let name = match impl_trait.trait_decl_ref.as_ref()?.id() {
+        InterfaceDeclId::Abi(decl_id) => engines.de().get_abi(decl_id).name.clone(),
+        InterfaceDeclId::Trait(decl_id) => engines.de().get_trait(decl_id).name.clone(),
+    };
+    Some(TokenIdent::new(&name, engines.se(), encoding))
+}
+
+/// Returns the idents of the names of the methods of the trait or abi that `impl_trait` implements.
+fn interface_fn_names(
+    engines: &Engines,
+    impl_trait: &TyImplTrait,
+    encoding: PositionEncoding,
+) -> Vec<TokenIdent> {
+    let Some(decl_ref) = impl_trait.trait_decl_ref.as_ref() else {
+        return vec![];
+    };
+    let interface_surface = match decl_ref.id() {
+        InterfaceDeclId::Abi(decl_id) => engines.de().get_abi(decl_id).interface_surface.clone(),
+        InterfaceDeclId::Trait(decl_id) => {
+            engines.de().get_trait(decl_id).interface_surface.clone()
+        }
+    };
+    interface_surface
+        .iter()
+        .filter_map(|item| match item {
+            TyTraitInterfaceItem::TraitFn(decl_ref) => {
+                Some(TokenIdent::new(decl_ref.name(), engines.se(), encoding))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the idents of the names of the methods of `impl_trait`.
+fn impl_fn_names(
+    engines: &Engines,
+    impl_trait: &TyImplTrait,
+    encoding: PositionEncoding,
+) -> Vec<TokenIdent> {
+    impl_trait
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            TyTraitItem::Fn(decl_ref) => {
+                Some(TokenIdent::new(decl_ref.name(), engines.se(), encoding))
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -7,13 +7,17 @@ use crate::{
 };
 use lsp_types::{self, Range, Url};
 use std::sync::Arc;
-use sway_core::{language::ty::TyDecl, type_system::TypeInfo};
+use sway_core::{
+    language::ty::{self, TyDecl},
+    type_system::TypeInfo,
+};
 use sway_types::Spanned;
 
 // Future PR's will add more kinds
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum InlayKind {
     TypeHint,
+    ChainingHint,
 }
 
 #[derive(Debug)]
@@ -29,16 +33,33 @@ pub fn inlay_hints(
     range: &Range,
     config: &InlayHintsConfig,
 ) -> Option<Vec<lsp_types::InlayHint>> {
+    if !config.type_hints && !config.chaining_hints {
+        return None;
+    }
+
+    let mut hints = vec![];
+    if config.type_hints {
+        hints.extend(type_hints(&session, uri, range, config));
+    }
+    if config.chaining_hints {
+        hints.extend(chaining_hints(&session, uri, range, config));
+    }
+    Some(hints)
+}
+
+fn type_hints(
+    session: &Session,
+    uri: &Url,
+    range: &Range,
+    config: &InlayHintsConfig,
+) -> Vec<lsp_types::InlayHint> {
     // 1. Loop through all our tokens and filter out all tokens that aren't TypedVariableDeclaration tokens
     // 2. Also filter out all tokens that have a span that fall outside of the provided range
     // 3. Filter out all variable tokens that have a type_ascription
     // 4. Look up the type id for the remaining tokens
     // 5. Convert the type into a string
-    if !config.type_hints {
-        return None;
-    }
-
-    let hints: Vec<lsp_types::InlayHint> = session
+    let encoding = session.position_encoding();
+    session
         .token_map()
         .tokens_for_file(uri)
         .filter_map(|(_, token)| {
@@ -47,7 +68,7 @@ pub fn inlay_hints(
                     match var_decl.type_ascription.call_path_tree {
                         Some(_) => None,
                         None => {
-                            let var_range = get_range_from_span(&var_decl.name.span());
+                            let var_range = get_range_from_span(&var_decl.name.span(), encoding);
                             if var_range.start >= range.start && var_range.end <= range.end {
                                 Some(var_decl.clone())
                             } else {
@@ -67,36 +88,94 @@ pub fn inlay_hints(
             }
         })
         .map(|var| {
-            let range = get_range_from_span(&var.name.span());
+            let range = get_range_from_span(&var.name.span(), encoding);
             let kind = InlayKind::TypeHint;
             let label = format!("{}", session.engines.read().help_out(var.type_ascription));
             let inlay_hint = InlayHint { range, kind, label };
             self::inlay_hint(config.render_colons, inlay_hint)
         })
-        .collect();
+        .collect()
+}
 
-    Some(hints)
+/// Returns a hint with the type of each method call in a chain, e.g. `a.foo().bar()`,
+/// that is continued by another method call on a later line.
+///
+/// Chains that are written on a single line don't get hints, and neither does the last call of a chain.
+fn chaining_hints(
+    session: &Session,
+    uri: &Url,
+    range: &Range,
+    config: &InlayHintsConfig,
+) -> Vec<lsp_types::InlayHint> {
+    let engines = session.engines.read();
+    let encoding = session.position_encoding();
+    session
+        .token_map()
+        .tokens_for_file(uri)
+        .filter_map(|(ident, token)| {
+            let Some(TypedAstToken::TypedExpression(call)) = token.typed.as_ref() else {
+                return None;
+            };
+            let receiver = method_call_receiver(call)?;
+            method_call_receiver(receiver)?;
+            let receiver_range = get_range_from_span(&receiver.span, encoding);
+            if ident.range.start.line <= receiver_range.end.line
+                || receiver_range.end < range.start
+                || receiver_range.end > range.end
+            {
+                return None;
+            }
+            match &*engines.te().get(receiver.return_type) {
+                TypeInfo::Unknown | TypeInfo::UnknownGeneric { .. } => return None,
+                _ => {}
+            }
+            let inlay_hint = InlayHint {
+                range: receiver_range,
+                kind: InlayKind::ChainingHint,
+                label: format!("{}", engines.help_out(receiver.return_type)),
+            };
+            Some(self::inlay_hint(config.render_colons, inlay_hint))
+        })
+        .collect()
+}
+
+/// Returns the receiver of the expression if it calls a method with method syntax,
+/// e.g. `a` for `a.foo()` but not for `Foo::foo(a)`.
+fn method_call_receiver(expr: &ty::TyExpression) -> Option<&ty::TyExpression> {
+    match &expr.expression {
+        ty::TyExpressionVariant::FunctionApplication {
+            call_path,
+            arguments,
+            ..
+        } => {
+            let (_, receiver) = arguments.first()?;
+            (receiver.span.end() <= call_path.suffix.span().start()).then_some(receiver)
+        }
+        _ => None,
+    }
 }
 
 fn inlay_hint(render_colons: bool, inlay_hint: InlayHint) -> lsp_types::InlayHint {
     lsp_types::InlayHint {
         position: match inlay_hint.kind {
             // after annotated thing
-            InlayKind::TypeHint => inlay_hint.range.end,
+            InlayKind::TypeHint | InlayKind::ChainingHint => inlay_hint.range.end,
         },
         label: lsp_types::InlayHintLabel::String(match inlay_hint.kind {
-            InlayKind::TypeHint if render_colons => format!(": {}", inlay_hint.label),
+            InlayKind::TypeHint | InlayKind::ChainingHint if render_colons => {
+                format!(": {}", inlay_hint.label)
+            }
             _ => inlay_hint.label,
         }),
         kind: match inlay_hint.kind {
-            InlayKind::TypeHint => Some(lsp_types::InlayHintKind::TYPE),
+            InlayKind::TypeHint | InlayKind::ChainingHint => Some(lsp_types::InlayHintKind::TYPE),
         },
         tooltip: None,
         padding_left: Some(match inlay_hint.kind {
-            InlayKind::TypeHint => !render_colons,
+            InlayKind::TypeHint | InlayKind::ChainingHint => !render_colons,
         }),
         padding_right: Some(match inlay_hint.kind {
-            InlayKind::TypeHint => false,
+            InlayKind::TypeHint | InlayKind::ChainingHint => false,
         }),
         text_edits: None,
         data: None,
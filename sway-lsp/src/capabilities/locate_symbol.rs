@@ -0,0 +1,194 @@
+use crate::{
+    capabilities::{document_symbol, semantic_tokens::is_declaration},
+    core::{session::Session, token::TokenIdent},
+    error::LanguageServerError,
+    utils::position_encoding::PositionEncoding,
+};
+use forc_pkg::PackageManifestFile;
+use lsp_types::{DocumentSymbol, Location, Range, Url};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+/// Returns the locations of the declarations that the fully qualified `name` refers to, sorted by file and position.
+///
+/// The segments of `name` are matched against the end of the qualified name of each declaration, which is
+/// made of the name of its package, those of its modules and those of the items it's nested in. So `transfer`,
+/// `MyContract::transfer` and `my_project::MyContract::transfer` all name the method `transfer` of the abi
+/// `MyContract`. Impls are named after what they implement, so the methods of `impl MyContract for Contract`
+/// are nested in both `MyContract` and `Contract`.
+///
+/// Fails if no declaration matches, or if several do and `unique` is set.
+pub fn locate_symbol(
+    session: &Session,
+    name: &str,
+    unique: bool,
+) -> Result<Vec<Location>, LanguageServerError> {
+    let not_found = || LanguageServerError::SymbolNotFound {
+        name: name.to_string(),
+    };
+    let segments: Vec<&str> = name.split("::").map(str::trim).collect();
+    let Some((symbol_name, qualifiers)) = segments.split_last() else {
+        return Err(not_found());
+    };
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(not_found());
+    }
+    let encoding = session.position_encoding();
+    let candidates: Vec<TokenIdent> = {
+        let engines = session.engines.read();
+        session
+            .token_map()
+            .iter()
+            .filter(|(ident, token)| {
+                ident.name == *symbol_name && is_declaration(ident, token, &engines, encoding)
+            })
+            .map(|(ident, _)| ident)
+            .collect()
+    };
+
+    let mut outlines = HashMap::new();
+    let mut packages = HashMap::new();
+    let mut locations: Vec<Location> = candidates
+        .into_iter()
+        .filter(|ident| {
+            qualified_name(ident, encoding, &mut outlines, &mut packages)
+                .is_some_and(|qualified_name| is_qualified_by(&qualified_name, qualifiers))
+        })
+        .filter_map(|ident| {
+            let url = Url::from_file_path(ident.path?).ok()?;
+            let url = session.sync.to_workspace_url(url)?;
+            Some(Location::new(url, ident.range))
+        })
+        .filter(|location| !session.is_excluded_from_workspace(&location.uri))
+        .collect();
+    locations.sort_by(|a, b| (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start)));
+    locations.dedup();
+    match locations.len() {
+        0 => Err(not_found()),
+        count if count > 1 && unique => Err(LanguageServerError::AmbiguousSymbol {
+            name: name.to_string(),
+            count,
+        }),
+        _ => Ok(locations),
+    }
+}
+
+/// Returns the qualified name of the declaration, with the names that each of its segments can be
+/// written as, or `None` if it isn't an item of the outline of its file, e.g. because it's a local variable.
+///
+/// The outlines of the files and the packages of the `src` directories are cached across declarations.
+fn qualified_name(
+    ident: &TokenIdent,
+    encoding: PositionEncoding,
+    outlines: &mut HashMap<PathBuf, Option<Vec<DocumentSymbol>>>,
+    packages: &mut HashMap<PathBuf, Option<(String, PathBuf)>>,
+) -> Option<Vec<Vec<String>>> {
+    let path = ident.path.as_ref()?;
+    let src_dir = path
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == "src"))?;
+    let (package_name, entry) = packages
+        .entry(src_dir.to_path_buf())
+        .or_insert_with(|| {
+            let manifest = PackageManifestFile::from_dir(src_dir.parent()?).ok()?;
+            Some((manifest.project.name.clone(), manifest.entry_path()))
+        })
+        .clone()?;
+
+    let mut qualified_name = vec![vec![package_name]];
+    if *path != entry {
+        let module = path.strip_prefix(src_dir).ok()?.with_extension("");
+        qualified_name.extend(
+            module
+                .iter()
+                .map(|segment| vec![segment.to_string_lossy().to_string()]),
+        );
+    }
+    // The files are read from the temp directory, which has the unsaved changes of the editor.
+    let outline = outlines
+        .entry(path.clone())
+        .or_insert_with(|| {
+            document_symbol::outline_of_text(&fs::read_to_string(path).ok()?, encoding)
+        })
+        .as_deref()?;
+    qualified_name.extend(
+        containers(outline, &ident.range)?
+            .into_iter()
+            .map(container_names),
+    );
+    qualified_name.push(vec![ident.name.clone()]);
+    Some(qualified_name)
+}
+
+/// Returns the names of the items of the outline that the item whose name is at `range` is nested in,
+/// outermost first, or `None` if no item of the outline is named at `range`.
+fn containers<'a>(symbols: &'a [DocumentSymbol], range: &Range) -> Option<Vec<&'a str>> {
+    symbols.iter().find_map(|symbol| {
+        if symbol.selection_range == *range {
+            return Some(vec![]);
+        }
+        let mut names = containers(symbol.children.as_deref()?, range)?;
+        names.insert(0, symbol.name.as_str());
+        Some(names)
+    })
+}
+
+/// Returns the names that the items nested in the outline item `name` can be qualified with, which are
+/// both the trait and the type for an impl like `impl Foo for Bar<T>`.
+fn container_names(name: &str) -> Vec<String> {
+    let Some(implemented) = name.strip_prefix("impl ") else {
+        return vec![name.to_string()];
+    };
+    implemented
+        .split(" for ")
+        .map(|name| name.split('<').next().unwrap_or(name).trim().to_string())
+        .collect()
+}
+
+/// Whether the segments of `qualified_name` before the name itself end with `qualifiers`.
+fn is_qualified_by(qualified_name: &[Vec<String>], qualifiers: &[&str]) -> bool {
+    let Some((_, containers)) = qualified_name.split_last() else {
+        return false;
+    };
+    containers.len() >= qualifiers.len()
+        && containers[containers.len() - qualifiers.len()..]
+            .iter()
+            .zip(qualifiers)
+            .all(|(names, qualifier)| names.iter().any(|name| name == qualifier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impls_qualify_their_items_with_the_trait_and_the_type() {
+        assert_eq!(
+            container_names("impl MyContract for Contract"),
+            vec!["MyContract", "Contract"]
+        );
+        assert_eq!(container_names("impl Foo<T>"), vec!["Foo"]);
+        assert_eq!(container_names("storage"), vec!["storage"]);
+
+        let qualified_name = vec![
+            vec!["my_project".to_string()],
+            vec!["foo".to_string()],
+            container_names("impl MyContract for Contract"),
+            vec!["transfer".to_string()],
+        ];
+        assert!(is_qualified_by(&qualified_name, &[]));
+        assert!(is_qualified_by(&qualified_name, &["Contract"]));
+        assert!(is_qualified_by(&qualified_name, &["foo", "MyContract"]));
+        assert!(is_qualified_by(
+            &qualified_name,
+            &["my_project", "foo", "MyContract"]
+        ));
+        assert!(!is_qualified_by(
+            &qualified_name,
+            &["my_project", "MyContract"]
+        ));
+        assert!(!is_qualified_by(
+            &qualified_name,
+            &["other", "my_project", "foo", "MyContract"]
+        ));
+    }
+}
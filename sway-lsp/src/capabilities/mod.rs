@@ -1,16 +1,26 @@
+pub mod call_hierarchy;
 pub mod code_actions;
 pub mod code_lens;
 pub mod completion;
 pub mod diagnostic;
+pub mod document_link;
 pub mod document_symbol;
 pub mod formatting;
 pub mod highlight;
 pub mod hover;
+pub mod implementation;
 pub mod inlay_hints;
+pub mod locate_symbol;
+pub mod monomorphized_type;
 pub mod on_enter;
+pub mod on_type_formatting;
+pub mod peek_signature;
+pub mod references;
 pub mod rename;
 pub mod runnable;
 pub mod semantic_tokens;
+pub mod signature_help;
+pub mod type_of_expression;
 
 pub(crate) use code_actions::code_actions;
 pub(crate) use on_enter::on_enter;
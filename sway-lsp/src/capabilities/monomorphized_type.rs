@@ -0,0 +1,42 @@
+use crate::{
+    core::{session::Session, token::TypedAstToken},
+    lsp_ext::{MonomorphizedTypeResult, TypeSubstitution},
+};
+use lsp_types::{Position, Url};
+use std::sync::Arc;
+use sway_core::language::ty;
+
+/// Returns the concrete types that the type parameters of the generic function called
+/// at the given position were substituted with.
+pub fn monomorphized_type(
+    session: Arc<Session>,
+    url: &Url,
+    position: Position,
+) -> Option<MonomorphizedTypeResult> {
+    let (_, token) = session.token_at_position(url, position)?;
+    let Some(TypedAstToken::TypedExpression(expr)) = token.typed else {
+        return None;
+    };
+    let ty::TyExpressionVariant::FunctionApplication { fn_ref, .. } = expr.expression else {
+        return None;
+    };
+    let engines = session.engines.read();
+    // The declaration referenced by a call site is the monomorphized copy of the function,
+    // so its type parameters already refer to the concrete types.
+    let fn_decl = engines.de().get_function(&fn_ref);
+    if fn_decl.type_parameters.is_empty() {
+        return None;
+    }
+    let substitutions = fn_decl
+        .type_parameters
+        .iter()
+        .map(|type_param| TypeSubstitution {
+            type_parameter: type_param.name_ident.as_str().to_string(),
+            concrete_type: engines.help_out(type_param.type_id).to_string(),
+        })
+        .collect();
+    Some(MonomorphizedTypeResult {
+        function: fn_decl.name.as_str().to_string(),
+        substitutions,
+    })
+}
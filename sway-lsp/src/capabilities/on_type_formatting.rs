@@ -0,0 +1,171 @@
+use crate::utils::position_encoding::PositionEncoding;
+use lsp_types::{DocumentOnTypeFormattingOptions, Position, Range, TextEdit};
+
+const CLOSING_BRACE: &str = "}";
+const SEMICOLON: &str = ";";
+const NEWLINE: &str = "\n";
+
+/// Returns the characters that trigger on-type formatting.
+pub fn on_type_formatting_options() -> DocumentOnTypeFormattingOptions {
+    DocumentOnTypeFormattingOptions {
+        first_trigger_character: CLOSING_BRACE.to_string(),
+        more_trigger_character: Some(vec![SEMICOLON.to_string(), NEWLINE.to_string()]),
+    }
+}
+
+/// Returns the edit that reindents the line at `position` after `ch` was typed, using `indent`
+/// for each level of nesting.
+///
+/// The line is indented to the depth of the blocks, parentheses and brackets it's in, and a line
+/// that starts with a closing delimiter lines up with the line that opened it. Only the leading
+/// whitespace of the line is edited, so the cursor stays where it is in the code.
+///
+/// The code that's being typed usually doesn't parse, so the depth is counted from the delimiters
+/// before the line, skipping comments and string literals. For `;` and newlines, the line is left
+/// as it is if it continues an expression from the previous line, e.g. in a method chain.
+pub fn on_type_formatting(
+    text: &str,
+    position: Position,
+    ch: &str,
+    indent: &str,
+    encoding: PositionEncoding,
+) -> Option<TextEdit> {
+    if ![CLOSING_BRACE, SEMICOLON, NEWLINE].contains(&ch) {
+        return None;
+    }
+    let lines: Vec<&str> = text.split('\n').collect();
+    let line_index = position.line as usize;
+    let line = lines.get(line_index)?.trim_end_matches('\r');
+    let content = line.trim_start();
+    let leading_whitespace = &line[..line.len() - content.len()];
+    let closes = content.starts_with(['}', ')', ']']);
+    if ch == CLOSING_BRACE && !closes {
+        return None;
+    }
+    if ch != CLOSING_BRACE && !closes && continues_expression(&lines[..line_index]) {
+        return None;
+    }
+
+    let line_start: usize = lines[..line_index].iter().map(|line| line.len() + 1).sum();
+    let depth = nesting_depth(&text[..line_start])?;
+    let level = if closes {
+        depth.saturating_sub(1)
+    } else {
+        depth
+    };
+    let expected = indent.repeat(level);
+    (leading_whitespace != expected).then(|| TextEdit {
+        range: Range::new(
+            Position::new(position.line, 0),
+            Position::new(position.line, encoding.len(leading_whitespace)),
+        ),
+        new_text: expected,
+    })
+}
+
+/// Returns true if the last non-empty line doesn't end a statement, item or attribute, nor opens a block.
+fn continues_expression(lines_before: &[&str]) -> bool {
+    let Some(previous) = lines_before
+        .iter()
+        .map(|line| line.trim())
+        .rfind(|line| !line.is_empty())
+    else {
+        return false;
+    };
+    if previous.starts_with("//") || previous.starts_with("#[") {
+        return false;
+    }
+    !previous.ends_with([';', ',', '{', '}', '(', '[', ']'])
+}
+
+/// Returns the nesting depth of the delimiters at the end of `text`, or `None` if it ends
+/// inside a comment or string literal.
+fn nesting_depth(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                chars.find(|c| *c == '\n')?;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut comment_depth = 1;
+                while comment_depth > 0 {
+                    let c = chars.next()?;
+                    match (c, chars.peek().copied()) {
+                        ('*', Some('/')) => {
+                            chars.next();
+                            comment_depth -= 1;
+                        }
+                        ('/', Some('*')) => {
+                            chars.next();
+                            comment_depth += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            '"' => loop {
+                match chars.next()? {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => break,
+                    _ => {}
+                }
+            },
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Some(depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reindent(text: &str, line: u32, ch: &str) -> Option<(u32, String)> {
+        on_type_formatting(
+            text,
+            Position::new(line, 1),
+            ch,
+            "    ",
+            PositionEncoding::Utf16,
+        )
+        .map(|edit| (edit.range.end.character, edit.new_text))
+    }
+
+    #[test]
+    fn closing_brace_lines_up_with_its_block() {
+        let text = "fn main() {\n    if true {\n        let x = 1;\n        }\n";
+        assert_eq!(reindent(text, 3, "}"), Some((8, "    ".to_string())));
+        let text = "fn main() {\n    let x = 1;\n}\n";
+        assert_eq!(reindent(text, 2, "}"), None);
+    }
+
+    #[test]
+    fn statements_and_new_lines_are_indented_to_their_depth() {
+        let text = "fn main() {\nlet x = 1;\n";
+        assert_eq!(reindent(text, 1, ";"), Some((0, "    ".to_string())));
+        let text = "fn main() {\n    foo(\n";
+        assert_eq!(reindent(text, 2, "\n"), Some((0, "        ".to_string())));
+    }
+
+    #[test]
+    fn continued_expressions_are_left_alone() {
+        let text = "fn main() {\n    let x = foo()\n            .bar();\n";
+        assert_eq!(reindent(text, 2, ";"), None);
+    }
+
+    #[test]
+    fn delimiters_in_comments_and_strings_are_skipped() {
+        let text = "fn main() {\n    // {\n    /* ( */\n    let s = \"[\";\nlet x = 1;\n";
+        assert_eq!(reindent(text, 4, ";"), Some((0, "    ".to_string())));
+        // The line is in a block comment.
+        let text = "/* {\nlet x = 1;\n";
+        assert_eq!(reindent(text, 1, ";"), None);
+    }
+}
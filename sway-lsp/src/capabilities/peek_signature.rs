@@ -0,0 +1,64 @@
+use crate::{
+    capabilities::hover::{fn_signature, format_doc_attributes, markup_content},
+    core::{
+        session::Session,
+        token::{Token, TypedAstToken},
+    },
+    lsp_ext::PeekSignatureResult,
+    utils::markup::Markup,
+};
+use lsp_types::{Position, Url};
+use sway_core::language::ty;
+use sway_types::Spanned;
+
+/// Returns the signature of the declaration of the token at `position`, along with its doc comment.
+///
+/// Unlike hover, this only has the header of functions and of type, trait and abi declarations, without
+/// links to related types or implementations. Tokens of anything else, like variables or literals, have none.
+pub fn peek_signature(
+    session: &Session,
+    url: &Url,
+    position: Position,
+) -> Option<PeekSignatureResult> {
+    let (ident, token) = session.token_at_position(url, position)?;
+    let engines = session.engines.read();
+    let decl_token = match token.declared_token_ident(&engines, session.position_encoding()) {
+        Some(decl_ident) => session
+            .token_map()
+            .try_get(&decl_ident)
+            .try_unwrap()
+            .map(|item| item.value().clone())?,
+        None => token,
+    };
+    let signature = signature(&decl_token)?;
+    let doc_comment = format_doc_attributes(&decl_token);
+    let content = Markup::new()
+        .maybe_add_sway_block(Some(signature.clone()))
+        .text(&doc_comment);
+    Some(PeekSignatureResult {
+        signature,
+        documentation: Some(doc_comment.trim_end().to_string()).filter(|doc| !doc.is_empty()),
+        contents: markup_content(content),
+        range: ident.range,
+    })
+}
+
+/// Returns the header of the declaration, which is its source up to its body.
+fn signature(token: &Token) -> Option<String> {
+    let span = match token.typed.as_ref()? {
+        TypedAstToken::TypedFunctionDeclaration(decl) => decl.span(),
+        TypedAstToken::TypedTraitFn(decl) => decl.span(),
+        TypedAstToken::TypedDeclaration(decl) => match decl {
+            ty::TyDecl::FunctionDecl(ty::FunctionDecl { decl_span, .. })
+            | ty::TyDecl::StructDecl(ty::StructDecl { decl_span, .. })
+            | ty::TyDecl::EnumDecl(ty::EnumDecl { decl_span, .. })
+            | ty::TyDecl::TraitDecl(ty::TraitDecl { decl_span, .. })
+            | ty::TyDecl::AbiDecl(ty::AbiDecl { decl_span, .. }) => decl_span.clone(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let signature = fn_signature(span.as_str());
+    Some(signature.trim_end_matches(';').trim_end().to_string())
+        .filter(|signature| !signature.is_empty())
+}
@@ -0,0 +1,48 @@
+use crate::core::{session::Session, token_map::TokenMapExt};
+use lsp_types::{Location, Position, Url};
+use std::sync::Arc;
+
+/// Returns the locations of every reference to the declaration of the token at `position`,
+/// across all files of the session.
+///
+/// The declaration itself is only included if `include_declaration` is set.
+pub fn get_references(
+    session: Arc<Session>,
+    url: Url,
+    position: Position,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    let (_, token) = session.token_at_position(&url, position)?;
+    let engines = session.engines.read();
+    let encoding = session.position_encoding();
+    let decl_ident = token.declared_token_ident(&engines, encoding);
+    let mut locations: Vec<Location> = session
+        .token_map()
+        .iter()
+        .all_references_of_token(&token, &engines, encoding)
+        .filter(|(ident, _)| include_declaration || Some(ident) != decl_ident.as_ref())
+        .filter_map(|(ident, _)| {
+            let url = Url::from_file_path(ident.path?).ok()?;
+            let url = session.sync.to_workspace_url(url)?;
+            Some(Location::new(url, ident.range))
+        })
+        .filter(|location| !session.is_excluded_from_workspace(&location.uri))
+        .collect();
+
+    locations.sort_by(|a, b| {
+        (a.uri.as_str(), a.range.start.line, a.range.start.character).cmp(&(
+            b.uri.as_str(),
+            b.range.start.line,
+            b.range.start.character,
+        ))
+    });
+    // Tokens generated by macro expansion can share the span of the code they were expanded from,
+    // so only keep the first of any locations that overlap.
+    locations.dedup_by(|curr, prev| {
+        curr.uri == prev.uri
+            && (curr.range == prev.range
+                || (curr.range.start.line, curr.range.start.character)
+                    < (prev.range.end.line, prev.range.end.character))
+    });
+    Some(locations)
+}
@@ -1,16 +1,16 @@
 use crate::{
     core::{
         session::Session,
-        token::{SymbolKind, Token, TokenIdent, TypedAstToken},
+        token::{get_range_from_span, SymbolKind, Token, TokenIdent, TypedAstToken},
         token_map::TokenMapExt,
     },
     error::{LanguageServerError, RenameError},
-    utils::document::get_url_from_path,
+    utils::{document::get_url_from_path, position_encoding::PositionEncoding},
 };
-use lsp_types::{Position, PrepareRenameResponse, TextEdit, Url, WorkspaceEdit};
+use lsp_types::{Position, PrepareRenameResponse, Range, TextEdit, Url, WorkspaceEdit};
 use std::{collections::HashMap, sync::Arc};
 use sway_core::{language::ty, Engines};
-use sway_types::SourceEngine;
+use sway_types::{SourceEngine, Spanned};
 
 const RAW_IDENTIFIER: &str = "r#";
 
@@ -37,7 +37,6 @@ pub fn rename(
 
     // Get the token at the current cursor position
     let (_, token) = session
-        .token_map()
         .token_at_position(&url, position)
         .ok_or(RenameError::TokenNotFound)?;
 
@@ -48,6 +47,9 @@ pub fn rename(
         ));
     }
 
+    // Renaming onto another declaration of the same scope would merge the two.
+    check_name_collision(&session, &session.engines.read(), &token, &new_name)?;
+
     // If the token is a function, find the parent declaration
     // and collect idents for all methods of ABI Decl, Trait Decl, and Impl Trait
     let map_of_changes: HashMap<Url, Vec<TextEdit>> = (if token.kind == SymbolKind::Function {
@@ -57,7 +59,7 @@ pub fn rename(
         session
             .token_map()
             .iter()
-            .all_references_of_token(&token, &session.engines.read())
+            .all_references_of_token(&token, &session.engines.read(), session.position_encoding())
             .map(|(ident, _)| ident)
             .collect::<Vec<TokenIdent>>()
     })
@@ -102,7 +104,6 @@ pub fn prepare_rename(
     position: Position,
 ) -> Result<PrepareRenameResponse, LanguageServerError> {
     let (ident, token) = session
-        .token_map()
         .token_at_position(&url, position)
         .ok_or(RenameError::TokenNotFound)?;
 
@@ -149,7 +150,7 @@ fn is_token_in_workspace(
     token: &Token,
 ) -> Result<bool, LanguageServerError> {
     let decl_ident = token
-        .declared_token_ident(engines)
+        .declared_token_ident(engines, session.position_encoding())
         .ok_or(RenameError::TokenNotFound)?;
 
     // Check the span of the tokens defintions to determine if it's in the users workspace.
@@ -165,15 +166,94 @@ fn is_token_in_workspace(
     Ok(true)
 }
 
+/// Returns an error if a declaration of the same kind as the one of the token is already named
+/// `new_name` in its scope.
+///
+/// The scope of a declaration is the innermost declaration containing it, e.g. the function of a
+/// local variable or the struct of a field, and items of a module are in the scope of its file.
+/// Declarations in nested scopes are left alone, as they only shadow the one being renamed.
+fn check_name_collision(
+    session: &Session,
+    engines: &Engines,
+    token: &Token,
+    new_name: &str,
+) -> Result<(), LanguageServerError> {
+    let encoding = session.position_encoding();
+    let Some(decl_ident) = token.declared_token_ident(engines, encoding) else {
+        return Ok(());
+    };
+    let token_map = session.token_map();
+    let Some(kind) = token_map
+        .try_get(&decl_ident)
+        .try_unwrap()
+        .map(|item| item.value().kind.clone())
+    else {
+        return Ok(());
+    };
+    let name = new_name.strip_prefix(RAW_IDENTIFIER).unwrap_or(new_name);
+    let scopes = scopes_in_file(session, &decl_ident);
+    let scope = enclosing_scope(&scopes, &decl_ident);
+    let is_declared = token_map.iter().any(|(ident, token)| {
+        ident.name == name
+            && ident.path == decl_ident.path
+            && token.kind == kind
+            && token.declared_token_ident(engines, encoding).as_ref() == Some(&ident)
+            && enclosing_scope(&scopes, &ident) == scope
+    });
+    if is_declared {
+        return Err(LanguageServerError::RenameError(
+            RenameError::NameAlreadyDeclared {
+                name: new_name.to_string(),
+            },
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the declarations in the file of the identifier along with the ranges of their bodies.
+fn scopes_in_file(session: &Session, ident: &TokenIdent) -> Vec<(TokenIdent, Range)> {
+    let encoding = session.position_encoding();
+    session
+        .token_map()
+        .iter()
+        .filter(|(decl_ident, _)| decl_ident.path == ident.path)
+        .filter_map(|(decl_ident, token)| {
+            let span = match token.typed? {
+                TypedAstToken::TypedDeclaration(decl) => decl.span(),
+                TypedAstToken::TypedFunctionDeclaration(decl) => decl.span,
+                _ => return None,
+            };
+            Some((decl_ident, get_range_from_span(&span, encoding)))
+        })
+        .collect()
+}
+
+/// Returns the innermost of the `scopes` that contains the identifier, other than its own declaration.
+fn enclosing_scope<'a>(
+    scopes: &'a [(TokenIdent, Range)],
+    ident: &TokenIdent,
+) -> Option<&'a TokenIdent> {
+    scopes
+        .iter()
+        .filter(|(decl_ident, range)| {
+            decl_ident != ident && range.start <= ident.range.start && ident.range.end <= range.end
+        })
+        .max_by_key(|(_, range)| range.start)
+        .map(|(decl_ident, _)| decl_ident)
+}
+
 /// Returns a `Vec<Ident>` containing the identifiers of all trait functions found.
 fn trait_interface_idents(
     interface_surface: &[ty::TyTraitInterfaceItem],
     se: &SourceEngine,
+    encoding: PositionEncoding,
 ) -> Vec<TokenIdent> {
     interface_surface
         .iter()
         .flat_map(|item| match item {
-            ty::TyTraitInterfaceItem::TraitFn(fn_decl) => Some(TokenIdent::new(fn_decl.name(), se)),
+            ty::TyTraitInterfaceItem::TraitFn(fn_decl) => {
+                Some(TokenIdent::new(fn_decl.name(), se, encoding))
+            }
             _ => None,
         })
         .collect()
@@ -187,15 +267,16 @@ fn find_all_methods_for_decl(
     position: Position,
 ) -> Result<Vec<TokenIdent>, LanguageServerError> {
     // Find the parent declaration
+    let encoding = session.position_encoding();
     let (_, decl_token) = session
         .token_map()
-        .parent_decl_at_position(engines.se(), url, position)
+        .parent_decl_at_position(engines.se(), url, position, encoding)
         .ok_or(RenameError::TokenNotFound)?;
 
     let idents = session
         .token_map()
         .iter()
-        .all_references_of_token(&decl_token, engines)
+        .all_references_of_token(&decl_token, engines, encoding)
         .filter_map(|(_, token)| {
             token.typed.as_ref().and_then(|typed| match typed {
                 TypedAstToken::TypedDeclaration(decl) => match decl {
@@ -204,6 +285,7 @@ fn find_all_methods_for_decl(
                         Some(trait_interface_idents(
                             &abi_decl.interface_surface,
                             engines.se(),
+                            encoding,
                         ))
                     }
                     ty::TyDecl::TraitDecl(ty::TraitDecl { decl_id, .. }) => {
@@ -211,6 +293,7 @@ fn find_all_methods_for_decl(
                         Some(trait_interface_idents(
                             &trait_decl.interface_surface,
                             engines.se(),
+                            encoding,
                         ))
                     }
                     ty::TyDecl::ImplTrait(ty::ImplTrait { decl_id, .. }) => {
@@ -220,9 +303,11 @@ fn find_all_methods_for_decl(
                                 .items
                                 .iter()
                                 .filter_map(|item| match item {
-                                    ty::TyTraitItem::Fn(fn_decl) => {
-                                        Some(TokenIdent::new(fn_decl.name(), engines.se()))
-                                    }
+                                    ty::TyTraitItem::Fn(fn_decl) => Some(TokenIdent::new(
+                                        fn_decl.name(),
+                                        engines.se(),
+                                        encoding,
+                                    )),
                                     _ => None,
                                 })
                                 .collect::<Vec<TokenIdent>>(),
@@ -1,23 +1,71 @@
-use crate::core::{
-    session::Session,
-    token::{SymbolKind, Token, TokenIdent},
+use crate::{
+    core::{
+        session::Session,
+        token::{AstToken, SymbolKind, Token, TokenIdent, TypedAstToken},
+    },
+    utils::position_encoding::PositionEncoding,
 };
 use lsp_types::{
     Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
-    SemanticTokensRangeResult, SemanticTokensResult, Url,
+    SemanticTokensEdit, SemanticTokensRangeResult, SemanticTokensResult, Url,
 };
 use std::sync::{
     atomic::{AtomicU32, Ordering},
     Arc,
 };
+use sway_core::{
+    language::{parsed::Declaration, ty},
+    Engines,
+};
 
 // https://github.com/microsoft/vscode-extension-samples/blob/5ae1f7787122812dcc84e37427ca90af5ee09f14/semantic-tokens-sample/vscode.proposed.d.ts#L71
 
 /// Get the semantic tokens for the entire file.
 pub fn semantic_tokens_full(session: Arc<Session>, url: &Url) -> Option<SemanticTokensResult> {
+    Some(semantic_tokens_for_file(&session, url).into())
+}
+
+/// Get the semantic tokens for the entire file, encoded with a new result id.
+pub fn semantic_tokens_for_file(session: &Session, url: &Url) -> SemanticTokens {
     let mut tokens: Vec<_> = session.token_map().tokens_for_file(url).collect();
     sort_tokens(&mut tokens);
-    Some(semantic_tokens(&tokens).into())
+    semantic_tokens(
+        &tokens,
+        &session.engines.read(),
+        session.position_encoding(),
+    )
+}
+
+/// Get the edits that turn the `previous` semantic tokens of a file into the `current` ones.
+///
+/// Only the tokens between the longest common prefix and suffix of the two are replaced, which
+/// is usually a small part of the file while it's being edited.
+pub fn semantic_tokens_edits(
+    previous: &[SemanticToken],
+    current: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(prev, curr)| prev == curr)
+        .count();
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(prev, curr)| prev == curr)
+        .count();
+    let deleted = &previous[prefix..previous.len() - suffix];
+    let inserted = &current[prefix..current.len() - suffix];
+    if deleted.is_empty() && inserted.is_empty() {
+        return vec![];
+    }
+    // Edits are expressed in the integers of the encoded tokens, of which there are 5 per token.
+    vec![SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted.len() as u32,
+        data: Some(inserted.to_vec()),
+    }]
 }
 
 /// Get the semantic tokens within a range.
@@ -29,14 +77,68 @@ pub fn semantic_tokens_range(
     let mut tokens: Vec<_> = session
         .token_map()
         .tokens_for_file(url)
-        .filter(|t| {
-            // make sure the token_ident range is within the range that was passed in
-            let token_range = t.0.range;
-            token_range.start >= range.start && token_range.end <= range.end
-        })
+        .filter(|(ident, _)| overlaps(&ident.range, range))
         .collect();
     sort_tokens(&mut tokens);
-    Some(semantic_tokens(&tokens).into())
+    Some(
+        semantic_tokens(
+            &tokens,
+            &session.engines.read(),
+            session.position_encoding(),
+        )
+        .into(),
+    )
+}
+
+/// The share of the lines of a file that the ranges of a `sway/semanticTokensRanges` request must cover
+/// for all of its tokens to be returned instead.
+const FULL_FILE_COVERAGE: f64 = 0.8;
+
+/// Get the semantic tokens within any of the `ranges`, such as the viewport and the minimap of an editor,
+/// in one scan of the tokens of the file.
+///
+/// The tokens are encoded relative to each other across the gaps between the ranges, as if they were all
+/// of the file's tokens. If the ranges cover most of the lines up to the last token, all tokens are returned.
+pub fn semantic_tokens_ranges(session: &Session, url: &Url, ranges: &[Range]) -> SemanticTokens {
+    let mut tokens: Vec<_> = session.token_map().tokens_for_file(url).collect();
+    let line_count = tokens
+        .iter()
+        .map(|(ident, _)| ident.range.end.line + 1)
+        .max()
+        .unwrap_or(0);
+    let ranges = merge_ranges(ranges);
+    let covered_lines: u32 = ranges
+        .iter()
+        .map(|range| range.end.line - range.start.line + 1)
+        .sum();
+    if f64::from(covered_lines) < f64::from(line_count) * FULL_FILE_COVERAGE {
+        tokens.retain(|(ident, _)| ranges.iter().any(|range| overlaps(&ident.range, range)));
+    }
+    sort_tokens(&mut tokens);
+    semantic_tokens(
+        &tokens,
+        &session.engines.read(),
+        session.position_encoding(),
+    )
+}
+
+/// Returns the `ranges` sorted by their start, with those that overlap or touch merged into one.
+fn merge_ranges(ranges: &[Range]) -> Vec<Range> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Whether the range of a token overlaps `range`.
+fn overlaps(token_range: &Range, range: &Range) -> bool {
+    token_range.start < range.end && token_range.end > range.start
 }
 
 /// Sort tokens by their span so each token is sequential.
@@ -108,18 +210,25 @@ impl SemanticTokensBuilder {
     }
 }
 
-pub fn semantic_tokens(tokens_sorted: &[(TokenIdent, Token)]) -> SemanticTokens {
+/// Returns a result id that hasn't been sent to the client yet.
+pub(crate) fn next_result_id() -> String {
     static TOKEN_RESULT_COUNTER: AtomicU32 = AtomicU32::new(1);
-    let id = TOKEN_RESULT_COUNTER
+    TOKEN_RESULT_COUNTER
         .fetch_add(1, Ordering::SeqCst)
-        .to_string();
-    let mut builder = SemanticTokensBuilder::new(id);
+        .to_string()
+}
+
+pub fn semantic_tokens(
+    tokens_sorted: &[(TokenIdent, Token)],
+    engines: &Engines,
+    encoding: PositionEncoding,
+) -> SemanticTokens {
+    let mut builder = SemanticTokensBuilder::new(next_result_id());
 
     for (ident, token) in tokens_sorted.iter() {
         let ty = semantic_token_type(&token.kind);
         let token_index = type_index(ty);
-        // TODO - improve with modifiers
-        let modifier_bitset = 0;
+        let modifier_bitset = modifier_bitset(ident, token, engines, encoding);
         builder.push(ident.range, token_index, modifier_bitset);
     }
     builder.build()
@@ -148,6 +257,7 @@ pub(crate) const SUPPORTED_TYPES: &[SemanticTokenType] = &[
     SemanticTokenType::new("selfKeyword"),
     SemanticTokenType::new("selfTypeKeyword"),
     SemanticTokenType::new("typeAlias"),
+    SemanticTokenType::new("traitType"),
 ];
 
 pub(crate) const SUPPORTED_MODIFIERS: &[SemanticTokenModifier] = &[
@@ -195,3 +305,99 @@ fn semantic_token_type(kind: &SymbolKind) -> SemanticTokenType {
 fn type_index(ty: SemanticTokenType) -> u32 {
     SUPPORTED_TYPES.iter().position(|it| *it == ty).unwrap() as u32
 }
+
+/// Get the bitset of the [SUPPORTED_MODIFIERS] that apply to the token.
+///
+/// The typed token is used if the program could be type-checked, otherwise the modifiers are
+/// inferred from the parsed token.
+fn modifier_bitset(
+    ident: &TokenIdent,
+    token: &Token,
+    engines: &Engines,
+    encoding: PositionEncoding,
+) -> u32 {
+    let mut modifiers = vec![];
+    if is_declaration(ident, token, engines, encoding) {
+        modifiers.push(SemanticTokenModifier::DECLARATION);
+    }
+    if is_readonly(token) {
+        modifiers.push(SemanticTokenModifier::READONLY);
+    }
+    modifiers
+        .into_iter()
+        .fold(0, |bitset, modifier| bitset | 1 << modifier_index(modifier))
+}
+
+fn modifier_index(modifier: SemanticTokenModifier) -> u32 {
+    SUPPORTED_MODIFIERS
+        .iter()
+        .position(|it| *it == modifier)
+        .unwrap() as u32
+}
+
+/// Returns true if the token is the name of the symbol it declares.
+pub(crate) fn is_declaration(
+    ident: &TokenIdent,
+    token: &Token,
+    engines: &Engines,
+    encoding: PositionEncoding,
+) -> bool {
+    if token.typed.is_some() {
+        return token.declared_token_ident(engines, encoding).as_ref() == Some(ident);
+    }
+    matches!(
+        token.parsed,
+        AstToken::Declaration(_)
+            | AstToken::EnumVariant(_)
+            | AstToken::FunctionParameter(_)
+            | AstToken::StorageField(_)
+            | AstToken::StructField(_)
+            | AstToken::TraitFn(_)
+    )
+}
+
+/// Returns true if the token is a constant, or a variable or parameter that isn't mutable.
+fn is_readonly(token: &Token) -> bool {
+    match &token.typed {
+        Some(TypedAstToken::TypedDeclaration(ty::TyDecl::VariableDecl(decl))) => {
+            !decl.mutability.is_mutable()
+        }
+        Some(TypedAstToken::TypedExpression(expr)) => match &expr.expression {
+            ty::TyExpressionVariant::VariableExpression { mutability, .. } => {
+                !mutability.is_mutable()
+            }
+            ty::TyExpressionVariant::ConstantExpression { .. } => true,
+            _ => false,
+        },
+        Some(TypedAstToken::TypedFunctionParameter(param)) => {
+            !param.is_mutable && !param.is_reference
+        }
+        Some(TypedAstToken::TypedConstantDeclaration(_)) => true,
+        Some(_) => token.kind == SymbolKind::Const,
+        None => match &token.parsed {
+            AstToken::Declaration(Declaration::VariableDeclaration(decl)) => !decl.is_mutable,
+            AstToken::FunctionParameter(param) => !param.is_mutable && !param.is_reference,
+            _ => token.kind == SymbolKind::Const,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+
+    #[test]
+    fn overlapping_ranges_are_merged() {
+        let range =
+            |start: u32, end: u32| Range::new(Position::new(start, 0), Position::new(end, 0));
+        assert_eq!(
+            merge_ranges(&[range(40, 60), range(0, 10), range(5, 20), range(20, 30)]),
+            vec![range(0, 30), range(40, 60)]
+        );
+        assert_eq!(
+            merge_ranges(&[range(0, 30), range(10, 20)]),
+            vec![range(0, 30)]
+        );
+    }
+}
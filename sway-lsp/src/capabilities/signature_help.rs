@@ -0,0 +1,256 @@
+use crate::{
+    core::{
+        session::Session,
+        token::{Token, TypedAstToken},
+    },
+    utils::position_encoding::PositionEncoding,
+};
+use lsp_types::{
+    ParameterInformation, ParameterLabel, Position, SignatureHelp, SignatureInformation, Url,
+};
+use std::sync::Arc;
+use sway_core::{
+    language::ty::{self, TyFunctionDecl},
+    Engines,
+};
+
+/// Returns the signature of the function whose argument list the cursor is in, with the
+/// argument under the cursor as the active parameter.
+///
+/// Returns `None` if the cursor isn't inside an argument list or the callee can't be resolved.
+pub fn signature_help(
+    session: Arc<Session>,
+    url: &Url,
+    position: Position,
+) -> Option<SignatureHelp> {
+    let text = session.get_text_document(url).ok()?.get_text();
+    let call = enclosing_call(&text, position)?;
+    let (_, token) = session.token_at_position(url, call.callee)?;
+    let engines = session.engines.read();
+    let fn_decl = function_decl(&token, &engines)?;
+    Some(SignatureHelp {
+        signatures: vec![signature_information(
+            &engines,
+            &fn_decl,
+            call.is_method_call,
+            session.position_encoding(),
+        )],
+        active_signature: Some(0),
+        active_parameter: Some(call.active_parameter),
+    })
+}
+
+/// The call whose argument list contains the cursor.
+#[derive(Debug, PartialEq, Eq)]
+struct EnclosingCall {
+    /// The position of the first character of the callee's name.
+    callee: Position,
+    /// Whether the function is called with method syntax, e.g. `foo.bar()`, so `self` isn't an argument.
+    is_method_call: bool,
+    /// The index of the argument the cursor is in.
+    active_parameter: u32,
+}
+
+/// Finds the innermost call whose argument list contains `position`, by scanning backwards
+/// from it to the unmatched opening parenthesis.
+///
+/// Arguments are counted by the commas that aren't nested in other parentheses, brackets or braces.
+/// The scan stops at the end of the statement, so only calls in the current statement are found.
+fn enclosing_call(text: &str, position: Position) -> Option<EnclosingCall> {
+    let chars = chars_before(text, position);
+    let mut depth = 0;
+    let mut active_parameter = 0;
+    let mut open_paren = None;
+    for (i, c) in chars.iter().enumerate().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            '(' => {
+                open_paren = Some(i);
+                break;
+            }
+            '[' | '{' | ';' => return None,
+            ',' if depth == 0 => active_parameter += 1,
+            _ => {}
+        }
+    }
+
+    // Skip over any whitespace and turbofish between the callee and the parenthesis, e.g. `foo::<u64>(`.
+    let mut end = skip_whitespace_back(&chars, open_paren?);
+    if end > 0 && chars[end - 1] == '>' {
+        let mut depth = 0;
+        while end > 0 {
+            end -= 1;
+            match chars[end] {
+                '>' => depth += 1,
+                '<' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        end = skip_whitespace_back(&chars, end);
+        if end < 2 || chars[end - 2..end] != [':', ':'] {
+            return None;
+        }
+        end = skip_whitespace_back(&chars, end - 2);
+    }
+
+    let mut start = end;
+    while start > 0 && is_ident_char(chars[start - 1]) {
+        start -= 1;
+    }
+    if start == end {
+        return None;
+    }
+    let is_method_call = skip_whitespace_back(&chars, start)
+        .checked_sub(1)
+        .is_some_and(|i| chars[i] == '.');
+    Some(EnclosingCall {
+        callee: position_of(&chars, start),
+        is_method_call,
+        active_parameter,
+    })
+}
+
+/// Returns the characters of `text` that are before `position`.
+fn chars_before(text: &str, position: Position) -> Vec<char> {
+    let mut chars = vec![];
+    for (line, content) in text.split('\n').enumerate() {
+        if line == position.line as usize {
+            chars.extend(content.chars().take(position.character as usize));
+            break;
+        }
+        chars.extend(content.chars());
+        chars.push('\n');
+    }
+    chars
+}
+
+/// Returns the index after the last non-whitespace character before `end`.
+fn skip_whitespace_back(chars: &[char], mut end: usize) -> usize {
+    while end > 0 && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    end
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the [Position] of the character at `index`.
+fn position_of(chars: &[char], index: usize) -> Position {
+    let line_start = chars[..index]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let line = chars[..line_start].iter().filter(|c| **c == '\n').count();
+    Position::new(line as u32, (index - line_start) as u32)
+}
+
+/// Returns the declaration of the function that the token calls or declares.
+fn function_decl(token: &Token, engines: &Engines) -> Option<TyFunctionDecl> {
+    match token.typed.as_ref()? {
+        TypedAstToken::TypedExpression(ty::TyExpression {
+            expression: ty::TyExpressionVariant::FunctionApplication { fn_ref, .. },
+            ..
+        }) => Some((*engines.de().get_function(fn_ref)).clone()),
+        TypedAstToken::TypedFunctionDeclaration(fn_decl) => Some(fn_decl.clone()),
+        _ => None,
+    }
+}
+
+/// Builds the signature of the function, e.g. `fn foo(a: u64, b: bool) -> u64`.
+///
+/// If the function is called with method syntax, `self` is left out of the parameters.
+fn signature_information(
+    engines: &Engines,
+    fn_decl: &TyFunctionDecl,
+    is_method_call: bool,
+    encoding: PositionEncoding,
+) -> SignatureInformation {
+    let mut label = format!("fn {}(", fn_decl.name.as_str());
+    let mut parameters = vec![];
+    for param in fn_decl
+        .parameters
+        .iter()
+        .filter(|param| !(is_method_call && param.is_self()))
+    {
+        if !parameters.is_empty() {
+            label.push_str(", ");
+        }
+        let start = encoding.len(&label);
+        label.push_str(param.name.as_str());
+        if !param.is_self() {
+            label.push_str(": ");
+            label.push_str(param.type_argument.span.as_str());
+        }
+        let end = encoding.len(&label);
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: None,
+        });
+    }
+    label.push(')');
+    let return_type = engines.help_out(&fn_decl.return_type.type_id).to_string();
+    if return_type != "()" {
+        label.push_str(" -> ");
+        label.push_str(&return_type);
+    }
+    SignatureInformation {
+        label,
+        documentation: None,
+        parameters: Some(parameters),
+        active_parameter: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_at(text: &str, line: u32, character: u32) -> Option<(u32, u32, bool, u32)> {
+        enclosing_call(text, Position::new(line, character)).map(|call| {
+            (
+                call.callee.line,
+                call.callee.character,
+                call.is_method_call,
+                call.active_parameter,
+            )
+        })
+    }
+
+    #[test]
+    fn enclosing_call_counts_arguments() {
+        let text = "let x = add(1, 2);";
+        assert_eq!(call_at(text, 0, 12), Some((0, 8, false, 0)));
+        assert_eq!(call_at(text, 0, 15), Some((0, 8, false, 1)));
+        assert_eq!(call_at(text, 0, 8), None);
+    }
+
+    #[test]
+    fn enclosing_call_skips_nested_calls() {
+        let text = "let x = add(1, add(2, [3, 4]), S { a: 1, b: 2 }, 5);";
+        // Inside the nested call.
+        assert_eq!(call_at(text, 0, 22), Some((0, 15, false, 1)));
+        // After the nested call, array and struct expression.
+        assert_eq!(call_at(text, 0, 49), Some((0, 8, false, 3)));
+    }
+
+    #[test]
+    fn enclosing_call_handles_methods_and_turbofish() {
+        let text = "foo.bar(1,\n    baz::<u64, bool>(2, ";
+        assert_eq!(call_at(text, 0, 10), Some((0, 4, true, 1)));
+        assert_eq!(call_at(text, 1, 24), Some((1, 4, false, 1)));
+    }
+
+    #[test]
+    fn enclosing_call_stops_at_statement_end() {
+        let text = "foo(1);\nlet x = 1";
+        assert_eq!(call_at(text, 1, 9), None);
+    }
+}
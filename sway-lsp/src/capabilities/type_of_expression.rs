@@ -0,0 +1,143 @@
+use crate::{
+    capabilities::completion::{enclosing_fn, parse_module_of_file, span_contains},
+    core::{
+        session::{self, Session, TraversalResult},
+        sync::SyncWorkspace,
+        token::{get_range_from_span, TypedAstToken},
+    },
+    error::{DirectoryError, LanguageServerError},
+    lsp_ext::TypeOfExpressionResult,
+};
+use lsp_types::{Position, Url};
+use std::{fs, sync::Arc};
+use sway_core::{language::ty, TypeInfo};
+use sway_types::Spanned;
+
+/// The name of the variable that the expression is assigned to, so that its type can be read from the tokens.
+const PROBE_NAME: &str = "__sway_lsp_type_of_expression";
+
+/// Type checks `expression` in the scope of `position` and returns its type, or the errors of type checking it.
+///
+/// The expression is assigned to a variable that is inserted before the statement of the enclosing function that
+/// contains `position`, or that follows it, in a copy of the project. Only the variables that are declared before
+/// that statement are in scope. The session itself is left untouched.
+pub fn type_of_expression(
+    session: Arc<Session>,
+    uri: &Url,
+    position: Position,
+    expression: &str,
+) -> Result<TypeOfExpressionResult, LanguageServerError> {
+    let Some((source, probe_range)) = probe_source(&session, uri, position, expression) else {
+        return Ok(error_result(
+            "The position must be inside the body of a function",
+        ));
+    };
+
+    // The project is compiled from a copy of its temp directory, which has the unsaved changes of its files.
+    let scratch = SyncWorkspace::new();
+    scratch.create_temp_dir_from_workspace(&session.sync.temp_dir()?, None)?;
+    let result = scratch
+        .clone_manifest_dir_to_temp()
+        .map_err(LanguageServerError::from)
+        .and_then(|()| {
+            let scratch_uri = scratch.workspace_to_temp_url(uri)?;
+            let path =
+                scratch_uri
+                    .to_file_path()
+                    .map_err(|_| DirectoryError::UrlFromPathFailed {
+                        path: scratch_uri.to_string(),
+                    })?;
+            fs::write(&path, source).map_err(|_| DirectoryError::CopyContentsFailed)?;
+            let engines = session.engines.read().clone();
+            let build_config = session.config.read().build.clone();
+            let results = session::compile(&scratch_uri, &engines, &build_config, None)?;
+            let TraversalResult {
+                diagnostics: (errors, _),
+                token_map,
+                ..
+            } = session::traverse(results, &engines, session.position_encoding())?;
+
+            // The errors of the rest of the project were already reported for the session.
+            let errors = errors
+                .iter()
+                .filter(|error| {
+                    let span = error.span();
+                    span.source_id()
+                        .is_some_and(|source_id| engines.se().get_path(source_id) == path)
+                        && probe_range.contains(&span.start())
+                })
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            if !errors.is_empty() {
+                return Ok(TypeOfExpressionResult {
+                    type_name: None,
+                    errors,
+                });
+            }
+            let type_id = token_map
+                .tokens_for_file(&scratch_uri)
+                .find_map(|(ident, token)| match token.typed {
+                    Some(TypedAstToken::TypedDeclaration(ty::TyDecl::VariableDecl(decl)))
+                        if ident.name == PROBE_NAME =>
+                    {
+                        Some(decl.type_ascription.type_id)
+                    }
+                    _ => None,
+                })
+                .filter(|type_id| {
+                    !matches!(
+                        &*engines.te().get(*type_id),
+                        TypeInfo::Unknown | TypeInfo::ErrorRecovery(_)
+                    )
+                });
+            Ok(match type_id {
+                Some(type_id) => TypeOfExpressionResult {
+                    type_name: Some(engines.help_out(type_id).to_string()),
+                    errors: vec![],
+                },
+                None => error_result("The expression couldn't be type checked"),
+            })
+        });
+    if let Ok(dir) = scratch.temp_dir() {
+        dir.parent().map(fs::remove_dir_all);
+    }
+    result
+}
+
+fn error_result(message: &str) -> TypeOfExpressionResult {
+    TypeOfExpressionResult {
+        type_name: None,
+        errors: vec![message.to_string()],
+    }
+}
+
+/// Returns the source of the file with the expression assigned to the probe variable, and the byte range of
+/// the inserted statement, if `position` is inside the body of a function of the last compiled program.
+fn probe_source(
+    session: &Session,
+    uri: &Url,
+    position: Position,
+    expression: &str,
+) -> Option<(String, std::ops::Range<usize>)> {
+    let path = uri.to_file_path().ok()?;
+    let compiled_program = session.compiled_program.read();
+    let program = compiled_program.parsed.as_ref()?;
+    let engines = session.engines.read();
+    let module = parse_module_of_file(&program.root, engines.se(), &path)?;
+    let encoding = session.position_encoding();
+    let body = &enclosing_fn(&module.tree.root_nodes, position, encoding)?.body;
+    let body_span = body.span();
+    if !span_contains(&body_span, position, encoding) {
+        return None;
+    }
+    // Before the closing brace if the position is after every statement.
+    let offset = body
+        .contents
+        .iter()
+        .find(|node| get_range_from_span(&node.span, encoding).end >= position)
+        .map_or(body_span.end() - 1, |node| node.span.start());
+    let statement = format!("let {PROBE_NAME} = {expression};\n");
+    let src = body_span.src();
+    let source = format!("{}{statement}{}", &src[..offset], &src[offset..]);
+    Some((source, offset..offset + statement.len()))
+}
@@ -1,7 +1,20 @@
+use crate::utils::position_encoding::PositionEncoding;
+use forc_pkg::BuildProfile;
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Url};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use sway_core::BuildTarget;
 use tracing::metadata::LevelFilter;
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+/// The name of the optional file, placed next to a project's `Forc.toml`, that overrides
+/// the global [Config] for that project.
+pub const SESSION_CONFIG_FILE_NAME: &str = ".sway-lsp.toml";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     #[serde(default)]
@@ -14,34 +27,381 @@ pub struct Config {
     pub diagnostic: DiagnosticConfig,
     #[serde(default)]
     pub on_enter: OnEnterConfig,
+    #[serde(default)]
+    pub completion: CompletionConfig,
+    #[serde(default)]
+    pub symbols: SymbolsConfig,
+    #[serde(default)]
+    pub workspace: WorkspaceConfig,
+    /// How the tokens of a successful compilation are written to the session.
+    #[serde(default)]
+    pub token_map_write_strategy: TokenMapWriteStrategy,
+    #[serde(default)]
+    pub code_lens: CodeLensConfig,
+    #[serde(default)]
+    pub code_action: CodeActionConfig,
+    #[serde(default)]
+    pub format: FormatConfig,
+    #[serde(default)]
+    pub experimental: ExperimentalConfig,
+    #[serde(default)]
+    pub compilation: CompilationConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
+    #[serde(default)]
+    pub features: FeaturesConfig,
+    /// The workspace folders of the projects to initialize and compile as soon as the server is initialized.
+    #[serde(default)]
+    pub prewarm_projects: Vec<Url>,
+    /// If set, compilations are aborted once the memory used by the server exceeds this many megabytes.
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+    /// The directory to clone workspaces into while they're open. Defaults to the system's temp directory.
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+    /// How the projects are made available to the compiler. Only applies to projects opened after it's changed.
+    #[serde(default)]
+    pub sync_mode: SyncMode,
+    /// How long a request waits for the compilation it needs before answering with the last compiled results.
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+    /// The extensions, without the leading dot, of the files that are treated as Sway source.
+    /// Opened documents with any other extension are ignored.
+    #[serde(default = "default_file_extensions")]
+    pub file_extensions: Vec<String>,
+    /// Whether opening a file of a project that hasn't been compiled yet compiles it. If disabled, the project is
+    /// compiled on its first edit, save, or request that needs the typed program, like hover or go to definition.
+    #[serde(default = "default_compile_on_open")]
+    pub compile_on_open: bool,
+    /// Whether `didOpen` waits for the first compilation of a project it opens, so that its diagnostics and tokens
+    /// are there once the notification is handled, e.g. for scripted clients and tests. Later compilations don't block.
+    #[serde(default)]
+    pub synchronous_first_compile: bool,
+    /// The maximum number of projects that are initialized at the same time, e.g. when many files are
+    /// opened at once. Other projects wait for their turn. Defaults to the number of CPUs. Values below 1 are treated as 1.
+    #[serde(default = "default_max_parallel_init")]
+    pub max_parallel_init: usize,
+    /// Whether a change to the files of a path dependency, outside the editor, recompiles the projects that depend on it.
+    #[serde(default = "default_recompile_dependents")]
+    pub recompile_dependents: bool,
+    /// How many times a compilation that failed to read or write the project's files is retried, e.g. while
+    /// another process holds a lock on them. Compilations that fail because of errors in the code aren't retried.
+    #[serde(default)]
+    pub compile_retries: u32,
+    /// How long a compilation may take before it's aborted and counted as failed, so that a compiler that is stuck
+    /// doesn't keep the requests waiting for it from being answered. If unset, compilations may take any time.
+    #[serde(default)]
+    pub compile_timeout_ms: Option<u64>,
+    /// Whether the unsaved contents of the editor are compiled. If disabled, the files are compiled as they're
+    /// saved on disk, so that the diagnostics match a build, and edits are only compiled once they're saved.
+    #[serde(default = "default_analyze_unsaved")]
+    pub analyze_unsaved: bool,
+    /// The encoding of the character offsets of positions to use if the client supports it, `utf-8` or `utf-16`.
+    /// Clients that don't support it are sent UTF-16 offsets, which every client supports. Only read in `initialize`.
+    #[serde(default = "default_position_encoding")]
+    pub position_encoding: PositionEncoding,
+    /// Whether a `.sw` file that isn't in any package is analyzed on its own, as the entry of a package that is
+    /// generated around it. Only the file itself is compiled, so results may be incomplete.
+    #[serde(default)]
+    pub single_file_mode: bool,
+    /// Whether every package under the workspace folders is compiled once the server is initialized, so that
+    /// the diagnostics of all of them are shown upfront rather than as their files are opened.
+    #[serde(default)]
+    pub compile_workspace_on_open: bool,
+    /// Whether the compilation thread runs at a lower priority than the rest of the system, so that compiling
+    /// doesn't slow down the editor on a busy machine. Only supported on Linux. The priority can't be raised
+    /// back without privileges, so turning it off again takes effect once the server is restarted.
+    #[serde(default)]
+    pub low_priority_compilation: bool,
+    /// How the project that a file belongs to is found, which decides the session that analyzes it.
+    #[serde(default)]
+    pub root_detection: RootDetection,
+    /// Whether files outside all of the workspace folders, such as those opened on their own, are analyzed.
+    /// If not, requests for them get empty results, except for the files of the dependencies of open projects.
+    #[serde(default = "default_allow_files_outside_workspace")]
+    pub allow_files_outside_workspace: bool,
     #[serde(default, skip_serializing)]
     trace: TraceConfig,
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            debug: DebugConfig::default(),
+            logging: LoggingConfig::default(),
+            inlay_hints: InlayHintsConfig::default(),
+            diagnostic: DiagnosticConfig::default(),
+            on_enter: OnEnterConfig::default(),
+            completion: CompletionConfig::default(),
+            symbols: SymbolsConfig::default(),
+            workspace: WorkspaceConfig::default(),
+            token_map_write_strategy: TokenMapWriteStrategy::default(),
+            code_lens: CodeLensConfig::default(),
+            code_action: CodeActionConfig::default(),
+            format: FormatConfig::default(),
+            experimental: ExperimentalConfig::default(),
+            compilation: CompilationConfig::default(),
+            build: BuildConfig::default(),
+            features: FeaturesConfig::default(),
+            prewarm_projects: vec![],
+            max_memory_mb: None,
+            temp_dir: None,
+            sync_mode: SyncMode::default(),
+            request_timeout_ms: default_request_timeout_ms(),
+            file_extensions: default_file_extensions(),
+            compile_on_open: default_compile_on_open(),
+            synchronous_first_compile: false,
+            max_parallel_init: default_max_parallel_init(),
+            recompile_dependents: default_recompile_dependents(),
+            compile_retries: 0,
+            compile_timeout_ms: None,
+            analyze_unsaved: default_analyze_unsaved(),
+            position_encoding: default_position_encoding(),
+            single_file_mode: false,
+            compile_workspace_on_open: false,
+            low_priority_compilation: false,
+            root_detection: RootDetection::default(),
+            allow_files_outside_workspace: default_allow_files_outside_workspace(),
+            trace: TraceConfig::default(),
+        }
+    }
+}
+
+fn default_request_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_file_extensions() -> Vec<String> {
+    vec![sway_utils::constants::SWAY_EXTENSION.to_string()]
+}
+
+fn default_compile_on_open() -> bool {
+    true
+}
+
+fn default_max_parallel_init() -> usize {
+    std::thread::available_parallelism().map_or(1, |cpus| cpus.get())
+}
+
+fn default_recompile_dependents() -> bool {
+    true
+}
+
+fn default_analyze_unsaved() -> bool {
+    true
+}
+
+fn default_allow_files_outside_workspace() -> bool {
+    true
+}
+
+fn default_position_encoding() -> PositionEncoding {
+    PositionEncoding::Utf8
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Default)]
 struct TraceConfig {}
 
+impl Config {
+    /// Returns true if the file at `uri` has one of the [Config::file_extensions].
+    pub fn is_sway_file(&self, uri: &Url) -> bool {
+        let Some(extension) = Path::new(uri.path()).extension() else {
+            return false;
+        };
+        self.file_extensions
+            .iter()
+            .any(|sway_extension| extension == sway_extension.trim_start_matches('.'))
+    }
+
+    /// Resolves the config for the project in `manifest_dir`.
+    ///
+    /// If the project contains a [SESSION_CONFIG_FILE_NAME] file, the options it sets are
+    /// layered on top of `self`, which acts as the global default. Options that are not set
+    /// in the file keep their global value. If the file can't be read or parsed, the global
+    /// config is used as is.
+    pub fn resolve_for_manifest_dir(&self, manifest_dir: &Path) -> Config {
+        let path = manifest_dir.join(SESSION_CONFIG_FILE_NAME);
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return self.clone();
+        };
+        let overrides = match toml::from_str::<Value>(&contents) {
+            Ok(overrides) => overrides,
+            Err(err) => {
+                tracing::warn!("Unable to parse {}: {}", path.display(), err);
+                return self.clone();
+            }
+        };
+        let Ok(mut resolved) = serde_json::to_value(self) else {
+            return self.clone();
+        };
+        merge_values(&mut resolved, overrides);
+        serde_json::from_value(resolved).unwrap_or_else(|err| {
+            tracing::warn!("Invalid config in {}: {}", path.display(), err);
+            self.clone()
+        })
+    }
+}
+
+/// Recursively merges `overrides` into `base`. Tables are merged key by key,
+/// any other value in `overrides` replaces the one in `base`.
+fn merge_values(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_values(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
 // Options for debugging various parts of the server.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DebugConfig {
     pub show_collected_tokens_as_warnings: Warnings,
+    /// Whether to log each failed garbage collection to the client's output.
+    #[serde(default)]
+    pub log_gc_failures: bool,
+    /// Whether to log each compilation that's cancelled by a newer one to the client's output.
+    #[serde(default)]
+    pub log_cancelled_compilations: bool,
+    /// Whether to show the name of the project and how long it took to compile after each compilation.
+    /// The message is shown at most once every few seconds, however often the project is recompiled.
+    #[serde(default)]
+    pub show_compile_time: bool,
+    /// Whether to answer `sway/syntaxTree` requests with the syntax tree of a file.
+    #[serde(default)]
+    pub syntax_tree: bool,
+    /// Whether to answer `sway/memoryReport` requests with an estimate of the memory held by each session.
+    #[serde(default)]
+    pub memory_report: bool,
+    /// Restricts the tokens that are shown by `showCollectedTokensAsWarnings`.
+    #[serde(default)]
+    pub collected_tokens_filter: CollectedTokensFilter,
 }
 
 impl Default for DebugConfig {
     fn default() -> Self {
         Self {
             show_collected_tokens_as_warnings: Warnings::Default,
+            log_gc_failures: false,
+            log_cancelled_compilations: false,
+            show_compile_time: false,
+            syntax_tree: false,
+            memory_report: false,
+            collected_tokens_filter: CollectedTokensFilter::default(),
         }
     }
 }
 
+// Options for restricting the tokens that are shown as warnings when debugging the lsp parser.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CollectedTokensFilter {
+    /// The kinds of the tokens to show, as they're named in `SymbolKind`, e.g. `Function`.
+    /// Tokens of every kind are shown if it's empty.
+    pub kinds: Vec<String>,
+    /// A glob pattern, e.g. `get_*`, that the names of the shown tokens must match.
+    /// An invalid pattern doesn't match any name.
+    pub name_pattern: Option<String>,
+}
+
+impl CollectedTokensFilter {
+    /// Returns true if a token with the given name and kind should be shown.
+    pub fn matches(&self, name: &str, kind: &str) -> bool {
+        let kind_matches = self.kinds.is_empty() || self.kinds.iter().any(|k| k == kind);
+        let name_matches = self.name_pattern.as_ref().map_or(true, |pattern| {
+            glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(name))
+        });
+        kind_matches && name_matches
+    }
+}
+
 // Options for displaying compiler diagnostics.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct DiagnosticConfig {
     pub show_warnings: bool,
     pub show_errors: bool,
+    /// Whether to publish the diagnostics of the most recently edited file before those of other files.
+    pub prioritize_active_file: bool,
+    /// The codes of the warnings to hide, e.g. `dead_code`. Errors are always shown.
+    pub disabled_codes: Vec<String>,
+    /// Whether to warn about storage fields that are never read or written.
+    pub unused_storage_fields: bool,
+    /// Whether to prefix the message of each diagnostic with the path of its module, e.g. `[my_lib::utils]`.
+    pub module_prefix: bool,
+    /// If set, the diagnostics of the project are written to a file after each successful compilation.
+    pub snapshot: Option<DiagnosticSnapshotConfig>,
+    /// Glob patterns, e.g. `generated/**`, of the files to publish no diagnostics for.
+    /// They are matched against the path of each file relative to the project's `Forc.toml`.
+    pub exclude_globs: Vec<String>,
+    /// Whether to publish warnings with error severity. They are then shown if `show_errors` is set.
+    pub warnings_as_errors: bool,
+    /// Whether to publish the diagnostics of files in the project's path dependencies.
+    pub include_dependencies: bool,
+    /// Whether to only publish the diagnostics of the edited file while edits arrive in quick succession,
+    /// and those of the rest of the project once editing settles.
+    pub fast_path: bool,
+    /// The severity to publish the diagnostics of each code with, e.g. `{ "dead_code": 4 }` to show
+    /// unused declarations as hints. Takes precedence over `warnings_as_errors`.
+    pub severity_overrides: HashMap<String, DiagnosticSeverity>,
+    /// Whether to publish only the first of the diagnostics of a file that have the same range, code and message,
+    /// e.g. those that are reported for each instantiation of a generic function.
+    pub deduplicate: bool,
+    /// The source that the diagnostics are published with, so that they can be told apart from those of other tools.
+    pub source_label: String,
+    /// Whether a compilation that fails only replaces the diagnostics of the files it reported errors in, and
+    /// those of the last successful compilation are kept for the other files.
+    pub retain_on_failure: bool,
+}
+
+impl DiagnosticConfig {
+    /// Returns true if the warning should be published, based on its code.
+    pub fn is_warning_enabled(&self, warning: &Diagnostic) -> bool {
+        match &warning.code {
+            Some(NumberOrString::String(code)) => !self.disabled_codes.contains(code),
+            _ => true,
+        }
+    }
+
+    /// Returns the severity to publish the warning with, which is its override if it has one.
+    pub fn warning_severity(&self, warning: &Diagnostic) -> DiagnosticSeverity {
+        self.severity_override(warning)
+            .unwrap_or(if self.warnings_as_errors {
+                DiagnosticSeverity::ERROR
+            } else {
+                DiagnosticSeverity::WARNING
+            })
+    }
+
+    /// Returns the severity to publish the error with, which is its override if it has one.
+    ///
+    /// Errors can't be overridden to hints, as editors barely show them and the project wouldn't compile
+    /// without a visible reason, so they're shown as information instead.
+    pub fn error_severity(&self, error: &Diagnostic) -> DiagnosticSeverity {
+        match self.severity_override(error) {
+            Some(DiagnosticSeverity::HINT) => DiagnosticSeverity::INFORMATION,
+            Some(severity) => severity,
+            None => DiagnosticSeverity::ERROR,
+        }
+    }
+
+    fn severity_override(&self, diagnostic: &Diagnostic) -> Option<DiagnosticSeverity> {
+        match &diagnostic.code {
+            Some(NumberOrString::String(code)) => self.severity_overrides.get(code).copied(),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the file at `path`, relative to the project's `Forc.toml`, matches one of the
+    /// [DiagnosticConfig::exclude_globs]. Invalid patterns don't match any file.
+    pub fn is_path_excluded(&self, path: &Path) -> bool {
+        self.exclude_globs
+            .iter()
+            .any(|glob| glob::Pattern::new(glob).is_ok_and(|pattern| pattern.matches_path(path)))
+    }
 }
 
 impl Default for DiagnosticConfig {
@@ -49,10 +409,108 @@ impl Default for DiagnosticConfig {
         Self {
             show_warnings: true,
             show_errors: true,
+            prioritize_active_file: true,
+            disabled_codes: vec![],
+            unused_storage_fields: false,
+            module_prefix: false,
+            snapshot: None,
+            exclude_globs: vec![],
+            warnings_as_errors: false,
+            include_dependencies: false,
+            fast_path: false,
+            severity_overrides: HashMap::new(),
+            deduplicate: true,
+            source_label: "sway".to_string(),
+            retain_on_failure: false,
+        }
+    }
+}
+
+// Options for writing the diagnostics of a project to a file, e.g. for CI to pick up.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiagnosticSnapshotConfig {
+    /// The path of the file, relative to the project's `Forc.toml`.
+    pub path: PathBuf,
+    /// The minimum time between two writes of the file, in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl Default for DiagnosticSnapshotConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(".sway-lsp/diagnostics.json"),
+            interval_ms: 0,
+        }
+    }
+}
+
+// Options for the compilation thread and its queue of requests.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompilationConfig {
+    /// The maximum number of compilation requests that can wait for the compilation thread.
+    ///
+    /// Once the queue is full, the oldest waiting request is dropped to make room for the newest one,
+    /// as only the latest edit of a file matters. Enqueueing never blocks. Values below 1 are treated as 1.
+    pub queue_capacity: usize,
+    /// The maximum number of background compilation requests, like those of `prewarmProjects` and
+    /// `sway/recompileWorkspace`, that can wait for the compilation thread.
+    ///
+    /// They wait in a queue of their own, which is only taken from while no edit is waiting to be compiled.
+    /// Values below 1 are treated as 1.
+    pub background_queue_capacity: usize,
+    /// Whether to send `sway/compilationStatus` notifications when a compilation starts and finishes,
+    /// so that editor extensions don't have to poll for the state of their projects.
+    pub status_notifications: bool,
+}
+
+impl Default for CompilationConfig {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 1,
+            background_queue_capacity: 1,
+            status_notifications: false,
+        }
+    }
+}
+
+// Options passed through to the build of a project.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BuildConfig {
+    /// The name of the build profile in the project's `Forc.toml` to compile with, e.g. `release`.
+    /// Falls back to `debug` if the project doesn't define a profile with this name.
+    pub profile: String,
+    /// The target to compile for.
+    pub target: BuildTarget,
+    /// Whether to compile the project's tests.
+    pub include_tests: bool,
+    /// If set, overrides the `experimental.new-encoding` flag of the profile.
+    pub new_encoding: Option<bool>,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            profile: BuildProfile::DEFAULT.to_string(),
+            target: BuildTarget::default(),
+            include_tests: true,
+            new_encoding: None,
         }
     }
 }
 
+// Options for features that are still in development.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExperimentalConfig {
+    /// Whether to publish the parse errors of the edited files before the rest of the compilation has finished.
+    pub streaming_diagnostics: bool,
+    /// Whether to answer `sway/typeOfExpression` requests, which compile a copy of the project each time.
+    pub type_of_expression: bool,
+}
+
 // Options for confguring server logging.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LoggingConfig {
@@ -73,7 +531,7 @@ impl Default for LoggingConfig {
 #[serde(rename_all = "lowercase")]
 #[serde(remote = "LevelFilter")]
 #[allow(clippy::upper_case_acronyms)]
-enum LevelFilterDef {
+pub(crate) enum LevelFilterDef {
     OFF,
     ERROR,
     WARN,
@@ -86,11 +544,39 @@ enum LevelFilterDef {
 /// under all of the tokens that our server managed to parse.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub enum Warnings {
+    #[serde(rename = "off")]
     Default,
+    #[serde(rename = "parsed")]
     Parsed,
+    #[serde(rename = "typed")]
     Typed,
 }
 
+/// How the project that a file belongs to is found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RootDetection {
+    /// The package of the nearest `Forc.toml` above the file.
+    #[default]
+    NearestManifest,
+    /// The package of the `Forc.toml` at the root of the workspace folder that the file is in, even if a
+    /// `Forc.toml` nested in the folder is nearer, e.g. one of a package that's only used by tests.
+    /// Files of folders without a package at their root fall back to the nearest `Forc.toml`.
+    WorkspaceRoot,
+}
+
+/// How the compiler sees the unsaved contents of the editor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncMode {
+    /// The project is cloned into a temp directory, where the unsaved changes are written to.
+    #[default]
+    Temp,
+    /// The project is compiled from its own directory, with the unsaved changes kept in memory.
+    /// Nothing is written to disk, for environments where the temp directory can't be written to.
+    InPlace,
+}
+
 // Options for configuring inlay hints.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -101,6 +587,9 @@ pub struct InlayHintsConfig {
     pub type_hints: bool,
     /// Maximum length for inlay hints. Set to null to have an unlimited length.
     pub max_length: Option<usize>,
+    /// Whether to show the type of each method call in chains that span multiple lines.
+    #[serde(default)]
+    pub chaining_hints: bool,
 }
 
 impl Default for InlayHintsConfig {
@@ -109,6 +598,7 @@ impl Default for InlayHintsConfig {
             render_colons: true,
             type_hints: true,
             max_length: Some(25),
+            chaining_hints: false,
         }
     }
 }
@@ -130,6 +620,216 @@ impl Default for OnEnterConfig {
     }
 }
 
+// Options for configuring code lenses.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CodeLensConfig {
+    /// Whether to show the number of references above each function, struct and trait declaration.
+    /// Counting them requires a scan of all references of the project, so it's off by default.
+    pub references: bool,
+}
+
+// Options for configuring code actions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CodeActionConfig {
+    /// The fixes that the `source.fixAll` code action applies. Fixes that change more than the code they fix,
+    /// like removing storage fields, which moves the slots of the other fields, aren't applied by default.
+    pub fix_all: Vec<FixAllCategory>,
+}
+
+impl Default for CodeActionConfig {
+    fn default() -> Self {
+        Self {
+            fix_all: vec![
+                FixAllCategory::OrganizeImports,
+                FixAllCategory::UnusedVariables,
+                FixAllCategory::MakeMutable,
+            ],
+        }
+    }
+}
+
+/// A kind of fix that the `source.fixAll` code action can apply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FixAllCategory {
+    /// Sorts and merges the imports and removes the unused ones, like `source.organizeImports`.
+    OrganizeImports,
+    /// Removes the `let` statements of variables that are never used.
+    UnusedVariables,
+    /// Removes the storage fields that are never accessed.
+    UnusedStorageFields,
+    /// Declares the variables that are reassigned as mutable.
+    MakeMutable,
+}
+
+// Options for configuring document formatting.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatConfig {
+    /// Whether documents can be formatted with the project's `swayfmt.toml`, or the client's options
+    /// if it has none. Like the other capabilities, it's only advertised when the client initializes.
+    pub enabled: bool,
+    /// Whether to reindent the current line as `}`, `;` and newlines are typed.
+    pub on_type: bool,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_type: false,
+        }
+    }
+}
+
+// Options for configuring completion.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompletionConfig {
+    /// Whether to complete function calls as snippets, with a placeholder for each argument.
+    /// Has no effect if the client doesn't support snippets.
+    pub snippets: bool,
+    /// Whether the names that aren't in scope are completed as well, with an edit that imports them.
+    pub auto_import: bool,
+    /// Whether names that begin with `_`, which are conventionally unused or private, are completed.
+    /// Defaults to false, so they're only offered once their name is typed in full.
+    pub include_underscore_items: bool,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            snippets: true,
+            auto_import: true,
+            include_underscore_items: false,
+        }
+    }
+}
+
+/// How the tokens of a successful compilation are written to the session, see `tokenMapWriteStrategy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenMapWriteStrategy {
+    /// The tokens that are gone are removed from the session's map and the others are replaced in place.
+    /// Requests that read the map meanwhile wait for each shard that is being written.
+    #[default]
+    Incremental,
+    /// The map that the compilation built replaces the session's map, which only takes a lock for the time of
+    /// a pointer swap. Requests that are reading the old map keep doing so until they finish, which holds
+    /// both maps in memory for that long.
+    Swap,
+}
+
+// Options for configuring the symbols of `textDocument/documentSymbol` and `sway/symbolsByKind`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SymbolsConfig {
+    /// Whether symbols whose names begin with `_` are listed, along with those nested in them.
+    /// Defaults to true, so that the outline shows every item of the file.
+    pub include_underscore_items: bool,
+}
+
+impl Default for SymbolsConfig {
+    fn default() -> Self {
+        Self {
+            include_underscore_items: true,
+        }
+    }
+}
+
+// Options for the results that are gathered across the files and projects of the workspace,
+// like references, call hierarchies and the packages compiled by `compileWorkspaceOnOpen`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkspaceConfig {
+    /// The directories whose files are left out of those results, either by name, e.g. `out`, which matches
+    /// directories of that name at any depth, or by their path relative to the project's `Forc.toml`,
+    /// e.g. `tests/fixtures`. Defaults to the build outputs of forc and cargo.
+    pub exclude_dirs: Vec<String>,
+}
+
+impl WorkspaceConfig {
+    /// Returns true if the file at `path`, relative to the project's `Forc.toml`, is in one of the
+    /// [WorkspaceConfig::exclude_dirs].
+    pub fn is_path_excluded(&self, path: &Path) -> bool {
+        path.parent().is_some_and(|dir| self.is_dir_excluded(dir))
+    }
+
+    /// Returns true if the directory at `dir`, relative to the project's `Forc.toml` or to a workspace folder,
+    /// is one of the [WorkspaceConfig::exclude_dirs] or is nested in one.
+    pub fn is_dir_excluded(&self, dir: &Path) -> bool {
+        self.exclude_dirs.iter().any(|exclude_dir| {
+            let exclude_dir = Path::new(exclude_dir);
+            if exclude_dir.components().count() == 1 {
+                dir.components()
+                    .any(|component| component.as_os_str() == exclude_dir.as_os_str())
+            } else {
+                dir.starts_with(exclude_dir)
+            }
+        })
+    }
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            exclude_dirs: vec!["out".to_string(), "target".to_string()],
+        }
+    }
+}
+
+// Options for turning off individual language features.
+//
+// Disabled features aren't advertised in the server capabilities, and their requests get an empty response.
+// The capabilities are only sent when the client initializes, so features disabled later are still requested.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FeaturesConfig {
+    pub call_hierarchy: bool,
+    pub code_action: bool,
+    pub code_lens: bool,
+    pub completion: bool,
+    pub definition: bool,
+    pub document_highlight: bool,
+    pub document_link: bool,
+    pub document_symbol: bool,
+    pub formatting: bool,
+    pub hover: bool,
+    pub implementation: bool,
+    pub inlay_hint: bool,
+    pub references: bool,
+    pub rename: bool,
+    pub semantic_tokens: bool,
+    pub signature_help: bool,
+    pub type_definition: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            call_hierarchy: true,
+            code_action: true,
+            code_lens: true,
+            completion: true,
+            definition: true,
+            document_highlight: true,
+            document_link: true,
+            document_symbol: true,
+            formatting: true,
+            hover: true,
+            implementation: true,
+            inlay_hint: true,
+            references: true,
+            rename: true,
+            semantic_tokens: true,
+            signature_help: true,
+            type_definition: true,
+        }
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for Warnings {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -157,3 +857,173 @@ impl<'de> serde::Deserialize<'de> for Warnings {
         deserializer.deserialize_any(WarningsVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_for_manifest_dir_overrides_global_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(SESSION_CONFIG_FILE_NAME),
+            "[diagnostic]\nshowWarnings = false\n",
+        )
+        .unwrap();
+        let global = Config::default();
+        let resolved = global.resolve_for_manifest_dir(dir.path());
+        assert!(!resolved.diagnostic.show_warnings);
+        assert!(resolved.diagnostic.show_errors);
+        assert_eq!(resolved.inlay_hints, global.inlay_hints);
+    }
+
+    #[test]
+    fn disabled_codes_hide_matching_warnings() {
+        let config = DiagnosticConfig {
+            disabled_codes: vec!["dead_code".to_string()],
+            ..Default::default()
+        };
+        let warning = |code: Option<&str>| Diagnostic {
+            code: code.map(|code| NumberOrString::String(code.to_string())),
+            ..Default::default()
+        };
+        assert!(!config.is_warning_enabled(&warning(Some("dead_code"))));
+        assert!(config.is_warning_enabled(&warning(Some("naming_convention"))));
+        assert!(config.is_warning_enabled(&warning(None)));
+    }
+
+    #[test]
+    fn severity_overrides_take_precedence_over_warnings_as_errors() {
+        let config = DiagnosticConfig {
+            warnings_as_errors: true,
+            severity_overrides: HashMap::from([
+                ("dead_code".to_string(), DiagnosticSeverity::HINT),
+                ("type_error".to_string(), DiagnosticSeverity::HINT),
+                ("shadowing".to_string(), DiagnosticSeverity::WARNING),
+            ]),
+            ..Default::default()
+        };
+        let diagnostic = |code: &str| Diagnostic {
+            code: Some(NumberOrString::String(code.to_string())),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.warning_severity(&diagnostic("dead_code")),
+            DiagnosticSeverity::HINT
+        );
+        assert_eq!(
+            config.warning_severity(&diagnostic("naming_convention")),
+            DiagnosticSeverity::ERROR
+        );
+        // Errors stay visible.
+        assert_eq!(
+            config.error_severity(&diagnostic("type_error")),
+            DiagnosticSeverity::INFORMATION
+        );
+        assert_eq!(
+            config.error_severity(&diagnostic("shadowing")),
+            DiagnosticSeverity::WARNING
+        );
+        assert_eq!(
+            config.error_severity(&diagnostic("naming_convention")),
+            DiagnosticSeverity::ERROR
+        );
+    }
+
+    #[test]
+    fn collected_tokens_filter_matches_kinds_and_names() {
+        assert!(CollectedTokensFilter::default().matches("foo", "Variable"));
+        let filter = CollectedTokensFilter {
+            kinds: vec!["Function".to_string()],
+            name_pattern: Some("get_*".to_string()),
+        };
+        assert!(filter.matches("get_balance", "Function"));
+        assert!(!filter.matches("set_balance", "Function"));
+        assert!(!filter.matches("get_balance", "Variable"));
+    }
+
+    #[test]
+    fn exclude_globs_match_relative_paths() {
+        let config = DiagnosticConfig {
+            exclude_globs: vec!["src/generated/**".to_string(), "[".to_string()],
+            ..Default::default()
+        };
+        assert!(config.is_path_excluded(Path::new("src/generated/abi.sw")));
+        assert!(config.is_path_excluded(Path::new("src/generated/nested/abi.sw")));
+        assert!(!config.is_path_excluded(Path::new("src/main.sw")));
+    }
+
+    #[test]
+    fn exclude_dirs_match_names_and_relative_paths() {
+        let config = WorkspaceConfig {
+            exclude_dirs: vec!["out".to_string(), "tests/fixtures".to_string()],
+        };
+        assert!(config.is_path_excluded(Path::new("out/debug/main.sw")));
+        assert!(config.is_path_excluded(Path::new("contracts/a/out/main.sw")));
+        assert!(config.is_path_excluded(Path::new("tests/fixtures/a/src/main.sw")));
+        assert!(!config.is_path_excluded(Path::new("out.sw")));
+        assert!(!config.is_path_excluded(Path::new("src/fixtures/main.sw")));
+        assert!(!config.is_path_excluded(Path::new("src/tests/fixtures.sw")));
+        assert!(config.is_dir_excluded(Path::new("tests/fixtures")));
+        assert!(!config.is_dir_excluded(Path::new("tests")));
+    }
+
+    #[test]
+    fn is_sway_file_matches_the_file_extensions() {
+        let url = |path: &str| Url::parse(&format!("file:///project/src/{path}")).unwrap();
+        let config = Config::default();
+        assert!(config.is_sway_file(&url("main.sw")));
+        assert!(!config.is_sway_file(&url("main.sw.tera")));
+        assert!(!config.is_sway_file(&url("Forc")));
+
+        let config = Config {
+            file_extensions: vec!["sw".to_string(), ".tera".to_string()],
+            ..Default::default()
+        };
+        assert!(config.is_sway_file(&url("main.sw.tera")));
+        assert!(!config.is_sway_file(&url("main.txt")));
+    }
+
+    #[test]
+    fn missing_options_use_the_defaults() {
+        let config: Config = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.request_timeout_ms, 30_000);
+        assert!(config.compile_on_open);
+        assert!(!config.synchronous_first_compile);
+        assert!(config.max_parallel_init >= 1);
+        assert!(config.recompile_dependents);
+        assert_eq!(config.compile_retries, 0);
+        assert_eq!(config.compile_timeout_ms, None);
+        assert!(config.analyze_unsaved);
+        assert_eq!(config.position_encoding, PositionEncoding::Utf8);
+        assert!(!config.single_file_mode);
+        assert!(!config.compile_workspace_on_open);
+        assert!(!config.low_priority_compilation);
+        assert_eq!(config.root_detection, RootDetection::NearestManifest);
+        assert!(config.allow_files_outside_workspace);
+        assert!(config.completion.auto_import);
+        assert!(!config.completion.include_underscore_items);
+        assert!(config.symbols.include_underscore_items);
+        assert_eq!(config.workspace.exclude_dirs, vec!["out", "target"]);
+        assert_eq!(
+            config.token_map_write_strategy,
+            TokenMapWriteStrategy::Incremental
+        );
+        assert!(config.diagnostic.deduplicate);
+        assert_eq!(config.diagnostic.source_label, "sway");
+        assert!(!config.diagnostic.retain_on_failure);
+        assert!(!config.compilation.status_notifications);
+        assert!(!config
+            .code_action
+            .fix_all
+            .contains(&FixAllCategory::UnusedStorageFields));
+    }
+
+    #[test]
+    fn resolve_for_manifest_dir_without_file_uses_global_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = Config::default();
+        assert_eq!(global.resolve_for_manifest_dir(dir.path()), global);
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 use crate::{
     error::{DirectoryError, DocumentError, LanguageServerError},
-    utils::document,
+    utils::{document, position_encoding::PositionEncoding},
 };
 use lsp_types::{Position, Range, TextDocumentContentChangeEvent, Url};
 use ropey::Rope;
@@ -38,8 +38,13 @@ impl TextDocument {
         self.content.line(line).to_string()
     }
 
-    pub fn apply_change(&mut self, change: &TextDocumentContentChangeEvent) {
-        let edit = self.build_edit(change);
+    /// Applies `change`, whose range is in `encoding`, to the contents of the document.
+    pub fn apply_change(
+        &mut self,
+        change: &TextDocumentContentChangeEvent,
+        encoding: PositionEncoding,
+    ) {
+        let edit = self.build_edit(change, encoding);
 
         self.content.remove(edit.start_index..edit.end_index);
         self.content.insert(edit.start_index, edit.change_text);
@@ -55,6 +60,7 @@ impl TextDocument {
     fn build_edit<'change>(
         &self,
         change: &'change TextDocumentContentChangeEvent,
+        encoding: PositionEncoding,
     ) -> EditText<'change> {
         let change_text = change.text.as_str();
         let text_bytes = change_text.as_bytes();
@@ -63,14 +69,14 @@ impl TextDocument {
         let range = match change.range {
             Some(range) => range,
             None => {
-                let start = self.byte_to_position(0);
-                let end = self.byte_to_position(text_end_byte_index);
+                let start = self.byte_to_position(0, encoding);
+                let end = self.byte_to_position(text_end_byte_index, encoding);
                 Range { start, end }
             }
         };
 
-        let start_index = self.position_to_index(range.start);
-        let end_index = self.position_to_index(range.end);
+        let start_index = self.position_to_index(range.start, encoding);
+        let end_index = self.position_to_index(range.end, encoding);
 
         EditText {
             start_index,
@@ -79,32 +85,26 @@ impl TextDocument {
         }
     }
 
-    fn byte_to_position(&self, byte_index: usize) -> Position {
+    fn byte_to_position(&self, byte_index: usize, encoding: PositionEncoding) -> Position {
         let line_index = self.content.byte_to_line(byte_index);
+        let line_byte_index = self.content.line_to_byte(line_index);
+        let line_prefix = self.content.byte_slice(line_byte_index..byte_index);
 
-        let line_utf16_cu_index = {
-            let char_index = self.content.line_to_char(line_index);
-            self.content.char_to_utf16_cu(char_index)
-        };
-
-        let character_utf16_cu_index = {
-            let char_index = self.content.byte_to_char(byte_index);
-            self.content.char_to_utf16_cu(char_index)
-        };
+        let character = encoding.len(&line_prefix.to_string());
 
-        let character = character_utf16_cu_index - line_utf16_cu_index;
-
-        Position::new(line_index as u32, character as u32)
+        Position::new(line_index as u32, character)
     }
 
-    fn position_to_index(&self, position: Position) -> usize {
-        let row_index = position.line as usize;
-        let column_index = position.character as usize;
-
+    /// Returns the char index of the `position`, whose character offset is in `encoding`.
+    fn position_to_index(&self, position: Position, encoding: PositionEncoding) -> usize {
+        let row_index = (position.line as usize).min(self.content.len_lines().saturating_sub(1));
         let row_char_index = self.content.line_to_char(row_index);
-        let column_char_index = self.content.utf16_cu_to_char(column_index);
 
-        row_char_index + column_char_index
+        let line = self.content.line(row_index).to_string();
+        let line = line.trim_end_matches(['\n', '\r']);
+        let column_byte_index = encoding.byte_offset(line, position.character);
+
+        row_char_index + line[..column_byte_index].chars().count()
     }
 }
 
@@ -167,6 +167,35 @@ mod tests {
         assert!(result.is_ok(), "result = {result:?}");
     }
 
+    #[test]
+    fn apply_change_counts_characters_in_utf16() {
+        let mut document = TextDocument {
+            language_id: "sway".into(),
+            version: 1,
+            uri: "main.sw".into(),
+            content: Rope::from_str("let a = \"é🦀\";\nlet b = 1;\n"),
+        };
+        // `é` is 1 UTF-16 code unit and `🦀` is 2, so the closing quote is at character 12.
+        document.apply_change(
+            &TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 12), Position::new(0, 13))),
+                range_length: None,
+                text: "!\"".into(),
+            },
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(document.get_text(), "let a = \"é🦀!\";\nlet b = 1;\n");
+        document.apply_change(
+            &TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(1, 8), Position::new(1, 9))),
+                range_length: None,
+                text: "2".into(),
+            },
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(document.get_text(), "let a = \"é🦀!\";\nlet b = 2;\n");
+    }
+
     #[tokio::test]
     async fn build_from_path_returns_document_not_found_error() {
         let path = get_absolute_path("not/a/real/file/path");
@@ -1,60 +1,88 @@
 use crate::{
     capabilities::{
         self,
-        diagnostic::DiagnosticMap,
-        formatting::get_page_text_edit,
+        code_actions::diagnostic::auto_import::FileImports,
+        completion::{CompletionContext, TypedNames},
+        diagnostic::{DiagnosticMap, DiagnosticsHistory},
+        formatting::{get_page_text_edit, get_range_text_edit, project_formatter},
+        on_type_formatting::on_type_formatting,
         runnable::{Runnable, RunnableMainFn, RunnableTestFn},
     },
+    config::{BuildConfig, Config, SyncMode, TokenMapWriteStrategy},
     core::{
         document::TextDocument,
         sync::SyncWorkspace,
-        token::{self, TypedAstToken},
+        token::{self, AstToken, Token, TokenIdent, TypedAstToken},
         token_map::{TokenMap, TokenMapExt},
     },
-    error::{DocumentError, LanguageServerError},
+    error::{DirectoryError, DocumentError, LanguageServerError},
+    server_state::LastCompilationState,
     traverse::{
         dependency, lexed_tree, parsed_tree::ParsedTree, typed_tree::TypedTree, ParseContext,
     },
+    utils::{
+        keyword_docs::KeywordDocs,
+        position_encoding::PositionEncoding,
+        position_kind::{self, PositionKind},
+    },
 };
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use forc_pkg as pkg;
 use lsp_types::{
-    CompletionItem, GotoDefinitionResponse, Location, Position, Range, SymbolInformation,
-    TextDocumentContentChangeEvent, TextEdit, Url,
+    request::GotoTypeDefinitionResponse, CompletionItem, FormattingOptions, GotoDefinitionResponse,
+    Location, Position, Range, SymbolInformation, TextDocumentContentChangeEvent, TextEdit, Url,
 };
-use parking_lot::RwLock;
-use pkg::{manifest::ManifestFile, BuildPlan};
+use parking_lot::{Mutex, RwLock};
+use pkg::{manifest::ManifestFile, BuildPlan, BuildProfile};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::{
-    ops::Deref,
-    path::PathBuf,
-    sync::{atomic::AtomicBool, Arc},
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 use sway_core::{
     decl_engine::DeclEngine,
     language::{
         lexed::LexedProgram,
-        parsed::{AstNode, ParseProgram},
+        parsed::{AstNode, Expression, ExpressionKind, ParseProgram, SubfieldExpression},
         ty::{self},
         HasSubmodules,
     },
-    BuildTarget, Engines, Namespace, Programs,
+    Engines, Namespace, Programs, TypeInfo,
 };
 use sway_error::{error::CompileError, handler::Handler, warning::CompileWarning};
 use sway_types::{SourceEngine, SourceId, Spanned};
 use sway_utils::{helpers::get_sway_files, PerformanceData};
-use tokio::{fs::File, io::AsyncWriteExt};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Notify};
 
 pub type Documents = DashMap<String, TextDocument>;
 pub type ProjectDirectory = PathBuf;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct CompiledProgram {
     pub lexed: Option<LexedProgram>,
     pub parsed: Option<ParseProgram>,
     pub typed: Option<ty::TyProgram>,
 }
 
+/// The results of a compilation of a session, taken with [Session::snapshot] so that they can be put back
+/// with [Session::restore], e.g. to run many tests against the same compilation without recompiling.
+///
+/// The engines, tokens, programs and diagnostics are cloned, so later compilations of the session don't
+/// change the snapshot. Runnables and metrics aren't part of it.
+#[derive(Debug)]
+pub struct SessionSnapshot {
+    engines: Engines,
+    token_map: TokenMap,
+    compiled_program: CompiledProgram,
+    diagnostics: DiagnosticMap,
+    last_compilation_state: LastCompilationState,
+}
+
 /// Used to write the result of compiling into so we can update
 /// the types in [Session] after successfully parsing.
 #[derive(Debug, Default)]
@@ -63,6 +91,21 @@ pub struct ParseResult {
     pub(crate) token_map: TokenMap,
     pub(crate) compiled_program: CompiledProgram,
     pub(crate) metrics: DashMap<SourceId, PerformanceData>,
+    pub(crate) phase_durations: PhaseDurations,
+}
+
+/// How long each phase of a compilation took.
+///
+/// The compiler parses and type checks each module in one pass, so the split between the two is
+/// only available per module, in the [PerformanceData] of its metrics.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PhaseDurations {
+    /// The garbage collection of the engines before the compilation, if there was one.
+    pub garbage_collection: Option<Duration>,
+    /// Parsing and type checking the project.
+    pub compilation: Duration,
+    /// Collecting the tokens of the parsed and typed programs.
+    pub traversal: Duration,
 }
 
 /// A `Session` is used to store information about a single member in a workspace.
@@ -71,15 +114,267 @@ pub struct ParseResult {
 /// The API provides methods for responding to LSP requests from the server.
 #[derive(Debug)]
 pub struct Session {
-    token_map: TokenMap,
+    // Behind a lock so that a compilation can swap in the map it built, see [TokenMapWriteStrategy::Swap].
+    token_map: RwLock<Arc<TokenMap>>,
     pub documents: Documents,
+    // The paths of the documents whose contents in the editor haven't been saved to the user's workspace.
+    pub unsaved_documents: DashSet<String>,
     pub runnables: DashMap<PathBuf, Vec<Box<dyn Runnable>>>,
     pub compiled_program: RwLock<CompiledProgram>,
     pub engines: RwLock<Engines>,
     pub sync: SyncWorkspace,
     // Cached diagnostic results that require a lock to access. Readers will wait for writers to complete.
     pub diagnostics: Arc<RwLock<DiagnosticMap>>,
+    // The diagnostics of the last successful compilation of this project, for `diagnostic.retainOnFailure`.
+    pub last_successful_diagnostics: RwLock<DiagnosticMap>,
     pub metrics: DashMap<SourceId, PerformanceData>,
+    // The config resolved for this project. See [Config::resolve_for_manifest_dir].
+    pub config: RwLock<Config>,
+    // True while the compilation thread is compiling this project.
+    pub is_compiling: AtomicBool,
+    // Notifies waiters each time a compilation of this project finishes.
+    pub finished_compilation: Notify,
+    // When the diagnostics of this project were last written to the `diagnostic.snapshot` file.
+    pub last_diagnostics_snapshot: RwLock<Option<Instant>>,
+    // The temp url of the file whose last compilation was aborted for exceeding `maxMemoryMb`, and the limit in megabytes.
+    pub memory_limit_exceeded: RwLock<Option<(Url, u64)>>,
+    // The state of the last compilation of this project.
+    pub last_compilation_state: RwLock<LastCompilationState>,
+    // The number of times the garbage collection of this project has failed.
+    pub gc_failures: AtomicUsize,
+    // The number of compilations of this project that were cancelled by a newer one.
+    pub cancelled_compilations: AtomicUsize,
+    // The number of compilations of this project in which the compiler panicked or reported an internal error.
+    pub internal_compiler_errors: AtomicUsize,
+    // How long each phase of the last successful compilation of this project took.
+    pub phase_durations: RwLock<PhaseDurations>,
+    // Whether the user has been asked to recompile the project because of repeated garbage collection failures.
+    pub suggested_recompile: AtomicBool,
+    // Whether the next compilation of this project garbage collects the engines first, whatever the version
+    // of the edits it compiles. Set by `sway/collectGarbage`.
+    pub garbage_collection_requested: AtomicBool,
+    // Which of the requested compilations of this project have been compiled, see [ServerState::compile_now].
+    pub compilation_generations: Mutex<CompilationGenerations>,
+    // The versions of the edits that compilations of this project were requested for, which decide whether
+    // the compilation in progress is cancelled.
+    pub compilation_cancellation: CompilationCancellation,
+    // The timing of the edits to this project, used by `diagnostic.fastPath` to detect bursts of edits.
+    pub edit_burst: Mutex<EditBurst>,
+    // Whether only the diagnostics of the edited file were published during a burst of edits,
+    // so that the rest still need to be published once editing settles.
+    pub diagnostics_sweep_pending: AtomicBool,
+    // The diagnostics of the last two successful compilations of this project, for `sway/diagnosticsDiff`.
+    // A reset starts a new session, so it starts over.
+    pub diagnostics_history: Mutex<DiagnosticsHistory>,
+    /// Changes each time the engines are swapped for those of a new compilation, so that results cached
+    /// against the old ones are never served. Unique across sessions, so a reset session doesn't reuse one.
+    pub engines_generation: AtomicU64,
+    // The tokens that were last looked up by position, so that requests for the same cursor don't each scan the file.
+    pub token_position_cache: Mutex<TokenPositionCache>,
+    // The encoding that was negotiated with the client, which the ranges of this project are computed in.
+    pub position_encoding: RwLock<PositionEncoding>,
+}
+
+/// Returns an engines generation that no session has had yet, see [Session::engines_generation].
+fn next_engines_generation() -> u64 {
+    static ENGINES_GENERATION: AtomicU64 = AtomicU64::new(0);
+    ENGINES_GENERATION.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Edits that arrive less than this far apart are a burst, see [EditBurst].
+pub const EDIT_BURST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Tracks whether a project is being edited in quick succession.
+#[derive(Debug, Default)]
+pub struct EditBurst {
+    /// When the project was last edited.
+    pub last_edit: Option<Instant>,
+    /// Whether the last edit arrived less than [EDIT_BURST_INTERVAL] after the one before it.
+    pub in_burst: bool,
+}
+
+impl EditBurst {
+    /// Records an edit at `now`.
+    pub fn record_edit(&mut self, now: Instant) {
+        self.in_burst = self
+            .last_edit
+            .is_some_and(|last_edit| now.duration_since(last_edit) < EDIT_BURST_INTERVAL);
+        self.last_edit = Some(now);
+    }
+
+    /// Returns true if the project is still being edited in quick succession at `now`.
+    pub fn is_active(&self, now: Instant) -> bool {
+        self.in_burst
+            && self
+                .last_edit
+                .is_some_and(|last_edit| now.duration_since(last_edit) < EDIT_BURST_INTERVAL)
+    }
+}
+
+/// The number of positions whose tokens are kept by a [TokenPositionCache].
+pub const TOKEN_POSITION_CACHE_CAPACITY: usize = 64;
+
+/// The tokens that were looked up at positions of a session's files, including the positions without one.
+///
+/// The token map only changes along with the engines, so the entries are dropped as soon as a lookup is made
+/// for a newer [Session::engines_generation]. Once full, the oldest entry is evicted.
+#[derive(Debug, Default)]
+pub struct TokenPositionCache {
+    generation: u64,
+    tokens: HashMap<(Url, Position), Option<(TokenIdent, Token)>>,
+    order: VecDeque<(Url, Position)>,
+}
+
+impl TokenPositionCache {
+    /// Returns the token that was looked up at `position` of `uri` during `generation`, if it was.
+    pub fn get(
+        &mut self,
+        generation: u64,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Option<(TokenIdent, Token)>> {
+        if generation != self.generation {
+            self.generation = generation;
+            self.tokens.clear();
+            self.order.clear();
+            return None;
+        }
+        self.tokens.get(&(uri.clone(), position)).cloned()
+    }
+
+    pub fn insert(
+        &mut self,
+        generation: u64,
+        uri: Url,
+        position: Position,
+        token: Option<(TokenIdent, Token)>,
+    ) {
+        if generation != self.generation {
+            return;
+        }
+        let key = (uri, position);
+        if self.tokens.insert(key.clone(), token).is_none() {
+            self.order.push_back(key);
+        }
+        if self.order.len() > TOKEN_POSITION_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.tokens.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+/// Counts the compilations requested for a session, so that callers of [ServerState::compile_now]
+/// can wait for a compilation that includes all the changes made before their call.
+///
+/// [ServerState::compile_now]: crate::server_state::ServerState::compile_now
+#[derive(Debug, Default)]
+pub struct CompilationGenerations {
+    /// The generation of the most recently enqueued compilation.
+    pub requested: u64,
+    /// The generation that was requested when the current or last compilation started.
+    pub started: u64,
+    /// The generation of the last compilation that ran to completion without being cancelled.
+    pub finished: u64,
+}
+
+/// Cancels the compilation of a session in progress only once a newer version of the edits it compiles is requested.
+///
+/// Versions are those of the documents, so they're only compared for the same file. A request for another
+/// file, or one without a version, like a save, always cancels the compilation in progress.
+#[derive(Debug, Default)]
+pub struct CompilationCancellation {
+    /// The flag that the compiler checks to abandon the compilation in progress.
+    flag: Arc<AtomicBool>,
+    versions: Mutex<CompilationVersions>,
+}
+
+#[derive(Debug, Default)]
+struct CompilationVersions {
+    /// The latest version that a compilation was requested for, by the temp url of the file.
+    latest: HashMap<Url, i32>,
+    /// The file and version of the compilation in progress, if any.
+    compiling: Option<(Url, Option<i32>)>,
+}
+
+impl CompilationCancellation {
+    /// Records a request to compile the `version` of the file at `uri`, and cancels the compilation in progress
+    /// unless it compiles the same or a newer version of the file. Returns whether it was cancelled.
+    pub fn request(&self, uri: &Url, version: Option<i32>) -> bool {
+        let mut versions = self.versions.lock();
+        if let Some(version) = version {
+            let latest = versions.latest.entry(uri.clone()).or_insert(version);
+            *latest = (*latest).max(version);
+        }
+        let is_newer = match (&versions.compiling, version) {
+            (None, _) => return false,
+            (Some((compiling_uri, Some(compiling))), Some(version)) if compiling_uri == uri => {
+                version > *compiling
+            }
+            _ => true,
+        };
+        if is_newer {
+            self.flag.store(true, Ordering::SeqCst);
+        }
+        is_newer
+    }
+
+    /// Starts the compilation of the `version` of the file at `uri`.
+    ///
+    /// Returns false, without starting it, if a newer version of the file has been requested since,
+    /// as the compilation of that version is queued and this one would be cancelled by it anyway.
+    pub fn start(&self, uri: &Url, version: Option<i32>) -> bool {
+        let mut versions = self.versions.lock();
+        let is_stale = version.is_some_and(|version| {
+            versions
+                .latest
+                .get(uri)
+                .is_some_and(|latest| *latest > version)
+        });
+        if is_stale {
+            return false;
+        }
+        versions.compiling = Some((uri.clone(), version));
+        self.flag.store(false, Ordering::SeqCst);
+        true
+    }
+
+    /// Forgets the versions that were requested for the file at `uri`, e.g. once it's closed, as they start over
+    /// when it's opened again.
+    pub fn forget(&self, uri: &Url) {
+        self.versions.lock().latest.remove(uri);
+    }
+
+    /// Ends the compilation in progress, and returns whether it was cancelled.
+    pub fn finish(&self) -> bool {
+        self.versions.lock().compiling = None;
+        self.flag.swap(false, Ordering::SeqCst)
+    }
+
+    /// Cancels the compilation in progress regardless of the versions, e.g. on shutdown.
+    pub fn cancel(&self) {
+        if self.versions.lock().compiling.is_some() {
+            self.flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns whether the compilation in progress has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// Returns the flag that the compiler checks to abandon the compilation in progress.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.flag.clone()
+    }
 }
 
 impl Default for Session {
@@ -91,29 +386,114 @@ impl Default for Session {
 impl Session {
     pub fn new() -> Self {
         Session {
-            token_map: TokenMap::new(),
+            token_map: RwLock::new(Arc::new(TokenMap::new())),
             documents: DashMap::new(),
+            unsaved_documents: DashSet::new(),
             runnables: DashMap::new(),
             metrics: DashMap::new(),
             compiled_program: RwLock::new(Default::default()),
             engines: <_>::default(),
             sync: SyncWorkspace::new(),
             diagnostics: Arc::new(RwLock::new(DiagnosticMap::new())),
+            last_successful_diagnostics: RwLock::new(DiagnosticMap::new()),
+            config: RwLock::new(Config::default()),
+            is_compiling: AtomicBool::new(false),
+            finished_compilation: Notify::new(),
+            last_diagnostics_snapshot: RwLock::new(None),
+            memory_limit_exceeded: RwLock::new(None),
+            last_compilation_state: RwLock::new(LastCompilationState::Uninitialized),
+            gc_failures: AtomicUsize::new(0),
+            cancelled_compilations: AtomicUsize::new(0),
+            internal_compiler_errors: AtomicUsize::new(0),
+            phase_durations: RwLock::new(PhaseDurations::default()),
+            suggested_recompile: AtomicBool::new(false),
+            garbage_collection_requested: AtomicBool::new(false),
+            compilation_generations: Mutex::new(CompilationGenerations::default()),
+            compilation_cancellation: CompilationCancellation::default(),
+            edit_burst: Mutex::new(EditBurst::default()),
+            diagnostics_sweep_pending: AtomicBool::new(false),
+            diagnostics_history: Mutex::new(DiagnosticsHistory::default()),
+            engines_generation: AtomicU64::new(next_engines_generation()),
+            token_position_cache: Mutex::new(TokenPositionCache::default()),
+            position_encoding: RwLock::new(PositionEncoding::default()),
         }
     }
 
-    pub async fn init(&self, uri: &Url) -> Result<ProjectDirectory, LanguageServerError> {
+    /// Returns the encoding that the positions exchanged with the client are counted in.
+    pub fn position_encoding(&self) -> PositionEncoding {
+        *self.position_encoding.read()
+    }
+
+    /// Clones the workspace at `uri` into a new temp dir, which is created in `temp_dir` if given.
+    ///
+    /// If `uri` is a file rather than a directory, it's a file that isn't in any package, which is cloned into
+    /// a package that is generated around it in single-file mode, regardless of `sync_mode`. The file is then
+    /// returned as the directory of the project, as every such file has a session of its own.
+    pub async fn init(
+        &self,
+        uri: &Url,
+        temp_dir: Option<&Path>,
+        sync_mode: SyncMode,
+    ) -> Result<ProjectDirectory, LanguageServerError> {
         let manifest_dir = PathBuf::from(uri.path());
-        // Create a new temp dir that clones the current workspace
-        // and store manifest and temp paths
-        self.sync.create_temp_dir_from_workspace(&manifest_dir)?;
-        self.sync.clone_manifest_dir_to_temp()?;
+        if manifest_dir.is_file() {
+            self.sync
+                .create_temp_package_for_file(&manifest_dir, temp_dir)?;
+            self.sync.clone_manifest_dir_to_temp()?;
+            let _ = self.store_sway_files().await;
+            return Ok(manifest_dir);
+        }
+        match sync_mode {
+            SyncMode::Temp => {
+                // Create a new temp dir that clones the current workspace
+                // and store manifest and temp paths
+                self.sync
+                    .create_temp_dir_from_workspace(&manifest_dir, temp_dir)?;
+                self.sync.clone_manifest_dir_to_temp()?;
+            }
+            SyncMode::InPlace => self.sync.use_manifest_dir_in_place(&manifest_dir)?,
+        }
+        self.sync.resolve_dependency_dirs();
         // iterate over the project dir, parse all sway files
         let _ = self.store_sway_files().await;
         self.sync.watch_and_sync_manifest();
         self.sync.manifest_dir().map_err(Into::into)
     }
 
+    /// Returns the url of the file at `workspace_uri` in the temp directory.
+    ///
+    /// Fails with [LanguageServerError::SessionShutDown] once the session was shut down, e.g. because it was
+    /// replaced while a request was being handled with it.
+    pub(crate) fn temp_url(&self, workspace_uri: &Url) -> Result<Url, LanguageServerError> {
+        self.sync
+            .workspace_to_temp_url(workspace_uri)
+            .map_err(|err| match err {
+                DirectoryError::TempDirNotFound => LanguageServerError::SessionShutDown {
+                    dir: self
+                        .sync
+                        .manifest_dir()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                },
+                err => err.into(),
+            })
+    }
+
+    /// Returns true if the file at the workspace url `uri` is in one of the directories of `workspace.excludeDirs`,
+    /// whose files are left out of the results that are gathered across files, like references.
+    ///
+    /// Files outside the project, e.g. those of dependencies, are never excluded.
+    pub(crate) fn is_excluded_from_workspace(&self, uri: &Url) -> bool {
+        let Ok(manifest_dir) = self.sync.manifest_dir() else {
+            return false;
+        };
+        uri.to_file_path().is_ok_and(|path| {
+            path.strip_prefix(&manifest_dir)
+                .is_ok_and(|path| self.config.read().workspace.is_path_excluded(path))
+        })
+    }
+
     pub fn shutdown(&self) {
         // shutdown the thread watching the manifest file
         let handle = self.sync.notify_join_handle.read();
@@ -125,9 +505,30 @@ impl Session {
         self.sync.remove_temp_dir();
     }
 
-    /// Return a reference to the [TokenMap] of the current session.
-    pub fn token_map(&self) -> &TokenMap {
-        &self.token_map
+    /// Return the [TokenMap] of the current session.
+    ///
+    /// With [TokenMapWriteStrategy::Swap], a compilation replaces the map rather than updating it, so the
+    /// returned map stays as it is while it's being read, and later calls may return a newer one.
+    pub fn token_map(&self) -> Arc<TokenMap> {
+        self.token_map.read().clone()
+    }
+
+    /// Returns the first collected token at the cursor position, like [TokenMap::token_at_position],
+    /// from the [TokenPositionCache] if it was looked up since the engines were last swapped.
+    pub fn token_at_position(&self, uri: &Url, position: Position) -> Option<(TokenIdent, Token)> {
+        let generation = self.engines_generation.load(Ordering::SeqCst);
+        if let Some(token) = self
+            .token_position_cache
+            .lock()
+            .get(generation, uri, position)
+        {
+            return token;
+        }
+        let token = self.token_map().token_at_position(uri, position);
+        self.token_position_cache
+            .lock()
+            .insert(generation, uri.clone(), position, token.clone());
+        token
     }
 
     /// Clean up memory in the [TypeEngine] and [DeclEngine] for the user's workspace.
@@ -143,23 +544,48 @@ impl Session {
     /// Write the result of parsing to the session.
     /// This function should only be called after successfully parsing.
     pub fn write_parse_result(&self, res: &mut ParseResult) {
-        self.token_map.clear();
         self.runnables.clear();
         self.metrics.clear();
 
-        res.token_map.deref().iter().for_each(|item| {
-            let (i, t) = item.pair();
-            self.token_map.insert(i.clone(), t.clone());
-        });
+        let write_strategy = self.config.read().token_map_write_strategy;
+        match write_strategy {
+            TokenMapWriteStrategy::Incremental => {
+                let update = self.token_map().update_from(&res.token_map);
+                tracing::trace!("Updated the token map: {:?}", update);
+            }
+            TokenMapWriteStrategy::Swap => {
+                let token_map = Arc::new(std::mem::take(&mut res.token_map));
+                // The old map is dropped after the lock is released, or by the last reader that still holds it.
+                let old_token_map = std::mem::replace(&mut *self.token_map.write(), token_map);
+                drop(old_token_map);
+                tracing::trace!("Swapped in the new token map");
+            }
+        }
 
         res.metrics.iter().for_each(|item| {
             let (s, t) = item.pair();
             self.metrics.insert(*s, t.clone());
         });
+        *self.phase_durations.write() = res.phase_durations;
 
-        let (errors, warnings) = &res.diagnostics;
-        *self.diagnostics.write() =
-            capabilities::diagnostic::get_diagnostics(warnings, errors, self.engines.read().se());
+        let mut diagnostics = self.compile_diagnostics(res, self.engines.read().se());
+        if self.config.read().diagnostic.unused_storage_fields {
+            let unused_storage_fields =
+                capabilities::diagnostic::get_unused_storage_field_diagnostics(
+                    &self.token_map(),
+                    &self.engines.read(),
+                    self.position_encoding(),
+                );
+            for (path, warnings) in unused_storage_fields {
+                diagnostics
+                    .entry(path)
+                    .or_default()
+                    .warnings
+                    .extend(warnings);
+            }
+        }
+        *self.last_successful_diagnostics.write() = diagnostics.clone();
+        *self.diagnostics.write() = diagnostics;
 
         if let Some(typed) = &res.compiled_program.typed {
             self.create_runnables(typed, self.engines.read().de(), self.engines.read().se());
@@ -170,12 +596,97 @@ impl Session {
         );
     }
 
+    /// Write the diagnostics of a compilation that failed to the session.
+    ///
+    /// The token map and the compiled program of the last successful compilation are kept, so that
+    /// navigation keeps working while the project is broken. See [Session::has_stale_tokens].
+    /// With `diagnostic.retainOnFailure`, so are the diagnostics of the files without errors.
+    pub fn write_failed_parse_result(&self, res: &ParseResult, source_engine: &SourceEngine) {
+        let mut diagnostics = self.compile_diagnostics(res, source_engine);
+        if self.config.read().diagnostic.retain_on_failure {
+            diagnostics = capabilities::diagnostic::retain_unaffected_files(
+                &self.last_successful_diagnostics.read(),
+                diagnostics,
+            );
+        }
+        *self.diagnostics.write() = diagnostics;
+    }
+
+    /// Returns true if the token map is left over from an earlier compilation because the last one failed,
+    /// so it may not match the current contents of the files.
+    pub fn has_stale_tokens(&self) -> bool {
+        *self.last_compilation_state.read() == LastCompilationState::Failed
+            && self.token_map().iter().next().is_some()
+    }
+
+    /// Marks the results computed from the engines so far as outdated, after the engines were swapped.
+    pub fn bump_engines_generation(&self) {
+        self.engines_generation
+            .store(next_engines_generation(), Ordering::SeqCst);
+    }
+
+    /// Takes a [SessionSnapshot] of the results of the last compilation.
+    pub fn snapshot(&self) -> SessionSnapshot {
+        let token_map = TokenMap::new();
+        token_map.update_from(&self.token_map());
+        SessionSnapshot {
+            engines: self.engines.read().clone(),
+            token_map,
+            compiled_program: self.compiled_program.read().clone(),
+            diagnostics: self.diagnostics.read().clone(),
+            last_compilation_state: *self.last_compilation_state.read(),
+        }
+    }
+
+    /// Puts the results of the `snapshot` back in place of the current ones, the way the compilation thread
+    /// swaps in those of a new compilation. The snapshot is left untouched, so it can be restored again.
+    pub fn restore(&self, snapshot: &SessionSnapshot) {
+        let mut engines = snapshot.engines.clone();
+        let mut compiled_program = snapshot.compiled_program.clone();
+        std::mem::swap(&mut *self.engines.write(), &mut engines);
+        self.token_map().update_from(&snapshot.token_map);
+        std::mem::swap(&mut *self.compiled_program.write(), &mut compiled_program);
+        *self.diagnostics.write() = snapshot.diagnostics.clone();
+        *self.last_compilation_state.write() = snapshot.last_compilation_state;
+        self.bump_engines_generation();
+    }
+
+    fn compile_diagnostics(
+        &self,
+        res: &ParseResult,
+        source_engine: &SourceEngine,
+    ) -> DiagnosticMap {
+        let (errors, warnings) = &res.diagnostics;
+        let mut diagnostics = capabilities::diagnostic::get_diagnostics(
+            warnings,
+            errors,
+            source_engine,
+            self.position_encoding(),
+        );
+        // Related information points into the temp directory, so we need to convert it back to the user's workspace.
+        diagnostics
+            .values_mut()
+            .flat_map(|diagnostics| {
+                diagnostics
+                    .warnings
+                    .iter_mut()
+                    .chain(&mut diagnostics.errors)
+            })
+            .flat_map(|diagnostic| diagnostic.related_information.iter_mut().flatten())
+            .for_each(|info| {
+                if let Ok(uri) = self.sync.temp_to_workspace_url(&info.location.uri) {
+                    info.location.uri = uri;
+                }
+            });
+        diagnostics
+    }
+
     pub fn token_ranges(&self, url: &Url, position: Position) -> Option<Vec<Range>> {
-        let (_, token) = self.token_map.token_at_position(url, position)?;
+        let (_, token) = self.token_at_position(url, position)?;
         let mut token_ranges: Vec<_> = self
-            .token_map
+            .token_map()
             .tokens_for_file(url)
-            .all_references_of_token(&token, &self.engines.read())
+            .all_references_of_token(&token, &self.engines.read(), self.position_encoding())
             .map(|(ident, _)| ident.range)
             .collect();
 
@@ -188,53 +699,275 @@ impl Session {
         uri: Url,
         position: Position,
     ) -> Option<GotoDefinitionResponse> {
-        self.token_map
-            .token_at_position(&uri, position)
-            .and_then(|(_, token)| token.declared_token_ident(&self.engines.read()))
-            .and_then(|decl_ident| {
-                decl_ident.path.and_then(|path| {
-                    // We use ok() here because we don't care about propagating the error from from_file_path
-                    Url::from_file_path(path).ok().and_then(|url| {
-                        self.sync.to_workspace_url(url).map(|url| {
-                            GotoDefinitionResponse::Scalar(Location::new(url, decl_ident.range))
-                        })
-                    })
-                })
-            })
+        let (ident, token) = self.token_at_position(&uri, position)?;
+        let location = |ident: TokenIdent| {
+            // We use ok() here because we don't care about propagating the error from from_file_path
+            let url = Url::from_file_path(ident.path?).ok()?;
+            let url = self.sync.to_workspace_url(url)?;
+            Some(Location::new(url, ident.range))
+        };
+        if let AstToken::Expression(Expression {
+            kind: ExpressionKind::Subfield(subfield),
+            ..
+        }) = &token.parsed
+        {
+            let field_ident = self.field_declaration(&uri, &token, subfield)?;
+            return Some(GotoDefinitionResponse::Scalar(location(field_ident)?));
+        }
+        let (decl_ident, use_sites) = self.token_map().resolve_declaration(
+            &ident,
+            &token,
+            &self.engines.read(),
+            self.position_encoding(),
+        )?;
+        let decl_location = location(decl_ident)?;
+        // The aliases and re-exports that were passed through are offered after the declaration.
+        let use_locations: Vec<_> = use_sites.into_iter().filter_map(location).collect();
+        if use_locations.is_empty() {
+            Some(GotoDefinitionResponse::Scalar(decl_location))
+        } else {
+            Some(GotoDefinitionResponse::Array(
+                std::iter::once(decl_location)
+                    .chain(use_locations)
+                    .collect(),
+            ))
+        }
     }
 
-    pub fn completion_items(
+    /// Returns the declaration of the field that `subfield` accesses, found in the type of its receiver.
+    ///
+    /// The type of a receiver that is a variable or a field access is read from its own typed token, so each
+    /// `.` of a chain like `a.b.c` is resolved in turn. Returns `None` if that type is unknown or isn't a struct
+    /// with the field, rather than a location that was recorded for the token by an earlier compilation.
+    fn field_declaration(
+        &self,
+        uri: &Url,
+        token: &Token,
+        subfield: &SubfieldExpression,
+    ) -> Option<TokenIdent> {
+        let receiver_ident = match &subfield.prefix.kind {
+            ExpressionKind::Subfield(prefix) => Some(&prefix.field_to_access),
+            ExpressionKind::Variable(name) | ExpressionKind::AmbiguousVariableExpression(name) => {
+                Some(name)
+            }
+            _ => None,
+        };
+        let receiver_type = match receiver_ident {
+            Some(receiver_ident) => {
+                let range =
+                    token::get_range_from_span(&receiver_ident.span(), self.position_encoding());
+                let (_, receiver) = self.token_at_position(uri, range.start)?;
+                receiver.type_id()?
+            }
+            // Other receivers, such as calls, have no token of their own, so the type that the compiler
+            // resolved for the access is used.
+            None => match &token.typed {
+                Some(TypedAstToken::TypedExpression(ty::TyExpression {
+                    expression:
+                        ty::TyExpressionVariant::StructFieldAccess {
+                            resolved_type_of_parent,
+                            ..
+                        },
+                    ..
+                })) => *resolved_type_of_parent,
+                _ => return None,
+            },
+        };
+        let engines = self.engines.read();
+        let TypeInfo::Struct(decl_ref) = &*engines.te().get(receiver_type) else {
+            return None;
+        };
+        let struct_decl = engines.de().get_struct(&decl_ref.id().clone());
+        let field = struct_decl
+            .fields
+            .iter()
+            .find(|field| field.name.as_str() == subfield.field_to_access.as_str())?;
+        Some(TokenIdent::new(
+            &field.name,
+            engines.se(),
+            self.position_encoding(),
+        ))
+    }
+
+    /// Returns the location of the declaration of the type of the token at the given position.
+    ///
+    /// Returns `None` for types that have no declaration in the source code, such as primitives.
+    pub fn type_definition_response(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Option<GotoTypeDefinitionResponse> {
+        let (_, token) = self.token_at_position(&uri, position)?;
+        let type_id = token.type_id()?;
+        let decl_ident =
+            token::ident_of_type_id(&self.engines.read(), &type_id, self.position_encoding())?;
+        let url = Url::from_file_path(decl_ident.path?).ok()?;
+        let url = self.sync.to_workspace_url(url)?;
+        Some(GotoTypeDefinitionResponse::Scalar(Location::new(
+            url,
+            decl_ident.range,
+        )))
+    }
+
+    /// Returns the context of the completion at the [Position] in the document, and the range of the
+    /// partially typed name that the completion replaces.
+    pub fn completion_context(
         &self,
         uri: &Url,
         position: Position,
-        trigger_char: &str,
+    ) -> Option<(CompletionContext, Range)> {
+        let text = self.get_text_document(uri).ok()?.get_text();
+        Some(capabilities::completion::completion_context(
+            &text, position,
+        ))
+    }
+
+    /// Returns whether the [Position] in the document is inside a comment, a string literal or code.
+    pub fn position_kind(&self, uri: &Url, position: Position) -> Option<PositionKind> {
+        let text = self.get_text_document(uri).ok()?.get_text();
+        Some(position_kind::position_kind(
+            &text,
+            position,
+            self.position_encoding(),
+        ))
+    }
+
+    /// Returns the fields and methods of the receiver whose last character is at `shifted_position`.
+    pub fn member_completion_items(
+        &self,
+        uri: &Url,
+        shifted_position: Position,
+        range: Range,
+        snippets: bool,
     ) -> Option<Vec<CompletionItem>> {
-        let shifted_position = Position {
-            line: position.line,
-            character: position.character - trigger_char.len() as u32 - 1,
-        };
-        let (ident_to_complete, _) = self.token_map.token_at_position(uri, shifted_position)?;
-        let fn_tokens = self.token_map.tokens_at_position(
+        let fn_tokens = self.token_map().tokens_at_position(
             self.engines.read().se(),
             uri,
             shifted_position,
             Some(true),
+            self.position_encoding(),
         );
         let (_, fn_token) = fn_tokens.first()?;
         let compiled_program = &*self.compiled_program.read();
         if let Some(TypedAstToken::TypedFunctionDeclaration(fn_decl)) = fn_token.typed.clone() {
             let program = compiled_program.typed.clone()?;
-            return Some(capabilities::completion::to_completion_items(
-                &program.root.namespace,
+            if let Some((ident_to_complete, _)) = self.token_at_position(uri, shifted_position) {
+                return Some(capabilities::completion::to_completion_items(
+                    &program.root.namespace,
+                    &self.engines.read(),
+                    &ident_to_complete,
+                    &fn_decl,
+                    range,
+                    snippets,
+                ));
+            }
+            // Otherwise, the receiver may be a `match` or `if` expression, e.g. `match x { ... }.`
+            let receiver_end = Position {
+                line: shifted_position.line,
+                character: shifted_position.character + 1,
+            };
+            let receiver = self.token_map().branching_expression_ending_at(
+                uri,
+                receiver_end,
+                self.position_encoding(),
+            )?;
+            return Some(capabilities::completion::completion_items_for_type_id(
                 &self.engines.read(),
-                &ident_to_complete,
-                &fn_decl,
-                position,
+                &program.root.namespace,
+                receiver.return_type,
+                range,
+                snippets,
             ));
         }
         None
     }
 
+    /// Returns the enum variants and associated functions of the type whose name ends at `qualifier`.
+    pub fn path_completion_items(
+        &self,
+        uri: &Url,
+        qualifier: Position,
+        range: Range,
+        snippets: bool,
+    ) -> Option<Vec<CompletionItem>> {
+        let (_, token) = self.token_at_position(uri, qualifier)?;
+        let engines = self.engines.read();
+        let ty_decl = match &token.typed {
+            Some(TypedAstToken::TypedDeclaration(ty_decl)) => ty_decl.clone(),
+            _ => {
+                let decl_ident = token.declared_token_ident(&engines, self.position_encoding())?;
+                let token_map = self.token_map();
+                let decl_token = token_map.try_get(&decl_ident).try_unwrap()?;
+                match decl_token.value().typed.clone() {
+                    Some(TypedAstToken::TypedDeclaration(ty_decl)) => ty_decl,
+                    _ => return None,
+                }
+            }
+        };
+        let program = self.compiled_program.read().typed.clone()?;
+        Some(capabilities::completion::completion_items_for_path(
+            &engines,
+            &program.root.namespace,
+            &ty_decl,
+            range,
+            snippets,
+        ))
+    }
+
+    /// Returns the names that are in scope at the [Position], followed by the keywords.
+    ///
+    /// The scope comes from the last parsed version of the file, so this doesn't need to wait for the
+    /// current compilation to finish. With `completion.autoImport`, the declarations that start with the
+    /// partially typed name but aren't in scope come before the keywords, with the edit that imports them.
+    pub fn scope_completion_items(
+        &self,
+        uri: &Url,
+        position: Position,
+        range: Range,
+        keyword_docs: &KeywordDocs,
+        snippets: bool,
+    ) -> Vec<CompletionItem> {
+        let engines = self.engines.read();
+        let token_map = self.token_map();
+        let names = TypedNames {
+            token_map: &token_map,
+            engines: &engines,
+            encoding: self.position_encoding(),
+        };
+        let mut completion_items = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| {
+                let compiled_program = self.compiled_program.read();
+                let program = compiled_program.parsed.as_ref()?;
+                capabilities::completion::parse_module_of_file(&program.root, engines.se(), &path)
+                    .map(|module| {
+                        capabilities::completion::completion_items_for_scope(
+                            module, position, range, &names, snippets,
+                        )
+                    })
+            })
+            .unwrap_or_default();
+        if self.config.read().completion.auto_import && range.start != range.end {
+            if let (Ok(document), Some(namespace)) = (self.get_text_document(uri), self.namespace())
+            {
+                let prefix = capabilities::completion::typed_prefix(&document.get_text(), range);
+                let in_scope = completion_items
+                    .iter()
+                    .map(|item| item.label.clone())
+                    .collect();
+                let imports = FileImports::of_file(&token_map, uri, self.position_encoding());
+                completion_items.extend(capabilities::completion::completion_items_for_imports(
+                    &token_map, &engines, &namespace, &imports, &prefix, &in_scope,
+                ));
+            }
+        }
+        completion_items.extend(capabilities::completion::completion_items_for_keywords(
+            keyword_docs,
+        ));
+        completion_items
+    }
+
     /// Returns the [Namespace] from the compiled program if it exists.
     pub fn namespace(&self) -> Option<Namespace> {
         let compiled_program = &*self.compiled_program.read();
@@ -243,13 +976,69 @@ impl Session {
     }
 
     pub fn symbol_information(&self, url: &Url) -> Option<Vec<SymbolInformation>> {
-        let tokens = self.token_map.tokens_for_file(url);
+        let token_map = self.token_map();
+        let tokens = token_map.tokens_for_file(url);
         self.sync
             .to_workspace_url(url.clone())
             .map(|url| capabilities::document_symbol::to_symbol_information(tokens, url))
     }
 
-    pub fn format_text(&self, url: &Url) -> Result<Vec<TextEdit>, LanguageServerError> {
+    /// Formats the document at `url` with the project's formatter config, or with the client's
+    /// `options` if the project has none.
+    pub fn format_text(
+        &self,
+        url: &Url,
+        options: &FormattingOptions,
+    ) -> Result<Vec<TextEdit>, LanguageServerError> {
+        let document = self
+            .documents
+            .try_get(url.path())
+            .try_unwrap()
+            .ok_or_else(|| DocumentError::DocumentNotFound {
+                path: url.path().to_string(),
+            })?;
+
+        let mut formatter = project_formatter(&self.sync.manifest_dir()?, options);
+        get_page_text_edit(
+            Arc::from(document.get_text()),
+            &mut formatter,
+            self.position_encoding(),
+        )
+    }
+
+    /// Formats the top-level items of the document at `url` that overlap the `range`.
+    pub fn format_range(
+        &self,
+        url: &Url,
+        range: Range,
+        options: &FormattingOptions,
+    ) -> Result<Vec<TextEdit>, LanguageServerError> {
+        let document = self
+            .documents
+            .try_get(url.path())
+            .try_unwrap()
+            .ok_or_else(|| DocumentError::DocumentNotFound {
+                path: url.path().to_string(),
+            })?;
+
+        let mut formatter = project_formatter(&self.sync.manifest_dir()?, options);
+        get_range_text_edit(
+            Arc::from(document.get_text()),
+            range,
+            &mut formatter,
+            self.position_encoding(),
+        )
+    }
+
+    /// Reindents the line at `position` of the document at `url` after `ch` was typed,
+    /// with the indentation of the project's formatter config.
+    pub fn format_on_type(
+        &self,
+        url: &Url,
+        position: Position,
+        ch: &str,
+        options: &FormattingOptions,
+    ) -> Result<Vec<TextEdit>, LanguageServerError> {
         let document = self
             .documents
             .try_get(url.path())
@@ -258,8 +1047,23 @@ impl Session {
                 path: url.path().to_string(),
             })?;
 
-        get_page_text_edit(Arc::from(document.get_text()), &mut <_>::default())
-            .map(|page_text_edit| vec![page_text_edit])
+        let whitespace = project_formatter(&self.sync.manifest_dir()?, options)
+            .config
+            .whitespace;
+        let indent = if whitespace.hard_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(whitespace.tab_spaces)
+        };
+        Ok(on_type_formatting(
+            &document.get_text(),
+            position,
+            ch,
+            &indent,
+            self.position_encoding(),
+        )
+        .into_iter()
+        .collect())
     }
 
     pub async fn handle_open_file(&self, uri: &Url) {
@@ -271,6 +1075,8 @@ impl Session {
     }
 
     /// Asynchronously writes the changes to the file and updates the document.
+    ///
+    /// In place, only the document is updated, as the file is the user's.
     pub async fn write_changes_to_file(
         &self,
         uri: &Url,
@@ -281,6 +1087,11 @@ impl Session {
                 path: uri.path().to_string(),
             }
         })?;
+        self.unsaved_documents.insert(uri.path().to_string());
+        // Without `analyzeUnsaved`, the compiler keeps seeing the file as it's saved.
+        if self.sync.is_in_place() || !self.config.read().analyze_unsaved {
+            return Ok(());
+        }
 
         let mut file =
             File::create(uri.path())
@@ -299,6 +1110,28 @@ impl Session {
         Ok(())
     }
 
+    /// Returns the parse errors of the current, possibly unsaved, contents of the session's documents.
+    ///
+    /// Only the documents are parsed, so this is much faster than a full compilation
+    /// and can be used to show syntax errors before the type errors are known.
+    pub fn parse_diagnostics(&self) -> DiagnosticMap {
+        let handler = Handler::default();
+        let engines = self.engines.read();
+        for item in self.documents.iter() {
+            let path = PathBuf::from(item.key());
+            let source_id = engines.se().get_source_id(&path);
+            let _ =
+                sway_parse::parse_file(&handler, item.value().get_text().into(), Some(source_id));
+        }
+        let (errors, warnings) = handler.consume();
+        capabilities::diagnostic::get_diagnostics(
+            &warnings,
+            &errors,
+            engines.se(),
+            self.position_encoding(),
+        )
+    }
+
     /// Get the document at the given [Url].
     pub fn get_text_document(&self, url: &Url) -> Result<TextDocument, DocumentError> {
         self.documents
@@ -310,6 +1143,66 @@ impl Session {
             .map(|document| document.clone())
     }
 
+    /// Writes the contents of the documents with unsaved changes to the temp workspace.
+    ///
+    /// Resyncing the temp workspace copies the files on disk over it, so this is called before each
+    /// compilation to make sure that the compiler sees the contents of the editor.
+    /// In place, nothing is written and the contents are set as source overrides of the `engines` instead.
+    /// Without `analyzeUnsaved`, the saved contents are restored instead.
+    pub fn write_unsaved_documents(&self, engines: &Engines) -> Result<(), DocumentError> {
+        if !self.config.read().analyze_unsaved {
+            return self.restore_saved_documents(engines);
+        }
+        if self.sync.is_in_place() {
+            engines.se().clear_source_overrides();
+            for path in self.unsaved_documents.iter() {
+                if let Some(document) = self.documents.try_get(path.key()).try_unwrap() {
+                    engines
+                        .se()
+                        .set_source_override(PathBuf::from(path.key()), document.get_text().into());
+                }
+            }
+            return Ok(());
+        }
+        for path in self.unsaved_documents.iter() {
+            let Some(document) = self.documents.try_get(path.key()).try_unwrap() else {
+                continue;
+            };
+            std::fs::write(path.key(), document.get_text()).map_err(|err| {
+                DocumentError::UnableToWriteFile {
+                    path: path.key().clone(),
+                    err: err.to_string(),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Undoes [Session::write_unsaved_documents], so that the compiler sees the documents as they're saved on disk.
+    fn restore_saved_documents(&self, engines: &Engines) -> Result<(), DocumentError> {
+        if self.sync.is_in_place() {
+            engines.se().clear_source_overrides();
+            return Ok(());
+        }
+        for path in self.unsaved_documents.iter() {
+            let Some(saved_path) = Url::from_file_path(path.key())
+                .ok()
+                .and_then(|uri| self.sync.temp_to_workspace_url(&uri).ok())
+                .and_then(|uri| uri.to_file_path().ok())
+                .filter(|saved_path| saved_path.is_file())
+            else {
+                continue;
+            };
+            std::fs::copy(&saved_path, path.key()).map_err(|err| {
+                DocumentError::UnableToWriteFile {
+                    path: path.key().clone(),
+                    err: err.to_string(),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
     /// Update the document at the given [Url] with the Vec of changes returned by the client.
     pub fn update_text_document(
         &self,
@@ -320,8 +1213,9 @@ impl Session {
             .try_get_mut(url.path())
             .try_unwrap()
             .map(|mut document| {
+                let encoding = self.position_encoding();
                 changes.iter().for_each(|change| {
-                    document.apply_change(change);
+                    document.apply_change(change, encoding);
                 });
                 document.get_text()
             })
@@ -329,6 +1223,7 @@ impl Session {
 
     /// Remove the text document from the session.
     pub fn remove_document(&self, url: &Url) -> Result<TextDocument, DocumentError> {
+        self.unsaved_documents.remove(url.path());
         self.documents
             .remove(url.path())
             .ok_or_else(|| DocumentError::DocumentNotFound {
@@ -364,7 +1259,7 @@ impl Session {
             if let Some(source_id) = span.source_id() {
                 let path = source_engine.get_path(source_id);
                 let runnable = Box::new(RunnableTestFn {
-                    range: token::get_range_from_span(&span.clone()),
+                    range: token::get_range_from_span(&span.clone(), self.position_encoding()),
                     tree_type: typed_program.kind.tree_type(),
                     test_name: Some(decl.name.to_string()),
                 });
@@ -382,7 +1277,7 @@ impl Session {
             if let Some(source_id) = span.source_id() {
                 let path = source_engine.get_path(source_id);
                 let runnable = Box::new(RunnableMainFn {
-                    range: token::get_range_from_span(&span.clone()),
+                    range: token::get_range_from_span(&span.clone(), self.position_encoding()),
                     tree_type: typed_program.kind.tree_type(),
                 });
                 self.runnables.entry(path).or_default().push(runnable);
@@ -402,12 +1297,13 @@ impl Session {
 }
 
 /// Create a [BuildPlan] from the given [Url] appropriate for the language server.
+///
+/// The lock file is only updated inside the language server's own temp directories, so that
+/// a project compiled in place (or inspected for its dependencies) never gains a `Forc.lock`.
 pub(crate) fn build_plan(uri: &Url) -> Result<BuildPlan, LanguageServerError> {
     let manifest_dir = PathBuf::from(uri.path());
-    let manifest =
-        ManifestFile::from_dir(&manifest_dir).map_err(|_| DocumentError::ManifestFileNotFound {
-            dir: uri.path().into(),
-        })?;
+    let manifest = ManifestFile::from_dir(&manifest_dir)
+        .map_err(|err| DocumentError::manifest_error(&manifest_dir, &err))?;
     let member_manifests =
         manifest
             .member_manifests()
@@ -422,28 +1318,91 @@ pub(crate) fn build_plan(uri: &Url) -> Result<BuildPlan, LanguageServerError> {
     // TODO: Either we want LSP to deploy a local node in the background or we want this to
     // point to Fuel operated IPFS node.
     let ipfs_node = pkg::source::IPFSNode::Local;
-    pkg::BuildPlan::from_lock_and_manifests(&lock_path, &member_manifests, false, false, ipfs_node)
-        .map_err(LanguageServerError::BuildPlanFailed)
+    let build_plan = if uri.as_str().contains(SyncWorkspace::LSP_TEMP_PREFIX) {
+        pkg::BuildPlan::from_lock_and_manifests(
+            &lock_path,
+            &member_manifests,
+            false,
+            false,
+            ipfs_node,
+        )
+    } else {
+        pkg::BuildPlan::from_lock_and_manifests_without_writing(
+            &lock_path,
+            &member_manifests,
+            false,
+            ipfs_node,
+        )
+    };
+    build_plan.map_err(LanguageServerError::BuildPlanFailed)
+}
+
+/// Returns the directories of the packages of the build plan that aren't members of the workspace.
+///
+/// These include the sources of git and registry dependencies, such as `std`, which are fetched outside of the workspace.
+pub(crate) fn dependency_dirs(build_plan: &BuildPlan) -> Vec<PathBuf> {
+    let graph = build_plan.graph();
+    build_plan
+        .compilation_order()
+        .iter()
+        .map(|&node| &graph[node])
+        .filter(|pinned| !matches!(pinned.source, pkg::source::Pinned::Member(_)))
+        .filter_map(|pinned| build_plan.manifest_map().get(&pinned.id()))
+        .map(|manifest| manifest.dir().to_path_buf())
+        .collect()
 }
 
 pub fn compile(
     uri: &Url,
     engines: &Engines,
+    build_config: &BuildConfig,
     retrigger_compilation: Option<Arc<AtomicBool>>,
 ) -> Result<Vec<(Option<Programs>, Handler)>, LanguageServerError> {
     let build_plan = build_plan(uri)?;
-    let tests_enabled = true;
-    pkg::check(
+    let profile = build_profile(&build_plan, build_config);
+    pkg::check_with_profile(
         &build_plan,
-        BuildTarget::default(),
-        true,
-        tests_enabled,
+        build_config.target,
+        &profile,
+        build_config.include_tests,
         engines,
         retrigger_compilation,
     )
     .map_err(LanguageServerError::FailedToCompile)
 }
 
+/// Resolves the build profile selected by `build_config` from the manifests of the build plan.
+///
+/// Falls back to the `debug` profile if no member of the project defines the selected one.
+/// As the server talks to the client over stdio, the options that print to stdout are disabled.
+pub(crate) fn build_profile(build_plan: &BuildPlan, build_config: &BuildConfig) -> BuildProfile {
+    let mut profile = build_plan
+        .build_profiles()
+        .find(|(name, _)| *name == build_config.profile)
+        .map(|(_, profile)| profile)
+        .unwrap_or_else(|| {
+            tracing::warn!(
+                "Build profile `{}` was not found, using `{}` instead",
+                build_config.profile,
+                BuildProfile::DEBUG
+            );
+            BuildProfile::debug()
+        });
+    if let Some(new_encoding) = build_config.new_encoding {
+        profile.experimental.new_encoding = new_encoding;
+    }
+    BuildProfile {
+        terse: true,
+        print_ast: false,
+        print_dca_graph: None,
+        print_ir: false,
+        print_finalized_asm: false,
+        print_intermediate_asm: false,
+        time_phases: false,
+        ..profile
+    }
+}
+
 pub struct TraversalResult {
     pub diagnostics: (Vec<CompileError>, Vec<CompileWarning>),
     pub programs: Option<(LexedProgram, ParseProgram, ty::TyProgram)>,
@@ -454,6 +1413,7 @@ pub struct TraversalResult {
 pub fn traverse(
     results: Vec<(Option<Programs>, Handler)>,
     engines: &Engines,
+    encoding: PositionEncoding,
 ) -> Result<TraversalResult, LanguageServerError> {
     let token_map = TokenMap::new();
     let metrics_map = DashMap::new();
@@ -480,15 +1440,15 @@ pub fn traverse(
             metrics_map.insert(source_id, metrics.clone());
         }
 
-        // Get a reference to the typed program AST.
-        let typed_program = typed
-            .as_ref()
-            .ok()
-            .ok_or_else(|| LanguageServerError::FailedToParse)?;
+        // A program that failed to type check has no tokens to collect, but its diagnostics are kept, as are
+        // the tokens of the other programs.
+        let Ok(typed_program) = typed.as_ref() else {
+            continue;
+        };
 
         // Create context with write guards to make readers wait until the update to token_map is complete.
         // This operation is fast because we already have the compile results.
-        let ctx = ParseContext::new(&token_map, engines, &typed_program.root.namespace);
+        let ctx = ParseContext::new(&token_map, engines, &typed_program.root.namespace, encoding);
 
         // The final element in the results is the main program.
         if i == results_len - 1 {
@@ -531,22 +1491,30 @@ pub fn traverse(
 pub fn parse_project(
     uri: &Url,
     engines: &Engines,
+    encoding: PositionEncoding,
+    build_config: &BuildConfig,
     retrigger_compilation: Option<Arc<AtomicBool>>,
     parse_result: &mut ParseResult,
 ) -> Result<(), LanguageServerError> {
-    let results = compile(uri, engines, retrigger_compilation)?;
+    let start = Instant::now();
+    let results = compile(uri, engines, build_config, retrigger_compilation)?;
     if results.last().is_none() {
         return Err(LanguageServerError::ProgramsIsNone);
     }
+    parse_result.phase_durations.compilation = start.elapsed();
+    let start = Instant::now();
     let TraversalResult {
         diagnostics,
         programs,
         token_map,
         metrics,
-    } = traverse(results, engines)?;
-    let (lexed, parsed, typed) = programs.ok_or(LanguageServerError::ProgramsIsNone)?;
-
+    } = traverse(results, engines, encoding)?;
+    parse_result.phase_durations.traversal = start.elapsed();
+    // The diagnostics are kept even if the main program failed to type check, so that they can be
+    // published while the tokens of the last successful compilation are kept.
     parse_result.diagnostics = diagnostics;
+    let (lexed, parsed, typed) = programs.ok_or(LanguageServerError::FailedToParse)?;
+
     parse_result.token_map = token_map;
     parse_result.compiled_program.lexed = Some(lexed);
     parse_result.compiled_program.parsed = Some(parsed);
@@ -599,7 +1567,222 @@ fn parse_ast_to_typed_tokens(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::token::{AstToken, SymbolKind};
     use sway_lsp_test_utils::{get_absolute_path, get_url};
+    use sway_types::Ident;
+
+    #[test]
+    fn token_lookups_are_cached_until_the_engines_are_swapped() {
+        let path = get_absolute_path("sway-lsp/tests/fixtures/tokens/paths/src/main.sw");
+        let uri = get_url(&path);
+        let ident = TokenIdent {
+            name: "foo".to_string(),
+            range: Range::new(Position::new(1, 4), Position::new(1, 7)),
+            path: Some(PathBuf::from(&path)),
+            is_raw_ident: false,
+        };
+        let token = Token::from_parsed(
+            AstToken::Keyword(Ident::new_no_span("foo".into())),
+            SymbolKind::Keyword,
+        );
+        let session = Session::new();
+        session.token_map().insert(ident.clone(), token);
+        let position = Position::new(1, 5);
+        assert_eq!(
+            session
+                .token_at_position(&uri, position)
+                .map(|(ident, _)| ident),
+            Some(ident.clone())
+        );
+        assert!(session
+            .token_at_position(&uri, Position::new(3, 0))
+            .is_none());
+        assert_eq!(session.token_position_cache.lock().len(), 2);
+
+        // Served from the cache, even though the token map has changed since.
+        session.token_map().remove(&ident);
+        assert!(session.token_at_position(&uri, position).is_some());
+        session.bump_engines_generation();
+        assert!(session.token_at_position(&uri, position).is_none());
+        assert_eq!(session.token_position_cache.lock().len(), 1);
+    }
+
+    #[test]
+    fn restored_snapshots_are_independent_of_later_changes() {
+        let path = get_absolute_path("sway-lsp/tests/fixtures/tokens/paths/src/main.sw");
+        let uri = get_url(&path);
+        let ident = |name: &str, line| TokenIdent {
+            name: name.to_string(),
+            range: Range::new(Position::new(line, 0), Position::new(line, 3)),
+            path: Some(PathBuf::from(&path)),
+            is_raw_ident: false,
+        };
+        let token = || {
+            Token::from_parsed(
+                AstToken::Keyword(Ident::new_no_span("foo".into())),
+                SymbolKind::Keyword,
+            )
+        };
+        let session = Session::new();
+        session.token_map().insert(ident("foo", 1), token());
+        *session.last_compilation_state.write() = LastCompilationState::Success;
+        let snapshot = session.snapshot();
+        let generation = session.engines_generation.load(Ordering::SeqCst);
+
+        session.token_map().clear();
+        session.token_map().insert(ident("bar", 2), token());
+        *session.last_compilation_state.write() = LastCompilationState::Failed;
+        // Restoring it twice gives the same state, as the snapshot itself isn't changed.
+        for _ in 0..2 {
+            session.restore(&snapshot);
+            assert!(session
+                .token_at_position(&uri, Position::new(1, 1))
+                .is_some());
+            assert!(session
+                .token_at_position(&uri, Position::new(2, 1))
+                .is_none());
+            assert_eq!(
+                *session.last_compilation_state.read(),
+                LastCompilationState::Success
+            );
+            session.token_map().insert(ident("bar", 2), token());
+        }
+        assert_ne!(
+            session.engines_generation.load(Ordering::SeqCst),
+            generation
+        );
+    }
+
+    #[test]
+    fn swapped_token_maps_are_left_unchanged_for_their_readers() {
+        let path = get_absolute_path("sway-lsp/tests/fixtures/tokens/paths/src/main.sw");
+        let ident = |name: &str, line| TokenIdent {
+            name: name.to_string(),
+            range: Range::new(Position::new(line, 0), Position::new(line, 3)),
+            path: Some(PathBuf::from(&path)),
+            is_raw_ident: false,
+        };
+        let token = || {
+            Token::from_parsed(
+                AstToken::Keyword(Ident::new_no_span("foo".into())),
+                SymbolKind::Keyword,
+            )
+        };
+        for write_strategy in [
+            TokenMapWriteStrategy::Incremental,
+            TokenMapWriteStrategy::Swap,
+        ] {
+            let session = Session::new();
+            session.config.write().token_map_write_strategy = write_strategy;
+            session.token_map().insert(ident("foo", 1), token());
+            let reader = session.token_map();
+
+            let mut parse_result = ParseResult::default();
+            parse_result.token_map.insert(ident("bar", 2), token());
+            session.write_parse_result(&mut parse_result);
+            let token_map = session.token_map();
+            assert_eq!(token_map.len(), 1);
+            assert!(token_map.contains_key(&ident("bar", 2)));
+            // Only a swap leaves the map that was being read as it was.
+            let swapped = write_strategy == TokenMapWriteStrategy::Swap;
+            assert_eq!(reader.contains_key(&ident("foo", 1)), swapped);
+            assert_eq!(Arc::ptr_eq(&reader, &token_map), !swapped);
+        }
+    }
+
+    #[test]
+    fn token_position_cache_evicts_the_oldest_entry() {
+        let uri = get_url("/project/src/main.sw");
+        let mut cache = TokenPositionCache::default();
+        for line in 0..=TOKEN_POSITION_CACHE_CAPACITY as u32 {
+            cache.insert(0, uri.clone(), Position::new(line, 0), None);
+        }
+        assert_eq!(cache.len(), TOKEN_POSITION_CACHE_CAPACITY);
+        assert!(cache.get(0, &uri, Position::new(0, 0)).is_none());
+        assert!(matches!(
+            cache.get(0, &uri, Position::new(1, 0)),
+            Some(None)
+        ));
+        // Entries of an older generation are never served.
+        assert!(cache.get(1, &uri, Position::new(1, 0)).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn edit_bursts_end_once_edits_slow_down() {
+        let start = Instant::now();
+        let mut edit_burst = EditBurst::default();
+        edit_burst.record_edit(start);
+        // A single edit isn't a burst.
+        assert!(!edit_burst.is_active(start));
+
+        let next = start + Duration::from_millis(100);
+        edit_burst.record_edit(next);
+        assert!(edit_burst.is_active(next));
+        assert!(!edit_burst.is_active(next + EDIT_BURST_INTERVAL));
+
+        let slow = next + EDIT_BURST_INTERVAL * 2;
+        edit_burst.record_edit(slow);
+        assert!(!edit_burst.is_active(slow));
+    }
+
+    #[test]
+    fn compilations_are_only_cancelled_by_newer_versions() {
+        let main = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/benchmark/src/main.sw",
+        ));
+        let other = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/benchmark/src/other.sw",
+        ));
+        let cancellation = CompilationCancellation::default();
+        // Nothing is compiling yet.
+        assert!(!cancellation.request(&main, Some(3)));
+        assert!(cancellation.start(&main, Some(3)));
+
+        // A request that arrives out of order doesn't cancel the newer version that is compiling.
+        assert!(!cancellation.request(&main, Some(2)));
+        assert!(!cancellation.is_cancelled());
+        assert!(cancellation.request(&main, Some(4)));
+        assert!(cancellation.is_cancelled());
+        assert!(cancellation.finish());
+
+        // The compilation of the older version is skipped, as the newer one is queued.
+        assert!(!cancellation.start(&main, Some(2)));
+        assert!(cancellation.start(&main, Some(4)));
+        assert!(!cancellation.finish());
+
+        // Versions of other files, and requests without versions, can't be compared.
+        assert!(cancellation.start(&main, Some(4)));
+        assert!(cancellation.request(&other, Some(1)));
+        assert!(cancellation.finish());
+        assert!(cancellation.start(&main, Some(4)));
+        assert!(cancellation.request(&main, None));
+        assert!(cancellation.finish());
+    }
+
+    #[test]
+    fn versions_start_over_when_a_file_is_reopened() {
+        let main = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/benchmark/src/main.sw",
+        ));
+        let cancellation = CompilationCancellation::default();
+        assert!(!cancellation.request(&main, Some(7)));
+        assert!(cancellation.start(&main, Some(7)));
+        assert!(!cancellation.finish());
+        // Without forgetting the old versions, the first ones after reopening would be stale.
+        assert!(!cancellation.start(&main, Some(1)));
+
+        cancellation.forget(&main);
+        assert!(!cancellation.request(&main, Some(1)));
+        assert!(cancellation.start(&main, Some(1)));
+        // The reopened file's requests still arrive out of order.
+        assert!(!cancellation.request(&main, Some(0)));
+        assert!(cancellation.request(&main, Some(2)));
+        assert!(cancellation.finish());
+        assert!(!cancellation.start(&main, Some(1)));
+        assert!(cancellation.start(&main, Some(2)));
+        assert!(!cancellation.finish());
+    }
 
     #[tokio::test]
     async fn store_document_returns_empty_tuple() {
@@ -622,14 +1805,214 @@ mod tests {
         assert_eq!(result, DocumentError::DocumentAlreadyStored { path });
     }
 
+    #[tokio::test]
+    async fn init_creates_temp_dir_in_configured_dir() {
+        let session = Session::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let uri = get_url(&get_absolute_path("sway-lsp/tests/fixtures/benchmark"));
+        session
+            .init(&uri, Some(temp_dir.path()), SyncMode::Temp)
+            .await
+            .unwrap();
+        let session_temp_dir = session.sync.temp_dir().unwrap();
+        session.shutdown();
+        assert!(session_temp_dir.starts_with(temp_dir.path().canonicalize().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn init_of_a_standalone_file_generates_a_package() {
+        let session = Session::new();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().canonicalize().unwrap().join("main.sw");
+        std::fs::write(&path, "script;\n\nfn main() {}\n").unwrap();
+        let project_dir = session
+            .init(&get_url(path.to_str().unwrap()), None, SyncMode::InPlace)
+            .await
+            .unwrap();
+        assert_eq!(project_dir, path);
+        assert!(session.sync.is_standalone());
+        let temp_dir = session.sync.temp_dir().unwrap();
+        session.shutdown();
+        assert!(temp_dir
+            .to_string_lossy()
+            .contains(SyncWorkspace::LSP_TEMP_PREFIX));
+        assert!(!temp_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn init_in_place_uses_manifest_dir() {
+        let session = Session::new();
+        let dir = get_absolute_path("sway-lsp/tests/fixtures/benchmark");
+        let uri = get_url(&dir);
+        let manifest_dir = PathBuf::from(&dir);
+        session.init(&uri, None, SyncMode::InPlace).await.unwrap();
+        assert_eq!(session.sync.temp_dir().unwrap(), manifest_dir);
+        let main = get_url(&format!("{dir}/src/main.sw"));
+        assert_eq!(session.sync.workspace_to_temp_url(&main).unwrap(), main);
+        assert!(session.sync.is_path_in_temp_workspace(&main));
+
+        // Unsaved changes are only visible to the compiler through the source overrides.
+        let change = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "library;".to_string(),
+        };
+        session
+            .write_changes_to_file(&main, vec![change])
+            .await
+            .unwrap();
+        let engines = Engines::default();
+        session.write_unsaved_documents(&engines).unwrap();
+        let path = manifest_dir.join("src/main.sw");
+        assert_eq!(&*engines.se().read_source(&path).unwrap(), "library;");
+        assert_ne!(std::fs::read_to_string(&path).unwrap(), "library;");
+        session.shutdown();
+        assert!(manifest_dir.exists());
+    }
+
+    #[test]
+    fn build_plan_outside_of_a_temp_dir_does_not_write_the_lock() {
+        let dir = get_absolute_path("sway-lsp/tests/fixtures/benchmark");
+        let lock_path = PathBuf::from(&dir).join("Forc.lock");
+        assert!(!lock_path.exists());
+        let build_plan = build_plan(&get_url(&dir)).unwrap();
+        assert!(!build_plan.compilation_order().is_empty());
+        assert!(!lock_path.exists());
+    }
+
+    #[tokio::test]
+    async fn saved_contents_are_compiled_without_analyze_unsaved() {
+        let session = Session::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = get_absolute_path("sway-lsp/tests/fixtures/benchmark");
+        session
+            .init(&get_url(&dir), Some(temp_dir.path()), SyncMode::Temp)
+            .await
+            .unwrap();
+        session.config.write().analyze_unsaved = false;
+        let main = get_url(&format!("{dir}/src/main.sw"));
+        let temp_main = session.sync.workspace_to_temp_url(&main).unwrap();
+        let saved = std::fs::read_to_string(main.to_file_path().unwrap()).unwrap();
+        let read_temp = || std::fs::read_to_string(temp_main.to_file_path().unwrap()).unwrap();
+
+        // The edit is kept in the document, but not written for the compiler.
+        let change = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "library;".to_string(),
+        };
+        session
+            .write_changes_to_file(&temp_main, vec![change])
+            .await
+            .unwrap();
+        assert_eq!(read_temp(), saved);
+
+        let engines = Engines::default();
+        session.config.write().analyze_unsaved = true;
+        session.write_unsaved_documents(&engines).unwrap();
+        assert_eq!(read_temp(), "library;");
+
+        // Turning it off again restores the saved contents.
+        session.config.write().analyze_unsaved = false;
+        session.write_unsaved_documents(&engines).unwrap();
+        assert_eq!(read_temp(), saved);
+        session.shutdown();
+    }
+
+    #[tokio::test]
+    async fn failed_compilations_keep_the_last_tokens() {
+        let session = Session::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = get_absolute_path("sway-lsp/tests/fixtures/benchmark");
+        session
+            .init(&get_url(&dir), Some(temp_dir.path()), SyncMode::Temp)
+            .await
+            .unwrap();
+        let main = get_url(&format!("{dir}/src/main.sw"));
+        let temp_main = session.sync.workspace_to_temp_url(&main).unwrap();
+        let build_config = BuildConfig::default();
+        let mut parse_result = ParseResult::default();
+        parse_project(
+            &temp_main,
+            &session.engines.read(),
+            session.position_encoding(),
+            &build_config,
+            None,
+            &mut parse_result,
+        )
+        .unwrap();
+        session.write_parse_result(&mut parse_result);
+        *session.last_compilation_state.write() = LastCompilationState::Success;
+        assert!(!session.has_stale_tokens());
+
+        // A type error fails the compilation, but its diagnostics are still returned.
+        std::fs::write(
+            temp_main.to_file_path().unwrap(),
+            "script;\n\nfn main() {\n    let x: u64 = true;\n}\n",
+        )
+        .unwrap();
+        let engines = Engines::default();
+        let mut parse_result = ParseResult::default();
+        let result = parse_project(
+            &temp_main,
+            &engines,
+            session.position_encoding(),
+            &build_config,
+            None,
+            &mut parse_result,
+        )
+        .expect_err("expected FailedToParse");
+        assert!(matches!(result, LanguageServerError::FailedToParse));
+        session.write_failed_parse_result(&parse_result, engines.se());
+        *session.last_compilation_state.write() = LastCompilationState::Failed;
+
+        assert!(session
+            .diagnostics
+            .read()
+            .values()
+            .any(|diagnostics| !diagnostics.errors.is_empty()));
+        assert!(session
+            .token_map()
+            .tokens_for_file(&temp_main)
+            .next()
+            .is_some());
+        assert!(session.has_stale_tokens());
+        session.shutdown();
+    }
+
+    #[tokio::test]
+    async fn init_returns_temp_dir_not_writable() {
+        let session = Session::new();
+        // A directory can't be created under a file.
+        let temp_dir =
+            PathBuf::from(get_absolute_path("sway-lsp/tests/fixtures/cats.txt")).join("temp");
+        let uri = get_url(&get_absolute_path("sway-lsp/tests/fixtures/benchmark"));
+        let result = session
+            .init(&uri, Some(&temp_dir), SyncMode::Temp)
+            .await
+            .expect_err("expected TempDirNotWritable");
+        assert!(matches!(
+            result,
+            LanguageServerError::DirectoryError(DirectoryError::TempDirNotWritable { dir })
+            if dir == temp_dir.to_string_lossy()
+        ));
+    }
+
     #[test]
     fn parse_project_returns_manifest_file_not_found() {
         let dir = get_absolute_path("sway-lsp/tests/fixtures");
         let uri = get_url(&dir);
         let engines = Engines::default();
         let parse_result = &mut ParseResult::default();
-        let result = parse_project(&uri, &engines, None, parse_result)
-            .expect_err("expected ManifestFileNotFound");
+        let result = parse_project(
+            &uri,
+            &engines,
+            PositionEncoding::default(),
+            &BuildConfig::default(),
+            None,
+            parse_result,
+        )
+        .expect_err("expected ManifestFileNotFound");
         assert!(matches!(
             result,
             LanguageServerError::DocumentError(
@@ -1,4 +1,6 @@
 use crate::{
+    config::SyncMode,
+    core::session,
     error::{DirectoryError, DocumentError, LanguageServerError},
     utils::document::{get_path_from_url, get_url_from_path, get_url_from_span},
 };
@@ -9,14 +11,15 @@ use notify::RecursiveMode;
 use notify_debouncer_mini::new_debouncer;
 use parking_lot::RwLock;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, File},
-    io::{Read, Write},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use sway_types::{SourceEngine, Span};
+use sway_utils::constants::MANIFEST_FILE_NAME;
 use tempfile::Builder;
 use tokio::task::JoinHandle;
 
@@ -26,10 +29,43 @@ pub enum Directory {
     Temp,
 }
 
+/// The size and modification time of a file, which tell whether it changed since it was last synced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// The stamps of a file of the workspace and of its copy in the temp directory when it was last synced.
+///
+/// The copy is compared as well, as the unsaved contents of the editor are written to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SyncedFile {
+    source: FileStamp,
+    temp: FileStamp,
+}
+
 #[derive(Debug)]
 pub struct SyncWorkspace {
     pub directories: DashMap<Directory, PathBuf>,
     pub notify_join_handle: RwLock<Option<JoinHandle<()>>>,
+    pub mode: RwLock<SyncMode>,
+    // The directories of the project's path dependencies, including transitive ones, whose files are read-only.
+    pub dependency_dirs: RwLock<Vec<PathBuf>>,
+    // The files that have been copied to the temp directory, by their path relative to the manifest directory.
+    synced_files: RwLock<HashMap<PathBuf, SyncedFile>>,
+    // In single-file mode, the name of the file that is analyzed on its own as the entry of a generated package.
+    standalone_file: RwLock<Option<PathBuf>>,
 }
 
 impl SyncWorkspace {
@@ -39,41 +75,102 @@ impl SyncWorkspace {
         Self {
             directories: DashMap::new(),
             notify_join_handle: RwLock::new(None),
+            mode: RwLock::new(SyncMode::default()),
+            dependency_dirs: RwLock::new(vec![]),
+            synced_files: RwLock::new(HashMap::new()),
+            standalone_file: RwLock::new(None),
         }
     }
 
-    /// Overwrite the contents of the tmp/folder with everything in
-    /// the current workspace.
+    /// Returns whether the project is compiled from its own directory rather than from a temp directory.
+    pub(crate) fn is_in_place(&self) -> bool {
+        *self.mode.read() == SyncMode::InPlace
+    }
+
+    /// Brings the temp directory up to date with the current workspace.
+    ///
+    /// Only the files that changed since they were last synced are copied, and the files that were removed
+    /// from the workspace are removed from the temp directory. If that fails, e.g. because the temp directory
+    /// was tampered with, it's cloned again from scratch.
     pub fn resync(&self) -> Result<(), LanguageServerError> {
-        self.clone_manifest_dir_to_temp()?;
+        if self.is_in_place() {
+            return Ok(());
+        }
+        let manifest_dir = self.manifest_dir()?;
+        let temp_dir = self.temp_dir()?;
+        let result = self.sync_contents(&manifest_dir, &temp_dir, &mut self.synced_files.write());
+        if let Err(err) = result {
+            tracing::warn!(
+                "Unable to sync {} incrementally, cloning it again: {}",
+                manifest_dir.display(),
+                err
+            );
+            self.rebuild_temp_dir()?;
+        }
         if let Some(manifest) = self
             .manifest_path()
             .and_then(|manifest_path| PackageManifestFile::from_dir(manifest_path).ok())
         {
             if let Some(temp_manifest_path) = &self.temp_manifest_path() {
-                edit_manifest_dependency_paths(&manifest, temp_manifest_path)
+                edit_manifest_dependency_paths(&manifest, temp_manifest_path);
+                // The edited manifest is what the temp directory is meant to have.
+                if let (Some(synced), Ok(stamp)) = (
+                    self.synced_files
+                        .write()
+                        .get_mut(Path::new(MANIFEST_FILE_NAME)),
+                    FileStamp::of(temp_manifest_path),
+                ) {
+                    synced.temp = stamp;
+                }
             }
         }
         Ok(())
     }
 
+    /// Removes the temp directory and clones the workspace into it again.
+    pub(crate) fn rebuild_temp_dir(&self) -> Result<(), DirectoryError> {
+        if let Ok(temp_dir) = self.temp_dir() {
+            let _ = fs::remove_dir_all(temp_dir);
+        }
+        self.clone_manifest_dir_to_temp()
+    }
+
     /// Clean up the temp directory that was created once the
     /// server closes down.
+    ///
+    /// Urls can no longer be converted to the temp directory afterwards, so that the requests that are
+    /// still handled with the session fail rather than read files that no longer exist.
     pub(crate) fn remove_temp_dir(&self) {
-        if let Ok(dir) = self.temp_dir() {
+        let Some((_, dir)) = self.directories.remove(&Directory::Temp) else {
+            return;
+        };
+        // In place, the "temp" directory is the user's project.
+        if self.is_in_place() {
+            return;
+        }
+        // The temp directory of a standalone file is the `src` directory of the generated package.
+        if self.is_standalone() {
+            dir.ancestors().nth(2).map(fs::remove_dir_all);
+        } else {
             dir.parent().map(fs::remove_dir);
         }
     }
 
+    /// Returns whether the session is of a file that isn't in any package, analyzed in single-file mode.
+    pub(crate) fn is_standalone(&self) -> bool {
+        self.standalone_file.read().is_some()
+    }
+
+    /// Creates the temp directory that the workspace in `manifest_dir` is cloned into.
+    ///
+    /// It's created in `parent_dir` if given, or in the system's temp directory otherwise.
     pub(crate) fn create_temp_dir_from_workspace(
         &self,
         manifest_dir: &Path,
+        parent_dir: Option<&Path>,
     ) -> Result<(), LanguageServerError> {
-        let manifest = PackageManifestFile::from_dir(manifest_dir).map_err(|_| {
-            DocumentError::ManifestFileNotFound {
-                dir: manifest_dir.to_string_lossy().to_string(),
-            }
-        })?;
+        let manifest = PackageManifestFile::from_dir(manifest_dir)
+            .map_err(|err| DocumentError::manifest_error(manifest_dir, &err))?;
 
         // strip Forc.toml from the path to get the manifest directory
         let manifest_dir = manifest
@@ -90,10 +187,16 @@ impl SyncWorkspace {
             })?;
 
         // Create a new temporary directory that we can clone the current workspace into.
-        let temp_dir = Builder::new()
-            .prefix(SyncWorkspace::LSP_TEMP_PREFIX)
-            .tempdir()
-            .map_err(|_| DirectoryError::TempDirFailed)?;
+        let mut builder = Builder::new();
+        builder.prefix(SyncWorkspace::LSP_TEMP_PREFIX);
+        let temp_dir = match parent_dir {
+            Some(parent_dir) => {
+                ensure_dir_is_writable(parent_dir)?;
+                builder.tempdir_in(parent_dir)
+            }
+            None => builder.tempdir(),
+        }
+        .map_err(|_| DirectoryError::TempDirFailed)?;
 
         let temp_path = temp_dir
             .into_path()
@@ -108,21 +211,186 @@ impl SyncWorkspace {
         Ok(())
     }
 
+    /// Creates the temp directory of a package that is generated around the file at `path`, which isn't in any
+    /// package, so that it can be compiled on its own.
+    ///
+    /// The file is the entry of the package, and the directory it's in is treated as the manifest directory,
+    /// so that urls are converted between them as usual. Only the file itself is cloned.
+    pub(crate) fn create_temp_package_for_file(
+        &self,
+        path: &Path,
+        parent_dir: Option<&Path>,
+    ) -> Result<(), LanguageServerError> {
+        let file_name = path
+            .file_name()
+            .ok_or(DirectoryError::CantExtractProjectName {
+                dir: path.to_string_lossy().to_string(),
+            })?;
+        let dir = path.parent().ok_or(DirectoryError::ManifestDirNotFound)?;
+        let project_name = standalone_project_name(path);
+
+        let mut builder = Builder::new();
+        builder.prefix(SyncWorkspace::LSP_TEMP_PREFIX);
+        let temp_dir = match parent_dir {
+            Some(parent_dir) => {
+                ensure_dir_is_writable(parent_dir)?;
+                builder.tempdir_in(parent_dir)
+            }
+            None => builder.tempdir(),
+        }
+        .map_err(|_| DirectoryError::TempDirFailed)?;
+        let package_dir = temp_dir
+            .into_path()
+            .canonicalize()
+            .map_err(|_| DirectoryError::CanonicalizeFailed)?
+            .join(&project_name);
+        let src_dir = package_dir.join(sway_utils::constants::SRC_DIR);
+        fs::create_dir_all(&src_dir).map_err(|_| DirectoryError::TempDirFailed)?;
+        let manifest = format!(
+            "[project]\nname = \"{project_name}\"\nlicense = \"Apache-2.0\"\nentry = \"{}\"\n",
+            file_name.to_string_lossy()
+        );
+        fs::write(package_dir.join(MANIFEST_FILE_NAME), manifest)
+            .map_err(|_| DirectoryError::TempDirFailed)?;
+
+        *self.standalone_file.write() = Some(PathBuf::from(file_name));
+        self.directories
+            .insert(Directory::Manifest, dir.to_path_buf());
+        self.directories.insert(Directory::Temp, src_dir);
+
+        Ok(())
+    }
+
+    /// Uses the project in `manifest_dir` as it is, without cloning it.
+    ///
+    /// The temp directory is then the manifest directory itself, so converting urls between them is the identity.
+    pub(crate) fn use_manifest_dir_in_place(
+        &self,
+        manifest_dir: &Path,
+    ) -> Result<(), LanguageServerError> {
+        let manifest = PackageManifestFile::from_dir(manifest_dir)
+            .map_err(|err| DocumentError::manifest_error(manifest_dir, &err))?;
+        let manifest_dir = manifest
+            .path()
+            .parent()
+            .ok_or(DirectoryError::ManifestDirNotFound)?;
+
+        *self.mode.write() = SyncMode::InPlace;
+        self.directories
+            .insert(Directory::Manifest, manifest_dir.to_path_buf());
+        self.directories
+            .insert(Directory::Temp, manifest_dir.to_path_buf());
+
+        Ok(())
+    }
+
+    /// Copies every file of the workspace into the temp directory, regardless of what was synced before.
     pub(crate) fn clone_manifest_dir_to_temp(&self) -> Result<(), DirectoryError> {
-        copy_dir_contents(self.manifest_dir()?, self.temp_dir()?)
+        let mut synced_files = self.synced_files.write();
+        synced_files.clear();
+        self.sync_contents(&self.manifest_dir()?, &self.temp_dir()?, &mut synced_files)
             .map_err(|_| DirectoryError::CopyContentsFailed)?;
 
         Ok(())
     }
 
+    /// Copies the changed files of the workspace into the temp directory, or only the standalone file in
+    /// single-file mode.
+    fn sync_contents(
+        &self,
+        manifest_dir: &Path,
+        temp_dir: &Path,
+        synced_files: &mut HashMap<PathBuf, SyncedFile>,
+    ) -> io::Result<usize> {
+        match &*self.standalone_file.read() {
+            Some(file_name) => {
+                fs::create_dir_all(temp_dir)?;
+                let copied = copy_if_changed(
+                    &manifest_dir.join(file_name),
+                    &temp_dir.join(file_name),
+                    file_name,
+                    synced_files,
+                )?;
+                Ok(usize::from(copied))
+            }
+            None => sync_dir_contents(manifest_dir, temp_dir, synced_files),
+        }
+    }
+
     /// Check if the current path is part of the users workspace.
     /// Returns false if the path is from a dependancy
     pub(crate) fn is_path_in_temp_workspace(&self, uri: &Url) -> bool {
+        if self.is_in_place() {
+            return self
+                .temp_dir()
+                .is_ok_and(|dir| Path::new(uri.path()).starts_with(dir));
+        }
         uri.as_ref().contains(SyncWorkspace::LSP_TEMP_PREFIX)
     }
 
-    /// Convert the Url path from the client to point to the same file in our temp folder
+    /// Finds the directories of the project's dependencies, so that their files can be recognized as read-only.
+    ///
+    /// The path dependencies of the project's manifest, and their path dependencies in turn, are found from
+    /// the manifests, even if the build plan can't be created. The sources of the other dependencies, such as
+    /// `std`, are found through the build plan.
+    pub(crate) fn resolve_dependency_dirs(&self) {
+        let mut dirs: Vec<PathBuf> = vec![];
+        let mut manifests = self
+            .manifest_dir()
+            .ok()
+            .and_then(|dir| PackageManifestFile::from_dir(dir).ok())
+            .into_iter()
+            .collect::<Vec<_>>();
+        while let Some(manifest) = manifests.pop() {
+            for name in manifest.dependencies.iter().flat_map(|deps| deps.keys()) {
+                let Some(dir) = manifest.dep_path(name) else {
+                    continue;
+                };
+                // Dependencies can be shared, so only visit each one once.
+                if dirs.contains(&dir) {
+                    continue;
+                }
+                if let Ok(dependency) = PackageManifestFile::from_dir(&dir) {
+                    manifests.push(dependency);
+                }
+                dirs.push(dir);
+            }
+        }
+        if let Some(build_plan) = self
+            .manifest_dir()
+            .ok()
+            .and_then(|dir| get_url_from_path(&dir).ok())
+            .and_then(|uri| session::build_plan(&uri).ok())
+        {
+            for dir in session::dependency_dirs(&build_plan) {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+        *self.dependency_dirs.write() = dirs;
+    }
+
+    /// Check if the path is in one of the project's dependencies rather than in the user's workspace.
+    /// Such files can be navigated, but edits to them aren't compiled.
+    pub(crate) fn is_path_in_dependency(&self, uri: &Url) -> bool {
+        let path = Path::new(uri.path());
+        if self.manifest_dir().is_ok_and(|dir| path.starts_with(dir)) {
+            return false;
+        }
+        self.dependency_dirs
+            .read()
+            .iter()
+            .any(|dir| path.starts_with(dir))
+    }
+
+    /// Convert the Url path from the client to point to the same file in our temp folder.
+    ///
+    /// Files of dependencies aren't cloned, so their urls are returned as-is.
     pub(crate) fn workspace_to_temp_url(&self, uri: &Url) -> Result<Url, DirectoryError> {
+        if self.is_path_in_dependency(uri) {
+            return Ok(uri.clone());
+        }
         self.convert_url(uri, self.temp_dir()?, self.manifest_dir()?)
     }
 
@@ -184,6 +452,10 @@ impl SyncWorkspace {
 
     /// Watch the manifest directory and check for any save events on Forc.toml
     pub(crate) fn watch_and_sync_manifest(&self) {
+        // In place, the temp manifest is the user's, which must not be edited.
+        if self.is_in_place() {
+            return;
+        }
         let _ = self
             .manifest_path()
             .and_then(|manifest_path| PackageManifestFile::from_dir(manifest_path).ok())
@@ -252,6 +524,16 @@ impl SyncWorkspace {
     }
 }
 
+/// Creates `dir` if it doesn't exist, and checks that files can be created in it.
+fn ensure_dir_is_writable(dir: &Path) -> Result<(), DirectoryError> {
+    let not_writable = || DirectoryError::TempDirNotWritable {
+        dir: dir.to_string_lossy().to_string(),
+    };
+    fs::create_dir_all(dir).map_err(|_| not_writable())?;
+    tempfile::tempfile_in(dir).map_err(|_| not_writable())?;
+    Ok(())
+}
+
 /// Deserialize the manifest file and loop through the dependancies.
 /// Check if the dependancy is specifying a 'path'.
 /// If so, check if the path is relative and convert the relative path to an absolute path.
@@ -295,20 +577,171 @@ pub(crate) fn edit_manifest_dependency_paths(
     }
 }
 
-/// Copy the contents of the current workspace folder into the target directory
-fn copy_dir_contents(
-    src_dir: impl AsRef<Path>,
-    target_dir: impl AsRef<Path>,
-) -> std::io::Result<()> {
-    fs::create_dir_all(&target_dir)?;
-    for entry in fs::read_dir(src_dir)? {
+/// Copies the files of `src_dir` that changed since they were recorded in `synced_files` into `target_dir`,
+/// and removes the recorded files that no longer exist in `src_dir` from `target_dir`.
+///
+/// Returns the number of files that were copied.
+fn sync_dir_contents(
+    src_dir: &Path,
+    target_dir: &Path,
+    synced_files: &mut HashMap<PathBuf, SyncedFile>,
+) -> io::Result<usize> {
+    let mut seen = HashSet::new();
+    let copied = copy_changed_files(src_dir, target_dir, Path::new(""), synced_files, &mut seen)?;
+    synced_files.retain(|relative_path, _| {
+        let exists = seen.contains(relative_path);
+        if !exists {
+            let _ = fs::remove_file(target_dir.join(relative_path));
+        }
+        exists
+    });
+    Ok(copied)
+}
+
+/// Copies the changed files of the directory at `relative_dir` of `src_dir` into `target_dir`,
+/// adding the path of each of its files to `seen`.
+fn copy_changed_files(
+    src_dir: &Path,
+    target_dir: &Path,
+    relative_dir: &Path,
+    synced_files: &mut HashMap<PathBuf, SyncedFile>,
+    seen: &mut HashSet<PathBuf>,
+) -> io::Result<usize> {
+    fs::create_dir_all(target_dir.join(relative_dir))?;
+    let mut copied = 0;
+    for entry in fs::read_dir(src_dir.join(relative_dir))? {
         let entry = entry?;
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            copy_dir_contents(entry.path(), target_dir.as_ref().join(entry.file_name()))?;
-        } else {
-            fs::copy(entry.path(), target_dir.as_ref().join(entry.file_name()))?;
+        let relative_path = relative_dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copied += copy_changed_files(src_dir, target_dir, &relative_path, synced_files, seen)?;
+            continue;
         }
+        let target_path = target_dir.join(&relative_path);
+        if copy_if_changed(&entry.path(), &target_path, &relative_path, synced_files)? {
+            copied += 1;
+        }
+        seen.insert(relative_path);
+    }
+    Ok(copied)
+}
+
+/// Copies the file at `source_path` to `target_path` unless neither changed since it was last synced.
+/// Returns whether it was copied.
+fn copy_if_changed(
+    source_path: &Path,
+    target_path: &Path,
+    relative_path: &Path,
+    synced_files: &mut HashMap<PathBuf, SyncedFile>,
+) -> io::Result<bool> {
+    let source = FileStamp::of(source_path)?;
+    let is_up_to_date = synced_files.get(relative_path).is_some_and(|synced| {
+        synced.source == source && FileStamp::of(target_path).ok() == Some(synced.temp)
+    });
+    if is_up_to_date {
+        return Ok(false);
+    }
+    fs::copy(source_path, target_path)?;
+    let temp = FileStamp::of(target_path)?;
+    synced_files.insert(relative_path.to_path_buf(), SyncedFile { source, temp });
+    Ok(true)
+}
+
+/// Returns the name of the package that is generated around the standalone file at `path`.
+///
+/// It's prefixed so that it can't be a keyword or a reserved name, and the characters of the file stem
+/// that can't be in a package name are replaced.
+fn standalone_project_name(path: &Path) -> String {
+    let stem: String = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("standalone_{stem}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_dir_contents_only_copies_changed_files() {
+        let src = tempfile::tempdir().unwrap();
+        let target = tempfile::tempdir().unwrap();
+        fs::create_dir(src.path().join("src")).unwrap();
+        fs::write(src.path().join("Forc.toml"), "[project]").unwrap();
+        fs::write(src.path().join("src/main.sw"), "script;").unwrap();
+        fs::write(src.path().join("src/lib.sw"), "library;").unwrap();
+        let mut synced_files = HashMap::new();
+        assert_eq!(
+            sync_dir_contents(src.path(), target.path(), &mut synced_files).unwrap(),
+            3
+        );
+        assert_eq!(
+            sync_dir_contents(src.path(), target.path(), &mut synced_files).unwrap(),
+            0
+        );
+
+        // Changed and overwritten files are copied again, and removed ones are removed.
+        fs::write(src.path().join("src/main.sw"), "script;\nfn main() {}").unwrap();
+        fs::write(
+            target.path().join("Forc.toml"),
+            "[project]\nname = \"edited\"",
+        )
+        .unwrap();
+        fs::remove_file(src.path().join("src/lib.sw")).unwrap();
+        assert_eq!(
+            sync_dir_contents(src.path(), target.path(), &mut synced_files).unwrap(),
+            2
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("src/main.sw")).unwrap(),
+            "script;\nfn main() {}"
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("Forc.toml")).unwrap(),
+            "[project]"
+        );
+        assert!(!target.path().join("src/lib.sw").exists());
+        assert_eq!(synced_files.len(), 2);
+    }
+
+    #[test]
+    fn standalone_files_are_the_entry_of_a_generated_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.path().canonicalize().unwrap();
+        fs::write(dir_path.join("my script.sw"), "script;\nfn main() {}").unwrap();
+        fs::write(dir_path.join("other.sw"), "library;").unwrap();
+        let sync = SyncWorkspace::new();
+        sync.create_temp_package_for_file(&dir_path.join("my script.sw"), None)
+            .unwrap();
+        sync.clone_manifest_dir_to_temp().unwrap();
+        assert!(sync.is_standalone());
+
+        // Only the file itself is cloned, into the `src` directory of the generated package.
+        let temp_dir = sync.temp_dir().unwrap();
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 1);
+        let manifest = PackageManifestFile::from_dir(&temp_dir).unwrap();
+        assert_eq!(manifest.project.name, "standalone_my_script");
+        assert_eq!(manifest.entry_path(), temp_dir.join("my script.sw"));
+
+        let uri = get_url_from_path(&dir_path.join("my script.sw")).unwrap();
+        let temp_uri = sync.workspace_to_temp_url(&uri).unwrap();
+        assert_eq!(temp_uri, get_url_from_path(&manifest.entry_path()).unwrap());
+        assert_eq!(sync.temp_to_workspace_url(&temp_uri).unwrap(), uri);
+
+        sync.remove_temp_dir();
+        assert!(!temp_dir.exists());
+        assert_eq!(
+            sync.workspace_to_temp_url(&uri),
+            Err(DirectoryError::TempDirNotFound)
+        );
     }
-    Ok(())
 }
@@ -1,4 +1,5 @@
-use lsp_types::{Position, Range};
+use crate::utils::position_encoding::PositionEncoding;
+use lsp_types::Range;
 use std::path::PathBuf;
 use sway_ast::Intrinsic;
 use sway_core::{
@@ -171,12 +172,36 @@ impl Token {
     }
 
     /// Return the [TokenIdent] of the declaration of the provided token.
-    pub fn declared_token_ident(&self, engines: &Engines) -> Option<TokenIdent> {
+    pub fn declared_token_ident(
+        &self,
+        engines: &Engines,
+        encoding: PositionEncoding,
+    ) -> Option<TokenIdent> {
         self.type_def.as_ref().and_then(|type_def| match type_def {
-            TypeDefinition::TypeId(type_id) => ident_of_type_id(engines, type_id),
-            TypeDefinition::Ident(ident) => Some(TokenIdent::new(ident, engines.se())),
+            TypeDefinition::TypeId(type_id) => ident_of_type_id(engines, type_id, encoding),
+            TypeDefinition::Ident(ident) => Some(TokenIdent::new(ident, engines.se(), encoding)),
         })
     }
+
+    /// Return the [TypeId] of the value that the provided token refers to, if it has one.
+    pub fn type_id(&self) -> Option<TypeId> {
+        let type_id = match self.typed.as_ref()? {
+            TypedAstToken::TypedDeclaration(ty::TyDecl::VariableDecl(decl)) => decl.return_type,
+            TypedAstToken::TypedConstantDeclaration(decl) => decl.return_type,
+            TypedAstToken::TypedExpression(expr) => expr.return_type,
+            TypedAstToken::TypedScrutinee(scrutinee) => scrutinee.type_id,
+            TypedAstToken::TypedFunctionParameter(param) => param.type_argument.type_id,
+            TypedAstToken::TypedStructField(field) => field.type_argument.type_id,
+            TypedAstToken::TypedStorageField(field) => field.type_argument.type_id,
+            TypedAstToken::TypedStorageAccessDescriptor(descriptor) => descriptor.type_id,
+            TypedAstToken::TypedArgument(type_arg) => type_arg.type_id,
+            _ => match self.type_def.as_ref()? {
+                TypeDefinition::TypeId(type_id) => *type_id,
+                TypeDefinition::Ident(_) => return None,
+            },
+        };
+        Some(type_id)
+    }
 }
 
 /// A more convenient [Ident] type for use in the language server.
@@ -197,14 +222,14 @@ pub struct TokenIdent {
 }
 
 impl TokenIdent {
-    pub fn new(ident: &Ident, se: &SourceEngine) -> Self {
+    pub fn new(ident: &Ident, se: &SourceEngine, encoding: PositionEncoding) -> Self {
         let path = ident
             .span()
             .source_id()
             .map(|source_id| se.get_path(source_id));
         Self {
             name: ident.span().str(),
-            range: get_range_from_span(&ident.span()),
+            range: get_range_from_span(&ident.span(), encoding),
             path,
             is_raw_ident: ident.is_raw_ident(),
         }
@@ -238,7 +263,11 @@ pub fn desugared_op(prefixes: &[Ident]) -> bool {
 }
 
 /// Use the [TypeId] to look up the associated [TypeInfo] and return the [TokenIdent] if one is found.
-pub fn ident_of_type_id(engines: &Engines, type_id: &TypeId) -> Option<TokenIdent> {
+pub fn ident_of_type_id(
+    engines: &Engines,
+    type_id: &TypeId,
+    encoding: PositionEncoding,
+) -> Option<TokenIdent> {
     let ident = match &*engines.te().get(*type_id) {
         TypeInfo::UnknownGeneric { name, .. } => name.clone(),
         TypeInfo::Enum(decl_ref) => engines.de().get_enum(decl_ref).call_path.suffix.clone(),
@@ -250,7 +279,7 @@ pub fn ident_of_type_id(engines: &Engines, type_id: &TypeId) -> Option<TokenIden
         } => qualified_call_path.call_path.suffix.clone(),
         _ => return None,
     };
-    Some(TokenIdent::new(&ident, engines.se()))
+    Some(TokenIdent::new(&ident, engines.se(), encoding))
 }
 
 /// Intended to be used during traversal of the [sway_core::language::parsed::ParseProgram] AST.
@@ -287,12 +316,7 @@ pub fn type_info_to_symbol_kind(
     }
 }
 
-/// Given a [Span], convert into a [Range] and return.
-pub fn get_range_from_span(span: &Span) -> Range {
-    let start = span.start_pos().line_col();
-    let end = span.end_pos().line_col();
-    Range {
-        start: Position::new(start.0 as u32 - 1, start.1 as u32 - 1),
-        end: Position::new(end.0 as u32 - 1, end.1 as u32 - 1),
-    }
+/// Given a [Span], convert into a [Range] in the negotiated [PositionEncoding] and return.
+pub fn get_range_from_span(span: &Span, encoding: PositionEncoding) -> Range {
+    encoding.range_of(span)
 }
@@ -1,8 +1,15 @@
-use crate::core::token::{self, Token, TokenIdent, TypedAstToken};
+use crate::{
+    core::token::{self, Token, TokenIdent, TypedAstToken},
+    utils::position_encoding::PositionEncoding,
+};
 use dashmap::{mapref::one::RefMut, try_result::TryResult, DashMap};
 use lsp_types::{Position, Url};
-use std::{thread, time::Duration};
-use sway_core::{language::ty, type_system::TypeId, Engines};
+use std::{collections::HashSet, thread, time::Duration};
+use sway_core::{
+    language::{parsed::ImportType, ty},
+    type_system::TypeId,
+    Engines,
+};
 use sway_types::{Ident, SourceEngine, Spanned};
 
 // Re-export the TokenMapExt trait.
@@ -15,6 +22,15 @@ pub use crate::core::token_map_ext::TokenMapExt;
 #[derive(Debug, Default)]
 pub struct TokenMap(DashMap<TokenIdent, Token>);
 
+/// How [TokenMap::update_from] updated the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenMapUpdate {
+    /// The tokens that are no longer in the new map were removed, and the others were replaced in place.
+    Incremental { removed: usize },
+    /// The map was cleared and all of the tokens of the new map were inserted.
+    Rebuilt,
+}
+
 impl<'a> TokenMap {
     /// Create a new token map.
     pub fn new() -> TokenMap {
@@ -68,6 +84,42 @@ impl<'a> TokenMap {
         None // Return None if all retries are exhausted
     }
 
+    /// Updates the map to hold the same tokens as `new`.
+    ///
+    /// Rather than clearing the map, only the tokens whose ident isn't in `new` are removed and the others are
+    /// replaced in place, so that tokens outside of the edited region never go missing while the map is updated.
+    /// The tokens themselves are always taken from `new`, as they refer to the declarations and types of the
+    /// engines that it was traversed with.
+    ///
+    /// If most of the idents changed, e.g. because an edit near the top of a file shifted the spans below it,
+    /// diffing is more expensive than rebuilding, so the map is cleared and rebuilt instead.
+    pub fn update_from(&self, new: &TokenMap) -> TokenMapUpdate {
+        let stale = self
+            .0
+            .iter()
+            .filter(|item| !new.0.contains_key(item.key()))
+            .count();
+        if self.0.is_empty() || stale * 2 > self.0.len() {
+            self.0.clear();
+            new.0.iter().for_each(|item| {
+                let (ident, token) = item.pair();
+                self.0.insert(ident.clone(), token.clone());
+            });
+            return TokenMapUpdate::Rebuilt;
+        }
+        self.0.retain(|ident, _| new.0.contains_key(ident));
+        new.0.iter().for_each(|item| {
+            let (ident, token) = item.pair();
+            self.0.insert(ident.clone(), token.clone());
+        });
+        TokenMapUpdate::Incremental { removed: stale }
+    }
+
+    /// Returns an estimate of the memory held by the tokens, which only counts their inline size.
+    pub fn approximate_size(&self) -> usize {
+        self.len() * (std::mem::size_of::<TokenIdent>() + std::mem::size_of::<Token>())
+    }
+
     /// Create a custom iterator for the TokenMap.
     ///
     /// The iterator returns ([Ident], [Token]) pairs.
@@ -131,8 +183,9 @@ impl<'a> TokenMap {
         source_engine: &SourceEngine,
         uri: &Url,
         position: Position,
+        encoding: PositionEncoding,
     ) -> Option<(TokenIdent, Token)> {
-        self.tokens_at_position(source_engine, uri, position, None)
+        self.tokens_at_position(source_engine, uri, position, None, encoding)
             .iter()
             .find_map(|(ident, token)| {
                 if let Some(TypedAstToken::TypedDeclaration(_)) = token.typed {
@@ -169,6 +222,7 @@ impl<'a> TokenMap {
         uri: &Url,
         position: Position,
         functions_only: Option<bool>,
+        encoding: PositionEncoding,
     ) -> Vec<(TokenIdent, Token)> {
         self.tokens_for_file(uri)
             .filter_map(|(ident, token)| {
@@ -176,10 +230,10 @@ impl<'a> TokenMap {
                     Some(TypedAstToken::TypedFunctionDeclaration(decl))
                         if functions_only == Some(true) =>
                     {
-                        TokenIdent::new(&Ident::new(decl.span), source_engine)
+                        TokenIdent::new(&Ident::new(decl.span), source_engine, encoding)
                     }
                     Some(TypedAstToken::TypedDeclaration(decl)) => {
-                        TokenIdent::new(&Ident::new(decl.span()), source_engine)
+                        TokenIdent::new(&Ident::new(decl.span()), source_engine, encoding)
                     }
                     // Seems to be a clippy bug
                     #[allow(clippy::redundant_clone)]
@@ -205,6 +259,138 @@ impl<'a> TokenMap {
             .collect()
     }
 
+    /// Returns the ident of the declaration that the token at `ident` refers to, along with the `use` statements
+    /// that were passed through on the way to it.
+    ///
+    /// A name that's imported under an alias, like `c` after `use a::b as c;`, passes through the alias.
+    /// An import that points at another `use` statement is followed to what that statement imports, and an
+    /// import that the compiler couldn't resolve is followed to the `use` statement that re-exports the name
+    /// from the module it's imported from. A cycle of imports stops at the first `use` statement that's
+    /// visited twice.
+    pub fn resolve_declaration(
+        &self,
+        ident: &TokenIdent,
+        token: &Token,
+        engines: &Engines,
+        encoding: PositionEncoding,
+    ) -> Option<(TokenIdent, Vec<TokenIdent>)> {
+        let mut use_sites = vec![];
+        let mut visited = HashSet::new();
+        let mut token = token.clone();
+        if let Some(decl_ident) = token.declared_token_ident(engines, encoding) {
+            if !is_use_statement(&token) && decl_ident.name != ident.name {
+                use_sites.extend(self.alias_site(ident, &decl_ident, engines, encoding));
+            }
+        }
+        loop {
+            let next = match token
+                .declared_token_ident(engines, encoding)
+                .or_else(|| self.reexport_site(&token, engines, encoding))
+            {
+                Some(next) => next,
+                None => {
+                    let last = use_sites.pop()?;
+                    return Some((last, use_sites));
+                }
+            };
+            let next_token = self
+                .try_get(&next)
+                .try_unwrap()
+                .map(|item| item.value().clone());
+            match next_token {
+                Some(next_token)
+                    if is_use_statement(&next_token) && visited.insert(next.clone()) =>
+                {
+                    use_sites.push(next);
+                    token = next_token;
+                }
+                _ => return Some((next, use_sites)),
+            }
+        }
+    }
+
+    /// Returns the ident of the `use` statement of the file of `ident` that imports `decl_ident` under the name of `ident`.
+    fn alias_site(
+        &self,
+        ident: &TokenIdent,
+        decl_ident: &TokenIdent,
+        engines: &Engines,
+        encoding: PositionEncoding,
+    ) -> Option<TokenIdent> {
+        self.0.iter().find_map(|entry| {
+            let (other, token) = entry.pair();
+            (other.path == ident.path
+                && other.name == ident.name
+                && is_use_statement(token)
+                && token.declared_token_ident(engines, encoding).as_ref() == Some(decl_ident))
+            .then(|| other.clone())
+        })
+    }
+
+    /// Returns the ident of the `use` statement that re-exports the item that `token` imports, in the module
+    /// it's imported from.
+    fn reexport_site(
+        &self,
+        token: &Token,
+        engines: &Engines,
+        encoding: PositionEncoding,
+    ) -> Option<TokenIdent> {
+        let Some(TypedAstToken::TypedUseStatement(use_stmt)) = &token.typed else {
+            return None;
+        };
+        let ImportType::Item(item) = &use_stmt.import_type else {
+            return None;
+        };
+        // The last segment of the path refers to the module that the item is imported from.
+        let module = use_stmt.call_path.last()?;
+        let module_path = self
+            .try_get(&TokenIdent::new(module, engines.se(), encoding))
+            .try_unwrap()?
+            .value()
+            .declared_token_ident(engines, encoding)?
+            .path?;
+        self.0.iter().find_map(|entry| {
+            let (other, token) = entry.pair();
+            let Some(TypedAstToken::TypedUseStatement(other_use_stmt)) = &token.typed else {
+                return None;
+            };
+            let imported_as = match (&other_use_stmt.alias, &other_use_stmt.import_type) {
+                (Some(alias), _) => alias,
+                (None, ImportType::Item(other_item)) => other_item,
+                _ => return None,
+            };
+            (other.path.as_ref() == Some(&module_path)
+                && other.name == item.as_str()
+                && imported_as.as_str() == item.as_str())
+            .then(|| other.clone())
+        })
+    }
+
+    /// Returns the `match` or `if` expression of the file that ends at the given [Position].
+    ///
+    /// This is used to find the receiver of a method that is called on such an expression,
+    /// e.g. `match x { ... }.foo()`, as its type is unified from its branches.
+    pub fn branching_expression_ending_at(
+        &self,
+        uri: &Url,
+        position: Position,
+        encoding: PositionEncoding,
+    ) -> Option<ty::TyExpression> {
+        self.tokens_for_file(uri)
+            .find_map(|(_, token)| match token.typed {
+                Some(TypedAstToken::TypedExpression(expr))
+                    if matches!(
+                        expr.expression,
+                        ty::TyExpressionVariant::MatchExp { .. }
+                            | ty::TyExpressionVariant::IfExp { .. }
+                    ) && token::get_range_from_span(&expr.span, encoding).end == position =>
+                {
+                    Some(expr)
+                }
+                _ => None,
+            })
+    }
+
     /// Uses the [TypeId] to find the associated [ty::TyDecl] in the TokenMap.
     ///
     /// This is useful when dealing with tokens that are of the [sway_core::language::ty::TyExpression] type in the AST.
@@ -213,8 +399,9 @@ impl<'a> TokenMap {
         &self,
         engines: &Engines,
         type_id: &TypeId,
+        encoding: PositionEncoding,
     ) -> Option<ty::TyDecl> {
-        token::ident_of_type_id(engines, type_id)
+        token::ident_of_type_id(engines, type_id, encoding)
             .and_then(|decl_ident| self.try_get(&decl_ident).try_unwrap())
             .map(|item| item.value().clone())
             .and_then(|token| token.typed)
@@ -230,8 +417,9 @@ impl<'a> TokenMap {
         &self,
         engines: &Engines,
         type_id: &TypeId,
+        encoding: PositionEncoding,
     ) -> Option<ty::TyStructDecl> {
-        self.declaration_of_type_id(engines, type_id)
+        self.declaration_of_type_id(engines, type_id, encoding)
             .and_then(|decl| match decl {
                 ty::TyDecl::StructDecl(ty::StructDecl { decl_id, .. }) => {
                     Some((*engines.de().get_struct(&decl_id)).clone())
@@ -241,6 +429,11 @@ impl<'a> TokenMap {
     }
 }
 
+/// Returns true if the token is part of a `use` statement.
+fn is_use_statement(token: &Token) -> bool {
+    matches!(token.typed, Some(TypedAstToken::TypedUseStatement(_)))
+}
+
 impl std::ops::Deref for TokenMap {
     type Target = DashMap<TokenIdent, Token>;
     fn deref(&self) -> &Self::Target {
@@ -266,3 +459,55 @@ impl<'s> Iterator for TokenMapIter<'s> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::token::{AstToken, SymbolKind};
+    use lsp_types::Range;
+    use sway_types::Span;
+
+    fn token_map_of(lines: impl IntoIterator<Item = u32>) -> TokenMap {
+        let token_map = TokenMap::new();
+        for line in lines {
+            let ident = TokenIdent {
+                name: format!("x{line}"),
+                range: Range::new(Position::new(line, 0), Position::new(line, 2)),
+                ..Default::default()
+            };
+            let token =
+                Token::from_parsed(AstToken::ErrorRecovery(Span::dummy()), SymbolKind::Variable);
+            token_map.insert(ident, token);
+        }
+        token_map
+    }
+
+    fn lines(token_map: &TokenMap) -> Vec<u32> {
+        let mut lines: Vec<_> = token_map
+            .0
+            .iter()
+            .map(|item| item.key().range.start.line)
+            .collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    #[test]
+    fn update_from_only_removes_stale_tokens() {
+        let token_map = token_map_of(0..10);
+        let update = token_map.update_from(&token_map_of([0, 1, 2, 3, 4, 5, 6, 7, 8, 42]));
+        assert_eq!(update, TokenMapUpdate::Incremental { removed: 1 });
+        assert_eq!(lines(&token_map), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 42]);
+    }
+
+    #[test]
+    fn update_from_rebuilds_when_most_spans_shift() {
+        let token_map = token_map_of(0..10);
+        let update = token_map.update_from(&token_map_of(1..11));
+        assert_eq!(update, TokenMapUpdate::Incremental { removed: 1 });
+
+        let update = token_map.update_from(&token_map_of(20..30));
+        assert_eq!(update, TokenMapUpdate::Rebuilt);
+        assert_eq!(lines(&token_map), (20..30).collect::<Vec<_>>());
+    }
+}
@@ -3,7 +3,10 @@
 //!
 //! The `TokenMapExt` trait is implemented for any iterator that yields (Ident, Token) pairs.
 
-use crate::core::token::{Token, TokenIdent};
+use crate::{
+    core::token::{Token, TokenIdent},
+    utils::position_encoding::PositionEncoding,
+};
 use sway_core::Engines;
 
 /// A trait for extending iterators with the `all_references_of_token` method.
@@ -15,6 +18,7 @@ pub trait TokenMapExt: Sized {
         self,
         token_to_match: &'s Token,
         engines: &'s Engines,
+        encoding: PositionEncoding,
     ) -> AllReferencesOfToken<'s, Self>;
 }
 
@@ -27,10 +31,12 @@ where
         self,
         token_to_match: &'s Token,
         engines: &'s Engines,
+        encoding: PositionEncoding,
     ) -> AllReferencesOfToken<'s, Self> {
         AllReferencesOfToken {
             token_to_match,
             engines,
+            encoding,
             iter: self,
         }
     }
@@ -40,6 +46,7 @@ where
 pub struct AllReferencesOfToken<'s, I> {
     token_to_match: &'s Token,
     engines: &'s Engines,
+    encoding: PositionEncoding,
     iter: I,
 }
 
@@ -51,8 +58,11 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         for (ident, token) in self.iter.by_ref() {
-            let decl_ident_to_match = self.token_to_match.declared_token_ident(self.engines);
-            let is_same_type = decl_ident_to_match == token.declared_token_ident(self.engines);
+            let decl_ident_to_match = self
+                .token_to_match
+                .declared_token_ident(self.engines, self.encoding);
+            let is_same_type =
+                decl_ident_to_match == token.declared_token_ident(self.engines, self.encoding);
             let is_decl_of_token = Some(&ident) == decl_ident_to_match.as_ref();
 
             if decl_ident_to_match.is_some() && is_same_type || is_decl_of_token {
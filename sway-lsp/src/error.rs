@@ -1,5 +1,41 @@
+use serde_json::json;
+use std::path::Path;
+use sway_utils::constants::MANIFEST_FILE_NAME;
 use swayfmt::FormatterError;
 use thiserror::Error;
+use tower_lsp::jsonrpc;
+
+/// The JSON-RPC error code returned for requests that target a project whose session
+/// is still being initialized.
+pub const SESSION_INITIALIZING_ERROR_CODE: i64 = -32003;
+
+/// How long clients are advised to wait before retrying a request that failed
+/// with [SESSION_INITIALIZING_ERROR_CODE].
+pub const SESSION_INITIALIZING_RETRY_AFTER_MS: u64 = 500;
+
+/// The JSON-RPC error code returned for requests that target a project whose session
+/// recently failed to initialize and won't be retried until its backoff has passed.
+pub const SESSION_INIT_FAILED_ERROR_CODE: i64 = -32004;
+
+/// The JSON-RPC error code returned for `sway/keywordDoc` requests for a word that isn't a documented keyword.
+pub const KEYWORD_NOT_FOUND_ERROR_CODE: i64 = -32005;
+
+/// The JSON-RPC error code returned for `sway/manifestForFile` requests for a file that isn't in a Forc project.
+pub const MANIFEST_NOT_FOUND_ERROR_CODE: i64 = -32006;
+
+/// The JSON-RPC error code returned for requests that inspect the session of a project that hasn't been created yet.
+pub const SESSION_NOT_FOUND_ERROR_CODE: i64 = -32007;
+
+/// The JSON-RPC error code returned for `sway/locateSymbol` requests for a name that no declaration has.
+pub const SYMBOL_NOT_FOUND_ERROR_CODE: i64 = -32008;
+
+/// The JSON-RPC error code returned for `sway/locateSymbol` requests for a name that several declarations have,
+/// if the request asked for a unique one.
+pub const AMBIGUOUS_SYMBOL_ERROR_CODE: i64 = -32009;
+
+/// The JSON-RPC error code returned for requests whose session was shut down while they were handled,
+/// e.g. because the project was reinitialized. The request can be sent again after `retryAfterMs`.
+pub const SESSION_SHUT_DOWN_ERROR_CODE: i64 = -32010;
 
 #[derive(Debug, Error)]
 pub enum LanguageServerError {
@@ -24,6 +60,118 @@ pub enum LanguageServerError {
     ProgramsIsNone,
     #[error("Unable to acquire a semaphore permit for parsing")]
     UnableToAcquirePermit,
+    #[error("The project at {:?} is still initializing", dir)]
+    SessionInitializing { dir: String },
+    #[error(
+        "The project at {:?} failed to initialize, retrying in {}ms. {}",
+        dir,
+        retry_after_ms,
+        reason
+    )]
+    SessionInitFailed {
+        dir: String,
+        reason: String,
+        retry_after_ms: u64,
+    },
+    #[error("Failed to update the tracing level. {0}")]
+    TracingReloadFailed(String),
+    #[error("Compilation was aborted because the memory limit was exceeded")]
+    MemoryLimitExceeded,
+    #[error("Compilation didn't finish within {}ms", timeout_ms)]
+    CompilationTimedOut { timeout_ms: u64 },
+    #[error("No documentation was found for the keyword {:?}", keyword)]
+    KeywordNotFound { keyword: String },
+    #[error("Unable to run forc build. {0}")]
+    ForcBuildFailed(String),
+    #[error("No session has been created for the project at {:?} yet", dir)]
+    SessionNotFound { dir: String },
+    #[error("The compiler panicked. {0}")]
+    CompilerPanicked(String),
+    #[error(
+        "{:?} is outside of the workspace folders, which are the only ones analyzed",
+        path
+    )]
+    OutsideWorkspace { path: String },
+    #[error("No declaration was found for the symbol {:?}", name)]
+    SymbolNotFound { name: String },
+    #[error("The symbol {:?} matches {} declarations", name, count)]
+    AmbiguousSymbol { name: String, count: usize },
+    #[error("The session of the project at {:?} was shut down", dir)]
+    SessionShutDown { dir: String },
+}
+
+impl LanguageServerError {
+    /// Returns true if the error was caused by the filesystem, rather than the project, so that
+    /// trying again may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            LanguageServerError::BuildPlanFailed(err)
+            | LanguageServerError::FailedToCompile(err) => {
+                err.chain().any(|cause| cause.is::<std::io::Error>())
+            }
+            LanguageServerError::DocumentError(
+                DocumentError::UnableToCreateFile { .. }
+                | DocumentError::UnableToWriteFile { .. }
+                | DocumentError::UnableToRemoveFile { .. },
+            )
+            | LanguageServerError::DirectoryError(DirectoryError::CopyContentsFailed) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<LanguageServerError> for jsonrpc::Error {
+    fn from(err: LanguageServerError) -> Self {
+        match err {
+            LanguageServerError::SessionInitializing { .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(SESSION_INITIALIZING_ERROR_CODE),
+                message: err.to_string().into(),
+                data: Some(json!({ "retryAfterMs": SESSION_INITIALIZING_RETRY_AFTER_MS })),
+            },
+            LanguageServerError::SessionInitFailed { retry_after_ms, .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(SESSION_INIT_FAILED_ERROR_CODE),
+                message: err.to_string().into(),
+                data: Some(json!({ "retryAfterMs": retry_after_ms })),
+            },
+            LanguageServerError::SessionShutDown { .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(SESSION_SHUT_DOWN_ERROR_CODE),
+                message: err.to_string().into(),
+                data: Some(json!({ "retryAfterMs": SESSION_INITIALIZING_RETRY_AFTER_MS })),
+            },
+            LanguageServerError::KeywordNotFound { .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(KEYWORD_NOT_FOUND_ERROR_CODE),
+                message: err.to_string().into(),
+                data: None,
+            },
+            LanguageServerError::SymbolNotFound { .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(SYMBOL_NOT_FOUND_ERROR_CODE),
+                message: err.to_string().into(),
+                data: None,
+            },
+            LanguageServerError::AmbiguousSymbol { .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(AMBIGUOUS_SYMBOL_ERROR_CODE),
+                message: err.to_string().into(),
+                data: None,
+            },
+            LanguageServerError::SessionNotFound { .. } => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::ServerError(SESSION_NOT_FOUND_ERROR_CODE),
+                message: err.to_string().into(),
+                data: None,
+            },
+            LanguageServerError::DocumentError(DocumentError::ManifestFileNotFound { .. }) => {
+                jsonrpc::Error {
+                    code: jsonrpc::ErrorCode::ServerError(MANIFEST_NOT_FOUND_ERROR_CODE),
+                    message: err.to_string().into(),
+                    data: None,
+                }
+            }
+            _ => jsonrpc::Error {
+                code: jsonrpc::ErrorCode::InternalError,
+                message: err.to_string().into(),
+                data: None,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Error, PartialEq, Eq)]
@@ -32,6 +180,10 @@ pub enum DocumentError {
     DocumentNotFound { path: String },
     #[error("Missing Forc.toml in {:?}", dir)]
     ManifestFileNotFound { dir: String },
+    #[error("Unable to parse the manifest at {:?}: {}", path, err)]
+    ManifestParseFailed { path: String, err: String },
+    #[error("The workspace at {:?} has no member package that can be loaded", dir)]
+    WorkspaceWithoutMembers { dir: String },
     #[error("Cannot get member manifest files for the manifest at {:?}", dir)]
     MemberManifestsFailed { dir: String },
     #[error("Cannot get lock file path for the manifest at {:?}", dir)]
@@ -46,6 +198,25 @@ pub enum DocumentError {
     UnableToRemoveFile { path: String, err: String },
 }
 
+impl DocumentError {
+    /// Converts the error of loading the manifest of the project that `dir` is in,
+    /// telling a missing `Forc.toml` apart from one that can't be parsed.
+    pub(crate) fn manifest_error(dir: &Path, err: &anyhow::Error) -> Self {
+        match sway_utils::find_parent_manifest_dir(dir) {
+            Some(manifest_dir) => DocumentError::ManifestParseFailed {
+                path: manifest_dir
+                    .join(MANIFEST_FILE_NAME)
+                    .to_string_lossy()
+                    .to_string(),
+                err: err.to_string(),
+            },
+            None => DocumentError::ManifestFileNotFound {
+                dir: dir.to_string_lossy().to_string(),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum DirectoryError {
     #[error("Can't find temporary directory")]
@@ -58,6 +229,8 @@ pub enum DirectoryError {
     LspLocksDirFailed,
     #[error("Failed to create temp directory")]
     TempDirFailed,
+    #[error("The temp directory {:?} isn't writable", dir)]
+    TempDirNotWritable { dir: String },
     #[error("Failed to canonicalize path")]
     CanonicalizeFailed,
     #[error("Failed to copy workspace contents to temp directory")]
@@ -90,4 +263,6 @@ pub enum RenameError {
     FileAlreadyExists { path: String },
     #[error("The module {:?}: cannot be renamed", path)]
     UnableToRenameModule { path: String },
+    #[error("{:?} is already declared in the same scope", name)]
+    NameAlreadyDeclared { name: String },
 }
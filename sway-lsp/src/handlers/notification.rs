@@ -3,38 +3,81 @@
 
 use crate::{
     core::{document, session::Session},
-    error::LanguageServerError,
-    server_state::{CompilationContext, ServerState, TaskMessage},
+    error::{DocumentError, LanguageServerError},
+    lsp_ext,
+    server_state::{self, CompilationContext, CompilationPriority, ServerState},
 };
 use lsp_types::{
-    DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidOpenTextDocumentParams,
-    DidSaveTextDocumentParams, FileChangeType, Url,
+    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, FileChangeType, Url,
 };
-use std::sync::{atomic::Ordering, Arc};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use sway_utils::constants::MANIFEST_FILE_NAME;
 
 pub async fn handle_did_open_text_document(
     state: &ServerState,
     params: DidOpenTextDocumentParams,
 ) -> Result<(), LanguageServerError> {
-    let (uri, session) = state
+    if !state.config.read().is_sway_file(&params.text_document.uri) {
+        return Ok(());
+    }
+    state
+        .sessions
+        .wait_for_init(&params.text_document.uri)
+        .await;
+    let (uri, session) = match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
-        .await?;
+        .await
+    {
+        Ok(result) => result,
+        Err(err) => {
+            match &err {
+                LanguageServerError::DocumentError(DocumentError::ManifestFileNotFound {
+                    ..
+                }) => state.warn_missing_manifest(&params.text_document.uri).await,
+                LanguageServerError::OutsideWorkspace { .. } => {
+                    state
+                        .warn_outside_workspace(&params.text_document.uri)
+                        .await
+                }
+                LanguageServerError::DocumentError(DocumentError::ManifestParseFailed {
+                    path,
+                    err,
+                }) => state.publish_manifest_error(Path::new(path), err).await,
+                _ => {}
+            }
+            return Err(err);
+        }
+    };
     session.handle_open_file(&uri).await;
     // If the token map is empty, then we need to parse the project.
     // Otherwise, don't recompile the project when a new file in the project is opened
     // as the workspace is already compiled.
-    if session.token_map().is_empty() {
-        let _ = state
-            .cb_tx
-            .send(TaskMessage::CompilationContext(CompilationContext {
+    // With `compileOnOpen` disabled, it's compiled once it's first needed instead.
+    if session.token_map().is_empty() && state.config.read().compile_on_open {
+        if state.config.read().synchronous_first_compile {
+            // Waits for a compilation of this very project rather than for the compilation thread to be idle,
+            // so that it's requested again if it's dropped from the queue or cancelled in the meantime.
+            if let Err(err) = state.compile_now(&params.text_document.uri).await {
+                tracing::error!("The first compilation of {} failed: {}", uri, err);
+            }
+        } else {
+            state.enqueue_compilation(CompilationContext {
                 session: Some(session.clone()),
                 uri: Some(uri.clone()),
                 version: None,
-            }));
-        state.is_compiling.store(true, Ordering::SeqCst);
+                priority: CompilationPriority::Interactive,
+            });
 
-        state.wait_for_parsing().await;
+            state.wait_for_parsing().await;
+        }
         state
             .publish_diagnostics(uri, params.text_document.uri, session)
             .await;
@@ -42,53 +85,87 @@ pub async fn handle_did_open_text_document(
     Ok(())
 }
 
-fn send_new_compilation_request(
+pub(crate) fn send_new_compilation_request(
     state: &ServerState,
     session: Arc<Session>,
     uri: &Url,
     version: Option<i32>,
 ) {
-    if state.is_compiling.load(Ordering::SeqCst) {
-        // If we are already compiling, then we need to retrigger compilation
-        state.retrigger_compilation.store(true, Ordering::SeqCst);
-    }
+    // If this project is already compiling an older version, then we need to retrigger compilation
+    session.compilation_cancellation.request(uri, version);
 
-    // Check if the channel is full. If it is, we want to ensure that the compilation
-    // thread receives only the most recent value.
-    if state.cb_tx.is_full() {
-        while let Ok(TaskMessage::CompilationContext(_)) = state.cb_rx.try_recv() {
-            // Loop will continue to remove `CompilationContext` messages
-            // until the channel has no more of them.
-        }
-    }
-
-    let _ = state
-        .cb_tx
-        .send(TaskMessage::CompilationContext(CompilationContext {
-            session: Some(session.clone()),
-            uri: Some(uri.clone()),
-            version,
-        }));
+    state.enqueue_compilation(CompilationContext {
+        session: Some(session.clone()),
+        uri: Some(uri.clone()),
+        version,
+        priority: CompilationPriority::Interactive,
+    });
 }
 
 pub async fn handle_did_change_text_document(
     state: &ServerState,
     params: DidChangeTextDocumentParams,
 ) -> Result<(), LanguageServerError> {
-    document::mark_file_as_dirty(&params.text_document.uri).await?;
-    let (uri, session) = state
+    if !state.config.read().is_sway_file(&params.text_document.uri) {
+        return Ok(());
+    }
+    state
+        .sessions
+        .wait_for_init(&params.text_document.uri)
+        .await;
+    let (uri, session) = match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
-        .await?;
+        .await
+    {
+        Ok(result) => result,
+        Err(err) => {
+            // Keep the edit, so that it isn't lost once the session is initialized.
+            state
+                .sessions
+                .buffer_changes(&params.text_document.uri, params.content_changes)
+                .await;
+            return Err(err);
+        }
+    };
+    // Dependencies are read-only, so edits to them are neither written nor compiled.
+    if session.sync.is_path_in_dependency(&uri) {
+        return Ok(());
+    }
+    document::mark_file_as_dirty(&params.text_document.uri).await?;
+    // The file may not have been opened in this session, e.g. if it was initialized again since.
+    session.handle_open_file(&uri).await;
+    *state.active_file.write() = Some(params.text_document.uri.clone());
+    let fast_path = session.config.read().diagnostic.fast_path;
+    if fast_path {
+        session.edit_burst.lock().record_edit(Instant::now());
+    }
     session
         .write_changes_to_file(&uri, params.content_changes)
         .await?;
+    // Without `analyzeUnsaved`, the edit doesn't change what's compiled until it's saved.
+    if !session.config.read().analyze_unsaved {
+        return Ok(());
+    }
+    // The first compilation of a project that wasn't compiled on open publishes its diagnostics,
+    // as none have been published for it yet.
+    let deferred =
+        !state.config.read().compile_on_open && session.compiled_program.read().typed.is_none();
     send_new_compilation_request(
         state,
         session.clone(),
         &uri,
         Some(params.text_document.version),
     );
+    if fast_path {
+        state.schedule_diagnostics_sweep(session.clone());
+    }
+    if deferred {
+        state.wait_for_parsing().await;
+        state
+            .publish_diagnostics(uri, params.text_document.uri, session)
+            .await;
+    }
     Ok(())
 }
 
@@ -96,12 +173,35 @@ pub(crate) async fn handle_did_save_text_document(
     state: &ServerState,
     params: DidSaveTextDocumentParams,
 ) -> Result<(), LanguageServerError> {
-    document::remove_dirty_flag(&params.text_document.uri).await?;
-    let (uri, session) = state
+    if !state.config.read().is_sway_file(&params.text_document.uri) {
+        return Ok(());
+    }
+    state
+        .sessions
+        .wait_for_init(&params.text_document.uri)
+        .await;
+    let (uri, session) = match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
-        .await?;
+        .await
+    {
+        Ok(result) => result,
+        Err(err) => {
+            // The kept edits are on disk now, which is where the session will be initialized from.
+            state
+                .sessions
+                .discard_pending_document(&params.text_document.uri);
+            return Err(err);
+        }
+    };
+    if session.sync.is_path_in_dependency(&uri) {
+        return Ok(());
+    }
+    document::remove_dirty_flag(&params.text_document.uri).await?;
+    session.unsaved_documents.remove(uri.path());
     session.sync.resync()?;
+    // Resyncing overwrites the other files with their contents on disk, so restore their unsaved changes.
+    session.write_unsaved_documents(&session.engines.read())?;
     send_new_compilation_request(state, session.clone(), &uri, None);
     state.wait_for_parsing().await;
     state
@@ -110,19 +210,207 @@ pub(crate) async fn handle_did_save_text_document(
     Ok(())
 }
 
+/// How long to wait for more watched file events before handling them, so that a burst of
+/// changes, e.g. from a `git pull`, only recompiles each project once.
+const WATCHED_FILES_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Brings the sessions of the projects whose files changed outside the editor up to date.
+///
+/// Projects whose `Forc.toml` changed get a new session, so that their dependencies are resolved again.
+/// Otherwise, the changed files are synced to the temp workspace and the project is recompiled.
+/// With `recompileDependents` enabled, the projects that depend on a changed project through a path
+/// dependency are recompiled as well. Only projects that already have a session are affected.
 pub(crate) async fn handle_did_change_watched_files(
     state: &ServerState,
     params: DidChangeWatchedFilesParams,
 ) -> Result<(), LanguageServerError> {
+    let mut generations = HashMap::new();
+    let recompile_dependents = state.config.read().recompile_dependents;
     for event in params.changes {
-        let (uri, session) = state
+        let Ok(path) = event.uri.to_file_path() else {
+            continue;
+        };
+        let is_manifest = path.file_name() == Some(OsStr::new(MANIFEST_FILE_NAME));
+        if !is_manifest && !state.config.read().is_sway_file(&event.uri) {
+            continue;
+        }
+        if is_manifest && state.manifest_errors.contains(&path) {
+            state.revalidate_manifest(&path).await;
+        }
+        // The manifest may have been deleted, so its dir can't be found by searching for it.
+        let manifest_dir = if is_manifest {
+            path.parent().map(Path::to_path_buf)
+        } else {
+            server_state::manifest_dir_from_uri(&event.uri).ok()
+        };
+        let Some(manifest_dir) = manifest_dir else {
+            continue;
+        };
+        if recompile_dependents {
+            for dependent in dependent_manifest_dirs(state, &event.uri) {
+                let generation = add_pending_file_change(state, dependent.clone(), false, None);
+                generations.insert(dependent, generation);
+            }
+        }
+        let Some(session) = state
             .sessions
-            .uri_and_session_from_workspace(&event.uri)
-            .await?;
-        if let FileChangeType::DELETED = event.typ {
+            .get(&manifest_dir)
+            .map(|item| item.value().clone())
+        else {
+            continue;
+        };
+        if !is_manifest && event.typ == FileChangeType::DELETED {
             document::remove_dirty_flag(&event.uri).await?;
-            let _ = session.remove_document(&uri);
+            if let Ok(uri) = session.sync.workspace_to_temp_url(&event.uri) {
+                let _ = session.remove_document(&uri);
+            }
+        }
+        let file = (!is_manifest && event.typ != FileChangeType::DELETED).then_some(event.uri);
+        let generation = add_pending_file_change(state, manifest_dir.clone(), is_manifest, file);
+        generations.insert(manifest_dir, generation);
+    }
+    handle_pending_file_changes(state, generations).await
+}
+
+/// Recompiles the projects that depend on the file or directory at `params.uri` through a path dependency,
+/// e.g. after a dependency was rebuilt or regenerated by a tool that the editor doesn't watch.
+pub(crate) async fn handle_dependency_changed(
+    state: &ServerState,
+    params: lsp_ext::DependencyChangedParams,
+) -> Result<(), LanguageServerError> {
+    let mut generations = HashMap::new();
+    for dependent in dependent_manifest_dirs(state, &params.uri) {
+        let generation = add_pending_file_change(state, dependent.clone(), false, None);
+        generations.insert(dependent, generation);
+    }
+    handle_pending_file_changes(state, generations).await
+}
+
+/// Returns the manifest dirs of the sessions that have the file or directory at `uri` in one of their dependencies.
+fn dependent_manifest_dirs(state: &ServerState, uri: &Url) -> Vec<PathBuf> {
+    state
+        .sessions
+        .iter()
+        .filter(|item| item.value().sync.is_path_in_dependency(uri))
+        .map(|item| item.key().clone())
+        .collect()
+}
+
+/// Records a change to the project in `manifest_dir` and returns the generation of its pending changes.
+fn add_pending_file_change(
+    state: &ServerState,
+    manifest_dir: PathBuf,
+    is_manifest: bool,
+    file: Option<Url>,
+) -> u64 {
+    let mut pending = state.pending_file_changes.entry(manifest_dir).or_default();
+    pending.generation += 1;
+    pending.manifest_changed |= is_manifest;
+    pending.files.extend(file);
+    pending.generation
+}
+
+/// Waits for bursts of changes to end, then syncs and recompiles each changed project, one at a time.
+async fn handle_pending_file_changes(
+    state: &ServerState,
+    generations: HashMap<PathBuf, u64>,
+) -> Result<(), LanguageServerError> {
+    if generations.is_empty() {
+        return Ok(());
+    }
+
+    tokio::time::sleep(WATCHED_FILES_DEBOUNCE).await;
+    for (manifest_dir, generation) in generations {
+        // A later notification changed the same project, so it handles the changes instead.
+        let Some((_, pending)) = state
+            .pending_file_changes
+            .remove_if(&manifest_dir, |_, pending| pending.generation == generation)
+        else {
+            continue;
+        };
+        // Don't swap out files or sessions that are being compiled.
+        state.wait_for_parsing().await;
+        let session = if pending.manifest_changed {
+            match state.sessions.reinit(&manifest_dir).await? {
+                Some(session) => session,
+                None => continue,
+            }
+        } else {
+            let Some(session) = state
+                .sessions
+                .get(&manifest_dir)
+                .map(|item| item.value().clone())
+            else {
+                continue;
+            };
+            session.sync.resync()?;
+            // A dependency's manifest may have changed, which can change where its own dependencies are.
+            session.sync.resolve_dependency_dirs();
+            session.write_unsaved_documents(&session.engines.read())?;
+            // Reload the documents that aren't edited in the editor from their new contents.
+            for uri in pending.files {
+                let Ok(uri) = session.sync.workspace_to_temp_url(&uri) else {
+                    continue;
+                };
+                if !session.unsaved_documents.contains(uri.path()) {
+                    let _ = session.remove_document(&uri);
+                    session.handle_open_file(&uri).await;
+                }
+            }
+            session
+        };
+        let Ok(temp_dir) = session.sync.temp_dir() else {
+            continue;
+        };
+        let Ok(uri) = Url::from_file_path(temp_dir) else {
+            continue;
+        };
+        // Register for the notification before sending the request so that we can't miss it.
+        let finished = session.finished_compilation.notified();
+        send_new_compilation_request(state, session.clone(), &uri, None);
+        // A newer request for another project can replace ours in the channel,
+        // so also stop waiting once the compilation thread is idle.
+        tokio::select! {
+            _ = finished => {},
+            _ = state.wait_for_parsing() => {},
         }
+        state.publish_session_diagnostics(None, session).await;
     }
     Ok(())
 }
+
+/// Updates the global config and re-resolves the config of each session.
+///
+/// Sessions whose build config changed are recompiled one at a time, as the compilation thread
+/// only keeps the most recent requests, and their diagnostics are published once they're done.
+pub(crate) async fn handle_did_change_configuration(
+    state: &ServerState,
+    params: DidChangeConfigurationParams,
+) {
+    // Clients that don't push their settings send `null`. In that case we keep the
+    // current global config, but still re-resolve in case a project's config file changed.
+    if !params.settings.is_null() {
+        match serde_json::from_value(params.settings) {
+            Ok(config) => *state.config.write() = config,
+            Err(err) => tracing::error!("Unable to parse the updated config: {}", err),
+        }
+    }
+    for session in state.sessions.resolve_configs() {
+        let Ok(temp_dir) = session.sync.temp_dir() else {
+            continue;
+        };
+        let Ok(uri) = Url::from_file_path(temp_dir) else {
+            continue;
+        };
+        // Register for the notification before sending the request so that we can't miss it.
+        let finished = session.finished_compilation.notified();
+        send_new_compilation_request(state, session.clone(), &uri, None);
+        // A newer request for another project can replace ours in the channel,
+        // so also stop waiting once the compilation thread is idle.
+        tokio::select! {
+            _ = finished => {},
+            _ = state.wait_for_parsing() => {},
+        }
+        state.publish_session_diagnostics(None, session).await;
+    }
+}
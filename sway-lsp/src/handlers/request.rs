@@ -2,24 +2,46 @@
 //! Protocol. This module specifically handles requests.
 
 use crate::{
-    capabilities, core::session::build_plan, lsp_ext, server_state::ServerState, utils::debug,
+    capabilities::{
+        self,
+        completion::CompletionContext,
+        diagnostic::{DiagnosticsDiff, DiagnosticsSnapshot},
+    },
+    core::session::{self, build_plan, ParseResult, PhaseDurations, Session},
+    error::{LanguageServerError, RenameError},
+    handlers::notification::send_new_compilation_request,
+    lsp_ext,
+    server_state::{self, ServerState},
+    utils::{
+        client_capabilities::ClientCapabilities, debug, dependency_graph, forc_build,
+        position_encoding::PositionEncoding, position_kind::PositionKind,
+    },
 };
-use forc_tracing::{init_tracing_subscriber, TracingSubscriberOptions, TracingWriterMode};
+use forc_pkg::PackageManifestFile;
 use lsp_types::{
-    CodeLens, CompletionResponse, DocumentFormattingParams, DocumentSymbolResponse,
-    InitializeResult, InlayHint, InlayHintParams, PrepareRenameResponse, RenameParams,
-    SemanticTokensParams, SemanticTokensRangeParams, SemanticTokensRangeResult,
-    SemanticTokensResult, TextDocumentIdentifier, Url, WorkspaceEdit,
+    CodeLens, CompletionResponse, DiagnosticOptions, DiagnosticServerCapabilities,
+    DiagnosticSeverity, DocumentDiagnosticParams, DocumentDiagnosticReportResult,
+    DocumentFormattingParams, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    DocumentSymbolResponse, InitializeResult, InlayHint, InlayHintParams, MessageType,
+    PrepareRenameResponse, RenameParams, SemanticTokens, SemanticTokensDelta,
+    SemanticTokensDeltaParams, SemanticTokensFullDeltaResult, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult,
+    TextDocumentIdentifier, Url, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd,
+    WorkDoneProgressReport, WorkspaceDiagnosticParams, WorkspaceDiagnosticReport,
+    WorkspaceDiagnosticReportPartialResult, WorkspaceDiagnosticReportResult, WorkspaceEdit,
 };
 use std::{
+    collections::{HashMap, HashSet},
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Duration,
 };
+use sway_core::Engines;
 use sway_types::{Ident, Spanned};
-use sway_utils::PerformanceData;
-use tower_lsp::jsonrpc::Result;
-use tracing::metadata::LevelFilter;
+use sysinfo::{System, SystemExt};
+use tower_lsp::jsonrpc::{self, Result};
 
 pub fn handle_initialize(
     state: &ServerState,
@@ -31,41 +53,113 @@ pub fn handle_initialize(
             .ok()
             .unwrap_or_default();
     }
+    let client_capabilities = ClientCapabilities::new(&params.capabilities);
+    // Clients that don't support several workspace folders send their root folder instead.
+    #[allow(deprecated)]
+    let workspace_folders = match &params.workspace_folders {
+        Some(folders) => folders.iter().map(|folder| folder.uri.clone()).collect(),
+        None => params.root_uri.iter().cloned().collect(),
+    };
+    *state.workspace_folders.write() = workspace_folders;
+    let pull_diagnostics = client_capabilities.pull_diagnostics;
+    *state.client_capabilities.write() = client_capabilities;
+    // A client that doesn't list the encodings it supports only supports UTF-16, which is the default.
+    let position_encoding = params
+        .capabilities
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_ref())
+        .map(|position_encodings| {
+            PositionEncoding::negotiate(state.config.read().position_encoding, position_encodings)
+        });
+    *state.position_encoding.write() = position_encoding.unwrap_or_default();
     // Initalizing tracing library based on the user's config
+    state.init_tracing();
+    tracing::info!("Initializing the Sway Language Server");
     let config = state.config.read();
-    if config.logging.level != LevelFilter::OFF {
-        let tracing_options = TracingSubscriberOptions {
-            log_level: Some(config.logging.level),
-            writer_mode: Some(TracingWriterMode::Stderr),
-            ..Default::default()
-        };
-        init_tracing_subscriber(tracing_options);
+    let mut capabilities = crate::server_capabilities_with_features(&config.features);
+    if !config.format.enabled {
+        capabilities.document_formatting_provider = None;
+        capabilities.document_range_formatting_provider = None;
+    } else if config.format.on_type && config.features.formatting {
+        capabilities.document_on_type_formatting_provider =
+            Some(crate::capabilities::on_type_formatting::on_type_formatting_options());
     }
-    tracing::info!("Initializing the Sway Language Server");
+    // Clients that can't pull diagnostics have them pushed after each compilation instead.
+    if pull_diagnostics {
+        capabilities.diagnostic_provider =
+            Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                inter_file_dependencies: true,
+                workspace_diagnostics: true,
+                ..Default::default()
+            }));
+    }
+    capabilities.position_encoding = position_encoding.map(PositionEncoding::kind);
     Ok(InitializeResult {
         server_info: None,
-        capabilities: crate::server_capabilities(),
+        capabilities,
         ..InitializeResult::default()
     })
 }
 
+/// Converts an error that occurred while handling a request into a response.
+///
+/// Errors the client can act on, such as a project that is still initializing, that recently
+/// failed to initialize or whose session was shut down during the request, are returned as JSON-RPC errors. All other errors are logged and an
+/// empty response is returned.
+fn report_error<T>(err: LanguageServerError) -> Result<Option<T>> {
+    if let LanguageServerError::SessionInitializing { .. }
+    | LanguageServerError::SessionInitFailed { .. }
+    | LanguageServerError::SessionShutDown { .. } = err
+    {
+        return Err(err.into());
+    }
+    tracing::error!("{}", err.to_string());
+    Ok(None)
+}
+
 pub async fn handle_document_symbol(
     state: &ServerState,
     params: lsp_types::DocumentSymbolParams,
 ) -> Result<Option<lsp_types::DocumentSymbolResponse>> {
-    let _ = state.wait_for_parsing().await;
+    if !state.config.read().features.document_symbol {
+        return Ok(None);
+    }
+    // The outline is parsed from the document, so only the flat symbols need the compilation.
+    let hierarchical = state
+        .client_capabilities
+        .read()
+        .hierarchical_document_symbols;
+    if !hierarchical {
+        state.wait_for_parsing_until_deadline().await;
+    }
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
     {
-        Ok((uri, session)) => Ok(session
-            .symbol_information(&uri)
-            .map(DocumentSymbolResponse::Flat)),
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+        Ok((uri, session)) => {
+            let include_underscore_items = session.config.read().symbols.include_underscore_items;
+            if hierarchical {
+                let mut symbols = capabilities::document_symbol::document_symbols(session, &uri);
+                if !include_underscore_items {
+                    symbols
+                        .iter_mut()
+                        .for_each(capabilities::document_symbol::remove_underscore_symbols);
+                }
+                return Ok(symbols.map(DocumentSymbolResponse::Nested));
+            }
+            let mut symbols = session.symbol_information(&uri);
+            if !include_underscore_items {
+                symbols.iter_mut().for_each(|symbols| {
+                    symbols.retain(|symbol| {
+                        !capabilities::document_symbol::is_underscore_name(&symbol.name)
+                    })
+                });
+            }
+            Ok(symbols.map(DocumentSymbolResponse::Flat))
         }
+        Err(err) => report_error(err),
     }
 }
 
@@ -73,6 +167,14 @@ pub async fn handle_goto_definition(
     state: &ServerState,
     params: lsp_types::GotoDefinitionParams,
 ) -> Result<Option<lsp_types::GotoDefinitionResponse>> {
+    if !state.config.read().features.definition {
+        return Ok(None);
+    }
+    state
+        .compile_deferred(&params.text_document_position_params.text_document.uri)
+        .await;
+    // Wait for any pending compilation so that the ranges in other files reflect their unsaved changes.
+    state.wait_for_parsing_until_deadline().await;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
@@ -82,10 +184,58 @@ pub async fn handle_goto_definition(
             let position = params.text_document_position_params.position;
             Ok(session.token_definition_response(uri, position))
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_goto_type_definition(
+    state: &ServerState,
+    params: lsp_types::request::GotoTypeDefinitionParams,
+) -> Result<Option<lsp_types::request::GotoTypeDefinitionResponse>> {
+    if !state.config.read().features.type_definition {
+        return Ok(None);
+    }
+    state
+        .compile_deferred(&params.text_document_position_params.text_document.uri)
+        .await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let position = params.text_document_position_params.position;
+            Ok(session.type_definition_response(uri, position))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_goto_implementation(
+    state: &ServerState,
+    params: lsp_types::request::GotoImplementationParams,
+) -> Result<Option<lsp_types::request::GotoImplementationResponse>> {
+    if !state.config.read().features.implementation {
+        return Ok(None);
+    }
+    state
+        .compile_deferred(&params.text_document_position_params.text_document.uri)
+        .await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let position = params.text_document_position_params.position;
+            Ok(
+                capabilities::implementation::implementations(&session, &uri, position)
+                    .map(lsp_types::request::GotoImplementationResponse::Array),
+            )
         }
+        Err(err) => report_error(err),
     }
 }
 
@@ -93,31 +243,76 @@ pub async fn handle_completion(
     state: &ServerState,
     params: lsp_types::CompletionParams,
 ) -> Result<Option<lsp_types::CompletionResponse>> {
-    let trigger_char = params
-        .context
-        .as_ref()
-        .and_then(|ctx| ctx.trigger_character.as_deref())
-        .unwrap_or("");
+    if !state.config.read().features.completion {
+        return Ok(None);
+    }
     let position = params.text_document_position.position;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document_position.text_document.uri)
         .await
     {
-        Ok((uri, session)) => Ok(session
-            .completion_items(&uri, position, trigger_char)
-            .map(CompletionResponse::Array)),
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+        Ok((uri, session)) => {
+            // Names are of no use in comments and strings.
+            if session.position_kind(&uri, position) != Some(PositionKind::Code) {
+                return Ok(None);
+            }
+            let Some((context, range)) = session.completion_context(&uri, position) else {
+                return Ok(None);
+            };
+            let snippets = state.client_capabilities.read().snippet_support
+                && session.config.read().completion.snippets;
+            let mut items = match context {
+                // The members depend on the type of the receiver, so wait for the program to be typed.
+                CompletionContext::Member(receiver) => {
+                    state.wait_for_parsing_until_deadline().await;
+                    session.member_completion_items(&uri, receiver, range, snippets)
+                }
+                CompletionContext::Path(qualifier) => {
+                    state.wait_for_parsing_until_deadline().await;
+                    session.path_completion_items(&uri, qualifier, range, snippets)
+                }
+                CompletionContext::Scope => Some(session.scope_completion_items(
+                    &uri,
+                    position,
+                    range,
+                    &state.keyword_docs,
+                    snippets,
+                )),
+            };
+            if !session.config.read().completion.include_underscore_items {
+                items.iter_mut().for_each(|items| {
+                    items.retain(|item| {
+                        !capabilities::document_symbol::is_underscore_name(&item.label)
+                    })
+                });
+            }
+            Ok(items.map(CompletionResponse::Array))
         }
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_completion_resolve(
+    state: &ServerState,
+    item: lsp_types::CompletionItem,
+) -> Result<lsp_types::CompletionItem> {
+    if !state.config.read().features.completion {
+        return Ok(item);
     }
+    Ok(capabilities::completion::resolve_completion_item(
+        &state.keyword_docs,
+        item,
+    ))
 }
 
 pub async fn handle_hover(
     state: &ServerState,
     params: lsp_types::HoverParams,
 ) -> Result<Option<lsp_types::Hover>> {
+    if !state.config.read().features.hover {
+        return Ok(None);
+    }
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
@@ -125,6 +320,16 @@ pub async fn handle_hover(
     {
         Ok((uri, session)) => {
             let position = params.text_document_position_params.position;
+            // Keywords are documented without the types of the program, so they don't need to wait for the compilation.
+            if let Some(hover) =
+                capabilities::hover::keyword_hover(&session, &state.keyword_docs, &uri, position)
+            {
+                return Ok(Some(hover));
+            }
+            state
+                .compile_deferred(&params.text_document_position_params.text_document.uri)
+                .await;
+            state.wait_for_parsing_until_deadline().await;
             Ok(capabilities::hover::hover_data(
                 session,
                 &state.keyword_docs,
@@ -132,10 +337,7 @@ pub async fn handle_hover(
                 position,
             ))
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Err(err) => report_error(err),
     }
 }
 
@@ -143,6 +345,9 @@ pub async fn handle_prepare_rename(
     state: &ServerState,
     params: lsp_types::TextDocumentPositionParams,
 ) -> Result<Option<PrepareRenameResponse>> {
+    if !state.config.read().features.rename {
+        return Ok(None);
+    }
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
@@ -157,10 +362,7 @@ pub async fn handle_prepare_rename(
                 }
             }
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Err(err) => report_error(err),
     }
 }
 
@@ -168,6 +370,14 @@ pub async fn handle_rename(
     state: &ServerState,
     params: RenameParams,
 ) -> Result<Option<WorkspaceEdit>> {
+    if !state.config.read().features.rename {
+        return Ok(None);
+    }
+    // Rename the uses from the latest changes too, so that none of them are left behind.
+    let compilation = state.compile_now(&params.text_document_position.text_document.uri);
+    if let Some(Err(err)) = state.with_request_deadline(compilation).await {
+        return report_error(err);
+    }
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document_position.text_document.uri)
@@ -178,16 +388,19 @@ pub async fn handle_rename(
             let position = params.text_document_position.position;
             match capabilities::rename::rename(session, new_name, uri, position) {
                 Ok(res) => Ok(Some(res)),
+                // Let the user know why the rename was rejected, as nothing would be renamed otherwise.
+                Err(
+                    err @ LanguageServerError::RenameError(RenameError::NameAlreadyDeclared {
+                        ..
+                    }),
+                ) => Err(jsonrpc::Error::invalid_params(err.to_string())),
                 Err(err) => {
                     tracing::error!("{}", err.to_string());
                     Ok(None)
                 }
             }
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Err(err) => report_error(err),
     }
 }
 
@@ -195,7 +408,13 @@ pub async fn handle_document_highlight(
     state: &ServerState,
     params: lsp_types::DocumentHighlightParams,
 ) -> Result<Option<Vec<lsp_types::DocumentHighlight>>> {
-    let _ = state.wait_for_parsing().await;
+    if !state.config.read().features.document_highlight {
+        return Ok(None);
+    }
+    state
+        .compile_deferred(&params.text_document_position_params.text_document.uri)
+        .await;
+    state.wait_for_parsing_until_deadline().await;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
@@ -207,10 +426,109 @@ pub async fn handle_document_highlight(
                 session, uri, position,
             ))
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_references(
+    state: &ServerState,
+    params: lsp_types::ReferenceParams,
+) -> Result<Option<Vec<lsp_types::Location>>> {
+    if !state.config.read().features.references {
+        return Ok(None);
+    }
+    // Find the references in the compilation of the latest changes, rather than in whatever was compiled last.
+    let compilation = state.compile_now(&params.text_document_position.text_document.uri);
+    if let Some(Err(err)) = state.with_request_deadline(compilation).await {
+        return report_error(err);
+    }
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document_position.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let position = params.text_document_position.position;
+            Ok(capabilities::references::get_references(
+                session,
+                uri,
+                position,
+                params.context.include_declaration,
+            ))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_prepare_call_hierarchy(
+    state: &ServerState,
+    params: lsp_types::CallHierarchyPrepareParams,
+) -> Result<Option<Vec<lsp_types::CallHierarchyItem>>> {
+    if !state.config.read().features.call_hierarchy {
+        return Ok(None);
+    }
+    state
+        .compile_deferred(&params.text_document_position_params.text_document.uri)
+        .await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let position = params.text_document_position_params.position;
+            Ok(capabilities::call_hierarchy::prepare_call_hierarchy(
+                session, &uri, position,
+            ))
         }
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_incoming_calls(
+    state: &ServerState,
+    params: lsp_types::CallHierarchyIncomingCallsParams,
+) -> Result<Option<Vec<lsp_types::CallHierarchyIncomingCall>>> {
+    if !state.config.read().features.call_hierarchy {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.item.uri).await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.item.uri)
+        .await
+    {
+        Ok((uri, session)) => Ok(capabilities::call_hierarchy::incoming_calls(
+            session,
+            &uri,
+            &params.item,
+        )),
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_outgoing_calls(
+    state: &ServerState,
+    params: lsp_types::CallHierarchyOutgoingCallsParams,
+) -> Result<Option<Vec<lsp_types::CallHierarchyOutgoingCall>>> {
+    if !state.config.read().features.call_hierarchy {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.item.uri).await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.item.uri)
+        .await
+    {
+        Ok((uri, session)) => Ok(capabilities::call_hierarchy::outgoing_calls(
+            session,
+            &uri,
+            &params.item,
+        )),
+        Err(err) => report_error(err),
     }
 }
 
@@ -218,38 +536,119 @@ pub async fn handle_formatting(
     state: &ServerState,
     params: DocumentFormattingParams,
 ) -> Result<Option<Vec<lsp_types::TextEdit>>> {
-    let _ = state.wait_for_parsing().await;
-    state
+    {
+        let config = state.config.read();
+        if !config.features.formatting || !config.format.enabled {
+            return Ok(None);
+        }
+    }
+    state.wait_for_parsing_until_deadline().await;
+    match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
-        .and_then(|(uri, session)| session.format_text(&uri).map(Some))
-        .or_else(|err| {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        })
+    {
+        Ok((uri, session)) => match session.format_text(&uri, &params.options) {
+            Ok(edits) => Ok(Some(edits)),
+            // Leave the document as it is if it can't be formatted, e.g. because it doesn't parse.
+            Err(err) => {
+                tracing::error!("Unable to format {}: {}", params.text_document.uri, err);
+                Ok(Some(vec![]))
+            }
+        },
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_range_formatting(
+    state: &ServerState,
+    params: DocumentRangeFormattingParams,
+) -> Result<Option<Vec<lsp_types::TextEdit>>> {
+    {
+        let config = state.config.read();
+        if !config.features.formatting || !config.format.enabled {
+            return Ok(None);
+        }
+    }
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => match session.format_range(&uri, params.range, &params.options) {
+            Ok(edits) => Ok(Some(edits)),
+            // Leave the document as it is if it can't be formatted, e.g. because it doesn't parse.
+            Err(err) => {
+                tracing::error!("Unable to format {}: {}", params.text_document.uri, err);
+                Ok(Some(vec![]))
+            }
+        },
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_on_type_formatting(
+    state: &ServerState,
+    params: DocumentOnTypeFormattingParams,
+) -> Result<Option<Vec<lsp_types::TextEdit>>> {
+    {
+        let config = state.config.read();
+        if !config.features.formatting || !config.format.enabled || !config.format.on_type {
+            return Ok(None);
+        }
+    }
+    let position = params.text_document_position;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&position.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => session
+            .format_on_type(&uri, position.position, &params.ch, &params.options)
+            .map(Some)
+            .or_else(|err| {
+                tracing::error!("{}", err.to_string());
+                Ok(None)
+            }),
+        Err(err) => report_error(err),
+    }
 }
 
 pub async fn handle_code_action(
     state: &ServerState,
     params: lsp_types::CodeActionParams,
 ) -> Result<Option<lsp_types::CodeActionResponse>> {
+    if !state.config.read().features.code_action {
+        return Ok(None);
+    }
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
     {
-        Ok((temp_uri, session)) => Ok(capabilities::code_actions(
-            session,
-            &params.range,
-            &params.text_document.uri,
-            &temp_uri,
-            &params.context.diagnostics,
-        )),
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+        Ok((temp_uri, session)) => {
+            let only = params.context.only.as_deref();
+            let mut actions = capabilities::code_actions(
+                session.clone(),
+                &params.range,
+                &params.text_document.uri,
+                &temp_uri,
+                &params.context.diagnostics,
+            )
+            .map(|actions| capabilities::code_actions::filter_by_kind(actions, only));
+            if capabilities::code_actions::requests_fix_all(only) {
+                if let Some(action) = capabilities::code_actions::fix_all::code_action(
+                    &session,
+                    &params.text_document.uri,
+                    &temp_uri,
+                ) {
+                    actions.get_or_insert_with(Vec::new).push(action);
+                }
+            }
+            Ok(actions)
         }
+        Err(err) => report_error(err),
     }
 }
 
@@ -257,17 +656,88 @@ pub async fn handle_code_lens(
     state: &ServerState,
     params: lsp_types::CodeLensParams,
 ) -> Result<Option<Vec<CodeLens>>> {
-    let _ = state.wait_for_parsing().await;
+    if !state.config.read().features.code_lens {
+        return Ok(None);
+    }
+    state.wait_for_parsing_until_deadline().await;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
     {
         Ok((url, session)) => Ok(Some(capabilities::code_lens::code_lens(&session, &url))),
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_code_lens_resolve(
+    state: &ServerState,
+    code_lens: CodeLens,
+) -> Result<CodeLens> {
+    if !state.config.read().features.code_lens {
+        return Ok(code_lens);
+    }
+    let Some(uri) = capabilities::code_lens::references_lens_uri(&code_lens) else {
+        return Ok(code_lens);
+    };
+    // Count the references from the latest changes.
+    state.wait_for_parsing_until_deadline().await;
+    match state.sessions.uri_and_session_from_workspace(&uri).await {
+        Ok((url, session)) => Ok(capabilities::code_lens::resolve_references_lens(
+            session, url, code_lens,
+        )),
         Err(err) => {
             tracing::error!("{}", err.to_string());
-            Ok(None)
+            Ok(code_lens)
+        }
+    }
+}
+
+pub async fn handle_document_link(
+    state: &ServerState,
+    params: lsp_types::DocumentLinkParams,
+) -> Result<Option<Vec<lsp_types::DocumentLink>>> {
+    if !state.config.read().features.document_link {
+        return Ok(None);
+    }
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => Ok(capabilities::document_link::document_links(session, &uri)),
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_signature_help(
+    state: &ServerState,
+    params: lsp_types::SignatureHelpParams,
+) -> Result<Option<lsp_types::SignatureHelp>> {
+    if !state.config.read().features.signature_help {
+        return Ok(None);
+    }
+    state
+        .compile_deferred(&params.text_document_position_params.text_document.uri)
+        .await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document_position_params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let position = params.text_document_position_params.position;
+            // The arguments of a call in a comment or string aren't code.
+            if session.position_kind(&uri, position) != Some(PositionKind::Code) {
+                return Ok(None);
+            }
+            Ok(capabilities::signature_help::signature_help(
+                session, &uri, position,
+            ))
         }
+        Err(err) => report_error(err),
     }
 }
 
@@ -275,7 +745,11 @@ pub async fn handle_semantic_tokens_range(
     state: &ServerState,
     params: SemanticTokensRangeParams,
 ) -> Result<Option<SemanticTokensRangeResult>> {
-    let _ = state.wait_for_parsing().await;
+    if !state.config.read().features.semantic_tokens {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.text_document.uri).await;
+    state.wait_for_parsing_until_deadline().await;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
@@ -286,65 +760,298 @@ pub async fn handle_semantic_tokens_range(
             &uri,
             &params.range,
         )),
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Err(err) => report_error(err),
     }
 }
 
-pub async fn handle_semantic_tokens_full(
+/// Returns the semantic tokens within any of several ranges of a file, see
+/// [capabilities::semantic_tokens::semantic_tokens_ranges].
+pub async fn handle_semantic_tokens_ranges(
     state: &ServerState,
-    params: SemanticTokensParams,
-) -> Result<Option<SemanticTokensResult>> {
-    let _ = state.wait_for_parsing().await;
+    params: lsp_ext::SemanticTokensRangesParams,
+) -> Result<Option<SemanticTokens>> {
+    if !state.config.read().features.semantic_tokens {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.text_document.uri).await;
+    state.wait_for_parsing_until_deadline().await;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
     {
-        Ok((uri, session)) => Ok(capabilities::semantic_tokens::semantic_tokens_full(
-            session, &uri,
-        )),
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Ok((uri, session)) => Ok(Some(capabilities::semantic_tokens::semantic_tokens_ranges(
+            &session,
+            &uri,
+            &params.ranges,
+        ))),
+        Err(err) => report_error(err),
     }
 }
 
-pub(crate) async fn handle_inlay_hints(
+/// Returns the declarations of a kind in the file, see [capabilities::document_symbol::symbols_by_kind].
+pub async fn handle_symbols_by_kind(
     state: &ServerState,
-    params: InlayHintParams,
-) -> Result<Option<Vec<InlayHint>>> {
-    let _ = state.wait_for_parsing().await;
+    params: lsp_ext::SymbolsByKindParams,
+) -> Result<Option<Vec<lsp_ext::KindSymbol>>> {
+    if !state.config.read().features.document_symbol {
+        return Ok(None);
+    }
+    state.wait_for_parsing_until_deadline().await;
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
     {
         Ok((uri, session)) => {
-            let config = &state.config.read().inlay_hints;
-            Ok(capabilities::inlay_hints::inlay_hints(
-                session,
-                &uri,
-                &params.range,
-                config,
-            ))
-        }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+            let mut symbols =
+                capabilities::document_symbol::symbols_by_kind(&session, &uri, params.kind);
+            if !session.config.read().symbols.include_underscore_items {
+                symbols.iter_mut().for_each(|symbols| {
+                    symbols.retain(|symbol| {
+                        !capabilities::document_symbol::is_underscore_name(&symbol.name)
+                    })
+                });
+            }
+            Ok(symbols)
         }
+        Err(err) => report_error(err),
     }
 }
 
-/// This method is triggered by a command palette request in VScode
-/// The 3 commands are: "show lexed ast", "show parsed ast" or "show typed ast"
-///
-/// If any of these commands are executed, the client requests this method
-/// by calling the "sway/show_ast".
-///
+pub async fn handle_semantic_tokens_full(
+    state: &ServerState,
+    params: SemanticTokensParams,
+) -> Result<Option<SemanticTokensResult>> {
+    if !state.config.read().features.semantic_tokens {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.text_document.uri).await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let (tokens, _) =
+                update_semantic_tokens(state, &session, &uri, params.text_document.uri);
+            Ok(Some(tokens.into()))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns the edits to the semantic tokens last sent for the file, or all of its tokens if the
+/// client's `previous_result_id` doesn't match the last result sent.
+///
+/// The edits are computed against the tokens the client holds rather than against the last
+/// compilation, so they stay valid however the file was re-parsed in between.
+pub async fn handle_semantic_tokens_full_delta(
+    state: &ServerState,
+    params: SemanticTokensDeltaParams,
+) -> Result<Option<SemanticTokensFullDeltaResult>> {
+    if !state.config.read().features.semantic_tokens {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.text_document.uri).await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let (tokens, previous) =
+                update_semantic_tokens(state, &session, &uri, params.text_document.uri);
+            let previous = previous.filter(|previous| {
+                previous.result_id.as_deref() == Some(params.previous_result_id.as_str())
+            });
+            let result = match previous {
+                Some(previous) => SemanticTokensDelta {
+                    result_id: tokens.result_id,
+                    edits: capabilities::semantic_tokens::semantic_tokens_edits(
+                        &previous.data,
+                        &tokens.data,
+                    ),
+                }
+                .into(),
+                None => tokens.into(),
+            };
+            Ok(Some(result))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns the semantic tokens of the file at `uri`, along with those last sent for it, and records them as
+/// the last sent.
+///
+/// The tokens last sent are reused, with a new result id, unless the engines were swapped since they were
+/// computed, as the token map only changes with them.
+fn update_semantic_tokens(
+    state: &ServerState,
+    session: &Session,
+    uri: &Url,
+    workspace_uri: Url,
+) -> (SemanticTokens, Option<SemanticTokens>) {
+    let generation = session.engines_generation.load(Ordering::SeqCst);
+    let previous = state
+        .semantic_tokens
+        .get(&workspace_uri)
+        .map(|entry| entry.value().clone());
+    let tokens = match &previous {
+        Some((previous_generation, previous)) if *previous_generation == generation => {
+            SemanticTokens {
+                result_id: Some(capabilities::semantic_tokens::next_result_id()),
+                data: previous.data.clone(),
+            }
+        }
+        _ => capabilities::semantic_tokens::semantic_tokens_for_file(session, uri),
+    };
+    state
+        .semantic_tokens
+        .insert(workspace_uri, (generation, tokens.clone()));
+    (tokens, previous.map(|(_, previous)| previous))
+}
+
+pub async fn handle_inlay_hints(
+    state: &ServerState,
+    params: InlayHintParams,
+) -> Result<Option<Vec<InlayHint>>> {
+    if !state.config.read().features.inlay_hint {
+        return Ok(None);
+    }
+    state.compile_deferred(&params.text_document.uri).await;
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let config = session.config.read().inlay_hints.clone();
+            Ok(capabilities::inlay_hints::inlay_hints(
+                session,
+                &uri,
+                &params.range,
+                &config,
+            ))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns the diagnostics of the file to a client that pulls them, computed like the ones that are pushed.
+///
+/// If they're the same as the ones the client already has, as told by its `previous_result_id`,
+/// the report says that they're unchanged.
+pub async fn handle_document_diagnostic(
+    state: &ServerState,
+    params: DocumentDiagnosticParams,
+) -> Result<DocumentDiagnosticReportResult> {
+    state.wait_for_parsing_until_deadline().await;
+    let workspace_uri = params.text_document.uri;
+    let diagnostics = match state
+        .sessions
+        .uri_and_session_from_workspace(&workspace_uri)
+        .await
+    {
+        Ok((uri, session)) => server_state::diagnostics(&uri, &session),
+        Err(err) => {
+            report_error::<()>(err)?;
+            vec![]
+        }
+    };
+    Ok(DocumentDiagnosticReportResult::Report(
+        state.pulled_diagnostics.report(
+            &workspace_uri,
+            params.previous_result_id.as_deref(),
+            diagnostics,
+        ),
+    ))
+}
+
+/// Returns the diagnostics of every file with diagnostics across all sessions, to a client that pulls them.
+///
+/// The files that the client has a previous report for are included as well, so that their diagnostics are
+/// cleared once they have none. If the client asked for partial results, the reports of each project are
+/// streamed as soon as they're ready and the response itself is empty.
+pub async fn handle_workspace_diagnostic(
+    state: &ServerState,
+    params: WorkspaceDiagnosticParams,
+) -> Result<WorkspaceDiagnosticReportResult> {
+    state.wait_for_parsing_until_deadline().await;
+    let previous_result_ids: HashMap<Url, String> = params
+        .previous_result_ids
+        .into_iter()
+        .map(|previous| (previous.uri, previous.value))
+        .collect();
+    let partial_results = params
+        .partial_result_params
+        .partial_result_token
+        .zip(state.client.as_ref());
+    let sessions = state
+        .sessions
+        .iter()
+        .map(|item| item.value().clone())
+        .collect::<Vec<_>>();
+
+    let mut reported = HashSet::new();
+    let mut items = vec![];
+    for session in sessions {
+        let with_diagnostics = session
+            .diagnostics
+            .read()
+            .keys()
+            .filter_map(|path| {
+                let uri = Url::from_file_path(path).ok()?;
+                session.sync.temp_to_workspace_url(&uri).ok()
+            })
+            .collect::<Vec<_>>();
+        let previously_reported = previous_result_ids
+            .keys()
+            .filter(|workspace_uri| session.sync.workspace_to_temp_url(workspace_uri).is_ok())
+            .cloned();
+        let mut reports = vec![];
+        for workspace_uri in with_diagnostics.into_iter().chain(previously_reported) {
+            if !reported.insert(workspace_uri.clone()) {
+                continue;
+            }
+            let Ok(uri) = session.sync.workspace_to_temp_url(&workspace_uri) else {
+                continue;
+            };
+            reports.push(state.pulled_diagnostics.workspace_report(
+                &workspace_uri,
+                previous_result_ids.get(&workspace_uri).map(String::as_str),
+                server_state::diagnostics(&uri, &session),
+            ));
+        }
+        match &partial_results {
+            Some((token, client)) if !reports.is_empty() => {
+                client
+                    .send_notification::<lsp_ext::WorkspaceDiagnosticProgress>(
+                        lsp_ext::WorkspaceDiagnosticProgressParams {
+                            token: token.clone(),
+                            value: WorkspaceDiagnosticReportPartialResult { items: reports },
+                        },
+                    )
+                    .await;
+            }
+            _ => items.extend(reports),
+        }
+    }
+    Ok(WorkspaceDiagnosticReportResult::Report(
+        WorkspaceDiagnosticReport { items },
+    ))
+}
+
+/// This method is triggered by a command palette request in VScode
+/// The 3 commands are: "show lexed ast", "show parsed ast" or "show typed ast"
+///
+/// If any of these commands are executed, the client requests this method
+/// by calling the "sway/show_ast".
+///
 /// The function expects the URI of the current open file where the
 /// request was made, and if the "lexed", "parsed" or "typed" ast was requested.
 ///
@@ -440,10 +1147,7 @@ pub async fn handle_show_ast(
                 }
             }
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Err(err) => report_error(err),
     }
 }
 
@@ -466,10 +1170,7 @@ pub async fn handle_on_enter(
                 &params,
             ))
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
-        }
+        Err(err) => report_error(err),
     }
 }
 
@@ -496,14 +1197,14 @@ pub fn handle_visualize(
 pub(crate) async fn metrics(
     state: &ServerState,
     params: lsp_ext::MetricsParams,
-) -> Result<Option<Vec<(String, PerformanceData)>>> {
+) -> Result<Option<lsp_ext::MetricsResult>> {
     match state
         .sessions
         .uri_and_session_from_workspace(&params.text_document.uri)
         .await
     {
         Ok((_, session)) => {
-            let mut metrics = vec![];
+            let mut modules = vec![];
             for kv in session.metrics.iter() {
                 let path = session
                     .engines
@@ -512,13 +1213,858 @@ pub(crate) async fn metrics(
                     .get_path(kv.key())
                     .to_string_lossy()
                     .to_string();
-                metrics.push((path, kv.value().clone()));
+                modules.push((path, kv.value().clone()));
             }
-            Ok(Some(metrics))
+            Ok(Some(lsp_ext::MetricsResult {
+                modules,
+                gc_failures: session.gc_failures.load(Ordering::SeqCst),
+                cancelled_compilations: session.cancelled_compilations.load(Ordering::SeqCst),
+                internal_compiler_errors: session.internal_compiler_errors.load(Ordering::SeqCst),
+                phases: phase_timings(*session.phase_durations.read()),
+            }))
         }
-        Err(err) => {
-            tracing::error!("{}", err.to_string());
-            Ok(None)
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns the [PhaseDurations] of a compilation in milliseconds.
+fn phase_timings(durations: PhaseDurations) -> lsp_ext::PhaseTimings {
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    lsp_ext::PhaseTimings {
+        garbage_collection_ms: durations.garbage_collection.map(ms),
+        compilation_ms: ms(durations.compilation),
+        traversal_ms: ms(durations.traversal),
+    }
+}
+
+/// The most times that a `sway/benchmark` request compiles the project.
+const MAX_BENCHMARK_ITERATIONS: usize = 100;
+
+/// This method is triggered by tooling to benchmark the compiler through the language server.
+///
+/// The project is compiled `iterations` times, up to [MAX_BENCHMARK_ITERATIONS], without writing the
+/// results to the session, and the timing statistics of each compilation are returned.
+pub async fn handle_benchmark(
+    state: &ServerState,
+    params: lsp_ext::BenchmarkParams,
+) -> Result<Option<lsp_ext::BenchmarkResult>> {
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let iterations = params.iterations.min(MAX_BENCHMARK_ITERATIONS);
+            let clear_cache = params.clear_cache;
+            // The compilations block, so they run off the async runtime like the ones of the compilation thread.
+            let durations = tokio::task::spawn_blocking(move || {
+                let build_config = session.config.read().build.clone();
+                (0..iterations)
+                    .map(|_| {
+                        let engines = if clear_cache {
+                            Engines::default()
+                        } else {
+                            session.engines.read().clone()
+                        };
+                        let mut parse_result = ParseResult::default();
+                        session::parse_project(
+                            &uri,
+                            &engines,
+                            session.position_encoding(),
+                            &build_config,
+                            None,
+                            &mut parse_result,
+                        )?;
+                        Ok(parse_result.phase_durations)
+                    })
+                    .collect::<Result<Vec<_>, LanguageServerError>>()
+            })
+            .await;
+            match durations {
+                Ok(Ok(durations)) => Ok(benchmark_result(durations)),
+                Ok(Err(err)) => {
+                    tracing::error!("{}", err.to_string());
+                    Ok(None)
+                }
+                Err(err) => {
+                    tracing::error!("The benchmark failed: {err}");
+                    Ok(None)
+                }
+            }
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Computes the min, median, max and mean of the compilation times, which are the sums of the
+/// [PhaseDurations] of each compilation.
+fn benchmark_result(durations: Vec<PhaseDurations>) -> Option<lsp_ext::BenchmarkResult> {
+    if durations.is_empty() {
+        return None;
+    }
+    let phases: Vec<_> = durations.into_iter().map(phase_timings).collect();
+    let samples: Vec<f64> = phases
+        .iter()
+        .map(|phase| phase.compilation_ms + phase.traversal_ms)
+        .collect();
+    let mut sorted = samples.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let len = sorted.len();
+    let median = if len % 2 == 0 {
+        (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0
+    } else {
+        sorted[len / 2]
+    };
+    Some(lsp_ext::BenchmarkResult {
+        min: sorted[0],
+        median,
+        max: sorted[len - 1],
+        mean: sorted.iter().sum::<f64>() / len as f64,
+        samples,
+        phases,
+    })
+}
+
+/// This method is triggered by the `sway/recompileWorkspace` command to recompile every open project.
+///
+/// Projects are compiled one at a time, as the compilation thread only keeps the most recent request.
+/// If a project is already compiling, its current compilation is awaited instead of starting a new one.
+/// Diagnostics are published for each project once its compilation has finished.
+pub async fn handle_recompile_workspace(
+    state: &ServerState,
+) -> Result<lsp_ext::RecompileWorkspaceResult> {
+    const PROGRESS_TOKEN: &str = "sway/recompileWorkspace";
+    let sessions = state
+        .sessions
+        .iter()
+        .map(|item| item.value().clone())
+        .collect::<Vec<_>>();
+    let total = sessions.len();
+    let report_progress = state.create_work_done_progress(PROGRESS_TOKEN).await;
+    if report_progress {
+        state
+            .report_work_done_progress(
+                PROGRESS_TOKEN,
+                WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title: "Recompiling Sway projects".to_string(),
+                    cancellable: Some(false),
+                    message: Some(format!("0/{total}")),
+                    percentage: Some(0),
+                }),
+            )
+            .await;
+    }
+
+    let mut result = lsp_ext::RecompileWorkspaceResult::default();
+    for (i, session) in sessions.into_iter().enumerate() {
+        // Register for the notification before checking the flag so that we can't miss it.
+        let finished = session.finished_compilation.notified();
+        if session.is_compiling.load(Ordering::SeqCst) {
+            result.already_compiling += 1;
+            finished.await;
+        } else if let Some(uri) = session
+            .sync
+            .temp_dir()
+            .ok()
+            .and_then(|temp_dir| Url::from_file_path(temp_dir).ok())
+        {
+            result.compiled += 1;
+            // Unlike an edit, this doesn't cancel the compilation in progress, and waits for the
+            // user's edits to be compiled first.
+            state.enqueue_compilation(server_state::CompilationContext {
+                session: Some(session.clone()),
+                uri: Some(uri),
+                version: None,
+                priority: server_state::CompilationPriority::Background,
+            });
+            // A newer request for another project can replace ours in the channel,
+            // so also stop waiting once the compilation thread is idle.
+            tokio::select! {
+                _ = finished => {},
+                _ = state.wait_for_parsing() => {},
+            }
+        } else {
+            // There's nothing to compile without a workspace directory,
+            // but the progress is still reported.
+            tracing::error!("Unable to recompile a project without a workspace directory");
+        }
+        state
+            .publish_session_diagnostics(None, session.clone())
+            .await;
+
+        if report_progress {
+            let done = i + 1;
+            state
+                .report_work_done_progress(
+                    PROGRESS_TOKEN,
+                    WorkDoneProgress::Report(WorkDoneProgressReport {
+                        cancellable: Some(false),
+                        message: Some(format!("{done}/{total}")),
+                        percentage: Some((done * 100 / total) as u32),
+                    }),
+                )
+                .await;
         }
     }
+
+    if report_progress {
+        state
+            .report_work_done_progress(
+                PROGRESS_TOKEN,
+                WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+            )
+            .await;
+    }
+    Ok(result)
+}
+
+/// This method is triggered by tooling to show the concrete types that the compiler substituted
+/// for the type parameters of the generic function called at the given position.
+pub async fn handle_monomorphized_type(
+    state: &ServerState,
+    params: lsp_types::TextDocumentPositionParams,
+) -> Result<Option<lsp_ext::MonomorphizedTypeResult>> {
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => Ok(capabilities::monomorphized_type::monomorphized_type(
+            session,
+            &uri,
+            params.position,
+        )),
+        Err(err) => report_error(err),
+    }
+}
+
+/// This method is triggered by tooling to type check an expression in the scope of the given position,
+/// if `experimental.typeOfExpression` is enabled.
+///
+/// An expression that doesn't type check is answered with its errors rather than failing the request.
+/// Returns the signature and doc comment of the declaration of the token at `params.position`, or `None` if
+/// it has no signature, e.g. because it's a variable or a literal.
+pub async fn handle_peek_signature(
+    state: &ServerState,
+    params: lsp_ext::PeekSignatureParams,
+) -> Result<Option<lsp_ext::PeekSignatureResult>> {
+    if !state.config.read().features.hover {
+        return Ok(None);
+    }
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => Ok(capabilities::peek_signature::peek_signature(
+            &session,
+            &uri,
+            params.position,
+        )),
+        Err(err) => report_error(err),
+    }
+}
+
+pub async fn handle_type_of_expression(
+    state: &ServerState,
+    params: lsp_ext::TypeOfExpressionParams,
+) -> Result<Option<lsp_ext::TypeOfExpressionResult>> {
+    if !state.config.read().experimental.type_of_expression {
+        return Ok(None);
+    }
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            // Copying and compiling the project blocks, so it's kept off the async runtime.
+            let result = tokio::task::spawn_blocking(move || {
+                capabilities::type_of_expression::type_of_expression(
+                    session,
+                    &uri,
+                    params.position,
+                    &params.expression,
+                )
+            })
+            .await;
+            match result {
+                Ok(Ok(result)) => Ok(Some(result)),
+                Ok(Err(err)) => {
+                    tracing::error!("{}", err.to_string());
+                    Ok(None)
+                }
+                Err(err) => {
+                    tracing::error!("Type checking the expression failed: {err}");
+                    Ok(None)
+                }
+            }
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// This method is triggered by tooling to export the diagnostics of every file of a project.
+///
+/// The project is compiled first if it hasn't been yet, so that the diagnostics reflect its current state.
+pub async fn handle_export_diagnostics(
+    state: &ServerState,
+    params: lsp_ext::ExportDiagnosticsParams,
+) -> Result<Option<DiagnosticsSnapshot>> {
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.uri)
+        .await
+    {
+        Ok((_, session)) => {
+            if session.compiled_program.read().typed.is_none() {
+                // Like other requests, the diagnostics known at the deadline are exported if the
+                // compilation takes longer.
+                if let Some(Err(err)) = state
+                    .with_request_deadline(state.compile_now(&params.uri))
+                    .await
+                {
+                    return report_error(err);
+                }
+            }
+            Ok(Some(server_state::diagnostics_snapshot(&session)))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns the diagnostics that the latest compilation of the project added and removed, compared to
+/// the compilation before it, or `None` if it hasn't been compiled twice yet.
+pub async fn handle_diagnostics_diff(
+    state: &ServerState,
+    params: lsp_ext::DiagnosticsDiffParams,
+) -> Result<Option<DiagnosticsDiff>> {
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.uri)
+        .await
+    {
+        Ok((_, session)) => Ok(session.diagnostics_history.lock().diff()),
+        Err(err) => report_error(err),
+    }
+}
+
+/// This method is triggered by tooling to wait until the server has no compilation work left,
+/// e.g. before asserting on diagnostics in a test.
+///
+/// Returns an error instead of waiting forever if the compilation doesn't finish within `timeout_ms`.
+pub async fn handle_wait_for_compilation(
+    state: &ServerState,
+    params: lsp_ext::WaitForCompilationParams,
+) -> Result<lsp_ext::WaitForCompilationResult> {
+    let session = match &params.uri {
+        Some(uri) => Some(
+            state
+                .sessions
+                .uri_and_session_from_workspace(uri)
+                .await
+                .map(|(_, session)| session)?,
+        ),
+        None => None,
+    };
+    let wait = state.wait_for_parsing();
+    if let Some(timeout_ms) = params.timeout_ms {
+        tokio::time::timeout(Duration::from_millis(timeout_ms), wait)
+            .await
+            .map_err(|_| LanguageServerError::CompilationTimedOut { timeout_ms })?;
+    } else {
+        wait.await;
+    }
+    let last_compilation_state = match session {
+        Some(session) => *session.last_compilation_state.read(),
+        None => state.last_compilation_state(),
+    };
+    Ok(lsp_ext::WaitForCompilationResult {
+        last_compilation_state,
+    })
+}
+
+/// This method is triggered by tooling to check that the language server is healthy.
+pub fn handle_health_check(state: &ServerState) -> Result<lsp_ext::HealthCheckResult> {
+    Ok(state.health_check_result())
+}
+
+/// This method is triggered by tooling to list the projects that the server has open.
+///
+/// The sessions are collected before any of them are inspected, so that no map lock is held
+/// while waiting for the compilation thread. The engines aren't locked, so this responds even mid-compilation.
+pub fn handle_list_sessions(state: &ServerState) -> Result<lsp_ext::ListSessionsResult> {
+    let mut sessions = state
+        .sessions
+        .iter()
+        .map(|item| (item.key().clone(), item.value().clone()))
+        .collect::<Vec<_>>();
+    sessions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let sessions = sessions
+        .into_iter()
+        .map(|(manifest_dir, session)| {
+            let manifest = PackageManifestFile::from_dir(&manifest_dir).ok();
+            lsp_ext::SessionInfo {
+                project_name: manifest
+                    .as_ref()
+                    .map(|manifest| manifest.project.name.clone()),
+                workspace_root: manifest
+                    .and_then(|manifest| manifest.workspace().ok().flatten())
+                    .map(|workspace| workspace.dir().to_path_buf()),
+                manifest_dir,
+                is_compiling: session.is_compiling.load(Ordering::SeqCst),
+                last_compilation_state: *session.last_compilation_state.read(),
+                stale_tokens: session.has_stale_tokens(),
+                engines_generation: session.engines_generation.load(Ordering::SeqCst),
+                files_with_diagnostics: session
+                    .diagnostics
+                    .read()
+                    .values()
+                    .filter(|diagnostics| {
+                        !diagnostics.errors.is_empty() || !diagnostics.warnings.is_empty()
+                    })
+                    .count(),
+            }
+        })
+        .collect();
+    Ok(lsp_ext::ListSessionsResult { sessions })
+}
+
+/// This method is triggered by the client to change the log level without restarting the server.
+pub fn handle_set_trace_level(
+    state: &ServerState,
+    params: lsp_ext::SetTraceLevelParams,
+) -> Result<()> {
+    state.update_trace_level(params.level).map_err(Into::into)
+}
+
+/// This method is triggered by tooling to find out which versions the language server is built with.
+///
+/// If a document is given, the toolchain required by its project's `Forc.toml` is also returned
+/// so that mismatches between the editor and the CLI can be diagnosed.
+pub fn handle_server_info(params: lsp_ext::ServerInfoParams) -> Result<lsp_ext::ServerInfoResult> {
+    let project = params.text_document.and_then(|text_document| {
+        match server_state::manifest_from_uri(&text_document.uri) {
+            Ok(manifest) => Some(project_toolchain_info(&manifest)),
+            Err(err) => {
+                tracing::error!("{}", err.to_string());
+                None
+            }
+        }
+    });
+    Ok(lsp_ext::ServerInfoResult {
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        compiler_version: sway_core::VERSION.to_string(),
+        project,
+    })
+}
+
+/// Returns the toolchain info of the project, checking its `forc-version` against the compiler's version.
+fn project_toolchain_info(manifest: &PackageManifestFile) -> lsp_ext::ProjectToolchainInfo {
+    let forc_version = manifest.project.forc_version.as_ref();
+    let compatible = forc_version.map_or(true, |min_version| {
+        semver::Version::parse(sway_core::VERSION)
+            .map(|version| version >= *min_version)
+            .unwrap_or(false)
+    });
+    lsp_ext::ProjectToolchainInfo {
+        name: manifest.project.name.clone(),
+        manifest_path: manifest.path().to_path_buf(),
+        forc_version: forc_version.map(ToString::to_string),
+        compatible,
+    }
+}
+
+/// Replaces the session of the project with a new one, for when it got into a bad state.
+///
+/// The diagnostics published for the project are cleared and it's compiled from scratch, keeping
+/// the unsaved changes of its documents. If the project is being compiled, the compilation is waited
+/// for before the old session's temp directory is removed. Other projects are left alone.
+pub async fn handle_reset_session(
+    state: &ServerState,
+    params: lsp_ext::ResetSessionParams,
+) -> Result<lsp_ext::ResetSessionResult> {
+    let manifest_dir = server_state::manifest_dir_from_uri(&params.uri)?;
+    let Some(old_session) = state
+        .sessions
+        .get(&manifest_dir)
+        .map(|item| item.value().clone())
+    else {
+        return Ok(lsp_ext::ResetSessionResult { reset: false });
+    };
+    // Register for the notification before checking the flag so that we can't miss it.
+    let finished = old_session.finished_compilation.notified();
+    if old_session.is_compiling.load(Ordering::SeqCst) {
+        tokio::select! {
+            _ = finished => {},
+            _ = state.wait_for_parsing() => {},
+        }
+    }
+    state.clear_session_diagnostics(&old_session).await;
+    let Some(session) = state.sessions.reinit(&manifest_dir).await? else {
+        return Ok(lsp_ext::ResetSessionResult { reset: true });
+    };
+    if let Some(uri) = session
+        .sync
+        .temp_dir()
+        .ok()
+        .and_then(|temp_dir| Url::from_file_path(temp_dir).ok())
+    {
+        let finished = session.finished_compilation.notified();
+        send_new_compilation_request(state, session.clone(), &uri, None);
+        // A newer request for another project can replace ours in the channel,
+        // so also stop waiting once the compilation thread is idle.
+        tokio::select! {
+            _ = finished => {},
+            _ = state.wait_for_parsing() => {},
+        }
+        state.publish_session_diagnostics(None, session).await;
+    }
+    Ok(lsp_ext::ResetSessionResult { reset: true })
+}
+
+/// Builds the project of the document with `forc build`, so that the diagnostics of the editor can be
+/// compared with those of a clean build.
+///
+/// The output of the build is logged to the client as it's printed. The diagnostics it reports replace
+/// the ones published for the project until it's compiled again. As it's a real build, the files are
+/// read from disk, so unsaved changes aren't included. Returns `None` if there's no client to report to.
+pub async fn handle_build_project(
+    state: &ServerState,
+    params: lsp_ext::BuildProjectParams,
+) -> Result<Option<lsp_ext::BuildProjectResult>> {
+    let Some(client) = state.client.as_ref() else {
+        return Ok(None);
+    };
+    let manifest = server_state::manifest_from_uri(&params.uri)?;
+    let (sender, mut lines) = tokio::sync::mpsc::unbounded_channel();
+    let mut child = forc_build::spawn_build(manifest.dir(), sender)
+        .map_err(|err| LanguageServerError::ForcBuildFailed(err.to_string()))?;
+    let mut output = vec![];
+    while let Some(line) = lines.recv().await {
+        client.log_message(MessageType::LOG, &line).await;
+        output.push(line);
+    }
+    let status = tokio::task::spawn_blocking(move || child.wait())
+        .await
+        .map_err(|err| LanguageServerError::ForcBuildFailed(err.to_string()))?
+        .map_err(|err| LanguageServerError::ForcBuildFailed(err.to_string()))?;
+
+    let diagnostics = forc_build::parse_build_diagnostics(&output);
+    if let Some(session) = state
+        .sessions
+        .get(manifest.dir())
+        .map(|item| item.value().clone())
+    {
+        state.clear_session_diagnostics(&session).await;
+    }
+    let (mut error_count, mut warning_count) = (0, 0);
+    for (path, mut diagnostics) in diagnostics {
+        let Ok(uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+        for diagnostic in &diagnostics {
+            if diagnostic.severity == Some(DiagnosticSeverity::ERROR) {
+                error_count += 1;
+            } else {
+                warning_count += 1;
+            }
+        }
+        state.published_diagnostics.insert(uri.clone());
+        state
+            .client_capabilities
+            .read()
+            .strip_diagnostics(&mut diagnostics);
+        client.publish_diagnostics(uri, diagnostics, None).await;
+    }
+    Ok(Some(lsp_ext::BuildProjectResult {
+        success: status.success(),
+        exit_code: status.code(),
+        error_count,
+        warning_count,
+    }))
+}
+
+/// Returns the documentation of a keyword, as shown when hovering it.
+pub fn handle_keyword_doc(
+    state: &ServerState,
+    params: lsp_ext::KeywordDocParams,
+) -> Result<lsp_ext::KeywordDocResult> {
+    match state.keyword_docs.get(&params.keyword) {
+        Some(documentation) => Ok(lsp_ext::KeywordDocResult {
+            documentation: documentation.clone(),
+        }),
+        None => Err(LanguageServerError::KeywordNotFound {
+            keyword: params.keyword,
+        }
+        .into()),
+    }
+}
+
+/// Returns the declarations of the project of `params.uri` that have the fully qualified name `params.name`.
+///
+/// Fails with [SYMBOL_NOT_FOUND_ERROR_CODE] if there is none, and with [AMBIGUOUS_SYMBOL_ERROR_CODE]
+/// if there are several and `params.unique` is set.
+///
+/// [SYMBOL_NOT_FOUND_ERROR_CODE]: crate::error::SYMBOL_NOT_FOUND_ERROR_CODE
+/// [AMBIGUOUS_SYMBOL_ERROR_CODE]: crate::error::AMBIGUOUS_SYMBOL_ERROR_CODE
+pub async fn handle_locate_symbol(
+    state: &ServerState,
+    params: lsp_ext::LocateSymbolParams,
+) -> Result<lsp_ext::LocateSymbolResult> {
+    state.wait_for_parsing_until_deadline().await;
+    let (_, session) = state
+        .sessions
+        .uri_and_session_from_workspace(&params.uri)
+        .await?;
+    let locations =
+        capabilities::locate_symbol::locate_symbol(&session, &params.name, params.unique)?;
+    Ok(lsp_ext::LocateSymbolResult { locations })
+}
+
+/// Returns the package that the file belongs to, found the same way as the project of its session.
+///
+/// Fails with [MANIFEST_NOT_FOUND_ERROR_CODE] if the file isn't in a Forc project.
+///
+/// [MANIFEST_NOT_FOUND_ERROR_CODE]: crate::error::MANIFEST_NOT_FOUND_ERROR_CODE
+pub fn handle_manifest_for_file(
+    params: lsp_ext::ManifestForFileParams,
+) -> Result<lsp_ext::ManifestForFileResult> {
+    let manifest = server_state::manifest_from_uri(&params.uri)?;
+    Ok(lsp_ext::ManifestForFileResult {
+        manifest_dir: server_state::manifest_dir_of(&manifest)?,
+        project_name: manifest.project_name().to_string(),
+    })
+}
+
+/// Returns the temp directory that the project of the file at `params.uri` is compiled from, so that its contents
+/// can be compared with the project's when investigating how paths are mapped between them.
+///
+/// Unlike other requests, this doesn't create the session if it doesn't exist yet.
+pub fn handle_temp_workspace_path(
+    state: &ServerState,
+    params: lsp_ext::TempWorkspacePathParams,
+) -> Result<lsp_ext::TempWorkspacePathResult> {
+    let dir = state.sessions.session_dir_from_uri(&params.uri)?;
+    let Some(session) = state.sessions.get(&dir).map(|item| item.value().clone()) else {
+        return Err(LanguageServerError::SessionNotFound {
+            dir: dir.to_string_lossy().to_string(),
+        }
+        .into());
+    };
+    Ok(lsp_ext::TempWorkspacePathResult {
+        manifest_dir: session
+            .sync
+            .manifest_dir()
+            .map_err(LanguageServerError::from)?,
+        temp_dir: session.sync.temp_dir().map_err(LanguageServerError::from)?,
+    })
+}
+
+/// Garbage collects the engines of the project of `params.uri`, or of every project, and recompiles them,
+/// rather than waiting for the garbage collection that runs every 10th edit.
+///
+/// The projects are collected one at a time, and the request returns once all of them are recompiled.
+pub async fn handle_collect_garbage(
+    state: &ServerState,
+    params: lsp_ext::CollectGarbageParams,
+) -> Result<lsp_ext::CollectGarbageResult> {
+    let sessions = match &params.uri {
+        Some(uri) => vec![state
+            .sessions
+            .uri_and_session_from_workspace(uri)
+            .await
+            .map(|(_, session)| session)?],
+        None => state
+            .sessions
+            .iter()
+            .map(|item| item.value().clone())
+            .collect(),
+    };
+    let mut system = System::new();
+    let memory_before = server_state::resident_memory(&mut system);
+    for session in &sessions {
+        state.collect_garbage(session).await?;
+    }
+    let memory_after = server_state::resident_memory(&mut system);
+    Ok(lsp_ext::CollectGarbageResult {
+        collected: sessions.len(),
+        reclaimed_bytes: memory_before
+            .zip(memory_after)
+            .map(|(before, after)| before.saturating_sub(after)),
+    })
+}
+
+/// Returns an estimate of the memory held by the engines and tokens of each session, if `debug.memoryReport`
+/// is enabled.
+///
+/// The engines are only locked for reading while they're counted. Compilations work on a clone of them
+/// and only lock them to swap in their results, so the figures of a session that is being compiled are
+/// those of its last compilation.
+pub fn handle_memory_report(state: &ServerState) -> Result<Option<lsp_ext::MemoryReportResult>> {
+    if !state.config.read().debug.memory_report {
+        return Ok(None);
+    }
+    let mut sessions = state
+        .sessions
+        .iter()
+        .map(|item| (item.key().clone(), item.value().clone()))
+        .collect::<Vec<_>>();
+    sessions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let sessions = sessions
+        .into_iter()
+        .map(|(manifest_dir, session)| {
+            let (types, declarations, sources, engines_bytes) = {
+                let engines = session.engines.read();
+                (
+                    engines.te().type_count(),
+                    engines.de().decl_count(),
+                    engines.se().source_count(),
+                    engines.te().approximate_size() + engines.de().approximate_size(),
+                )
+            };
+            let token_map = session.token_map();
+            lsp_ext::SessionMemory {
+                manifest_dir,
+                types,
+                declarations,
+                sources,
+                tokens: token_map.len(),
+                approximate_bytes: engines_bytes + token_map.approximate_size(),
+            }
+        })
+        .collect();
+    Ok(Some(lsp_ext::MemoryReportResult {
+        sessions,
+        resident_bytes: server_state::resident_memory(&mut System::new()),
+    }))
+}
+
+/// Returns the diagnostics of the file at `params.uri` that were computed by the last compilation of its project.
+///
+/// Unlike `textDocument/diagnostic`, this never compiles the project, nor creates its session if it doesn't exist yet.
+pub fn handle_file_diagnostics(
+    state: &ServerState,
+    params: lsp_ext::FileDiagnosticsParams,
+) -> Result<lsp_ext::FileDiagnosticsResult> {
+    let dir = state.sessions.session_dir_from_uri(&params.uri)?;
+    let Some(session) = state.sessions.get(&dir).map(|item| item.value().clone()) else {
+        return Err(LanguageServerError::SessionNotFound {
+            dir: dir.to_string_lossy().to_string(),
+        }
+        .into());
+    };
+    let uri = session.temp_url(&params.uri)?;
+    let last_compilation_state = *session.last_compilation_state.read();
+    Ok(lsp_ext::FileDiagnosticsResult {
+        diagnostics: server_state::diagnostics(&uri, &session),
+        last_compilation_state,
+    })
+}
+
+/// Returns the innermost item that contains `params.position`, such as the function or method that the cursor
+/// is in, or `None` if it's outside of every item.
+///
+/// Like the document outline, this only parses the file, so it's available before the project is compiled.
+pub async fn handle_enclosing_item(
+    state: &ServerState,
+    params: lsp_ext::EnclosingItemParams,
+) -> Result<Option<lsp_ext::EnclosingItemResult>> {
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let Some(symbols) = capabilities::document_symbol::document_symbols(session, &uri)
+            else {
+                return Ok(None);
+            };
+            Ok(
+                capabilities::document_symbol::enclosing_symbol(&symbols, params.position).map(
+                    |(symbol, container_name)| lsp_ext::EnclosingItemResult {
+                        name: symbol.name.clone(),
+                        kind: symbol.kind,
+                        range: symbol.range,
+                        container_name: container_name.map(str::to_string),
+                    },
+                ),
+            )
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns the syntax tree of the file as JSON, if `debug.syntaxTree` is enabled.
+///
+/// It's answered once the file has been parsed, even if the project doesn't type check.
+pub async fn handle_syntax_tree(
+    state: &ServerState,
+    params: lsp_ext::SyntaxTreeParams,
+) -> Result<Option<serde_json::Value>> {
+    if !state.config.read().debug.syntax_tree {
+        return Ok(None);
+    }
+    state.wait_for_parsing_until_deadline().await;
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.uri)
+        .await
+    {
+        Ok((uri, session)) => {
+            let Ok(path) = uri.to_file_path() else {
+                return Ok(None);
+            };
+            let program = session.compiled_program.read();
+            Ok(program
+                .lexed
+                .as_ref()
+                .and_then(|lexed| debug::syntax_tree(lexed, &path, session.engines.read().se())))
+        }
+        Err(err) => report_error(err),
+    }
+}
+
+/// Returns whether the position is inside a comment, a string literal or code, as completion sees it.
+pub async fn handle_classify_position(
+    state: &ServerState,
+    params: lsp_types::TextDocumentPositionParams,
+) -> Result<Option<lsp_ext::ClassifyPositionResult>> {
+    match state
+        .sessions
+        .uri_and_session_from_workspace(&params.text_document.uri)
+        .await
+    {
+        Ok((uri, session)) => Ok(session
+            .position_kind(&uri, params.position)
+            .map(|kind| lsp_ext::ClassifyPositionResult { kind })),
+        Err(err) => report_error(err),
+    }
+}
+
+/// Clears the diagnostics of every file in the editor, until the projects are compiled again.
+pub async fn handle_clear_diagnostics(
+    state: &ServerState,
+) -> Result<lsp_ext::ClearDiagnosticsResult> {
+    Ok(lsp_ext::ClearDiagnosticsResult {
+        cleared_files: state.clear_all_diagnostics().await,
+    })
+}
+
+/// Returns the tree of dependencies of the project that `params.uri` belongs to, as declared in its manifests.
+pub async fn handle_dependency_graph(
+    params: lsp_ext::DependencyGraphParams,
+) -> Result<Option<lsp_ext::DependencyGraphResult>> {
+    let manifest = server_state::manifest_from_uri(&params.uri)?;
+    Ok(Some(lsp_ext::DependencyGraphResult {
+        name: manifest.project_name().to_string(),
+        path: manifest.dir().to_path_buf(),
+        dependencies: dependency_graph::dependency_tree(&manifest),
+    }))
 }
@@ -14,10 +14,14 @@ pub mod server;
 mod traverse;
 pub mod utils;
 
+use config::FeaturesConfig;
 use lsp_types::{
-    CodeActionProviderCapability, CodeLensOptions, CompletionOptions, ExecuteCommandOptions,
-    HoverProviderCapability, OneOf, RenameOptions, SemanticTokensLegend, SemanticTokensOptions,
-    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, WorkDoneProgressOptions,
+    CallHierarchyServerCapability, CodeActionKind, CodeActionOptions, CodeActionProviderCapability,
+    CodeLensOptions, CompletionOptions, DocumentLinkOptions, ExecuteCommandOptions,
+    HoverProviderCapability, ImplementationProviderCapability, OneOf, RenameOptions,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, ServerCapabilities,
+    SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TypeDefinitionProviderCapability, WorkDoneProgressOptions,
 };
 use server_state::ServerState;
 use tower_lsp::{LspService, Server};
@@ -28,6 +32,38 @@ pub async fn start() {
         .custom_method("sway/visualize", ServerState::visualize)
         .custom_method("sway/on_enter", ServerState::on_enter)
         .custom_method("sway/metrics", ServerState::metrics)
+        .custom_method("sway/benchmark", ServerState::benchmark)
+        .custom_method("sway/recompileWorkspace", ServerState::recompile_workspace)
+        .custom_method("sway/monomorphizedType", ServerState::monomorphized_type)
+        .custom_method("sway/typeOfExpression", ServerState::type_of_expression)
+        .custom_method("sway/serverInfo", ServerState::server_info)
+        .custom_method("sway/healthCheck", ServerState::health_check)
+        .custom_method("sway/listSessions", ServerState::list_sessions)
+        .custom_method("sway/setTraceLevel", ServerState::set_trace_level)
+        .custom_method("sway/exportDiagnostics", ServerState::export_diagnostics)
+        .custom_method("sway/diagnosticsDiff", ServerState::diagnostics_diff)
+        .custom_method("sway/waitForCompilation", ServerState::wait_for_compilation)
+        .custom_method("sway/resetSession", ServerState::reset_session)
+        .custom_method("sway/keywordDoc", ServerState::keyword_doc)
+        .custom_method("sway/buildProject", ServerState::build_project)
+        .custom_method("sway/dependencyGraph", ServerState::dependency_graph)
+        .custom_method("sway/enclosingItem", ServerState::enclosing_item)
+        .custom_method("sway/syntaxTree", ServerState::syntax_tree)
+        .custom_method("sway/clearDiagnostics", ServerState::clear_diagnostics)
+        .custom_method("sway/classifyPosition", ServerState::classify_position)
+        .custom_method("sway/manifestForFile", ServerState::manifest_for_file)
+        .custom_method("sway/tempWorkspacePath", ServerState::temp_workspace_path)
+        .custom_method("sway/fileDiagnostics", ServerState::file_diagnostics)
+        .custom_method("sway/collectGarbage", ServerState::collect_garbage)
+        .custom_method(
+            "sway/semanticTokensRanges",
+            ServerState::semantic_tokens_ranges,
+        )
+        .custom_method("sway/symbolsByKind", ServerState::symbols_by_kind)
+        .custom_method("sway/memoryReport", ServerState::memory_report)
+        .custom_method("sway/locateSymbol", ServerState::locate_symbol)
+        .custom_method("sway/peekSignature", ServerState::peek_signature)
+        .custom_method("sway/dependencyChanged", ServerState::dependency_changed)
         .finish();
     Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
         .serve(service)
@@ -36,19 +72,94 @@ pub async fn start() {
 
 /// Returns the capabilities of the server to the client,
 /// indicating its support for various language server protocol features.
+/// Returns the capabilities of the server, leaving out the features that are disabled in the config.
+pub fn server_capabilities_with_features(features: &FeaturesConfig) -> ServerCapabilities {
+    let mut capabilities = server_capabilities();
+    if !features.call_hierarchy {
+        capabilities.call_hierarchy_provider = None;
+    }
+    if !features.code_action {
+        capabilities.code_action_provider = None;
+    }
+    if !features.code_lens {
+        capabilities.code_lens_provider = None;
+    }
+    if !features.completion {
+        capabilities.completion_provider = None;
+    }
+    if !features.definition {
+        capabilities.definition_provider = None;
+    }
+    if !features.document_highlight {
+        capabilities.document_highlight_provider = None;
+    }
+    if !features.document_link {
+        capabilities.document_link_provider = None;
+    }
+    if !features.document_symbol {
+        capabilities.document_symbol_provider = None;
+    }
+    if !features.formatting {
+        capabilities.document_formatting_provider = None;
+        capabilities.document_range_formatting_provider = None;
+    }
+    if !features.hover {
+        capabilities.hover_provider = None;
+    }
+    if !features.implementation {
+        capabilities.implementation_provider = None;
+    }
+    if !features.inlay_hint {
+        capabilities.inlay_hint_provider = None;
+    }
+    if !features.references {
+        capabilities.references_provider = None;
+    }
+    if !features.rename {
+        capabilities.rename_provider = None;
+    }
+    if !features.semantic_tokens {
+        capabilities.semantic_tokens_provider = None;
+    }
+    if !features.signature_help {
+        capabilities.signature_help_provider = None;
+    }
+    if !features.type_definition {
+        capabilities.type_definition_provider = None;
+    }
+    capabilities
+}
+
 pub fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
-        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![
+                CodeActionKind::QUICKFIX,
+                CodeActionKind::REFACTOR,
+                CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                CodeActionKind::SOURCE_FIX_ALL,
+            ]),
+            ..Default::default()
+        })),
         code_lens_provider: Some(CodeLensOptions {
-            resolve_provider: Some(false),
+            resolve_provider: Some(true),
         }),
         completion_provider: Some(CompletionOptions {
-            trigger_characters: Some(vec![".".to_string()]),
+            trigger_characters: Some(vec![".".to_string(), ":".to_string()]),
+            resolve_provider: Some(true),
             ..Default::default()
         }),
         definition_provider: Some(OneOf::Left(true)),
+        type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+        implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
         document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
         document_highlight_provider: Some(OneOf::Left(true)),
+        document_link_provider: Some(DocumentLinkOptions {
+            resolve_provider: Some(false),
+            ..Default::default()
+        }),
         document_symbol_provider: Some(OneOf::Left(true)),
         execute_command_provider: Some(ExecuteCommandOptions {
             commands: vec![],
@@ -56,6 +167,7 @@ pub fn server_capabilities() -> ServerCapabilities {
         }),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
         inlay_hint_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
         rename_provider: Some(OneOf::Right(RenameOptions {
             prepare_provider: Some(true),
             work_done_progress_options: WorkDoneProgressOptions {
@@ -69,10 +181,15 @@ pub fn server_capabilities() -> ServerCapabilities {
                     token_modifiers: capabilities::semantic_tokens::SUPPORTED_MODIFIERS.to_vec(),
                 },
                 range: Some(true),
+                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                 ..Default::default()
             }
             .into(),
         ),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+            ..Default::default()
+        }),
         text_document_sync: Some(TextDocumentSyncCapability::Kind(
             TextDocumentSyncKind::INCREMENTAL,
         )),
@@ -1,7 +1,17 @@
 //! sway-lsp extensions to the LSP.
 
-use lsp_types::{TextDocumentContentChangeEvent, TextDocumentIdentifier, Url};
+use crate::{
+    config::LevelFilterDef, server_state::LastCompilationState, utils::position_kind::PositionKind,
+};
+use lsp_types::{
+    notification::Notification, Diagnostic, Location, MarkupContent, Position, ProgressToken,
+    Range, SymbolKind, TextDocumentContentChangeEvent, TextDocumentIdentifier, Url,
+    WorkspaceDiagnosticReportPartialResult,
+};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use sway_utils::PerformanceData;
+use tracing::metadata::LevelFilter;
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -31,3 +41,599 @@ pub struct VisualizeParams {
 pub struct MetricsParams {
     pub text_document: TextDocumentIdentifier,
 }
+
+/// The result of a `sway/metrics` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsResult {
+    /// The performance data of the last compilation of each module, by the path of its file.
+    pub modules: Vec<(String, PerformanceData)>,
+    /// The number of times the garbage collection of the project has failed.
+    pub gc_failures: usize,
+    /// The number of compilations of the project that were cancelled by a newer one.
+    pub cancelled_compilations: usize,
+    /// The number of compilations of the project in which the compiler panicked or reported an internal error.
+    pub internal_compiler_errors: usize,
+    /// How long each phase of the last successful compilation of the project took.
+    pub phases: PhaseTimings,
+}
+
+/// The duration of each phase of a compilation, in milliseconds.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTimings {
+    /// The garbage collection before the compilation, if there was one.
+    pub garbage_collection_ms: Option<f64>,
+    /// Parsing and type checking, which are measured together.
+    pub compilation_ms: f64,
+    /// Collecting the tokens of the compiled programs.
+    pub traversal_ms: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The number of times to compile the project, which is capped at 100.
+    pub iterations: usize,
+    /// Whether to start each compilation with fresh engines, discarding any cached modules.
+    #[serde(default)]
+    pub clear_cache: bool,
+}
+
+/// Timing statistics, in milliseconds, collected by the `sway/benchmark` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkResult {
+    /// The time of each compilation, which is the sum of the durations of its phases.
+    pub samples: Vec<f64>,
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// The durations of the phases of each compilation, in the order of the samples.
+    pub phases: Vec<PhaseTimings>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfoParams {
+    /// A document of the project to report the toolchain of.
+    #[serde(default)]
+    pub text_document: Option<TextDocumentIdentifier>,
+}
+
+/// The result of a `sway/healthCheck` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthCheckResult {
+    pub compilation_thread_alive: bool,
+    /// The number of compilation requests waiting for the compilation thread.
+    pub queue_length: usize,
+    /// The `compilation.queueCapacity` the queue is capped at.
+    pub queue_capacity: usize,
+    /// The number of background compilation requests, like those of `prewarmProjects`, waiting for
+    /// the queue to be empty.
+    pub background_queue_length: usize,
+    /// The `compilation.backgroundQueueCapacity` the background queue is capped at.
+    pub background_queue_capacity: usize,
+    /// The number of compilation requests that were dropped because a newer one replaced them.
+    pub coalesced_compilations: usize,
+    pub is_compiling: bool,
+    pub last_compilation_state: LastCompilationState,
+    pub last_compilation_duration_ms: Option<f64>,
+}
+
+/// The result of a `sway/listSessions` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSessionsResult {
+    pub sessions: Vec<SessionInfo>,
+}
+
+/// The state of a single project in a [ListSessionsResult].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub manifest_dir: PathBuf,
+    /// The name of the project in its `Forc.toml`, if it can be read.
+    pub project_name: Option<String>,
+    /// The root of the workspace that the project is a member of, if any.
+    pub workspace_root: Option<PathBuf>,
+    pub is_compiling: bool,
+    pub last_compilation_state: LastCompilationState,
+    /// Whether the last compilation failed and navigation is answered from the tokens of an earlier one,
+    /// which may not match the current contents of the files.
+    pub stale_tokens: bool,
+    /// Changes each time a compilation replaces the engines, which invalidates the results cached against them.
+    pub engines_generation: u64,
+    /// The number of files of the project that have at least one error or warning.
+    pub files_with_diagnostics: usize,
+}
+
+/// The parameters of a `sway/setTraceLevel` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTraceLevelParams {
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    #[serde(with = "LevelFilterDef")]
+    pub level: LevelFilter,
+}
+
+/// The result of a `sway/monomorphizedType` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonomorphizedTypeResult {
+    /// The name of the called function.
+    pub function: String,
+    pub substitutions: Vec<TypeSubstitution>,
+}
+
+/// The parameters of a `sway/peekSignature` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeekSignatureParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// The result of a `sway/peekSignature` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeekSignatureResult {
+    /// The header of the declaration, e.g. `pub fn transfer(amount: u64) -> bool`.
+    pub signature: String,
+    /// The doc comment of the declaration, if it has one.
+    pub documentation: Option<String>,
+    /// The signature in a code block followed by the doc comment, as Markdown.
+    pub contents: MarkupContent,
+    /// The range of the token at the requested position.
+    pub range: Range,
+}
+
+/// The parameters of a `sway/typeOfExpression` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeOfExpressionParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The position whose scope the expression is type checked in.
+    pub position: Position,
+    pub expression: String,
+}
+
+/// The result of a `sway/typeOfExpression` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeOfExpressionResult {
+    /// The resolved type of the expression, if it type checks.
+    pub type_name: Option<String>,
+    /// The errors of type checking the expression, if it doesn't.
+    pub errors: Vec<String>,
+}
+
+/// A type parameter and the concrete type the compiler substituted it with, e.g. `T = u64`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeSubstitution {
+    pub type_parameter: String,
+    pub concrete_type: String,
+}
+
+/// The result of a `sway/serverInfo` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfoResult {
+    /// The version of the language server.
+    pub server_version: String,
+    /// The version of the compiler the language server is built with.
+    pub compiler_version: String,
+    /// The toolchain of the project that the requested document belongs to.
+    pub project: Option<ProjectToolchainInfo>,
+}
+
+/// The toolchain info resolved from a project's `Forc.toml`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectToolchainInfo {
+    pub name: String,
+    pub manifest_path: PathBuf,
+    /// The minimum `forc-version` required by the manifest, if any.
+    pub forc_version: Option<String>,
+    /// Whether the compiler the language server is built with satisfies `forc_version`.
+    pub compatible: bool,
+}
+
+/// The result of a `sway/recompileWorkspace` request.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecompileWorkspaceResult {
+    /// The number of projects a new compilation was started for.
+    pub compiled: usize,
+    /// The number of projects that were already compiling, which were awaited instead.
+    pub already_compiling: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDiagnosticsParams {
+    /// A file or directory of the project to export the diagnostics of.
+    pub uri: Url,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsDiffParams {
+    /// A file or directory of the project to diff the diagnostics of.
+    pub uri: Url,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitForCompilationParams {
+    /// A file or directory of the project to report the compilation state of.
+    /// If omitted, the state of the last compilation of any project is reported.
+    #[serde(default)]
+    pub uri: Option<Url>,
+    /// How long to wait before giving up with an error. Waits indefinitely if omitted.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// The result of a `sway/waitForCompilation` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitForCompilationResult {
+    pub last_compilation_state: LastCompilationState,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetSessionParams {
+    /// A file or directory of the project to reset the session of.
+    pub uri: Url,
+}
+
+/// The result of a `sway/resetSession` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetSessionResult {
+    /// Whether the project had a session to reset.
+    pub reset: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestForFileParams {
+    pub uri: Url,
+}
+
+/// The parameters of a `sway/tempWorkspacePath` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempWorkspacePathParams {
+    pub uri: Url,
+}
+
+/// The result of a `sway/tempWorkspacePath` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempWorkspacePathResult {
+    /// The directory of the project's `Forc.toml` in the user's workspace.
+    pub manifest_dir: PathBuf,
+    /// The directory that the project is cloned into and compiled from, which is `manifest_dir` itself
+    /// if the project is compiled in place.
+    pub temp_dir: PathBuf,
+}
+
+/// The parameters of a `sway/collectGarbage` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectGarbageParams {
+    /// A file of the project whose engines to garbage collect. Every project is collected if it's omitted.
+    pub uri: Option<Url>,
+}
+
+/// The result of a `sway/collectGarbage` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectGarbageResult {
+    /// The number of projects that were garbage collected and recompiled.
+    pub collected: usize,
+    /// How much the resident memory of the server decreased, if it could be measured. Freed memory isn't
+    /// always returned to the system right away, so this can be 0 even if memory was reclaimed.
+    pub reclaimed_bytes: Option<u64>,
+}
+
+/// The result of a `sway/memoryReport` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryReportResult {
+    pub sessions: Vec<SessionMemory>,
+    /// The resident memory of the server, if it could be measured.
+    pub resident_bytes: Option<u64>,
+}
+
+/// The memory held by a single project in a [MemoryReportResult].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMemory {
+    pub manifest_dir: PathBuf,
+    /// The number of types in the type engine.
+    pub types: usize,
+    /// The number of declarations in the decl engine.
+    pub declarations: usize,
+    /// The number of source files in the source engine.
+    pub sources: usize,
+    pub tokens: usize,
+    /// An estimate of the memory held by the types, declarations and tokens. Only the inline size of each
+    /// is counted, not what they point to, so the actual figure is higher.
+    pub approximate_bytes: usize,
+}
+
+/// The parameters of a `sway/semanticTokensRanges` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensRangesParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The ranges to get the tokens of, which may overlap.
+    pub ranges: Vec<Range>,
+}
+
+/// The parameters of a `sway/symbolsByKind` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolsByKindParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The kind of the symbols to return, as it would be reported by `textDocument/documentSymbol`.
+    pub kind: SymbolKind,
+}
+
+/// A declaration of the kind that a `sway/symbolsByKind` request asked for.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindSymbol {
+    pub name: String,
+    pub location: Location,
+}
+
+/// The parameters of a `sway/locateSymbol` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocateSymbolParams {
+    /// A document of the project to look for the symbol in.
+    pub uri: Url,
+    /// The fully qualified name of the symbol, e.g. `MyContract::transfer`. Leading segments can be left out.
+    pub name: String,
+    /// Whether to fail if several declarations match, rather than returning all of them.
+    #[serde(default)]
+    pub unique: bool,
+}
+
+/// The result of a `sway/locateSymbol` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocateSymbolResult {
+    /// The locations of the names of the matching declarations, sorted by file and position.
+    pub locations: Vec<Location>,
+}
+
+/// The parameters of a `sway/fileDiagnostics` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiagnosticsParams {
+    pub uri: Url,
+}
+
+/// The result of a `sway/fileDiagnostics` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiagnosticsResult {
+    /// The diagnostics of the file as of the last compilation, as they would be published.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The state of the last compilation. The diagnostics of a failed one may be incomplete, as those of
+    /// later stages of the compilation aren't produced.
+    pub last_compilation_state: LastCompilationState,
+}
+
+/// The result of a `sway/manifestForFile` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestForFileResult {
+    /// The directory of the `Forc.toml` of the package that the file belongs to.
+    pub manifest_dir: PathBuf,
+    /// The name of the package.
+    pub project_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordDocParams {
+    pub keyword: String,
+}
+
+/// The result of a `sway/keywordDoc` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordDocResult {
+    /// The documentation of the keyword, in Markdown.
+    pub documentation: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildProjectParams {
+    /// A document of the project to build.
+    pub uri: Url,
+}
+
+/// The result of a `sway/buildProject` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildProjectResult {
+    /// Whether `forc build` exited successfully.
+    pub success: bool,
+    /// The exit code of `forc build`, if it wasn't terminated by a signal.
+    pub exit_code: Option<i32>,
+    /// The number of errors that the build reported.
+    pub error_count: usize,
+    /// The number of warnings that the build reported.
+    pub warning_count: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyGraphParams {
+    /// The manifest directory or a document of the project.
+    pub uri: Url,
+}
+
+/// The result of a `sway/dependencyGraph` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyGraphResult {
+    /// The name of the project's package.
+    pub name: String,
+    /// The directory of the project's manifest.
+    pub path: PathBuf,
+    pub dependencies: Vec<DependencyNode>,
+}
+
+/// A dependency of a package, as declared in its `Forc.toml`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyNode {
+    /// The name the package is imported as.
+    pub name: String,
+    /// The name of the package, if it's imported under another one.
+    pub package: Option<String>,
+    /// The version that's required, if any.
+    pub version: Option<String>,
+    /// Where the dependency comes from: `path`, `git`, `ipfs` or `registry`.
+    pub source: String,
+    /// The path, repository with its branch, tag or revision, or CID that the dependency is declared with.
+    pub location: Option<String>,
+    /// The directory of the dependency, if it's available locally.
+    pub path: Option<PathBuf>,
+    /// The dependencies of the dependency, which are only known for those that are available locally.
+    pub dependencies: Vec<DependencyNode>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnclosingItemParams {
+    pub uri: Url,
+    pub position: Position,
+}
+
+/// The result of a `sway/enclosingItem` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnclosingItemResult {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The range of the whole item, including its body.
+    pub range: Range,
+    /// The name of the item that it's nested in, e.g. `impl Foo` for a method of `Foo`.
+    pub container_name: Option<String>,
+}
+
+/// The result of a `sway/clearDiagnostics` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearDiagnosticsResult {
+    /// The number of files whose diagnostics were cleared.
+    pub cleared_files: usize,
+}
+
+/// The result of a `sway/classifyPosition` request.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassifyPositionResult {
+    pub kind: PositionKind,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxTreeParams {
+    pub uri: Url,
+}
+
+/// The parameters of a `sway/dependencyChanged` notification.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyChangedParams {
+    /// The changed file, or the directory of the changed dependency.
+    pub uri: Url,
+}
+
+/// The `$/progress` notification that streams the reports of a `workspace/diagnostic` request
+/// to clients that asked for partial results.
+pub enum WorkspaceDiagnosticProgress {}
+
+impl Notification for WorkspaceDiagnosticProgress {
+    type Params = WorkspaceDiagnosticProgressParams;
+    const METHOD: &'static str = "$/progress";
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDiagnosticProgressParams {
+    pub token: ProgressToken,
+    pub value: WorkspaceDiagnosticReportPartialResult,
+}
+
+/// The `sway/projectStatus` notification, which is sent after the diagnostics of each compilation
+/// of a project are published, so that editors can summarize the state of the project.
+pub enum ProjectStatus {}
+
+impl Notification for ProjectStatus {
+    type Params = ProjectStatusParams;
+    const METHOD: &'static str = "sway/projectStatus";
+}
+
+/// The parameters of a `sway/projectStatus` notification.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStatusParams {
+    pub manifest_dir: PathBuf,
+    /// The number of errors in all files of the project.
+    pub error_count: usize,
+    /// The number of warnings in all files of the project.
+    pub warning_count: usize,
+    pub last_compilation_state: LastCompilationState,
+    /// Whether navigation is answered from the tokens of an earlier compilation, see [SessionInfo::stale_tokens].
+    pub stale_tokens: bool,
+}
+
+/// The `sway/compilationStatus` notification, which is sent when the compilation thread starts and finishes
+/// compiling a project, if `compilation.statusNotifications` is enabled.
+pub enum CompilationStatus {}
+
+impl Notification for CompilationStatus {
+    type Params = CompilationStatusParams;
+    const METHOD: &'static str = "sway/compilationStatus";
+}
+
+/// The parameters of a `sway/compilationStatus` notification.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilationStatusParams {
+    /// The file whose change triggered the compilation.
+    pub uri: Url,
+    /// The version of the file that is compiled, if the compilation was triggered by an edit.
+    pub version: Option<i32>,
+    pub status: CompilationStatusKind,
+    /// The state of the project once the transition happened. It's that of the previous compilation
+    /// when one starts or is cancelled.
+    pub last_compilation_state: LastCompilationState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompilationStatusKind {
+    Started,
+    Succeeded,
+    Failed,
+    /// The compilation was cancelled by a newer one, which is started next.
+    Cancelled,
+}
@@ -2,23 +2,48 @@
 //! It provides an interface between the LSP protocol and the sway-lsp internals.
 
 use crate::{
+    capabilities::diagnostic::{DiagnosticsDiff, DiagnosticsSnapshot},
     core::document,
     handlers::{notification, request},
-    lsp_ext::{MetricsParams, OnEnterParams, ShowAstParams, VisualizeParams},
+    lsp_ext::{
+        BenchmarkParams, BenchmarkResult, BuildProjectParams, BuildProjectResult,
+        ClassifyPositionResult, ClearDiagnosticsResult, CollectGarbageParams, CollectGarbageResult,
+        DependencyChangedParams, DependencyGraphParams, DependencyGraphResult,
+        DiagnosticsDiffParams, EnclosingItemParams, EnclosingItemResult, ExportDiagnosticsParams,
+        FileDiagnosticsParams, FileDiagnosticsResult, HealthCheckResult, KeywordDocParams,
+        KeywordDocResult, KindSymbol, ListSessionsResult, LocateSymbolParams, LocateSymbolResult,
+        ManifestForFileParams, ManifestForFileResult, MemoryReportResult, MetricsParams,
+        MetricsResult, MonomorphizedTypeResult, OnEnterParams, PeekSignatureParams,
+        PeekSignatureResult, RecompileWorkspaceResult, ResetSessionParams, ResetSessionResult,
+        SemanticTokensRangesParams, ServerInfoParams, ServerInfoResult, SetTraceLevelParams,
+        ShowAstParams, SymbolsByKindParams, SyntaxTreeParams, TempWorkspacePathParams,
+        TempWorkspacePathResult, TypeOfExpressionParams, TypeOfExpressionResult, VisualizeParams,
+        WaitForCompilationParams, WaitForCompilationResult,
+    },
     server_state::ServerState,
 };
 use lsp_types::{
-    CodeActionParams, CodeActionResponse, CodeLens, CodeLensParams, CompletionParams,
-    CompletionResponse, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
-    DocumentFormattingParams, DocumentHighlight, DocumentHighlightParams, DocumentSymbolParams,
-    DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverParams,
-    InitializeParams, InitializeResult, InitializedParams, InlayHint, InlayHintParams,
-    PrepareRenameResponse, RenameParams, SemanticTokensParams, SemanticTokensRangeParams,
-    SemanticTokensRangeResult, SemanticTokensResult, TextDocumentIdentifier,
-    TextDocumentPositionParams, TextEdit, WorkspaceEdit,
+    request::{
+        GotoImplementationParams, GotoImplementationResponse, GotoTypeDefinitionParams,
+        GotoTypeDefinitionResponse,
+    },
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CodeActionParams, CodeActionResponse, CodeLens, CodeLensParams, CompletionItem,
+    CompletionParams, CompletionResponse, DidChangeConfigurationParams,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentDiagnosticParams,
+    DocumentDiagnosticReportResult, DocumentFormattingParams, DocumentHighlight,
+    DocumentHighlightParams, DocumentLink, DocumentLinkParams, DocumentOnTypeFormattingParams,
+    DocumentRangeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverParams, InitializeParams,
+    InitializeResult, InitializedParams, InlayHint, InlayHintParams, Location,
+    PrepareRenameResponse, ReferenceParams, RenameParams, SemanticTokens,
+    SemanticTokensDeltaParams, SemanticTokensFullDeltaResult, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult, SignatureHelp,
+    SignatureHelpParams, TextDocumentIdentifier, TextDocumentPositionParams, TextEdit,
+    WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult, WorkspaceEdit,
 };
-use sway_utils::PerformanceData;
 use tower_lsp::{jsonrpc::Result, LanguageServer};
 
 #[tower_lsp::async_trait]
@@ -29,6 +54,9 @@ impl LanguageServer for ServerState {
 
     async fn initialized(&self, _: InitializedParams) {
         tracing::info!("Sway Language Server Initialized");
+        let _ = self.prewarm_sessions();
+        let _ = self.compile_workspace_on_open();
+        self.register_watched_files().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -45,6 +73,22 @@ impl LanguageServer for ServerState {
         if let Err(err) = document::remove_dirty_flag(&params.text_document.uri).await {
             tracing::error!("{}", err.to_string());
         }
+        self.semantic_tokens.remove(&params.text_document.uri);
+        // Versions start over when the document is opened again.
+        self.diagnostics_versions.remove(&params.text_document.uri);
+        if let Some(session) = self
+            .sessions
+            .session_dir_from_uri(&params.text_document.uri)
+            .ok()
+            .and_then(|dir| self.sessions.get(&dir).map(|item| item.value().clone()))
+        {
+            if let Ok(uri) = session
+                .sync
+                .workspace_to_temp_url(&params.text_document.uri)
+            {
+                session.compilation_cancellation.forget(&uri);
+            }
+        }
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -65,6 +109,10 @@ impl LanguageServer for ServerState {
         }
     }
 
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        notification::handle_did_change_configuration(self, params).await;
+    }
+
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         request::handle_hover(self, params).await
     }
@@ -77,10 +125,18 @@ impl LanguageServer for ServerState {
         request::handle_code_lens(self, params).await
     }
 
+    async fn code_lens_resolve(&self, params: CodeLens) -> Result<CodeLens> {
+        request::handle_code_lens_resolve(self, params).await
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         request::handle_completion(self, params).await
     }
 
+    async fn completion_resolve(&self, params: CompletionItem) -> Result<CompletionItem> {
+        request::handle_completion_resolve(self, params).await
+    }
+
     async fn document_symbol(
         &self,
         params: DocumentSymbolParams,
@@ -95,6 +151,13 @@ impl LanguageServer for ServerState {
         request::handle_semantic_tokens_full(self, params).await
     }
 
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        request::handle_semantic_tokens_full_delta(self, params).await
+    }
+
     async fn semantic_tokens_range(
         &self,
         params: SemanticTokensRangeParams,
@@ -109,6 +172,18 @@ impl LanguageServer for ServerState {
         request::handle_document_highlight(self, params).await
     }
 
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        request::handle_document_link(self, params).await
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        request::handle_references(self, params).await
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        request::handle_signature_help(self, params).await
+    }
+
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -116,10 +191,59 @@ impl LanguageServer for ServerState {
         request::handle_goto_definition(self, params).await
     }
 
+    async fn goto_type_definition(
+        &self,
+        params: GotoTypeDefinitionParams,
+    ) -> Result<Option<GotoTypeDefinitionResponse>> {
+        request::handle_goto_type_definition(self, params).await
+    }
+
+    async fn goto_implementation(
+        &self,
+        params: GotoImplementationParams,
+    ) -> Result<Option<GotoImplementationResponse>> {
+        request::handle_goto_implementation(self, params).await
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        request::handle_prepare_call_hierarchy(self, params).await
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        request::handle_incoming_calls(self, params).await
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        request::handle_outgoing_calls(self, params).await
+    }
+
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
         request::handle_formatting(self, params).await
     }
 
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        request::handle_range_formatting(self, params).await
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        request::handle_on_type_formatting(self, params).await
+    }
+
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
         request::handle_rename(self, params).await
     }
@@ -134,6 +258,20 @@ impl LanguageServer for ServerState {
     async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
         request::handle_inlay_hints(self, params).await
     }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        request::handle_document_diagnostic(self, params).await
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<WorkspaceDiagnosticReportResult> {
+        request::handle_workspace_diagnostic(self, params).await
+    }
 }
 
 // Custom LSP-Server Methods
@@ -150,10 +288,173 @@ impl ServerState {
         request::handle_visualize(self, params)
     }
 
-    pub async fn metrics(
-        &self,
-        params: MetricsParams,
-    ) -> Result<Option<Vec<(String, PerformanceData)>>> {
+    pub async fn metrics(&self, params: MetricsParams) -> Result<Option<MetricsResult>> {
         request::metrics(self, params).await
     }
+
+    pub async fn benchmark(&self, params: BenchmarkParams) -> Result<Option<BenchmarkResult>> {
+        request::handle_benchmark(self, params).await
+    }
+
+    pub async fn recompile_workspace(&self) -> Result<RecompileWorkspaceResult> {
+        request::handle_recompile_workspace(self).await
+    }
+
+    pub async fn monomorphized_type(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<MonomorphizedTypeResult>> {
+        request::handle_monomorphized_type(self, params).await
+    }
+
+    pub async fn peek_signature(
+        &self,
+        params: PeekSignatureParams,
+    ) -> Result<Option<PeekSignatureResult>> {
+        request::handle_peek_signature(self, params).await
+    }
+
+    pub async fn type_of_expression(
+        &self,
+        params: TypeOfExpressionParams,
+    ) -> Result<Option<TypeOfExpressionResult>> {
+        request::handle_type_of_expression(self, params).await
+    }
+
+    pub async fn health_check(&self) -> Result<HealthCheckResult> {
+        request::handle_health_check(self)
+    }
+
+    pub async fn list_sessions(&self) -> Result<ListSessionsResult> {
+        request::handle_list_sessions(self)
+    }
+
+    pub async fn memory_report(&self) -> Result<Option<MemoryReportResult>> {
+        request::handle_memory_report(self)
+    }
+
+    pub async fn locate_symbol(&self, params: LocateSymbolParams) -> Result<LocateSymbolResult> {
+        request::handle_locate_symbol(self, params).await
+    }
+
+    pub async fn server_info(&self, params: ServerInfoParams) -> Result<ServerInfoResult> {
+        request::handle_server_info(params)
+    }
+
+    pub async fn set_trace_level(&self, params: SetTraceLevelParams) -> Result<()> {
+        request::handle_set_trace_level(self, params)
+    }
+
+    pub async fn wait_for_compilation(
+        &self,
+        params: WaitForCompilationParams,
+    ) -> Result<WaitForCompilationResult> {
+        request::handle_wait_for_compilation(self, params).await
+    }
+
+    pub async fn export_diagnostics(
+        &self,
+        params: ExportDiagnosticsParams,
+    ) -> Result<Option<DiagnosticsSnapshot>> {
+        request::handle_export_diagnostics(self, params).await
+    }
+
+    pub async fn manifest_for_file(
+        &self,
+        params: ManifestForFileParams,
+    ) -> Result<ManifestForFileResult> {
+        request::handle_manifest_for_file(params)
+    }
+
+    pub async fn temp_workspace_path(
+        &self,
+        params: TempWorkspacePathParams,
+    ) -> Result<TempWorkspacePathResult> {
+        request::handle_temp_workspace_path(self, params)
+    }
+
+    pub async fn collect_garbage(
+        &self,
+        params: CollectGarbageParams,
+    ) -> Result<CollectGarbageResult> {
+        request::handle_collect_garbage(self, params).await
+    }
+
+    pub async fn semantic_tokens_ranges(
+        &self,
+        params: SemanticTokensRangesParams,
+    ) -> Result<Option<SemanticTokens>> {
+        request::handle_semantic_tokens_ranges(self, params).await
+    }
+
+    pub async fn symbols_by_kind(
+        &self,
+        params: SymbolsByKindParams,
+    ) -> Result<Option<Vec<KindSymbol>>> {
+        request::handle_symbols_by_kind(self, params).await
+    }
+
+    pub async fn file_diagnostics(
+        &self,
+        params: FileDiagnosticsParams,
+    ) -> Result<FileDiagnosticsResult> {
+        request::handle_file_diagnostics(self, params)
+    }
+
+    pub async fn diagnostics_diff(
+        &self,
+        params: DiagnosticsDiffParams,
+    ) -> Result<Option<DiagnosticsDiff>> {
+        request::handle_diagnostics_diff(self, params).await
+    }
+
+    pub async fn keyword_doc(&self, params: KeywordDocParams) -> Result<KeywordDocResult> {
+        request::handle_keyword_doc(self, params)
+    }
+
+    pub async fn reset_session(&self, params: ResetSessionParams) -> Result<ResetSessionResult> {
+        request::handle_reset_session(self, params).await
+    }
+
+    pub async fn build_project(
+        &self,
+        params: BuildProjectParams,
+    ) -> Result<Option<BuildProjectResult>> {
+        request::handle_build_project(self, params).await
+    }
+
+    pub async fn dependency_graph(
+        &self,
+        params: DependencyGraphParams,
+    ) -> Result<Option<DependencyGraphResult>> {
+        request::handle_dependency_graph(params).await
+    }
+
+    pub async fn dependency_changed(&self, params: DependencyChangedParams) {
+        if let Err(err) = notification::handle_dependency_changed(self, params).await {
+            tracing::error!("{}", err.to_string());
+        }
+    }
+
+    pub async fn enclosing_item(
+        &self,
+        params: EnclosingItemParams,
+    ) -> Result<Option<EnclosingItemResult>> {
+        request::handle_enclosing_item(self, params).await
+    }
+
+    pub async fn clear_diagnostics(&self) -> Result<ClearDiagnosticsResult> {
+        request::handle_clear_diagnostics(self).await
+    }
+
+    pub async fn classify_position(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<ClassifyPositionResult>> {
+        request::handle_classify_position(self, params).await
+    }
+
+    pub async fn syntax_tree(&self, params: SyntaxTreeParams) -> Result<Option<serde_json::Value>> {
+        request::handle_syntax_tree(self, params).await
+    }
 }
@@ -1,27 +1,54 @@
 //! The context or environment in which the language server functions.
 
 use crate::{
-    config::{Config, Warnings},
-    core::session::{self, ParseResult, Session},
+    capabilities::diagnostic::{self, DiagnosticsSnapshot, FileDiagnostics, PulledDiagnostics},
+    config::{Config, RootDetection, Warnings, WorkspaceConfig},
+    core::{
+        document::TextDocument,
+        session::{self, ParseResult, Session, EDIT_BURST_INTERVAL},
+    },
     error::{DirectoryError, DocumentError, LanguageServerError},
+    lsp_ext::{self, HealthCheckResult},
+    utils::client_capabilities::ClientCapabilities,
     utils::debug,
     utils::keyword_docs::KeywordDocs,
+    utils::logging,
+    utils::position_encoding::PositionEncoding,
+};
+use crossbeam_channel::{Receiver, RecvError, RecvTimeoutError, Select, Sender, TryRecvError};
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use forc_pkg::{manifest::ManifestFile, PackageManifestFile};
+use forc_tracing::TracingFilterHandle;
+use lsp_types::{
+    notification::{DidChangeWatchedFiles, Notification, Progress},
+    request::WorkDoneProgressCreate,
+    Diagnostic, DiagnosticSeverity, DidChangeWatchedFilesRegistrationOptions, FileSystemWatcher,
+    GlobPattern, MessageType, NumberOrString, Position, ProgressParams, ProgressParamsValue, Range,
+    Registration, SemanticTokens, TextDocumentContentChangeEvent, Url, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
 };
-use crossbeam_channel::{Receiver, Sender};
-use dashmap::DashMap;
-use forc_pkg::PackageManifestFile;
-use lsp_types::{Diagnostic, Url};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use std::{
+    any::Any,
+    future::Future,
     mem,
-    path::PathBuf,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
+    thread,
+    time::{Duration, Instant},
 };
-use tokio::sync::Notify;
+use sway_core::Engines;
+use sway_error::error::CompileError;
+use sysinfo::{ProcessExt, System, SystemExt};
+use tokio::sync::{Notify, Semaphore};
 use tower_lsp::{jsonrpc, Client};
+use tracing::metadata::LevelFilter;
 
 /// `ServerState` is the primary mutable state of the language server
 pub struct ServerState {
@@ -29,28 +56,100 @@ pub struct ServerState {
     pub(crate) config: Arc<RwLock<Config>>,
     pub(crate) keyword_docs: Arc<KeywordDocs>,
     pub(crate) sessions: Arc<Sessions>,
-    pub(crate) retrigger_compilation: Arc<AtomicBool>,
+    // The session that the compilation thread is compiling, so that its compilation can be cancelled
+    // regardless of the versions, e.g. on shutdown.
+    pub(crate) compiling_session: Arc<RwLock<Option<Arc<Session>>>>,
     pub is_compiling: Arc<AtomicBool>,
+    // The queue of requests for the compilation thread. It's unbounded so that sending never blocks,
+    // and its length is instead capped at `compilation.queueCapacity` by [ServerState::enqueue_compilation].
     pub(crate) cb_tx: Sender<TaskMessage>,
     pub(crate) cb_rx: Arc<Receiver<TaskMessage>>,
+    // The queue of background compilations, like those of `prewarmProjects`, which the compilation thread
+    // only takes from while `cb_rx` is empty. Its length is capped at `compilation.backgroundQueueCapacity`.
+    pub(crate) background_tx: Sender<TaskMessage>,
+    pub(crate) background_rx: Arc<Receiver<TaskMessage>>,
     pub(crate) finished_compilation: Arc<Notify>,
+    // The workspace url of the file that was most recently edited.
+    pub(crate) active_file: Arc<RwLock<Option<Url>>>,
     last_compilation_state: Arc<RwLock<LastCompilationState>>,
+    last_compilation_duration: Arc<RwLock<Option<Duration>>>,
+    compilation_thread_alive: Arc<AtomicBool>,
+    // The handle used to change the level of the tracing subscriber at runtime.
+    tracing_filter: Arc<RwLock<Option<TracingFilterHandle>>>,
+    // The number of compilation requests that were dropped because a newer one replaced them in the queue.
+    coalesced_compilations: Arc<AtomicUsize>,
+    // The directories of the files that the user has been told aren't part of a Forc project.
+    missing_manifest_warnings: Arc<DashSet<PathBuf>>,
+    // Whether the user has been told that files outside the workspace folders aren't analyzed.
+    outside_workspace_warned: Arc<AtomicBool>,
+    // The manifests that a parse error was published for, so that it can be cleared once they're fixed.
+    pub(crate) manifest_errors: Arc<DashSet<PathBuf>>,
+    // The semantic tokens last sent to the client for each open file, used to compute deltas, along with
+    // the engines generation of the session they were computed from.
+    pub(crate) semantic_tokens: Arc<DashMap<Url, (u64, SemanticTokens)>>,
+    // The optional features that the client advertised in `initialize`.
+    pub(crate) client_capabilities: Arc<RwLock<ClientCapabilities>>,
+    // The workspace folders that the client opened, or its root folder if it doesn't support several.
+    pub(crate) workspace_folders: Arc<RwLock<Vec<Url>>>,
+    // The encoding of the positions exchanged with the client, which is negotiated in `initialize`.
+    pub(crate) position_encoding: Arc<RwLock<PositionEncoding>>,
+    // The watched file changes of each project that haven't been handled yet, by its manifest dir.
+    pub(crate) pending_file_changes: Arc<DashMap<PathBuf, PendingFileChanges>>,
+    // The newest document version that diagnostics were published for, by workspace url.
+    pub(crate) diagnostics_versions: Arc<DashMap<Url, i32>>,
+    // The workspace urls of the files that diagnostics were published for, so that they can all be cleared.
+    pub(crate) published_diagnostics: Arc<DashSet<Url>>,
+    // The diagnostics last pulled by the client for each file.
+    pub(crate) pulled_diagnostics: Arc<PulledDiagnostics>,
 }
 
 impl Default for ServerState {
     fn default() -> Self {
-        let (cb_tx, cb_rx) = crossbeam_channel::bounded(1);
+        ServerState::with_client(None)
+    }
+}
+
+impl ServerState {
+    pub fn new(client: Client) -> ServerState {
+        ServerState::with_client(Some(client))
+    }
+
+    // The client must be set before the compilation thread is spawned, as the thread
+    // publishes diagnostics with its own copy of it.
+    fn with_client(client: Option<Client>) -> ServerState {
+        let (cb_tx, cb_rx) = crossbeam_channel::unbounded();
+        let (background_tx, background_rx) = crossbeam_channel::unbounded();
+        let config = Arc::new(RwLock::new(Config::default()));
+        let sessions = Arc::new(Sessions::new(config.clone()));
         let state = ServerState {
-            client: None,
-            config: Arc::new(RwLock::new(Default::default())),
+            client,
+            config,
             keyword_docs: Arc::new(KeywordDocs::new()),
-            sessions: Arc::new(Sessions(DashMap::new())),
-            retrigger_compilation: Arc::new(AtomicBool::new(false)),
+            workspace_folders: sessions.workspace_folders.clone(),
+            position_encoding: sessions.position_encoding.clone(),
+            sessions,
+            compiling_session: Arc::new(RwLock::new(None)),
             is_compiling: Arc::new(AtomicBool::new(false)),
             cb_tx,
             cb_rx: Arc::new(cb_rx),
+            background_tx,
+            background_rx: Arc::new(background_rx),
             finished_compilation: Arc::new(Notify::new()),
+            active_file: Arc::new(RwLock::new(None)),
             last_compilation_state: Arc::new(RwLock::new(LastCompilationState::Uninitialized)),
+            last_compilation_duration: Arc::new(RwLock::new(None)),
+            compilation_thread_alive: Arc::new(AtomicBool::new(false)),
+            tracing_filter: Arc::new(RwLock::new(None)),
+            coalesced_compilations: Arc::new(AtomicUsize::new(0)),
+            missing_manifest_warnings: Arc::new(DashSet::new()),
+            outside_workspace_warned: Arc::new(AtomicBool::new(false)),
+            manifest_errors: Arc::new(DashSet::new()),
+            semantic_tokens: Arc::new(DashMap::new()),
+            client_capabilities: Arc::new(RwLock::new(ClientCapabilities::default())),
+            pending_file_changes: Arc::new(DashMap::new()),
+            diagnostics_versions: Arc::new(DashMap::new()),
+            published_diagnostics: Arc::new(DashSet::new()),
+            pulled_diagnostics: Arc::new(PulledDiagnostics::default()),
         };
         // Spawn a new thread dedicated to handling compilation tasks
         state.spawn_compilation_thread();
@@ -60,13 +159,25 @@ impl Default for ServerState {
 
 /// `LastCompilationState` represents the state of the last compilation process.
 /// It is primarily used for debugging purposes.
-#[derive(Debug, PartialEq)]
-enum LastCompilationState {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LastCompilationState {
     Success,
     Failed,
     Uninitialized,
 }
 
+/// The changes to the watched files of a project that are waiting for a burst of events to end.
+#[derive(Debug, Default)]
+pub(crate) struct PendingFileChanges {
+    /// Incremented by each notification, so that only the last one of a burst handles the changes.
+    pub(crate) generation: u64,
+    /// Whether the project's `Forc.toml` was changed, which requires a new session.
+    pub(crate) manifest_changed: bool,
+    /// The workspace urls of the changed sway files.
+    pub(crate) files: Vec<Url>,
+}
+
 /// `TaskMessage` represents the set of messages or commands that can be sent to and processed by a worker thread in the compilation environment.
 #[derive(Debug)]
 pub enum TaskMessage {
@@ -82,85 +193,1069 @@ pub struct CompilationContext {
     pub session: Option<Arc<Session>>,
     pub uri: Option<Url>,
     pub version: Option<i32>,
+    pub priority: CompilationPriority,
 }
 
-impl ServerState {
-    pub fn new(client: Client) -> ServerState {
-        ServerState {
-            client: Some(client),
-            ..Default::default()
+/// Which queue of the compilation thread a [CompilationContext] waits in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompilationPriority {
+    /// A compilation of the project the user is working on, e.g. after an edit.
+    #[default]
+    Interactive,
+    /// A compilation that warms up a project, e.g. for `prewarmProjects` or `sway/recompileWorkspace`.
+    /// It's only started while no interactive compilation is waiting.
+    Background,
+}
+
+/// The state of the server that the compilation thread shares, which it compiles the queued
+/// [CompilationContext]s with.
+struct CompilationWorker {
+    is_compiling: Arc<AtomicBool>,
+    compiling_session: Arc<RwLock<Option<Arc<Session>>>>,
+    finished_compilation: Arc<Notify>,
+    rx: Arc<Receiver<TaskMessage>>,
+    background_rx: Arc<Receiver<TaskMessage>>,
+    last_compilation_state: Arc<RwLock<LastCompilationState>>,
+    last_compilation_duration: Arc<RwLock<Option<Duration>>>,
+    client: Option<Client>,
+    active_file: Arc<RwLock<Option<Url>>>,
+    config: Arc<RwLock<Config>>,
+    diagnostics_versions: Arc<DashMap<Url, i32>>,
+    published_diagnostics: Arc<DashSet<Url>>,
+    client_capabilities: Arc<RwLock<ClientCapabilities>>,
+    // Used to publish diagnostics from the compilation thread.
+    runtime: Option<tokio::runtime::Handle>,
+    // Set when the server is over `maxMemoryMb`, either before a compilation or by the memory guard thread.
+    memory_limit_exceeded: Arc<AtomicBool>,
+}
+
+impl CompilationWorker {
+    /// Handles the messages of the compilation queues, interactive ones first, until it's told to
+    /// terminate or the queues are disconnected.
+    fn run(&self) {
+        // When the compile time was last shown, so that rapid recompilations don't flood the client with messages.
+        let mut compile_time_shown_at = None;
+        // The config is only known once the client has initialized, after the thread was spawned.
+        let mut priority_lowered = false;
+        while let Ok(msg) = recv_by_priority(&self.rx, &self.background_rx) {
+            if !priority_lowered && self.config.read().low_priority_compilation {
+                lower_current_thread_priority();
+                priority_lowered = true;
+            }
+            match msg {
+                TaskMessage::CompilationContext(ctx) => {
+                    self.compile(&ctx, &mut compile_time_shown_at);
+                }
+                TaskMessage::Terminate => {
+                    // If we receive a terminate message, we need to exit the thread
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the client along with the runtime to send it messages from the compilation thread, if there is one.
+    fn client_and_runtime(&self) -> Option<(&Client, &tokio::runtime::Handle)> {
+        self.client.as_ref().zip(self.runtime.as_ref())
+    }
+
+    /// Compiles the project of `ctx` and publishes its diagnostics, unless a newer version of the file
+    /// has been requested since.
+    fn compile(&self, ctx: &CompilationContext, compile_time_shown_at: &mut Option<Instant>) {
+        let uri = ctx.uri.as_ref().unwrap().clone();
+        let session = ctx.session.as_ref().unwrap().clone();
+        // A newer version of the file has been requested since, whose compilation is queued.
+        if !session.compilation_cancellation.start(&uri, ctx.version) {
+            session
+                .cancelled_compilations
+                .fetch_add(1, Ordering::SeqCst);
+            self.notify_finished(&session);
+            return;
+        }
+        *self.compiling_session.write() = Some(session.clone());
+        let mut engines_clone = session.engines.read().clone();
+        let gc_duration = self.collect_garbage(&session, &mut engines_clone, ctx.version);
+
+        // Set the is_compiling flag to true so that the wait_for_parsing function knows that we are compiling
+        self.is_compiling.store(true, Ordering::SeqCst);
+        session.is_compiling.store(true, Ordering::SeqCst);
+        let workspace_uri = session.sync.temp_to_workspace_url(&uri).ok();
+        let status_uri = workspace_uri.as_ref().unwrap_or(&uri);
+        let status_notifications = session.config.read().compilation.status_notifications;
+        if status_notifications {
+            self.send_status(
+                &session,
+                status_uri,
+                ctx.version,
+                lsp_ext::CompilationStatusKind::Started,
+            );
+        }
+        // This compilation includes the changes of every compilation requested so far.
+        let generation = {
+            let mut generations = session.compilation_generations.lock();
+            generations.started = generations.requested;
+            generations.started
+        };
+
+        // If enabled, publish the parse errors straight away, as type checking can take a while.
+        // Clients that pull diagnostics ask for them once the compilation has finished instead.
+        let capabilities = self.client_capabilities.read().clone();
+        let streaming = !capabilities.pull_diagnostics
+            && session.config.read().experimental.streaming_diagnostics;
+        if let Some((client, runtime)) = self.client_and_runtime().filter(|_| streaming) {
+            runtime.block_on(publish_parse_diagnostics(
+                client,
+                &capabilities,
+                &session,
+                &self.diagnostics_versions,
+                &self.published_diagnostics,
+                workspace_uri.as_ref().zip(ctx.version),
+            ));
+        }
+
+        // Make sure that the compiler sees the contents of the editor rather than of the disk.
+        if let Err(err) = session.write_unsaved_documents(&engines_clone) {
+            tracing::error!("Unable to write unsaved documents: {}", err);
+        }
+
+        // Don't start compiling if the server is already over the memory limit.
+        let max_memory_mb = self.config.read().max_memory_mb;
+        self.memory_limit_exceeded.store(
+            max_memory_mb.is_some_and(|limit| exceeds_memory_limit(&mut System::new(), limit)),
+            Ordering::SeqCst,
+        );
+        *session.memory_limit_exceeded.write() = None;
+
+        let mut parse_result = ParseResult::default();
+        parse_result.phase_durations.garbage_collection = gc_duration;
+        let start = Instant::now();
+        let result = self.run_compilation(&session, &uri, &engines_clone, &mut parse_result);
+        let timed_out = matches!(result, Err(LanguageServerError::CompilationTimedOut { .. }));
+        self.report_internal_compiler_error(
+            &session,
+            &result,
+            &parse_result,
+            status_uri,
+            ctx.version,
+        );
+        let succeeded = result.is_ok();
+        self.finish_compilation(
+            &session,
+            &uri,
+            succeeded,
+            engines_clone,
+            &mut parse_result,
+            max_memory_mb,
+        );
+        let compilation_duration = start.elapsed();
+        *self.last_compilation_duration.write() = Some(compilation_duration);
+        self.publish_diagnostics(
+            &session,
+            &uri,
+            workspace_uri.as_ref(),
+            ctx.version,
+            &capabilities,
+            streaming,
+        );
+
+        // A compilation that was cancelled by a newer one doesn't count as finished, unlike one
+        // that was aborted for exceeding the memory limit or `compileTimeoutMs`, which won't be retried.
+        *self.compiling_session.write() = None;
+        let cancelled = session.compilation_cancellation.finish()
+            && !self.memory_limit_exceeded.load(Ordering::SeqCst)
+            && !timed_out;
+        if cancelled {
+            session
+                .cancelled_compilations
+                .fetch_add(1, Ordering::SeqCst);
+            if let Some((client, runtime)) = self
+                .client_and_runtime()
+                .filter(|_| session.config.read().debug.log_cancelled_compilations)
+            {
+                runtime.block_on(log_cancelled_compilation(
+                    client,
+                    &session,
+                    &uri,
+                    ctx.version,
+                ));
+            }
+        } else {
+            let mut generations = session.compilation_generations.lock();
+            generations.finished = generations.finished.max(generation);
+        }
+        if status_notifications {
+            let status = if cancelled {
+                lsp_ext::CompilationStatusKind::Cancelled
+            } else if succeeded {
+                lsp_ext::CompilationStatusKind::Succeeded
+            } else {
+                lsp_ext::CompilationStatusKind::Failed
+            };
+            self.send_status(&session, status_uri, ctx.version, status);
+        }
+        if !cancelled && session.config.read().debug.show_compile_time {
+            if let Some((client, runtime)) = self.client_and_runtime() {
+                if throttle_compile_time_message(compile_time_shown_at, Instant::now()) {
+                    runtime.block_on(show_compile_time(client, &session, compilation_duration));
+                }
+            }
+        }
+        self.notify_finished(&session);
+    }
+
+    /// Garbage collects the modules of `session` from `engines` every 10th keystroke, or if it was
+    /// requested explicitly, and returns how long it took.
+    fn collect_garbage(
+        &self,
+        session: &Session,
+        engines: &mut Engines,
+        version: Option<i32>,
+    ) -> Option<Duration> {
+        // Garbage collection is fairly expsensive so we only clear on every 10th keystroke,
+        // unless it was requested explicitly.
+        let collect_garbage = session
+            .garbage_collection_requested
+            .swap(false, Ordering::SeqCst)
+            || version.is_some_and(|version| version % 10 == 0);
+        if !collect_garbage {
+            return None;
+        }
+        // Call this on the engines clone so we don't clear types that are still in use
+        // and might be needed in the case cancel compilation was triggered.
+        let gc_start = Instant::now();
+        let gc_result = session.garbage_collect(engines);
+        let gc_duration = gc_start.elapsed();
+        if let Err(err) = gc_result {
+            tracing::error!("Unable to perform garbage collection: {}", err.to_string());
+            if let Some((client, runtime)) = self.client_and_runtime() {
+                runtime.block_on(report_garbage_collection_failure(client, session, &err));
+            } else {
+                session.gc_failures.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        Some(gc_duration)
+    }
+
+    /// Parses and type checks the project of `session` into `parse_result`, retrying up to `compileRetries`
+    /// times on transient errors, and aborting once `compileTimeoutMs` is exceeded.
+    fn run_compilation(
+        &self,
+        session: &Arc<Session>,
+        uri: &Url,
+        engines: &Engines,
+        parse_result: &mut ParseResult,
+    ) -> Result<(), LanguageServerError> {
+        let watchdog = session
+            .config
+            .read()
+            .compile_timeout_ms
+            .map(|timeout_ms| CompileWatchdog::start(session.clone(), timeout_ms));
+        let result = if self.memory_limit_exceeded.load(Ordering::SeqCst) {
+            Err(LanguageServerError::MemoryLimitExceeded)
+        } else {
+            let build_config = session.config.read().build.clone();
+            let compile_retries = session.config.read().compile_retries;
+            let mut attempt = 0;
+            loop {
+                // A panic of the compiler fails the compilation rather than the thread.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    session::parse_project(
+                        uri,
+                        engines,
+                        session.position_encoding(),
+                        &build_config,
+                        Some(session.compilation_cancellation.flag()),
+                        &mut *parse_result,
+                    )
+                }))
+                .unwrap_or_else(|payload| {
+                    Err(LanguageServerError::CompilerPanicked(panic_message(
+                        &*payload,
+                    )))
+                });
+                // A newer compilation will start over anyway, so there's no point in retrying.
+                match result {
+                    Err(err)
+                        if err.is_transient()
+                            && attempt < compile_retries
+                            && !session.compilation_cancellation.is_cancelled() =>
+                    {
+                        attempt += 1;
+                        tracing::warn!(
+                            "Compilation failed, retrying ({}/{}): {}",
+                            attempt,
+                            compile_retries,
+                            err
+                        );
+                        std::thread::sleep(compile_retry_backoff(attempt));
+                    }
+                    result => break result,
+                }
+            }
+        };
+        // The compilation may have finished just as it timed out, in which case its result stands.
+        let timeout_ms = watchdog
+            .and_then(CompileWatchdog::finish)
+            .filter(|_| result.is_err());
+        match timeout_ms {
+            Some(timeout_ms) => {
+                tracing::error!("Compilation of {} was aborted after {}ms", uri, timeout_ms);
+                Err(LanguageServerError::CompilationTimedOut { timeout_ms })
+            }
+            None => result,
+        }
+    }
+
+    /// Logs an internal compiler error or panic of the compilation, and shows the first one of the project to the user.
+    fn report_internal_compiler_error(
+        &self,
+        session: &Session,
+        result: &Result<(), LanguageServerError>,
+        parse_result: &ParseResult,
+        workspace_uri: &Url,
+        version: Option<i32>,
+    ) {
+        let internal_error = match result {
+            Err(LanguageServerError::CompilerPanicked(message)) => Some(message.clone()),
+            _ => internal_compiler_error(&parse_result.diagnostics.0),
+        };
+        let Some(message) = internal_error else {
+            return;
+        };
+        let occurrences = session
+            .internal_compiler_errors
+            .fetch_add(1, Ordering::SeqCst);
+        tracing::error!(
+            "Internal compiler error while compiling {} (version {:?}): {}",
+            workspace_uri,
+            version,
+            message
+        );
+        // Only the first one of a project is shown, as it usually recurs on every edit.
+        if occurrences == 0 {
+            if let Some((client, runtime)) = self.client_and_runtime() {
+                runtime.block_on(client.show_message(
+                    MessageType::ERROR,
+                    internal_compiler_error_message(workspace_uri, version, &message),
+                ));
+            }
+        }
+    }
+
+    /// Writes the results of the compilation to `session`.
+    ///
+    /// If it succeeded, its engines and tokens replace those of the session. Otherwise, the tokens of the last
+    /// successful compilation are kept, but the errors that made this one fail are reported.
+    fn finish_compilation(
+        &self,
+        session: &Session,
+        uri: &Url,
+        succeeded: bool,
+        mut engines: Engines,
+        parse_result: &mut ParseResult,
+        max_memory_mb: Option<u64>,
+    ) {
+        if succeeded {
+            mem::swap(&mut *session.engines.write(), &mut engines);
+            session.write_parse_result(parse_result);
+            // Only once the new tokens are written, so that results computed from
+            // the old ones are never cached under the new generation.
+            session.bump_engines_generation();
+            *self.last_compilation_state.write() = LastCompilationState::Success;
+            *session.last_compilation_state.write() = LastCompilationState::Success;
+            write_diagnostics_snapshot(session);
+            session
+                .diagnostics_history
+                .lock()
+                .record(diagnostics_snapshot(session));
+            return;
+        }
+        *self.last_compilation_state.write() = LastCompilationState::Failed;
+        *session.last_compilation_state.write() = LastCompilationState::Failed;
+        if !parse_result.diagnostics.0.is_empty() {
+            session.write_failed_parse_result(parse_result, engines.se());
+            write_diagnostics_snapshot(session);
+            session
+                .diagnostics_history
+                .lock()
+                .record(diagnostics_snapshot(session));
+        }
+        if self.memory_limit_exceeded.load(Ordering::SeqCst) {
+            if let Some(limit) = max_memory_mb {
+                *session.memory_limit_exceeded.write() = Some((uri.clone(), limit));
+            }
+        }
+    }
+
+    /// Publishes the diagnostics of the compilation if they're streamed, or with `diagnostic.fastPath`,
+    /// unless the client pulls them.
+    fn publish_diagnostics(
+        &self,
+        session: &Session,
+        uri: &Url,
+        workspace_uri: Option<&Url>,
+        version: Option<i32>,
+        capabilities: &ClientCapabilities,
+        streaming: bool,
+    ) {
+        // With `diagnostic.fastPath`, the diagnostics of each edit are published as well, but only
+        // those of the edited file while the user is typing in quick succession.
+        let fast_path = version.is_some() && session.config.read().diagnostic.fast_path;
+        let publish = streaming || (fast_path && !capabilities.pull_diagnostics);
+        // Replace the parse errors with the full diagnostics, even if the compilation failed,
+        // so that no partial diagnostics are left behind.
+        let Some((client, runtime)) = self.client_and_runtime().filter(|_| publish) else {
+            return;
+        };
+        let trigger = workspace_uri.map(|workspace_uri| (uri.clone(), workspace_uri.clone()));
+        let mut files =
+            diagnostics_publish_order(trigger, session, self.active_file.read().clone());
+        let in_burst = fast_path && session.edit_burst.lock().is_active(Instant::now());
+        if in_burst {
+            files.retain(|(_, file)| Some(file) == workspace_uri);
+        }
+        session
+            .diagnostics_sweep_pending
+            .store(in_burst, Ordering::SeqCst);
+        // The diagnostics of the file that triggered the compilation are published with its version.
+        runtime.block_on(publish_files_diagnostics(
+            client,
+            capabilities,
+            session,
+            files,
+            &self.diagnostics_versions,
+            &self.published_diagnostics,
+            workspace_uri.zip(version),
+        ));
+    }
+
+    /// Sends a `sway/compilationStatus` notification of the compilation of `uri`.
+    fn send_status(
+        &self,
+        session: &Session,
+        uri: &Url,
+        version: Option<i32>,
+        status: lsp_ext::CompilationStatusKind,
+    ) {
+        if let Some((client, runtime)) = self.client_and_runtime() {
+            runtime.block_on(send_compilation_status(
+                client, session, uri, version, status,
+            ));
+        }
+    }
+
+    /// Resets the compiling flags after a compilation of `session`, and wakes those waiting for it.
+    fn notify_finished(&self, session: &Session) {
+        self.is_compiling.store(false, Ordering::SeqCst);
+        session.is_compiling.store(false, Ordering::SeqCst);
+        session.finished_compilation.notify_waiters();
+
+        // Make sure there isn't any pending compilation work
+        if self.rx.is_empty() && self.background_rx.is_empty() {
+            // finished compilation, notify waiters
+            self.finished_compilation.notify_waiters();
         }
     }
+}
 
+impl ServerState {
     /// Spawns a new thread dedicated to handling compilation tasks. This thread listens for
     /// `TaskMessage` instances sent over a channel and processes them accordingly.
     ///
     /// This approach allows for asynchronous compilation tasks to be handled in parallel to
     /// the main application flow, improving efficiency and responsiveness.
     pub fn spawn_compilation_thread(&self) {
-        let is_compiling = self.is_compiling.clone();
-        let retrigger_compilation = self.retrigger_compilation.clone();
-        let finished_compilation = self.finished_compilation.clone();
-        let rx = self.cb_rx.clone();
-        let last_compilation_state = self.last_compilation_state.clone();
-        std::thread::spawn(move || {
-            while let Ok(msg) = rx.recv() {
-                match msg {
-                    TaskMessage::CompilationContext(ctx) => {
-                        let uri = ctx.uri.as_ref().unwrap().clone();
-                        let session = ctx.session.as_ref().unwrap().clone();
-                        let mut engines_clone = session.engines.read().clone();
-
-                        if let Some(version) = ctx.version {
-                            // Garbage collection is fairly expsensive so we only clear on every 10th keystroke.
-                            if version % 10 == 0 {
-                                // Call this on the engines clone so we don't clear types that are still in use
-                                // and might be needed in the case cancel compilation was triggered.
-                                if let Err(err) = session.garbage_collect(&mut engines_clone) {
-                                    tracing::error!(
-                                        "Unable to perform garbage collection: {}",
-                                        err.to_string()
-                                    );
-                                }
-                            }
-                        }
+        let memory_limit_exceeded = Arc::new(AtomicBool::new(false));
+        let worker = CompilationWorker {
+            is_compiling: self.is_compiling.clone(),
+            compiling_session: self.compiling_session.clone(),
+            finished_compilation: self.finished_compilation.clone(),
+            rx: self.cb_rx.clone(),
+            background_rx: self.background_rx.clone(),
+            last_compilation_state: self.last_compilation_state.clone(),
+            last_compilation_duration: self.last_compilation_duration.clone(),
+            client: self.client.clone(),
+            active_file: self.active_file.clone(),
+            config: self.config.clone(),
+            diagnostics_versions: self.diagnostics_versions.clone(),
+            published_diagnostics: self.published_diagnostics.clone(),
+            client_capabilities: self.client_capabilities.clone(),
+            runtime: tokio::runtime::Handle::try_current().ok(),
+            memory_limit_exceeded: memory_limit_exceeded.clone(),
+        };
+        let compilation_thread_alive = self.compilation_thread_alive.clone();
+        compilation_thread_alive.store(true, Ordering::SeqCst);
+        self.spawn_memory_guard_thread(memory_limit_exceeded);
+        let spawned = thread::Builder::new()
+            .name(COMPILATION_THREAD_NAME.to_string())
+            .spawn(move || {
+                // Clears the alive flag when the thread exits, including when it panics.
+                let _alive_guard = AliveGuard(compilation_thread_alive);
+                worker.run();
+            });
+        if let Err(err) = spawned {
+            // Requests are still answered from the last results, but nothing is compiled.
+            self.compilation_thread_alive.store(false, Ordering::SeqCst);
+            tracing::error!("Unable to spawn the compilation thread: {}", err);
+        }
+    }
 
-                        // Set the is_compiling flag to true so that the wait_for_parsing function knows that we are compiling
-                        is_compiling.store(true, Ordering::SeqCst);
-                        let mut parse_result = ParseResult::default();
-                        match session::parse_project(
-                            &uri,
-                            &engines_clone,
-                            Some(retrigger_compilation.clone()),
-                            &mut parse_result,
-                        ) {
-                            Ok(_) => {
-                                mem::swap(&mut *session.engines.write(), &mut engines_clone);
-                                session.write_parse_result(&mut parse_result);
-                                *last_compilation_state.write() = LastCompilationState::Success;
-                            }
-                            Err(_err) => {
-                                *last_compilation_state.write() = LastCompilationState::Failed;
-                            }
+    /// Spawns a thread that periodically checks the memory used by the server while it's compiling.
+    ///
+    /// If `maxMemoryMb` is set and exceeded, the current compilation is cancelled and `memory_limit_exceeded`
+    /// is set so that the compilation thread can tell the user why. The thread exits along with the
+    /// compilation thread.
+    fn spawn_memory_guard_thread(&self, memory_limit_exceeded: Arc<AtomicBool>) {
+        let is_compiling = self.is_compiling.clone();
+        let compiling_session = self.compiling_session.clone();
+        let compilation_thread_alive = self.compilation_thread_alive.clone();
+        let config = self.config.clone();
+        let spawned = thread::Builder::new()
+            .name(MEMORY_GUARD_THREAD_NAME.to_string())
+            .spawn(move || {
+                let mut system = System::new();
+                while compilation_thread_alive.load(Ordering::SeqCst) {
+                    thread::sleep(MEMORY_CHECK_INTERVAL);
+                    let Some(limit) = config.read().max_memory_mb else {
+                        continue;
+                    };
+                    if is_compiling.load(Ordering::SeqCst)
+                        && !memory_limit_exceeded.load(Ordering::SeqCst)
+                        && exceeds_memory_limit(&mut system, limit)
+                    {
+                        tracing::warn!(
+                            "Memory limit of {limit} MB exceeded, cancelling compilation"
+                        );
+                        memory_limit_exceeded.store(true, Ordering::SeqCst);
+                        if let Some(session) = &*compiling_session.read() {
+                            session.compilation_cancellation.cancel();
                         }
+                    }
+                }
+            });
+        if let Err(err) = spawned {
+            tracing::error!("Unable to spawn the memory guard thread: {}", err);
+        }
+    }
 
-                        // Reset the flags to false
-                        is_compiling.store(false, Ordering::SeqCst);
-                        retrigger_compilation.store(false, Ordering::SeqCst);
+    /// Initializes and compiles the sessions of the projects in the `prewarmProjects` config option,
+    /// so that their diagnostics and hovers are ready by the time the user opens a file.
+    ///
+    /// This runs in a separate task so that it doesn't block the handling of other requests.
+    /// A project that fails to initialize is logged and skipped.
+    pub(crate) fn prewarm_sessions(&self) -> tokio::task::JoinHandle<()> {
+        let uris = self.config.read().prewarm_projects.clone();
+        let sessions = self.sessions.clone();
+        let background_tx = self.background_tx.clone();
+        let capacity = self
+            .config
+            .read()
+            .compilation
+            .background_queue_capacity
+            .max(1);
+        let finished_compilation = self.finished_compilation.clone();
+        let client = self.client.clone();
+        let capabilities = self.client_capabilities.read().clone();
+        tokio::spawn(async move {
+            // A workspace is prewarmed by prewarming each of its members.
+            let package_uris: Vec<_> = uris.iter().flat_map(package_uris).collect();
+            let mut progress = BatchProgress::begin(
+                client.as_ref().filter(|_| capabilities.work_done_progress),
+                "sway/prewarmProjects",
+                "Prewarming Sway projects",
+                package_uris.len(),
+            )
+            .await;
+            for uri in package_uris {
+                match sessions.url_to_session(&uri).await {
+                    Ok(session) => {
+                        compile_in_background(
+                            &session,
+                            &background_tx,
+                            capacity,
+                            &finished_compilation,
+                        )
+                        .await;
+                    }
+                    Err(err) => {
+                        tracing::error!("Unable to prewarm {}: {}", uri, err.to_string());
+                    }
+                }
+                progress.complete(1).await;
+            }
+            progress.end().await;
+        })
+    }
 
-                        // Make sure there isn't any pending compilation work
-                        if rx.is_empty() {
-                            // finished compilation, notify waiters
-                            finished_compilation.notify_waiters();
+    /// Initializes and compiles the sessions of every package under the workspace folders if
+    /// `compileWorkspaceOnOpen` is enabled, and publishes their diagnostics as each one is compiled.
+    ///
+    /// Like [ServerState::prewarm_sessions], this runs in a separate task. The sessions are initialized
+    /// up to `maxParallelInit` at a time, and compiled one after the other with background priority,
+    /// so that the user's edits are compiled first. The progress is reported across all of the packages.
+    pub(crate) fn compile_workspace_on_open(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.config.read().compile_workspace_on_open {
+            return None;
+        }
+        const PROGRESS_TOKEN: &str = "sway/compileWorkspaceOnOpen";
+        let package_dirs = self
+            .workspace_folders
+            .read()
+            .iter()
+            .filter_map(|folder| folder.to_file_path().ok())
+            .flat_map(|folder| discover_packages(&folder, &self.config.read().workspace))
+            .collect::<Vec<_>>();
+        let sessions = self.sessions.clone();
+        let background_tx = self.background_tx.clone();
+        let capacity = self
+            .config
+            .read()
+            .compilation
+            .background_queue_capacity
+            .max(1);
+        let finished_compilation = self.finished_compilation.clone();
+        let client = self.client.clone();
+        let capabilities = self.client_capabilities.read().clone();
+        let diagnostics_versions = self.diagnostics_versions.clone();
+        let published_diagnostics = self.published_diagnostics.clone();
+        let active_file = self.active_file.clone();
+        Some(tokio::spawn(async move {
+            let mut progress = BatchProgress::begin(
+                client.as_ref().filter(|_| capabilities.work_done_progress),
+                PROGRESS_TOKEN,
+                "Compiling Sway workspace",
+                package_dirs.len(),
+            )
+            .await;
+            let mut inits = tokio::task::JoinSet::new();
+            for package_dir in package_dirs {
+                let sessions = sessions.clone();
+                inits.spawn(async move {
+                    let uri = Url::from_file_path(&package_dir).ok()?;
+                    match sessions.url_to_session(&uri).await {
+                        Ok(session) => Some(session),
+                        Err(err) => {
+                            tracing::error!("Unable to compile {}: {}", uri, err.to_string());
+                            None
                         }
                     }
-                    TaskMessage::Terminate => {
-                        // If we receive a terminate message, we need to exit the thread
-                        return;
-                    }
+                });
+            }
+            let mut workspace_sessions = vec![];
+            let mut failed_inits = 0;
+            while let Some(result) = inits.join_next().await {
+                match result {
+                    Ok(Some(session)) => workspace_sessions.push(session),
+                    _ => failed_inits += 1,
                 }
             }
-        });
+            // The packages that can't be compiled are done.
+            progress.complete(failed_inits).await;
+            // Compile them in the order of their paths, regardless of which was initialized first.
+            workspace_sessions.sort_by_key(|session| session.sync.manifest_dir().ok());
+
+            for session in &workspace_sessions {
+                compile_in_background(session, &background_tx, capacity, &finished_compilation)
+                    .await;
+                // Clients that pull diagnostics are asked to pull them again once all are compiled instead.
+                if let Some(client) = client.as_ref().filter(|_| !capabilities.pull_diagnostics) {
+                    let files =
+                        diagnostics_publish_order(None, session, active_file.read().clone());
+                    publish_files_diagnostics(
+                        client,
+                        &capabilities,
+                        session,
+                        files,
+                        &diagnostics_versions,
+                        &published_diagnostics,
+                        None,
+                    )
+                    .await;
+                    publish_project_status(client, session).await;
+                }
+                progress.complete(1).await;
+            }
+            progress.end().await;
+            if let Some(client) = client.as_ref().filter(|_| capabilities.pull_diagnostics) {
+                if let Err(err) = client.workspace_diagnostic_refresh().await {
+                    tracing::error!("Unable to refresh the diagnostics of the client: {}", err);
+                }
+            }
+        }))
+    }
+
+    /// Adds a compilation request to the queue of its priority without blocking.
+    ///
+    /// If the queue already holds `compilation.queueCapacity` requests, or `compilation.backgroundQueueCapacity`
+    /// for background ones, the oldest ones are dropped to make room, so that the compilation thread picks up
+    /// the most recent edits.
+    pub(crate) fn enqueue_compilation(&self, ctx: CompilationContext) {
+        if let Some(session) = &ctx.session {
+            session.compilation_generations.lock().requested += 1;
+        }
+        self.push_compilation(ctx);
+    }
+
+    /// Adds a compilation request to the queue without counting it as a new generation of its session.
+    fn push_compilation(&self, ctx: CompilationContext) {
+        let compilation = self.config.read().compilation.clone();
+        let (tx, rx, capacity) = match ctx.priority {
+            CompilationPriority::Interactive => {
+                (&self.cb_tx, &self.cb_rx, compilation.queue_capacity)
+            }
+            CompilationPriority::Background => (
+                &self.background_tx,
+                &self.background_rx,
+                compilation.background_queue_capacity,
+            ),
+        };
+        while tx.len() >= capacity.max(1) {
+            match rx.try_recv() {
+                Ok(TaskMessage::CompilationContext(_)) => {
+                    self.coalesced_compilations.fetch_add(1, Ordering::SeqCst);
+                }
+                Ok(TaskMessage::Terminate) => {
+                    // The server is shutting down, so there's no point in compiling.
+                    let _ = tx.send(TaskMessage::Terminate);
+                    return;
+                }
+                // The compilation thread took the request in the meantime.
+                Err(_) => break,
+            }
+        }
+        // Set before the compilation thread can pick up the request, so that `wait_for_parsing` waits for
+        // it and the thread's reset to false once it's done isn't overwritten.
+        self.is_compiling.store(true, Ordering::SeqCst);
+        if tx.send(TaskMessage::CompilationContext(ctx)).is_err() {
+            self.is_compiling.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns true if neither queue of the compilation thread has a request waiting.
+    pub(crate) fn compilation_queue_is_empty(&self) -> bool {
+        self.cb_rx.is_empty() && self.background_rx.is_empty()
+    }
+
+    /// Compiles the project that `uri` belongs to, unless it's already up to date, and waits for a
+    /// compilation that includes all the changes made before this call.
+    ///
+    /// Concurrent callers share the same compilation. If that compilation is cancelled by a newer one,
+    /// or replaced in the queue by one for another project, the newer or a new one is awaited instead.
+    /// Dropping the future stops waiting without cancelling the compilation.
+    pub async fn compile_now(&self, uri: &Url) -> Result<(), LanguageServerError> {
+        let (temp_uri, session) = self.sessions.uri_and_session_from_workspace(uri).await?;
+        let ctx = || CompilationContext {
+            session: Some(session.clone()),
+            uri: Some(temp_uri.clone()),
+            version: None,
+            priority: CompilationPriority::Interactive,
+        };
+        let (target, enqueue) = {
+            let mut generations = session.compilation_generations.lock();
+            if generations.finished < generations.requested {
+                // Every change so far is included in a compilation that's queued or in progress.
+                (generations.requested, false)
+            } else if session.compiled_program.read().typed.is_none() {
+                generations.requested += 1;
+                (generations.requested, true)
+            } else {
+                return Ok(());
+            }
+        };
+        if enqueue {
+            self.push_compilation(ctx());
+        }
+        self.wait_for_generation(&session, ctx, target).await
+    }
+
+    /// Garbage collects the engines of the session and waits for the project to be recompiled with them.
+    ///
+    /// Like the garbage collection of edits, this runs in the compilation thread, on the clone of the engines
+    /// that the compilation uses, so it can't race a compilation in progress, and the types of the current
+    /// tokens stay valid until those of the recompiled project replace them.
+    pub(crate) async fn collect_garbage(
+        &self,
+        session: &Arc<Session>,
+    ) -> Result<(), LanguageServerError> {
+        let temp_dir = session.sync.temp_dir()?;
+        let temp_uri =
+            Url::from_file_path(&temp_dir).map_err(|_| DirectoryError::UrlFromPathFailed {
+                path: temp_dir.to_string_lossy().to_string(),
+            })?;
+        session
+            .garbage_collection_requested
+            .store(true, Ordering::SeqCst);
+        let ctx = || CompilationContext {
+            session: Some(session.clone()),
+            uri: Some(temp_uri.clone()),
+            version: None,
+            priority: CompilationPriority::Interactive,
+        };
+        let target = {
+            let mut generations = session.compilation_generations.lock();
+            generations.requested += 1;
+            generations.requested
+        };
+        self.push_compilation(ctx());
+        self.wait_for_generation(session, ctx, target).await
+    }
+
+    /// Waits until a compilation of the session that includes the changes of generation `target` has finished,
+    /// requesting `ctx` again if the compilation thread is idle before then.
+    async fn wait_for_generation(
+        &self,
+        session: &Arc<Session>,
+        ctx: impl Fn() -> CompilationContext,
+        target: u64,
+    ) -> Result<(), LanguageServerError> {
+        loop {
+            // Register for the notifications before checking so that we can't miss them.
+            let finished = session.finished_compilation.notified();
+            let idle = self.finished_compilation.notified();
+            {
+                let generations = session.compilation_generations.lock();
+                if generations.finished >= target {
+                    break;
+                }
+            }
+            if !self.is_compiling.load(Ordering::SeqCst) && self.compilation_queue_is_empty() {
+                // Nothing that includes our changes is queued or compiling, so request it again.
+                self.push_compilation(ctx());
+            }
+            tokio::select! {
+                _ = finished => {},
+                _ = idle => {},
+            }
+        }
+        match *session.memory_limit_exceeded.read() {
+            Some(_) => Err(LanguageServerError::MemoryLimitExceeded),
+            None => Ok(()),
+        }
+    }
+
+    /// Asks the client to tell us about changes to the projects' manifests and sway files,
+    /// including those made outside the editor, e.g. by a `git pull`.
+    pub(crate) async fn register_watched_files(&self) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        if !self.client_capabilities.read().watched_files_registration {
+            return;
+        }
+        let extensions = self.config.read().file_extensions.clone();
+        let watchers = std::iter::once(format!("**/{}", sway_utils::constants::MANIFEST_FILE_NAME))
+            .chain(
+                extensions
+                    .iter()
+                    .map(|extension| format!("**/*.{}", extension.trim_start_matches('.'))),
+            )
+            .map(|glob| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(glob),
+                kind: None,
+            })
+            .collect();
+        let register_options =
+            serde_json::to_value(DidChangeWatchedFilesRegistrationOptions { watchers }).ok();
+        let registration = Registration {
+            id: "sway/didChangeWatchedFiles".to_string(),
+            method: DidChangeWatchedFiles::METHOD.to_string(),
+            register_options,
+        };
+        if let Err(err) = client.register_capability(vec![registration]).await {
+            tracing::error!("Unable to register the watched files: {}", err);
+        }
+    }
+
+    /// Tells the user that the file at `uri` isn't part of a Forc project, so language features won't work.
+    ///
+    /// The warning is only shown once for each directory, however many of its files are opened.
+    pub(crate) async fn warn_missing_manifest(&self, uri: &Url) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        let Some(dir) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+        else {
+            return;
+        };
+        if !self.missing_manifest_warnings.insert(dir.clone()) {
+            return;
+        }
+        let message = format!(
+            "No {} was found in {} or any of its parent directories. Sway language features are only \
+            available in Forc projects, which can be created with `forc init`.",
+            sway_utils::constants::MANIFEST_FILE_NAME,
+            dir.display()
+        );
+        client.show_message(MessageType::WARNING, message).await;
+    }
+
+    /// Tells the user that the file at `uri` isn't analyzed because it's outside the workspace folders and
+    /// `allowFilesOutsideWorkspace` is disabled.
+    ///
+    /// The warning is only shown once, for the first such file that is opened.
+    pub(crate) async fn warn_outside_workspace(&self, uri: &Url) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        if self.outside_workspace_warned.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let message = format!(
+            "{} is outside of the workspace folders, so Sway language features aren't available for it. \
+            Open its folder, or enable `allowFilesOutsideWorkspace`, to analyze it.",
+            uri.path()
+        );
+        client.show_message(MessageType::WARNING, message).await;
+    }
+
+    /// Publishes the error of a `Forc.toml` that can't be parsed as a diagnostic on the manifest itself,
+    /// so that the user can see why the project has no language features.
+    ///
+    /// The diagnostic is cleared by [ServerState::revalidate_manifest] once the manifest is fixed.
+    pub(crate) async fn publish_manifest_error(&self, path: &Path, err: &str) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        let Ok(uri) = Url::from_file_path(path) else {
+            return;
+        };
+        self.manifest_errors.insert(path.to_path_buf());
+        self.published_diagnostics.insert(uri.clone());
+        let contents = std::fs::read_to_string(path).unwrap_or_default();
+        let diagnostic = Diagnostic {
+            range: manifest_error_range(&contents, err, self.position_encoding()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            message: err.to_string(),
+            ..Default::default()
+        };
+        client
+            .publish_diagnostics(uri, vec![diagnostic], None)
+            .await;
+    }
+
+    /// Clears the diagnostic of a manifest that failed to parse if it was fixed or removed,
+    /// or replaces it with its new error otherwise.
+    pub(crate) async fn revalidate_manifest(&self, path: &Path) {
+        if path.is_file() {
+            if let Err(err) = ManifestFile::from_file(path.to_path_buf()) {
+                self.publish_manifest_error(path, &err.to_string()).await;
+                return;
+            }
+        }
+        self.manifest_errors.remove(path);
+        if let (Some(client), Ok(uri)) = (self.client.as_ref(), Url::from_file_path(path)) {
+            client.publish_diagnostics(uri, vec![], None).await;
+        }
+    }
+
+    /// Returns a summary of the state of the compilation thread and its queue.
+    pub(crate) fn health_check_result(&self) -> HealthCheckResult {
+        HealthCheckResult {
+            compilation_thread_alive: self.compilation_thread_alive.load(Ordering::SeqCst),
+            queue_length: self.cb_rx.len(),
+            queue_capacity: self.config.read().compilation.queue_capacity.max(1),
+            background_queue_length: self.background_rx.len(),
+            background_queue_capacity: self
+                .config
+                .read()
+                .compilation
+                .background_queue_capacity
+                .max(1),
+            coalesced_compilations: self.coalesced_compilations.load(Ordering::SeqCst),
+            is_compiling: self.is_compiling.load(Ordering::SeqCst),
+            last_compilation_state: *self.last_compilation_state.read(),
+            last_compilation_duration_ms: self
+                .last_compilation_duration
+                .read()
+                .map(|duration| duration.as_secs_f64() * 1000.0),
+        }
+    }
+
+    /// Initializes the tracing subscriber at the level set in the config.
+    ///
+    /// If logging is disabled, the subscriber isn't installed until a level is set at runtime.
+    pub(crate) fn init_tracing(&self) {
+        let level = self.config.read().logging.level;
+        if level == LevelFilter::OFF {
+            return;
+        }
+        match logging::init_tracing_subscriber(level) {
+            Ok(handle) => *self.tracing_filter.write() = Some(handle),
+            Err(err) => tracing::error!("Unable to set the tracing level to {level}: {err}"),
+        }
+    }
+
+    /// Changes the level of the tracing subscriber without restarting the server.
+    pub(crate) fn update_trace_level(&self, level: LevelFilter) -> Result<(), LanguageServerError> {
+        let mut tracing_filter = self.tracing_filter.write();
+        match tracing_filter.as_ref() {
+            Some(handle) => logging::set_tracing_level(handle, level),
+            None => {
+                logging::init_tracing_subscriber(level).map(|handle| *tracing_filter = Some(handle))
+            }
+        }
+        .map_err(|err| LanguageServerError::TracingReloadFailed(err.to_string()))?;
+        self.config.write().logging.level = level;
+        Ok(())
+    }
+
+    /// Returns the state of the last compilation of any project.
+    pub fn last_compilation_state(&self) -> LastCompilationState {
+        *self.last_compilation_state.read()
+    }
+
+    /// Returns the encoding of positions that was negotiated with the client in `initialize`.
+    pub fn position_encoding(&self) -> PositionEncoding {
+        *self.position_encoding.read()
+    }
+
+    /// Runs `future` until the `requestTimeoutMs` deadline of a request, returning `None` if it passed.
+    pub(crate) async fn with_request_deadline<F: Future>(&self, future: F) -> Option<F::Output> {
+        let timeout = Duration::from_millis(self.config.read().request_timeout_ms);
+        match tokio::time::timeout(timeout, future).await {
+            Ok(output) => Some(output),
+            Err(_) => {
+                tracing::warn!(
+                    "The compilation didn't finish within {}ms, answering with the last compiled results",
+                    timeout.as_millis()
+                );
+                None
+            }
+        }
+    }
+
+    /// Compiles the project of the workspace url `uri` if it hasn't been compiled yet because `compileOnOpen`
+    /// is disabled, and publishes its diagnostics, so that requests that need the typed program can be answered.
+    ///
+    /// Like other requests, it only waits for the compilation until the `requestTimeoutMs` deadline.
+    pub(crate) async fn compile_deferred(&self, uri: &Url) {
+        if self.config.read().compile_on_open {
+            return;
+        }
+        let Ok((temp_uri, session)) = self.sessions.uri_and_session_from_workspace(uri).await
+        else {
+            return;
+        };
+        if session.compiled_program.read().typed.is_some() {
+            return;
+        }
+        match self.with_request_deadline(self.compile_now(uri)).await {
+            Some(Ok(())) => {
+                self.publish_diagnostics(temp_uri, uri.clone(), session)
+                    .await
+            }
+            Some(Err(err)) => tracing::error!("Unable to compile {}: {}", uri, err),
+            None => {}
+        }
+    }
+
+    /// Waits for the pending compilations like [ServerState::wait_for_parsing], but only until the
+    /// `requestTimeoutMs` deadline, so that requests are answered even while the user keeps typing.
+    pub(crate) async fn wait_for_parsing_until_deadline(&self) {
+        let _ = self.with_request_deadline(self.wait_for_parsing()).await;
     }
 
     /// Waits asynchronously for the `is_compiling` flag to become false.
@@ -172,7 +1267,7 @@ impl ServerState {
         loop {
             if !self.is_compiling.load(Ordering::SeqCst) {
                 // compilation is finished, lets check if there are pending compilation requests.
-                if self.cb_rx.is_empty() {
+                if self.compilation_queue_is_empty() {
                     // no pending compilation work, safe to break.
                     break;
                 }
@@ -183,131 +1278,2236 @@ impl ServerState {
     }
 
     pub async fn shutdown_server(&self) -> jsonrpc::Result<()> {
+        self.shutdown_with_timeout(SHUTDOWN_TIMEOUT).await
+    }
+
+    /// Stops the compilation thread and cleans up the sessions.
+    ///
+    /// If the current compilation doesn't stop within `timeout`, e.g. because it's stuck, the thread
+    /// is left to exit once it's done, so that the client's shutdown request still resolves promptly.
+    async fn shutdown_with_timeout(&self, timeout: Duration) -> jsonrpc::Result<()> {
         tracing::info!("Shutting Down the Sway Language Server");
 
         // Drain pending compilation requests
         while self.cb_rx.try_recv().is_ok() {}
+        while self.background_rx.try_recv().is_ok() {}
 
-        // Set the retrigger_compilation flag to true so that the compilation exits early
-        self.retrigger_compilation.store(true, Ordering::SeqCst);
-        self.wait_for_parsing().await;
+        // Cancel the compilation in progress so that it exits early
+        if let Some(session) = &*self.compiling_session.read() {
+            session.compilation_cancellation.cancel();
+        }
+        if tokio::time::timeout(timeout, self.wait_for_parsing())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "The compilation didn't stop within {}ms, shutting down without waiting for it",
+                timeout.as_millis()
+            );
+        }
 
         // Send a terminate message to the compilation thread
-        self.cb_tx
-            .send(TaskMessage::Terminate)
-            .expect("failed to send terminate message");
+        if !self.compilation_thread_alive.load(Ordering::SeqCst) {
+            tracing::warn!("The compilation thread has already exited");
+        }
+        let _ = self.cb_tx.send(TaskMessage::Terminate);
 
-        let _ = self.sessions.iter().map(|item| {
-            let session = item.value();
-            session.shutdown();
-        });
+        for item in self.sessions.iter() {
+            item.value().shutdown();
+        }
         Ok(())
     }
 
+    /// Asks the client to create a work done progress for `token`.
+    ///
+    /// Returns false if there is no client, the client doesn't support work done progress or it refused,
+    /// in which case progress should not be reported.
+    pub(crate) async fn create_work_done_progress(&self, token: &str) -> bool {
+        let Some(client) = self.client.as_ref() else {
+            return false;
+        };
+        if !self.client_capabilities.read().work_done_progress {
+            return false;
+        }
+        create_work_done_progress(client, token).await
+    }
+
+    /// Reports work done progress for a `token` that was created with [ServerState::create_work_done_progress].
+    pub(crate) async fn report_work_done_progress(&self, token: &str, progress: WorkDoneProgress) {
+        if let Some(client) = self.client.as_ref() {
+            report_work_done_progress(client, token, progress).await;
+        }
+    }
+
+    /// Publishes the diagnostics of a compilation that was triggered by the file at `uri`.
+    ///
+    /// `uri` is the temp url of the file that triggered the compilation and `workspace_uri` its url in the user's workspace.
+    /// See [ServerState::publish_session_diagnostics] for the order the files are published in.
     pub(crate) async fn publish_diagnostics(
         &self,
         uri: Url,
         workspace_uri: Url,
         session: Arc<Session>,
     ) {
-        let diagnostics = self.diagnostics(&uri, session.clone()).await;
-        // Note: Even if the computed diagnostics vec is empty, we still have to push the empty Vec
-        // in order to clear former diagnostics. Newly pushed diagnostics always replace previously pushed diagnostics.
-        if let Some(client) = self.client.as_ref() {
-            client
-                .publish_diagnostics(workspace_uri.clone(), diagnostics, None)
-                .await;
-        }
+        self.publish_session_diagnostics(Some((uri, workspace_uri)), session)
+            .await;
     }
 
-    async fn diagnostics(&self, uri: &Url, session: Arc<Session>) -> Vec<Diagnostic> {
-        let mut diagnostics_to_publish = vec![];
-        let config = &self.config.read();
-        let tokens = session.token_map().tokens_for_file(uri);
-        match config.debug.show_collected_tokens_as_warnings {
-            // If collected_tokens_as_warnings is Parsed or Typed,
-            // take over the normal error and warning display behavior
-            // and instead show the either the parsed or typed tokens as warnings.
-            // This is useful for debugging the lsp parser.
-            Warnings::Parsed => {
-                diagnostics_to_publish = debug::generate_warnings_for_parsed_tokens(tokens)
-            }
-            Warnings::Typed => {
-                diagnostics_to_publish = debug::generate_warnings_for_typed_tokens(tokens)
+    /// Publishes the diagnostics of every file in the session.
+    ///
+    /// If `diagnostic.prioritizeActiveFile` is enabled, the file that was most recently edited is published first
+    /// so that the user sees its diagnostics as soon as possible. It's followed by the file that triggered the
+    /// compilation, if any, and then by the rest of the files in the order of their paths.
+    pub(crate) async fn publish_session_diagnostics(
+        &self,
+        trigger: Option<(Url, Url)>,
+        session: Arc<Session>,
+    ) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        // Clients that pull diagnostics are asked to pull them again instead.
+        let capabilities = self.client_capabilities.read().clone();
+        if capabilities.pull_diagnostics {
+            if let Err(err) = client.workspace_diagnostic_refresh().await {
+                tracing::error!("Unable to refresh the diagnostics of the client: {}", err);
             }
-            Warnings::Default => {
-                if let Some(diagnostics) =
-                    session.diagnostics.read().get(&PathBuf::from(uri.path()))
-                {
-                    if config.diagnostic.show_warnings {
-                        diagnostics_to_publish.extend(diagnostics.warnings.clone());
-                    }
-                    if config.diagnostic.show_errors {
-                        diagnostics_to_publish.extend(diagnostics.errors.clone());
-                    }
+            return;
+        }
+        let files = diagnostics_publish_order(trigger, &session, self.active_file.read().clone());
+        publish_files_diagnostics(
+            client,
+            &capabilities,
+            &session,
+            files,
+            &self.diagnostics_versions,
+            &self.published_diagnostics,
+            None,
+        )
+        .await;
+        publish_project_status(client, &session).await;
+    }
+
+    /// Publishes the diagnostics of every file in the session once it's no longer edited in quick succession,
+    /// if only those of the edited file were published during the burst of edits. See `diagnostic.fastPath`.
+    ///
+    /// The check runs in a separate task, after [EDIT_BURST_INTERVAL] has passed without further edits.
+    pub(crate) fn schedule_diagnostics_sweep(&self, session: Arc<Session>) {
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        let capabilities = self.client_capabilities.read().clone();
+        if capabilities.pull_diagnostics {
+            return;
+        }
+        let diagnostics_versions = self.diagnostics_versions.clone();
+        let published_diagnostics = self.published_diagnostics.clone();
+        let active_file = self.active_file.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(EDIT_BURST_INTERVAL).await;
+            // A newer edit schedules a sweep of its own.
+            if session.edit_burst.lock().is_active(Instant::now()) {
+                return;
+            }
+            // Register for the notification before checking the flag so that we can't miss it.
+            let finished = session.finished_compilation.notified();
+            if session.is_compiling.load(Ordering::SeqCst) {
+                finished.await;
+            }
+            if !session
+                .diagnostics_sweep_pending
+                .swap(false, Ordering::SeqCst)
+            {
+                return;
+            }
+            let files = diagnostics_publish_order(None, &session, active_file.read().clone());
+            publish_files_diagnostics(
+                &client,
+                &capabilities,
+                &session,
+                files,
+                &diagnostics_versions,
+                &published_diagnostics,
+                None,
+            )
+            .await;
+            publish_project_status(&client, &session).await;
+        });
+    }
+
+    /// Publishes empty diagnostics for every file of the session that has diagnostics,
+    /// so that none of them are left behind in the editor.
+    pub(crate) async fn clear_session_diagnostics(&self, session: &Session) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        let workspace_uris = session
+            .diagnostics
+            .read()
+            .keys()
+            .filter_map(|path| {
+                let uri = Url::from_file_path(path).ok()?;
+                session.sync.temp_to_workspace_url(&uri).ok()
+            })
+            .collect::<Vec<_>>();
+        for workspace_uri in workspace_uris {
+            client
+                .publish_diagnostics(workspace_uri, vec![], None)
+                .await;
+        }
+    }
+
+    /// Publishes empty diagnostics for every file that diagnostics were published for, across all sessions,
+    /// and returns how many files were cleared. The sessions are left alone, so the next compilation publishes
+    /// the diagnostics again.
+    ///
+    /// The document versions of the published diagnostics are kept, so that the diagnostics of a compilation
+    /// that's in flight aren't dropped as stale.
+    pub(crate) async fn clear_all_diagnostics(&self) -> usize {
+        let Some(client) = self.client.as_ref() else {
+            return 0;
+        };
+        let workspace_uris: Vec<Url> = self
+            .published_diagnostics
+            .iter()
+            .map(|uri| uri.key().clone())
+            .collect();
+        for workspace_uri in &workspace_uris {
+            client
+                .publish_diagnostics(workspace_uri.clone(), vec![], None)
+                .await;
+        }
+        workspace_uris.len()
+    }
+}
+
+/// Enqueues a background compilation of the session and waits for it to finish.
+///
+/// The compilation thread only keeps the most recent background requests, so a project is compiled before the
+/// next one is enqueued. If a newer request replaced it, this stops waiting once the compilation thread is idle.
+async fn compile_in_background(
+    session: &Arc<Session>,
+    background_tx: &Sender<TaskMessage>,
+    capacity: usize,
+    finished_compilation: &Notify,
+) {
+    let Some(temp_uri) = session
+        .sync
+        .temp_dir()
+        .ok()
+        .and_then(|temp_dir| Url::from_file_path(temp_dir).ok())
+    else {
+        return;
+    };
+    // Register for the notifications before enqueueing the compilation so that we can't miss them.
+    let finished = session.finished_compilation.notified();
+    let idle = finished_compilation.notified();
+    // Don't replace a background compilation that was requested in the meantime.
+    let enqueued = background_tx.len() < capacity
+        && background_tx
+            .send(TaskMessage::CompilationContext(CompilationContext {
+                session: Some(session.clone()),
+                uri: Some(temp_uri),
+                version: None,
+                priority: CompilationPriority::Background,
+            }))
+            .is_ok();
+    if enqueued {
+        tokio::select! {
+            _ = finished => {},
+            _ = idle => {},
+        }
+    }
+}
+
+/// Asks the client to create a work done progress for `token`, and returns whether it accepted.
+async fn create_work_done_progress(client: &Client, token: &str) -> bool {
+    client
+        .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+            token: NumberOrString::String(token.to_string()),
+        })
+        .await
+        .is_ok()
+}
+
+/// Reports the progress of compiling a batch of projects with `$/progress`, as `completed/total projects`
+/// and the percentage of the projects that are done, whether they compiled or failed.
+struct BatchProgress<'a> {
+    client: Option<&'a Client>,
+    token: &'static str,
+    total: usize,
+    completed: usize,
+}
+
+impl<'a> BatchProgress<'a> {
+    /// Creates the progress for `token` and reports its beginning, if there is a `client` that supports it.
+    async fn begin(
+        client: Option<&'a Client>,
+        token: &'static str,
+        title: &str,
+        total: usize,
+    ) -> BatchProgress<'a> {
+        let client = match client {
+            Some(client) if create_work_done_progress(client, token).await => Some(client),
+            _ => None,
+        };
+        let progress = Self {
+            client,
+            token,
+            total,
+            completed: 0,
+        };
+        if let Some(client) = progress.client {
+            report_work_done_progress(
+                client,
+                token,
+                WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title: title.to_string(),
+                    cancellable: Some(false),
+                    message: Some(progress.message()),
+                    percentage: Some(progress.percentage()),
+                }),
+            )
+            .await;
+        }
+        progress
+    }
+
+    /// Records that `count` more projects are done, and reports it if any are.
+    async fn complete(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.completed = (self.completed + count).min(self.total);
+        if let Some(client) = self.client {
+            report_work_done_progress(
+                client,
+                self.token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(false),
+                    message: Some(self.message()),
+                    percentage: Some(self.percentage()),
+                }),
+            )
+            .await;
+        }
+    }
+
+    /// Reports the end of the progress, after completing the projects that weren't yet so that it reaches 100%.
+    async fn end(mut self) {
+        self.complete(self.total - self.completed).await;
+        if let Some(client) = self.client {
+            report_work_done_progress(
+                client,
+                self.token,
+                WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+            )
+            .await;
+        }
+    }
+
+    fn message(&self) -> String {
+        format!("{}/{} projects", self.completed, self.total)
+    }
+
+    /// An empty batch is done from the start.
+    fn percentage(&self) -> u32 {
+        if self.total == 0 {
+            100
+        } else {
+            (self.completed * 100 / self.total) as u32
+        }
+    }
+}
+
+async fn report_work_done_progress(client: &Client, token: &str, progress: WorkDoneProgress) {
+    client
+        .send_notification::<Progress>(ProgressParams {
+            token: NumberOrString::String(token.to_string()),
+            value: ProgressParamsValue::WorkDone(progress),
+        })
+        .await;
+}
+
+/// Sends the `sway/projectStatus` notification with the number of errors and warnings of the session.
+async fn publish_project_status(client: &Client, session: &Session) {
+    let Ok(manifest_dir) = session.sync.manifest_dir() else {
+        return;
+    };
+    let params = project_status(manifest_dir, session);
+    client
+        .send_notification::<lsp_ext::ProjectStatus>(params)
+        .await;
+}
+
+/// Sends a `sway/compilationStatus` notification for the compilation of `uri`.
+///
+/// It only puts the notification in the queue of messages to the client, so it doesn't hold up the
+/// compilation thread for long.
+async fn send_compilation_status(
+    client: &Client,
+    session: &Session,
+    uri: &Url,
+    version: Option<i32>,
+    status: lsp_ext::CompilationStatusKind,
+) {
+    let params = lsp_ext::CompilationStatusParams {
+        uri: uri.clone(),
+        version,
+        status,
+        last_compilation_state: *session.last_compilation_state.read(),
+    };
+    client
+        .send_notification::<lsp_ext::CompilationStatus>(params)
+        .await;
+}
+
+/// Sums up the diagnostics of all files of the session.
+///
+/// The lock on the diagnostics is released before returning, so the next compilation isn't held up
+/// while the notification is sent.
+fn project_status(manifest_dir: PathBuf, session: &Session) -> lsp_ext::ProjectStatusParams {
+    let (error_count, warning_count) =
+        session
+            .diagnostics
+            .read()
+            .values()
+            .fold((0, 0), |(errors, warnings), diagnostics| {
+                (
+                    errors + diagnostics.errors.len(),
+                    warnings + diagnostics.warnings.len(),
+                )
+            });
+    lsp_ext::ProjectStatusParams {
+        manifest_dir,
+        error_count,
+        warning_count,
+        last_compilation_state: *session.last_compilation_state.read(),
+        stale_tokens: session.has_stale_tokens(),
+    }
+}
+
+/// The number of files whose diagnostics [DiagnosticsBatch::flush] publishes before yielding to other tasks.
+const DIAGNOSTICS_BATCH_YIELD_INTERVAL: usize = 32;
+
+/// The diagnostics of the files of one compilation, which are collected before any of them are published.
+///
+/// Publishing them as each file's diagnostics are computed interleaves the notifications with the work of
+/// computing the rest, so the client redraws many times in a row after a large recompile. A batch is
+/// sent in one go instead, including the empty diagnostics that clear the files that no longer have any.
+#[derive(Default)]
+struct DiagnosticsBatch {
+    publishes: Vec<(Url, Vec<Diagnostic>, Option<i32>)>,
+}
+
+impl DiagnosticsBatch {
+    fn push(&mut self, workspace_uri: Url, diagnostics: Vec<Diagnostic>, version: Option<i32>) {
+        self.publishes.push((workspace_uri, diagnostics, version));
+    }
+
+    /// Publishes the diagnostics of the batch in order, yielding every [DIAGNOSTICS_BATCH_YIELD_INTERVAL]
+    /// files so that a large batch doesn't hold up the requests that are handled in the meantime.
+    async fn flush(self, client: &Client) {
+        for (i, (workspace_uri, diagnostics, version)) in self.publishes.into_iter().enumerate() {
+            if i > 0 && i % DIAGNOSTICS_BATCH_YIELD_INTERVAL == 0 {
+                tokio::task::yield_now().await;
+            }
+            client
+                .publish_diagnostics(workspace_uri, diagnostics, version)
+                .await;
+        }
+    }
+}
+
+/// Publishes the diagnostics of the given files, which are given as their temp and workspace urls.
+///
+/// `trigger_version` is the workspace url and document version of the file that triggered the compilation,
+/// whose diagnostics aren't published if newer ones already have been. The files are added to `published`,
+/// and the files of the session in `published` that were removed from it are cleared in the same batch.
+async fn publish_files_diagnostics(
+    client: &Client,
+    capabilities: &ClientCapabilities,
+    session: &Session,
+    files: Vec<(Url, Url)>,
+    versions: &DashMap<Url, i32>,
+    published: &DashSet<Url>,
+    trigger_version: Option<(&Url, i32)>,
+) {
+    let mut batch = DiagnosticsBatch::default();
+    for (uri, workspace_uri) in files {
+        let version = version_of(trigger_version, &workspace_uri);
+        if !record_diagnostics_version(versions, &workspace_uri, version) {
+            continue;
+        }
+        let mut diagnostics = diagnostics(&uri, session);
+        capabilities.strip_diagnostics(&mut diagnostics);
+        published.insert(workspace_uri.clone());
+        // Note: Even if the computed diagnostics vec is empty, we still have to push the empty Vec
+        // in order to clear former diagnostics. Newly pushed diagnostics always replace previously pushed diagnostics.
+        batch.push(
+            workspace_uri,
+            diagnostics,
+            capabilities.diagnostics_version(version),
+        );
+    }
+    for workspace_uri in removed_files(session, published) {
+        published.remove(&workspace_uri);
+        batch.push(workspace_uri, vec![], None);
+    }
+    batch.flush(client).await;
+}
+
+/// Returns the Sway files in `published` that are in the session's project but no longer exist, e.g. because
+/// they were deleted or renamed, so their diagnostics are never published again and have to be cleared.
+///
+/// Files that still exist on disk are left alone, as they can belong to a package nested in the project's folder.
+fn removed_files(session: &Session, published: &DashSet<Url>) -> Vec<Url> {
+    let config = session.config.read();
+    published
+        .iter()
+        .filter(|workspace_uri| {
+            let Ok(path) = workspace_uri.to_file_path() else {
+                return false;
+            };
+            if !config.is_sway_file(workspace_uri)
+                || path.exists()
+                || session.sync.is_path_in_dependency(workspace_uri)
+            {
+                return false;
+            }
+            session
+                .sync
+                .workspace_to_temp_url(workspace_uri)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+                .is_some_and(|temp_path| {
+                    !session
+                        .documents
+                        .contains_key(temp_path.to_string_lossy().as_ref())
+                })
+        })
+        .map(|workspace_uri| workspace_uri.key().clone())
+        .collect()
+}
+
+/// Returns the document version of `workspace_uri` if it's the file that triggered the compilation.
+fn version_of(trigger_version: Option<(&Url, i32)>, workspace_uri: &Url) -> Option<i32> {
+    trigger_version
+        .filter(|(uri, _)| *uri == workspace_uri)
+        .map(|(_, version)| version)
+}
+
+/// Records `version` as the newest one that diagnostics were published for at `workspace_uri`.
+///
+/// Returns false if diagnostics for a newer version were already published, as these are stale and shouldn't
+/// replace them. Diagnostics without a version are always published.
+fn record_diagnostics_version(
+    versions: &DashMap<Url, i32>,
+    workspace_uri: &Url,
+    version: Option<i32>,
+) -> bool {
+    let Some(version) = version else {
+        return true;
+    };
+    let mut latest = versions.entry(workspace_uri.clone()).or_insert(version);
+    if *latest > version {
+        return false;
+    }
+    *latest = version;
+    true
+}
+
+/// Logs a compilation of the file at the temp url `uri` that was cancelled by a newer one to the client's output.
+async fn log_cancelled_compilation(
+    client: &Client,
+    session: &Session,
+    uri: &Url,
+    version: Option<i32>,
+) {
+    let uri = session
+        .sync
+        .temp_to_workspace_url(uri)
+        .unwrap_or(uri.clone());
+    let version = version.map_or("none".to_string(), |version| version.to_string());
+    let cancelled = session.cancelled_compilations.load(Ordering::SeqCst);
+    client
+        .log_message(
+            MessageType::LOG,
+            format!(
+                "Compilation of {uri} (version {version}) was cancelled by a newer one, \
+                {cancelled} cancelled for this project so far"
+            ),
+        )
+        .await;
+}
+
+/// The minimum time between two compile time messages of [Config::debug]'s `show_compile_time`.
+const COMPILE_TIME_MESSAGE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Returns true if at least [COMPILE_TIME_MESSAGE_INTERVAL] has passed since `last`, and sets it to `now` if so.
+fn throttle_compile_time_message(last: &mut Option<Instant>, now: Instant) -> bool {
+    if last.is_some_and(|last| now.duration_since(last) < COMPILE_TIME_MESSAGE_INTERVAL) {
+        return false;
+    }
+    *last = Some(now);
+    true
+}
+
+/// Shows the name of the session's project and how long its last compilation took.
+async fn show_compile_time(client: &Client, session: &Session, duration: Duration) {
+    let project_name = session
+        .sync
+        .manifest_dir()
+        .ok()
+        .and_then(|manifest_dir| PackageManifestFile::from_dir(manifest_dir).ok())
+        .map_or("project".to_string(), |manifest| {
+            manifest.project.name.clone()
+        });
+    let outcome = match *session.last_compilation_state.read() {
+        LastCompilationState::Failed => " (failed)",
+        _ => "",
+    };
+    client
+        .show_message(
+            MessageType::INFO,
+            format!(
+                "Compiled {project_name} in {} ms{outcome}",
+                duration.as_millis()
+            ),
+        )
+        .await;
+}
+
+/// Where internal compiler errors are reported.
+const ISSUE_TRACKER_URL: &str = "https://github.com/FuelLabs/sway/issues/new";
+
+/// Returns the message of the first internal error that the compiler reported, if there is one.
+fn internal_compiler_error(errors: &[CompileError]) -> Option<String> {
+    errors.iter().find_map(|error| match error {
+        CompileError::Internal(..) | CompileError::InternalOwned(..) => Some(error.to_string()),
+        _ => None,
+    })
+}
+
+/// Returns the message of a caught panic, whose payload is a string unless it was raised with `panic_any`.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Tells the user that the compilation failed because of a bug in the compiler rather than in their code,
+/// with what to include in a report to reproduce it.
+fn internal_compiler_error_message(uri: &Url, version: Option<i32>, message: &str) -> String {
+    let version = version.map_or("unknown".to_string(), |version| version.to_string());
+    format!(
+        "The Sway compiler hit an internal error, which is a bug in the compiler rather than in your code. \
+        Please report it at {ISSUE_TRACKER_URL}, including the file it happened in ({uri}, version {version}) \
+        and this message: {message}"
+    )
+}
+
+/// The number of failed garbage collections of a project after which the user is asked to recompile it.
+const GC_FAILURES_BEFORE_SUGGESTION: usize = 3;
+
+/// Counts a failed garbage collection of the session and lets the user know about it.
+///
+/// Each failure is logged to the client if `debug.logGcFailures` is set. Once garbage collection has failed
+/// [GC_FAILURES_BEFORE_SUGGESTION] times for a project, the user is asked once to recompile the workspace,
+/// as the types left behind can make hovers and other features show outdated information.
+async fn report_garbage_collection_failure(
+    client: &Client,
+    session: &Session,
+    err: &LanguageServerError,
+) {
+    let failures = session.gc_failures.fetch_add(1, Ordering::SeqCst) + 1;
+    if session.config.read().debug.log_gc_failures {
+        client
+            .log_message(
+                MessageType::ERROR,
+                format!("Unable to perform garbage collection: {err}"),
+            )
+            .await;
+    }
+    if failures >= GC_FAILURES_BEFORE_SUGGESTION
+        && !session.suggested_recompile.swap(true, Ordering::SeqCst)
+    {
+        client
+            .show_message(
+                MessageType::WARNING,
+                format!(
+                    "Garbage collection has failed {failures} times for this project, so some types \
+                    may be out of date. Run the `sway/recompileWorkspace` command to recompile it."
+                ),
+            )
+            .await;
+    }
+}
+
+/// Publishes the parse errors of the session's files that have any, before the rest of the compilation has finished.
+///
+/// Files without parse errors are left untouched, so their previous diagnostics stay visible until the
+/// compilation has finished and all diagnostics are published.
+async fn publish_parse_diagnostics(
+    client: &Client,
+    capabilities: &ClientCapabilities,
+    session: &Session,
+    versions: &DashMap<Url, i32>,
+    published: &DashSet<Url>,
+    trigger_version: Option<(&Url, i32)>,
+) {
+    if !session.config.read().diagnostic.show_errors {
+        return;
+    }
+    let module_prefix = session.config.read().diagnostic.module_prefix;
+    let source_label = session.config.read().diagnostic.source_label.clone();
+    let mut batch = DiagnosticsBatch::default();
+    for (path, mut diagnostics) in session.parse_diagnostics() {
+        if diagnostics.errors.is_empty() {
+            continue;
+        }
+        if module_prefix {
+            diagnostic::prefix_module_path(&path, &mut diagnostics.errors);
+        }
+        diagnostic::set_source(&mut diagnostics.errors, &source_label);
+        let Ok(uri) = Url::from_file_path(path) else {
+            continue;
+        };
+        let Ok(workspace_uri) = session.sync.temp_to_workspace_url(&uri) else {
+            continue;
+        };
+        let version = version_of(trigger_version, &workspace_uri);
+        if !record_diagnostics_version(versions, &workspace_uri, version) {
+            continue;
+        }
+        published.insert(workspace_uri.clone());
+        capabilities.strip_diagnostics(&mut diagnostics.errors);
+        batch.push(
+            workspace_uri,
+            diagnostics.errors,
+            capabilities.diagnostics_version(version),
+        );
+    }
+    batch.flush(client).await;
+}
+
+/// Writes the diagnostics of every file in the session to the file set in `diagnostic.snapshot`, if any.
+///
+/// The file isn't written more often than the configured interval. Failures are logged, as they
+/// shouldn't stop the compilation thread.
+fn write_diagnostics_snapshot(session: &Session) {
+    let Some(snapshot_config) = session.config.read().diagnostic.snapshot.clone() else {
+        return;
+    };
+    let interval = Duration::from_millis(snapshot_config.interval_ms);
+    if let Some(last_snapshot) = *session.last_diagnostics_snapshot.read() {
+        if last_snapshot.elapsed() < interval {
+            return;
+        }
+    }
+    let Ok(manifest_dir) = session.sync.manifest_dir() else {
+        return;
+    };
+    let path = manifest_dir.join(snapshot_config.path);
+    let result = serde_json::to_string_pretty(&diagnostics_snapshot(session))
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+            }
+            std::fs::write(&path, contents).map_err(|err| err.to_string())
+        });
+    match result {
+        Ok(()) => *session.last_diagnostics_snapshot.write() = Some(Instant::now()),
+        Err(err) => tracing::error!(
+            "Unable to write the diagnostics snapshot to {}: {}",
+            path.display(),
+            err
+        ),
+    }
+}
+
+/// Returns the diagnostics of every file in the session, with paths relative to the project's `Forc.toml`.
+pub(crate) fn diagnostics_snapshot(session: &Session) -> DiagnosticsSnapshot {
+    let manifest_dir = session.sync.manifest_dir().ok();
+    let files = diagnostics_publish_order(None, session, None)
+        .into_iter()
+        .filter_map(|(uri, workspace_uri)| {
+            let path = workspace_uri.to_file_path().ok()?;
+            let path = match &manifest_dir {
+                Some(manifest_dir) => path
+                    .strip_prefix(manifest_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path),
+                None => path,
+            };
+            Some(FileDiagnostics {
+                path,
+                diagnostics: diagnostics(&uri, session),
+            })
+        })
+        .collect();
+    DiagnosticsSnapshot { files }
+}
+
+/// Returns the temp and workspace urls of the files in the session in the order their diagnostics should be published.
+///
+/// `trigger` is the file that triggered the compilation and `active_file` the workspace url of the most recently edited file.
+fn diagnostics_publish_order(
+    trigger: Option<(Url, Url)>,
+    session: &Session,
+    active_file: Option<Url>,
+) -> Vec<(Url, Url)> {
+    let mut files = session
+        .documents
+        .iter()
+        .filter_map(|item| {
+            let uri = Url::from_file_path(item.key()).ok()?;
+            let workspace_uri = session.sync.temp_to_workspace_url(&uri).ok()?;
+            Some((uri, workspace_uri))
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|(a, _), (b, _)| a.path().cmp(b.path()));
+
+    if let Some(trigger) = trigger {
+        move_to_front(&mut files, trigger);
+    }
+    if session.config.read().diagnostic.prioritize_active_file {
+        if let Some(file) = active_file.and_then(|active_file| {
+            files
+                .iter()
+                .find(|(_, workspace_uri)| *workspace_uri == active_file)
+                .cloned()
+        }) {
+            move_to_front(&mut files, file);
+        }
+    }
+    files
+}
+
+/// Returns the diagnostics of the file at the temp url `uri` that should be published, based on the session's config.
+pub(crate) fn diagnostics(uri: &Url, session: &Session) -> Vec<Diagnostic> {
+    let mut diagnostics_to_publish = vec![];
+    let config = &session.config.read();
+    // Dependencies aren't the user's to fix, so their diagnostics are only shown if asked for.
+    if !config.diagnostic.include_dependencies && session.sync.is_path_in_dependency(uri) {
+        return diagnostics_to_publish;
+    }
+    // Excluded files are still compiled, so that other files can use them, but publish no diagnostics.
+    if !config.diagnostic.exclude_globs.is_empty() {
+        let relative_path = session
+            .sync
+            .temp_to_workspace_url(uri)
+            .ok()
+            .and_then(|workspace_uri| workspace_uri.to_file_path().ok())
+            .zip(session.sync.manifest_dir().ok())
+            .and_then(|(path, manifest_dir)| {
+                path.strip_prefix(manifest_dir).map(Path::to_path_buf).ok()
+            });
+        if let Some(relative_path) = relative_path {
+            if config.diagnostic.is_path_excluded(&relative_path) {
+                return diagnostics_to_publish;
+            }
+        }
+    }
+    let token_map = session.token_map();
+    let tokens = token_map.tokens_for_file(uri);
+    match config.debug.show_collected_tokens_as_warnings {
+        // If collected_tokens_as_warnings is Parsed or Typed,
+        // take over the normal error and warning display behavior
+        // and instead show the either the parsed or typed tokens as warnings.
+        // This is useful for debugging the lsp parser.
+        Warnings::Parsed => {
+            diagnostics_to_publish = debug::generate_warnings_for_parsed_tokens(
+                tokens,
+                &config.debug.collected_tokens_filter,
+            )
+        }
+        Warnings::Typed => {
+            diagnostics_to_publish = debug::generate_warnings_for_typed_tokens(
+                tokens,
+                &config.debug.collected_tokens_filter,
+            )
+        }
+        Warnings::Default => {
+            let path = PathBuf::from(uri.path());
+            if let Some(diagnostics) = session.diagnostics.read().get(&path) {
+                // Warnings promoted to errors are shown along with the errors.
+                let warnings = diagnostics
+                    .warnings
+                    .iter()
+                    .filter(|warning| config.diagnostic.is_warning_enabled(warning))
+                    .map(|warning| Diagnostic {
+                        severity: Some(config.diagnostic.warning_severity(warning)),
+                        ..warning.clone()
+                    })
+                    .filter(|warning| {
+                        if warning.severity == Some(DiagnosticSeverity::ERROR) {
+                            config.diagnostic.show_errors
+                        } else {
+                            config.diagnostic.show_warnings
+                        }
+                    });
+                diagnostics_to_publish.extend(warnings);
+                // Errors are shown with their overridden severity, even if warnings are hidden.
+                if config.diagnostic.show_errors {
+                    diagnostics_to_publish.extend(diagnostics.errors.iter().map(|error| {
+                        Diagnostic {
+                            severity: Some(config.diagnostic.error_severity(error)),
+                            ..error.clone()
+                        }
+                    }));
                 }
             }
+            if config.diagnostic.deduplicate {
+                diagnostic::deduplicate(&mut diagnostics_to_publish);
+            }
+            diagnostic::set_source(&mut diagnostics_to_publish, &config.diagnostic.source_label);
+            if config.diagnostic.module_prefix {
+                diagnostic::prefix_module_path(&path, &mut diagnostics_to_publish);
+            }
+        }
+    }
+    if let Some((aborted_uri, limit)) = &*session.memory_limit_exceeded.read() {
+        if aborted_uri == uri {
+            diagnostics_to_publish.push(Diagnostic {
+                severity: Some(DiagnosticSeverity::INFORMATION),
+                code: Some(NumberOrString::String("memory_limit".to_string())),
+                message: format!(
+                    "Compilation was aborted because the language server used more than {limit} MB of memory."
+                ),
+                source: Some(config.diagnostic.source_label.clone()).filter(|label| !label.is_empty()),
+                ..Default::default()
+            });
+        }
+    }
+    diagnostics_to_publish
+}
+
+/// How long shutting down waits for the current compilation to stop.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the memory used by the server is checked while compiling, if `maxMemoryMb` is set.
+const MEMORY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The names of the threads that the server spawns, which tell them apart in profilers and crash dumps.
+const COMPILATION_THREAD_NAME: &str = "sway-lsp-compile";
+const MEMORY_GUARD_THREAD_NAME: &str = "sway-lsp-memory-guard";
+const COMPILE_WATCHDOG_THREAD_NAME: &str = "sway-lsp-compile-watchdog";
+
+/// Cancels the compilation of a session if it doesn't finish within `compileTimeoutMs`, e.g. because the
+/// compiler is stuck on a pathological input.
+///
+/// The compilation is cancelled with the same flag as when it's superseded, so it only stops the next time the
+/// compiler checks the flag.
+struct CompileWatchdog {
+    timeout_ms: u64,
+    // Disconnected once the compilation finishes, which stops the watchdog's thread.
+    finished: Sender<()>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl CompileWatchdog {
+    /// Starts watching the compilation of `session` that is about to start.
+    fn start(session: Arc<Session>, timeout_ms: u64) -> Self {
+        let (finished, finished_rx) = crossbeam_channel::bounded::<()>(0);
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let watchdog_timed_out = timed_out.clone();
+        let spawned = thread::Builder::new()
+            .name(COMPILE_WATCHDOG_THREAD_NAME.to_string())
+            .spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) =
+                    finished_rx.recv_timeout(Duration::from_millis(timeout_ms))
+                {
+                    watchdog_timed_out.store(true, Ordering::SeqCst);
+                    session.compilation_cancellation.cancel();
+                }
+            });
+        if let Err(err) = spawned {
+            tracing::error!("Unable to spawn the compile watchdog thread: {}", err);
+        }
+        Self {
+            timeout_ms,
+            finished,
+            timed_out,
+        }
+    }
+
+    /// Stops watching the compilation, and returns the timeout if it was exceeded.
+    fn finish(self) -> Option<u64> {
+        drop(self.finished);
+        Some(self.timeout_ms).filter(|_| self.timed_out.load(Ordering::SeqCst))
+    }
+}
+
+/// The nice value that the compilation thread runs at with `lowPriorityCompilation`, from 0 for the default
+/// priority to 19 for the lowest.
+#[cfg(target_os = "linux")]
+const LOW_PRIORITY_NICE_VALUE: libc::c_int = 10;
+
+/// Lowers the priority of the calling thread to [LOW_PRIORITY_NICE_VALUE].
+///
+/// On Linux, each thread has a nice value of its own, so the other threads of the server keep theirs.
+#[cfg(target_os = "linux")]
+fn lower_current_thread_priority() {
+    // SAFETY: `setpriority` only reads its arguments, and 0 refers to the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, LOW_PRIORITY_NICE_VALUE) } != 0 {
+        tracing::warn!(
+            "Unable to lower the priority of the compilation thread: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_current_thread_priority() {
+    tracing::warn!("`lowPriorityCompilation` is only supported on Linux");
+}
+
+/// Returns true if the resident memory of the server process is above `limit_mb` megabytes.
+fn exceeds_memory_limit(system: &mut System, limit_mb: u64) -> bool {
+    resident_memory(system).is_some_and(|memory| memory > limit_mb * 1024 * 1024)
+}
+
+/// Returns the resident memory of the server process in bytes, if it can be measured.
+pub(crate) fn resident_memory(system: &mut System) -> Option<u64> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    if !system.refresh_process(pid) {
+        return None;
+    }
+    system.process(pid).map(|process| process.memory())
+}
+
+/// Sets the flag it holds to false when dropped.
+struct AliveGuard(Arc<AtomicBool>);
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Marks the session of a manifest dir as initializing until it's dropped, and then wakes those waiting for it.
+///
+/// The marker is also removed if the request that initializes the session is dropped part-way through, e.g.
+/// because it was cancelled or timed out, so that later requests can initialize the session again.
+struct InitGuard<'a> {
+    initializing: &'a DashMap<PathBuf, Arc<Notify>>,
+    manifest_dir: PathBuf,
+    notify: Arc<Notify>,
+}
+
+impl Drop for InitGuard<'_> {
+    fn drop(&mut self) {
+        self.initializing.remove(&self.manifest_dir);
+        self.notify.notify_waiters();
+    }
+}
+
+/// Blocks until either queue of the compilation thread has a message, and returns it.
+///
+/// The messages of the `interactive` queue always come before those of the `background` one.
+/// Fails once the `interactive` queue is disconnected.
+fn recv_by_priority(
+    interactive: &Receiver<TaskMessage>,
+    background: &Receiver<TaskMessage>,
+) -> Result<TaskMessage, RecvError> {
+    loop {
+        match interactive.try_recv() {
+            Ok(msg) => return Ok(msg),
+            Err(TryRecvError::Disconnected) => return Err(RecvError),
+            Err(TryRecvError::Empty) => {}
+        }
+        if let Ok(msg) = background.try_recv() {
+            return Ok(msg);
+        }
+        // Wait without taking the message, so that it's taken in order of priority on the next iteration.
+        let mut select = Select::new();
+        select.recv(interactive);
+        select.recv(background);
+        select.ready();
+    }
+}
+
+/// Moves the file to the front of the list, inserting it if it isn't in the list yet.
+fn move_to_front(files: &mut Vec<(Url, Url)>, file: (Url, Url)) {
+    files.retain(|(_, workspace_uri)| *workspace_uri != file.1);
+    files.insert(0, file);
+}
+
+/// How long to wait before the first retry of a compilation that failed for a transient reason.
+const COMPILE_RETRY_MIN_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Returns how long to wait before the `attempt`th retry of a compilation, which doubles with each attempt.
+fn compile_retry_backoff(attempt: u32) -> Duration {
+    COMPILE_RETRY_MIN_BACKOFF.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+}
+
+/// How long to wait before retrying the initialization of a session after its first failure.
+const SESSION_INIT_MIN_BACKOFF: Duration = Duration::from_millis(500);
+/// The longest time to wait before retrying the initialization of a session.
+const SESSION_INIT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The most recent failure to initialize the session of a manifest dir.
+#[derive(Debug, Clone)]
+struct FailedInit {
+    /// The number of consecutive failed attempts.
+    attempts: u32,
+    /// The time before which no new attempt is made.
+    retry_at: Instant,
+    reason: String,
+}
+
+impl FailedInit {
+    /// Records another failed attempt, doubling the backoff of the previous one.
+    fn next(previous: Option<&FailedInit>, reason: String) -> Self {
+        let attempts = previous.map_or(1, |failed| failed.attempts.saturating_add(1));
+        Self {
+            attempts,
+            retry_at: Instant::now() + session_init_backoff(attempts),
+            reason,
         }
-        diagnostics_to_publish
     }
 }
 
+/// Returns how long to wait before retrying the initialization of a session after `attempts` consecutive failures.
+fn session_init_backoff(attempts: u32) -> Duration {
+    SESSION_INIT_MIN_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(SESSION_INIT_MAX_BACKOFF)
+}
+
 /// `Sessions` is a collection of [Session]s, each of which represents a project
 /// that has been opened in the users workspace.
-pub(crate) struct Sessions(DashMap<PathBuf, Arc<Session>>);
+pub(crate) struct Sessions {
+    sessions: DashMap<PathBuf, Arc<Session>>,
+    // Manifest dirs of the sessions that are currently being initialized.
+    initializing: DashMap<PathBuf, Arc<Notify>>,
+    // Manifest dirs whose session failed to initialize, so that we don't retry until the backoff has passed.
+    failed_inits: DashMap<PathBuf, FailedInit>,
+    // Limits the number of sessions that are initialized at the same time to `Config::max_parallel_init`.
+    init_permits: Semaphore,
+    // The number of permits that `init_permits` was created or resized with.
+    init_permits_total: Mutex<usize>,
+    // The global config that each session's config is resolved from.
+    config: Arc<RwLock<Config>>,
+    // The workspace folders of the client, which `rootDetection` can pin the sessions of their files to.
+    pub(crate) workspace_folders: Arc<RwLock<Vec<Url>>>,
+    // The encoding negotiated with the client, which each new session computes its ranges in.
+    pub(crate) position_encoding: Arc<RwLock<PositionEncoding>>,
+    // The latest contents of the documents that were edited while their session couldn't be found,
+    // by their workspace path. They're written to the session once it's initialized.
+    pending_documents: DashMap<PathBuf, TextDocument>,
+}
 
 impl Sessions {
+    pub(crate) fn new(config: Arc<RwLock<Config>>) -> Self {
+        let max_parallel_init = config.read().max_parallel_init.max(1);
+        Self {
+            sessions: DashMap::new(),
+            initializing: DashMap::new(),
+            failed_inits: DashMap::new(),
+            init_permits: Semaphore::new(max_parallel_init),
+            init_permits_total: Mutex::new(max_parallel_init),
+            config,
+            workspace_folders: Arc::new(RwLock::new(vec![])),
+            position_encoding: Arc::new(RwLock::new(PositionEncoding::default())),
+            pending_documents: DashMap::new(),
+        }
+    }
+
+    /// Resizes `init_permits` to the current `Config::max_parallel_init`, as it may have changed
+    /// since the semaphore was created.
+    ///
+    /// Permits that are held can't be taken back, so shrinking takes effect as the running inits finish.
+    fn resize_init_permits(&self) {
+        let max_parallel_init = self.config.read().max_parallel_init.max(1);
+        let mut total = self.init_permits_total.lock();
+        if max_parallel_init > *total {
+            self.init_permits.add_permits(max_parallel_init - *total);
+            *total = max_parallel_init;
+        } else if max_parallel_init < *total {
+            *total -= self.init_permits.forget_permits(*total - max_parallel_init);
+        }
+    }
+
     async fn init(&self, uri: &Url) -> Result<(), LanguageServerError> {
+        self.resize_init_permits();
+        // The permit is released when it's dropped, whether the init succeeds or fails.
+        let _permit = self
+            .init_permits
+            .acquire()
+            .await
+            .expect("the init semaphore is never closed");
         let session = Arc::new(Session::new());
-        let project_name = session.init(uri).await?;
+        *session.position_encoding.write() = *self.position_encoding.read();
+        let (temp_dir, sync_mode) = {
+            let config = self.config.read();
+            (config.temp_dir.clone(), config.sync_mode)
+        };
+        let project_name = session.init(uri, temp_dir.as_deref(), sync_mode).await?;
+        *session.config.write() = self.config.read().resolve_for_manifest_dir(&project_name);
         self.insert(project_name, session);
         Ok(())
     }
 
+    /// Re-resolves the config of every session from the current global config.
+    /// Returns the sessions whose build config or `analyzeUnsaved` changed, as they need to be recompiled.
+    pub(crate) fn resolve_configs(&self) -> Vec<Arc<Session>> {
+        let config = self.config.read();
+        self.iter()
+            .filter_map(|item| {
+                let resolved = config.resolve_for_manifest_dir(item.key());
+                let mut session_config = item.value().config.write();
+                let needs_recompile = session_config.build != resolved.build
+                    || session_config.analyze_unsaved != resolved.analyze_unsaved;
+                *session_config = resolved;
+                needs_recompile.then(|| item.value().clone())
+            })
+            .collect()
+    }
+
+    /// Replaces the session of `manifest_dir` with a new one, e.g. after its `Forc.toml` changed,
+    /// so that its dependencies are resolved again.
+    ///
+    /// The unsaved changes of the old session are carried over. Returns `None` if there was no session
+    /// for the project or its `Forc.toml` no longer exists.
+    pub(crate) async fn reinit(
+        &self,
+        manifest_dir: &Path,
+    ) -> Result<Option<Arc<Session>>, LanguageServerError> {
+        let Some((_, old_session)) = self.remove(manifest_dir) else {
+            return Ok(None);
+        };
+        let unsaved_documents = old_session
+            .unsaved_documents
+            .iter()
+            .filter_map(|path| {
+                let document = old_session.documents.get(path.key())?;
+                let uri = Url::from_file_path(path.key()).ok()?;
+                let uri = old_session.sync.temp_to_workspace_url(&uri).ok()?;
+                Some((uri, document.get_text()))
+            })
+            .collect::<Vec<_>>();
+        old_session.shutdown();
+        if !manifest_dir
+            .join(sway_utils::constants::MANIFEST_FILE_NAME)
+            .is_file()
+        {
+            return Ok(None);
+        }
+        let uri =
+            Url::from_file_path(manifest_dir).map_err(|_| DirectoryError::UrlFromPathFailed {
+                path: manifest_dir.to_string_lossy().to_string(),
+            })?;
+        let session = self.url_to_session(&uri).await?;
+        for (uri, text) in unsaved_documents {
+            let Ok(uri) = session.sync.workspace_to_temp_url(&uri) else {
+                continue;
+            };
+            let change = TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text,
+            };
+            let _ = session.write_changes_to_file(&uri, vec![change]).await;
+        }
+        Ok(Some(session))
+    }
+
     /// Constructs and returns a tuple of `(Url, Arc<Session>)` from a given workspace URI.
     /// The returned URL represents the temp directory workspace.
+    ///
+    /// Fails with [LanguageServerError::SessionShutDown] if the session was shut down in the meantime.
     pub(crate) async fn uri_and_session_from_workspace(
         &self,
         workspace_uri: &Url,
     ) -> Result<(Url, Arc<Session>), LanguageServerError> {
         let session = self.url_to_session(workspace_uri).await?;
-        let uri = session.sync.workspace_to_temp_url(workspace_uri)?;
+        let uri = session.temp_url(workspace_uri)?;
         Ok((uri, session))
     }
 
+    /// Keeps the edits of the file at `workspace_uri`, whose session couldn't be found, until the session
+    /// is initialized, so that it starts from the current contents of the file rather than those on disk.
+    ///
+    /// The first edits are applied to the contents on disk. If the session was initialized in the meantime,
+    /// the edits are written to it right away.
+    pub(crate) async fn buffer_changes(
+        &self,
+        workspace_uri: &Url,
+        changes: Vec<TextDocumentContentChangeEvent>,
+    ) {
+        let path = PathBuf::from(workspace_uri.path());
+        let mut document = match self.pending_documents.remove(&path) {
+            Some((_, document)) => document,
+            None => match TextDocument::build_from_path(workspace_uri.path()).await {
+                Ok(document) => document,
+                Err(err) => {
+                    tracing::error!("Unable to keep the changes of {}: {}", workspace_uri, err);
+                    return;
+                }
+            },
+        };
+        let encoding = *self.position_encoding.read();
+        for change in &changes {
+            document.apply_change(change, encoding);
+        }
+        self.pending_documents.insert(path, document);
+
+        let Ok(manifest_dir) = self.session_dir_from_uri(workspace_uri) else {
+            return;
+        };
+        if let Some(session) = self.get(&manifest_dir).map(|item| item.value().clone()) {
+            self.write_pending_documents(&manifest_dir, &session).await;
+        }
+    }
+
+    /// Drops the edits that were kept for the file at `workspace_uri`, e.g. as the file has been saved since.
+    pub(crate) fn discard_pending_document(&self, workspace_uri: &Url) {
+        self.pending_documents
+            .remove(&PathBuf::from(workspace_uri.path()));
+    }
+
+    /// Writes the documents that were edited before the session of `manifest_dir` was initialized to it.
+    async fn write_pending_documents(&self, manifest_dir: &Path, session: &Session) {
+        let paths: Vec<PathBuf> = self
+            .pending_documents
+            .iter()
+            .map(|item| item.key().clone())
+            .filter(|path| path.starts_with(manifest_dir))
+            .collect();
+        for path in paths {
+            let Some((_, document)) = self.pending_documents.remove(&path) else {
+                continue;
+            };
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let Ok(uri) = session.sync.workspace_to_temp_url(&uri) else {
+                continue;
+            };
+            session.handle_open_file(&uri).await;
+            let change = TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: document.get_text(),
+            };
+            if let Err(err) = session.write_changes_to_file(&uri, vec![change]).await {
+                tracing::error!("Unable to write the changes of {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Waits for any in-flight initialization of the session that `workspace_uri` belongs to.
+    ///
+    /// Notification handlers call this before looking up the session, as unlike requests
+    /// they can't be retried by the client.
+    pub(crate) async fn wait_for_init(&self, workspace_uri: &Url) {
+        let Ok(manifest_dir) = self.session_dir_from_uri(workspace_uri) else {
+            return;
+        };
+        let Some(notify) = self
+            .initializing
+            .get(&manifest_dir)
+            .map(|item| item.value().clone())
+        else {
+            return;
+        };
+        // Register for the notification before checking again so that we can't miss it.
+        let notified = notify.notified();
+        if self.initializing.contains_key(&manifest_dir) {
+            notified.await;
+        }
+    }
+
+    /// Returns the directory of the `Forc.toml` that the file at `uri` belongs to, which its session is kept by.
+    ///
+    /// In single-file mode, a Sway file that isn't in any package is kept by its own path instead.
+    pub(crate) fn session_dir_from_uri(&self, uri: &Url) -> Result<PathBuf, LanguageServerError> {
+        if self.config.read().root_detection == RootDetection::WorkspaceRoot {
+            if let Some(dir) = self.workspace_root_package_dir(uri) {
+                return Ok(dir);
+            }
+        }
+        match manifest_dir_from_uri(uri) {
+            Err(LanguageServerError::DocumentError(DocumentError::ManifestFileNotFound {
+                ..
+            })) if self.is_standalone_file(uri) => Ok(PathBuf::from(uri.path())),
+            result => result,
+        }
+    }
+
+    /// Returns the innermost workspace folder that contains the file at `uri`, if it has a package at its root.
+    fn workspace_root_package_dir(&self, uri: &Url) -> Option<PathBuf> {
+        let path = Path::new(uri.path());
+        self.workspace_folders
+            .read()
+            .iter()
+            .filter_map(|folder| folder.to_file_path().ok())
+            .filter(|folder| path.starts_with(folder))
+            .max_by_key(|folder| folder.components().count())
+            .filter(|folder| {
+                PackageManifestFile::from_file(
+                    folder.join(sway_utils::constants::MANIFEST_FILE_NAME),
+                )
+                .is_ok()
+            })
+    }
+
+    fn is_standalone_file(&self, uri: &Url) -> bool {
+        let config = self.config.read();
+        config.single_file_mode && config.is_sway_file(uri) && Path::new(uri.path()).is_file()
+    }
+
+    /// Returns true if the file at `uri` may be analyzed, which it may unless `allowFilesOutsideWorkspace`
+    /// is disabled and it's outside all of the workspace folders. The files of the dependencies of the
+    /// projects that have a session, such as `std`, may always be, as they're navigated to from the workspace.
+    fn is_analyzed(&self, uri: &Url) -> bool {
+        if self.config.read().allow_files_outside_workspace {
+            return true;
+        }
+        let path = Path::new(uri.path());
+        self.workspace_folders
+            .read()
+            .iter()
+            .filter_map(|folder| folder.to_file_path().ok())
+            .any(|folder| path.starts_with(folder))
+            || self
+                .iter()
+                .any(|item| item.value().sync.is_path_in_dependency(uri))
+    }
+
     async fn url_to_session(&self, uri: &Url) -> Result<Arc<Session>, LanguageServerError> {
-        let path = PathBuf::from(uri.path());
-        let manifest = PackageManifestFile::from_dir(&path).map_err(|_| {
-            DocumentError::ManifestFileNotFound {
-                dir: path.to_string_lossy().to_string(),
-            }
-        })?;
-
-        // strip Forc.toml from the path to get the manifest directory
-        let manifest_dir = manifest
-            .path()
-            .parent()
-            .ok_or(DirectoryError::ManifestDirNotFound)?
-            .to_path_buf();
-
-        let session = match self.try_get(&manifest_dir).try_unwrap() {
-            Some(item) => item.value().clone(),
-            None => {
-                // If no session can be found, then we need to call init and inserst a new session into the map
-                self.init(uri).await?;
-                self.try_get(&manifest_dir)
-                    .try_unwrap()
-                    .map(|item| item.value().clone())
-                    .expect("no session found even though it was just inserted into the map")
+        if !self.is_analyzed(uri) {
+            return Err(LanguageServerError::OutsideWorkspace {
+                path: uri.path().to_string(),
+            });
+        }
+        let manifest_dir = self.session_dir_from_uri(uri)?;
+        if let Some(item) = self.try_get(&manifest_dir).try_unwrap() {
+            return Ok(item.value().clone());
+        }
+
+        // The files of a dependency, e.g. one opened by going to a definition in it, belong to the
+        // session of a project that depends on it rather than to a new session of their own.
+        if let Some(session) = self
+            .iter()
+            .find(|item| item.value().sync.is_path_in_dependency(uri))
+            .map(|item| item.value().clone())
+        {
+            return Ok(session);
+        }
+
+        // If the last attempt failed recently, return its error rather than hitting the filesystem again.
+        if let Some(failed) = self.failed_inits.get(&manifest_dir) {
+            let now = Instant::now();
+            if now < failed.retry_at {
+                return Err(LanguageServerError::SessionInitFailed {
+                    dir: manifest_dir.to_string_lossy().to_string(),
+                    reason: failed.reason.clone(),
+                    retry_after_ms: (failed.retry_at - now).as_millis() as u64,
+                });
+            }
+        }
+
+        // If no session can be found, then we need to call init and insert a new session into the map.
+        // Only one init is allowed per manifest dir, other callers are told to try again later.
+        let init_guard = match self.initializing.entry(manifest_dir.clone()) {
+            Entry::Occupied(_) => {
+                return Err(LanguageServerError::SessionInitializing {
+                    dir: manifest_dir.to_string_lossy().to_string(),
+                })
             }
+            Entry::Vacant(entry) => InitGuard {
+                initializing: &self.initializing,
+                manifest_dir: manifest_dir.clone(),
+                notify: entry.insert(Arc::new(Notify::new())).value().clone(),
+            },
+        };
+        // The session is initialized from the manifest dir rather than `uri`, which may be the root
+        // of a workspace rather than a file of the member package it resolved to.
+        let manifest_uri =
+            Url::from_file_path(&manifest_dir).map_err(|_| DirectoryError::UrlFromPathFailed {
+                path: manifest_dir.to_string_lossy().to_string(),
+            });
+        let result = match manifest_uri {
+            Ok(manifest_uri) => self.init(&manifest_uri).await,
+            Err(err) => Err(err.into()),
         };
+        match &result {
+            Ok(()) => {
+                self.failed_inits.remove(&manifest_dir);
+            }
+            Err(err) => {
+                let failed = FailedInit::next(
+                    self.failed_inits.get(&manifest_dir).as_deref(),
+                    err.to_string(),
+                );
+                self.failed_inits.insert(manifest_dir.clone(), failed);
+            }
+        }
+        drop(init_guard);
+        result?;
+
+        // The session may have been removed again in the meantime, e.g. by a reload of the workspace.
+        let session = self
+            .try_get(&manifest_dir)
+            .try_unwrap()
+            .map(|item| item.value().clone())
+            .ok_or_else(|| LanguageServerError::SessionInitializing {
+                dir: manifest_dir.to_string_lossy().to_string(),
+            })?;
+        self.write_pending_documents(&manifest_dir, &session).await;
         Ok(session)
     }
 }
 
+/// Returns the range of the line that a `Forc.toml` parse error points at, such as `TOML parse error
+/// at line 3, column 5`, from the column of the error to the end of the line.
+///
+/// Errors without a location are put at the start of the file.
+fn manifest_error_range(contents: &str, err: &str, encoding: PositionEncoding) -> Range {
+    let location = err.split_once("at line ").and_then(|(_, rest)| {
+        let (line, rest) = rest.split_once(", column ")?;
+        let column = rest
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect::<String>();
+        Some((line.parse::<u32>().ok()?, column.parse::<u32>().ok()?))
+    });
+    let Some((line, column)) = location else {
+        return Range::default();
+    };
+    // The location is 1-based.
+    let line = line.saturating_sub(1);
+    let column = column.saturating_sub(1);
+    let line_length = contents
+        .lines()
+        .nth(line as usize)
+        .map_or(0, |text| encoding.len(text));
+    Range::new(
+        Position::new(line, column),
+        Position::new(line, line_length.max(column)),
+    )
+}
+
+/// Returns the `Forc.toml` of the package that the file at `uri` belongs to.
+///
+/// Files of a workspace that aren't in any of its members, like the workspace root itself,
+/// belong to the first of its members that can be loaded.
+pub(crate) fn manifest_from_uri(uri: &Url) -> Result<PackageManifestFile, LanguageServerError> {
+    let path = PathBuf::from(uri.path());
+    let manifest =
+        ManifestFile::from_dir(&path).map_err(|err| DocumentError::manifest_error(&path, &err))?;
+    match manifest {
+        ManifestFile::Package(manifest) => Ok(*manifest),
+        ManifestFile::Workspace(workspace) => workspace
+            .member_pkg_manifests()
+            .ok()
+            .and_then(|mut members| members.find_map(Result::ok))
+            .ok_or_else(|| {
+                DocumentError::WorkspaceWithoutMembers {
+                    dir: workspace.dir().to_string_lossy().to_string(),
+                }
+                .into()
+            }),
+    }
+}
+
+/// Returns the urls of the member packages of the workspace at `uri`,
+/// or `uri` itself if it isn't the root of a workspace.
+pub(crate) fn package_uris(uri: &Url) -> Vec<Url> {
+    let Ok(ManifestFile::Workspace(workspace)) = ManifestFile::from_dir(Path::new(uri.path()))
+    else {
+        return vec![uri.clone()];
+    };
+    let Ok(member_paths) = workspace.member_paths() else {
+        return vec![uri.clone()];
+    };
+    member_paths
+        .filter_map(|path| Url::from_file_path(path).ok())
+        .collect()
+}
+
+/// Returns the directories of the packages under `dir`, including `dir` itself, in the order of their paths.
+///
+/// Hidden directories and those of `workspace.excludeDirs`, like the build outputs of packages, aren't searched,
+/// and neither are workspace manifests, whose members are found as packages of their own.
+pub(crate) fn discover_packages(root: &Path, config: &WorkspaceConfig) -> Vec<PathBuf> {
+    let mut package_dirs = vec![];
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let manifest_path = dir.join(sway_utils::constants::MANIFEST_FILE_NAME);
+        if let Ok(ManifestFile::Package(_)) = ManifestFile::from_file(manifest_path) {
+            package_dirs.push(dir.clone());
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        dirs.extend(
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+                .filter(|entry| {
+                    entry
+                        .path()
+                        .strip_prefix(root)
+                        .map_or(true, |dir| !config.is_dir_excluded(dir))
+                })
+                .map(|entry| entry.path()),
+        );
+    }
+    package_dirs.sort();
+    package_dirs
+}
+
+/// Returns the directory of the `Forc.toml` that the file at `uri` belongs to.
+pub(crate) fn manifest_dir_from_uri(uri: &Url) -> Result<PathBuf, LanguageServerError> {
+    let manifest = manifest_from_uri(uri)?;
+    manifest_dir_of(&manifest)
+}
+
+/// Returns the directory that `manifest` is in.
+pub(crate) fn manifest_dir_of(
+    manifest: &PackageManifestFile,
+) -> Result<PathBuf, LanguageServerError> {
+    // strip Forc.toml from the path to get the manifest directory
+    let manifest_dir = manifest
+        .path()
+        .parent()
+        .ok_or(DirectoryError::ManifestDirNotFound)?
+        .to_path_buf();
+    Ok(manifest_dir)
+}
+
 impl std::ops::Deref for Sessions {
     type Target = DashMap<PathBuf, Arc<Session>>;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.sessions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::request;
+    use sway_lsp_test_utils::{get_absolute_path, get_url};
+    use sway_types::{Ident, Span};
+
+    #[tokio::test]
+    async fn prewarm_sessions_skips_projects_that_fail_to_initialize() {
+        let state = ServerState::default();
+        state.config.write().prewarm_projects = vec![
+            get_url(&get_absolute_path("sway-lsp/tests/fixtures")),
+            get_url(&get_absolute_path("sway-lsp/tests/fixtures/completion")),
+        ];
+        state.prewarm_sessions().await.unwrap();
+        assert_eq!(state.sessions.len(), 1);
+        let session = state.sessions.iter().next().unwrap().value().clone();
+        assert!(session.token_map().iter().next().is_some());
+        let _ = state.shutdown_server().await;
+    }
+
+    #[test]
+    fn discover_packages_finds_every_package_under_a_folder() {
+        let dir = PathBuf::from(get_absolute_path("sway-lsp/tests/fixtures/diagnostics"));
+        let package_dirs = discover_packages(&dir, &WorkspaceConfig::default());
+        assert_eq!(package_dirs.len(), 9);
+        assert_eq!(package_dirs[0], dir.join("dead_code"));
+        assert!(!package_dirs.contains(&dir));
+
+        let config = WorkspaceConfig {
+            exclude_dirs: vec!["dead_code".to_string()],
+        };
+        let package_dirs = discover_packages(&dir, &config);
+        assert_eq!(package_dirs.len(), 8);
+        assert!(!package_dirs.contains(&dir.join("dead_code")));
+    }
+
+    #[tokio::test]
+    async fn compile_workspace_on_open_compiles_every_package() {
+        let state = ServerState::default();
+        assert!(state.compile_workspace_on_open().is_none());
+        state.config.write().compile_workspace_on_open = true;
+        *state.workspace_folders.write() = vec![get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/diagnostics/multi_file",
+        ))];
+        state.compile_workspace_on_open().unwrap().await.unwrap();
+        assert_eq!(state.sessions.len(), 1);
+        let session = state.sessions.iter().next().unwrap().value().clone();
+        assert!(session.token_map().iter().next().is_some());
+        let _ = state.shutdown_server().await;
+    }
+
+    #[tokio::test]
+    async fn memory_report_counts_the_contents_of_each_session() {
+        let state = ServerState::default();
+        assert!(request::handle_memory_report(&state).unwrap().is_none());
+        state.config.write().debug.memory_report = true;
+        state.config.write().compile_workspace_on_open = true;
+        *state.workspace_folders.write() = vec![get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/diagnostics/multi_file",
+        ))];
+        state.compile_workspace_on_open().unwrap().await.unwrap();
+        let report = request::handle_memory_report(&state).unwrap().unwrap();
+        assert_eq!(report.sessions.len(), 1);
+        let session = &report.sessions[0];
+        assert!(session.types > 0);
+        assert!(session.declarations > 0);
+        assert!(session.sources > 0);
+        assert!(session.tokens > 0);
+        assert!(session.approximate_bytes > 0);
+        let _ = state.shutdown_server().await;
+    }
+
+    #[test]
+    fn stale_diagnostics_versions_are_dropped() {
+        let versions = DashMap::new();
+        let uri = get_url(&get_absolute_path("sway-lsp/tests/fixtures/cats.txt"));
+        assert!(record_diagnostics_version(&versions, &uri, Some(2)));
+        assert!(!record_diagnostics_version(&versions, &uri, Some(1)));
+        assert!(record_diagnostics_version(&versions, &uri, Some(2)));
+        assert!(record_diagnostics_version(&versions, &uri, None));
+        assert!(record_diagnostics_version(&versions, &uri, Some(3)));
+        assert_eq!(*versions.get(&uri).unwrap(), 3);
+    }
+
+    #[test]
+    fn enqueue_compilation_replaces_oldest_requests() {
+        let state = ServerState::default();
+        // Stop the compilation thread so that the queue isn't consumed.
+        state.cb_tx.send(TaskMessage::Terminate).unwrap();
+        while state.compilation_thread_alive.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        state.config.write().compilation.queue_capacity = 2;
+        for version in 1..=3 {
+            state.enqueue_compilation(CompilationContext {
+                version: Some(version),
+                ..Default::default()
+            });
+        }
+        let health = state.health_check_result();
+        assert_eq!(health.queue_length, 2);
+        assert_eq!(health.queue_capacity, 2);
+        assert_eq!(health.coalesced_compilations, 1);
+        let versions = state
+            .cb_rx
+            .try_iter()
+            .map(|msg| match msg {
+                TaskMessage::CompilationContext(ctx) => ctx.version,
+                TaskMessage::Terminate => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(versions, vec![Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn interactive_compilations_are_received_first() {
+        let (interactive_tx, interactive_rx) = crossbeam_channel::unbounded();
+        let (background_tx, background_rx) = crossbeam_channel::unbounded();
+        let ctx = |version, priority| {
+            TaskMessage::CompilationContext(CompilationContext {
+                version: Some(version),
+                priority,
+                ..Default::default()
+            })
+        };
+        background_tx
+            .send(ctx(1, CompilationPriority::Background))
+            .unwrap();
+        interactive_tx
+            .send(ctx(2, CompilationPriority::Interactive))
+            .unwrap();
+        background_tx
+            .send(ctx(3, CompilationPriority::Background))
+            .unwrap();
+        let versions = (0..3)
+            .map(
+                |_| match recv_by_priority(&interactive_rx, &background_rx).unwrap() {
+                    TaskMessage::CompilationContext(ctx) => ctx.version,
+                    TaskMessage::Terminate => None,
+                },
+            )
+            .collect::<Vec<_>>();
+        assert_eq!(versions, vec![Some(2), Some(1), Some(3)]);
+        drop(interactive_tx);
+        assert!(recv_by_priority(&interactive_rx, &background_rx).is_err());
+    }
+
+    #[tokio::test]
+    async fn shutdown_returns_if_the_compilation_is_stuck() {
+        let state = ServerState::default();
+        // Pretend that the compilation thread is stuck in a compilation that never finishes.
+        state.is_compiling.store(true, Ordering::SeqCst);
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            state.shutdown_with_timeout(Duration::from_millis(10)),
+        )
+        .await;
+        assert!(matches!(result, Ok(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn waiting_for_parsing_stops_at_the_request_deadline() {
+        let state = ServerState::default();
+        state.config.write().request_timeout_ms = 10;
+        // Pretend that the user keeps typing, so the compilation never finishes.
+        state.is_compiling.store(true, Ordering::SeqCst);
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            state.wait_for_parsing_until_deadline(),
+        )
+        .await;
+        assert!(result.is_ok());
+        state.is_compiling.store(false, Ordering::SeqCst);
+        let _ = state.shutdown_server().await;
+    }
+
+    #[test]
+    fn removed_files_are_cleared() {
+        let session = Session::new();
+        let project = PathBuf::from(get_absolute_path(
+            "sway-lsp/tests/fixtures/diagnostics/dead_code",
+        ));
+        session
+            .sync
+            .directories
+            .insert(crate::core::sync::Directory::Manifest, project.clone());
+        session.sync.directories.insert(
+            crate::core::sync::Directory::Temp,
+            std::env::temp_dir().join("removed_files_are_cleared"),
+        );
+        let existing = Url::from_file_path(project.join("src/main.sw")).unwrap();
+        let removed = Url::from_file_path(project.join("src/removed.sw")).unwrap();
+        let not_sway = Url::from_file_path(project.join("Removed.toml")).unwrap();
+        let elsewhere = Url::from_file_path(std::env::temp_dir().join("elsewhere.sw")).unwrap();
+        let published = DashSet::new();
+        for uri in [&existing, &removed, &not_sway, &elsewhere] {
+            published.insert(uri.clone());
+        }
+        assert_eq!(removed_files(&session, &published), vec![removed]);
+    }
+
+    #[test]
+    fn diagnostics_of_dependencies_are_only_published_if_included() {
+        let session = Session::new();
+        let project = get_absolute_path("sway-lsp/tests/fixtures/diagnostics/dead_code");
+        let dependency = get_absolute_path("sway-lib-core");
+        session.sync.directories.insert(
+            crate::core::sync::Directory::Manifest,
+            PathBuf::from(project),
+        );
+        *session.sync.dependency_dirs.write() = vec![PathBuf::from(&dependency)];
+        let path = PathBuf::from(&dependency).join("src/ops.sw");
+        session.diagnostics.write().insert(
+            path.clone(),
+            diagnostic::Diagnostics {
+                warnings: vec![Diagnostic::default()],
+                errors: vec![],
+            },
+        );
+        let uri = Url::from_file_path(path).unwrap();
+        assert!(diagnostics(&uri, &session).is_empty());
+        session.config.write().diagnostic.include_dependencies = true;
+        assert_eq!(diagnostics(&uri, &session).len(), 1);
+        assert_eq!(
+            diagnostics(&uri, &session)[0].source.as_deref(),
+            Some("sway")
+        );
+        session.config.write().diagnostic.source_label = String::new();
+        assert_eq!(diagnostics(&uri, &session)[0].source, None);
+    }
+
+    #[test]
+    fn manifest_error_range_points_at_the_error() {
+        let contents = "[project]\nname = \nlicense = \"Apache-2.0\"\n";
+        let err =
+            "failed to parse manifest: TOML parse error at line 2, column 8\n  |\n2 | name = \n";
+        assert_eq!(
+            manifest_error_range(contents, err, PositionEncoding::Utf16),
+            Range::new(Position::new(1, 7), Position::new(1, 7))
+        );
+        let err = "failed to parse manifest: TOML parse error at line 3, column 1";
+        assert_eq!(
+            manifest_error_range(contents, err, PositionEncoding::Utf16),
+            Range::new(Position::new(2, 0), Position::new(2, 22))
+        );
+        assert_eq!(
+            manifest_error_range(contents, "missing field `project`", PositionEncoding::Utf16),
+            Range::default()
+        );
+    }
+
+    #[test]
+    fn manifest_parse_errors_are_told_apart_from_missing_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+        std::fs::write(dir.path().join("Forc.toml"), "[project]\nname = \n").unwrap();
+        let uri = Url::from_file_path(src.join("main.sw")).unwrap();
+        let err = manifest_from_uri(&uri).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                LanguageServerError::DocumentError(DocumentError::ManifestParseFailed { .. })
+            ),
+            "{err:?}"
+        );
+
+        std::fs::remove_file(dir.path().join("Forc.toml")).unwrap();
+        let err = manifest_from_uri(&uri).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                LanguageServerError::DocumentError(DocumentError::ManifestFileNotFound { .. })
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn workspace_root_detection_ignores_nested_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let nested = root.join("tests").join("fixture");
+        for (package, name) in [(&root, "root"), (&nested, "fixture")] {
+            std::fs::create_dir_all(package.join("src")).unwrap();
+            std::fs::write(
+                package.join("Forc.toml"),
+                format!(
+                    "[project]\nname = \"{name}\"\nentry = \"main.sw\"\nlicense = \"Apache-2.0\"\n"
+                ),
+            )
+            .unwrap();
+            std::fs::write(package.join("src").join("main.sw"), "library;\n").unwrap();
+        }
+        let uri = Url::from_file_path(nested.join("src").join("main.sw")).unwrap();
+        let config = Arc::new(RwLock::new(Config::default()));
+        let sessions = Sessions::new(config.clone());
+        *sessions.workspace_folders.write() = vec![Url::from_file_path(&root).unwrap()];
+        assert_eq!(sessions.session_dir_from_uri(&uri).unwrap(), nested);
+
+        config.write().root_detection = RootDetection::WorkspaceRoot;
+        assert_eq!(sessions.session_dir_from_uri(&uri).unwrap(), root);
+        // Without a package at the root of the folder, the nearest manifest is used.
+        *sessions.workspace_folders.write() =
+            vec![Url::from_file_path(root.join("tests")).unwrap()];
+        assert_eq!(sessions.session_dir_from_uri(&uri).unwrap(), nested);
+    }
+
+    #[tokio::test]
+    async fn files_outside_the_workspace_can_be_ignored() {
+        let config = Arc::new(RwLock::new(Config::default()));
+        let sessions = Sessions::new(config.clone());
+        *sessions.workspace_folders.write() = vec![get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/tokens",
+        ))];
+        let outside = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/diagnostics/dead_code/src/main.sw",
+        ));
+        let inside = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/tokens/fields/src/main.sw",
+        ));
+        assert!(sessions.is_analyzed(&outside));
+
+        config.write().allow_files_outside_workspace = false;
+        assert!(sessions.is_analyzed(&inside));
+        assert!(matches!(
+            sessions.uri_and_session_from_workspace(&outside).await,
+            Err(LanguageServerError::OutsideWorkspace { .. })
+        ));
+        assert!(sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn synchronous_first_compile_finishes_before_did_open_returns() {
+        let state = ServerState::default();
+        state.config.write().synchronous_first_compile = true;
+        let path = get_absolute_path("sway-lsp/tests/fixtures/diagnostics/dead_code/src/main.sw");
+        let uri = get_url(&path);
+        let params = lsp_types::DidOpenTextDocumentParams {
+            text_document: lsp_types::TextDocumentItem {
+                uri: uri.clone(),
+                language_id: "sway".to_string(),
+                version: 1,
+                text: std::fs::read_to_string(&path).unwrap(),
+            },
+        };
+        crate::handlers::notification::handle_did_open_text_document(&state, params)
+            .await
+            .unwrap();
+        // Without waiting for the compilation thread.
+        assert_eq!(
+            state.last_compilation_state(),
+            LastCompilationState::Success
+        );
+        let (temp_uri, session) = state
+            .sessions
+            .uri_and_session_from_workspace(&uri)
+            .await
+            .unwrap();
+        assert!(session
+            .token_map()
+            .tokens_for_file(&temp_uri)
+            .next()
+            .is_some());
+        assert!(!diagnostics(&temp_uri, &session).is_empty());
+        let _ = state.shutdown_server().await;
+    }
+
+    #[tokio::test]
+    async fn requests_fail_cleanly_once_their_session_is_shut_down() {
+        let state = ServerState::default();
+        let uri = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/diagnostics/dead_code/src/main.sw",
+        ));
+        let (_, session) = state
+            .sessions
+            .uri_and_session_from_workspace(&uri)
+            .await
+            .unwrap();
+        // As if the session was replaced while a request was being handled with it.
+        session.shutdown();
+        assert!(matches!(
+            state.sessions.uri_and_session_from_workspace(&uri).await,
+            Err(LanguageServerError::SessionShutDown { .. })
+        ));
+        let err = request::handle_file_diagnostics(
+            &state,
+            crate::lsp_ext::FileDiagnosticsParams { uri: uri.clone() },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.code,
+            jsonrpc::ErrorCode::ServerError(crate::error::SESSION_SHUT_DOWN_ERROR_CODE)
+        );
+        let err = request::handle_document_symbol(
+            &state,
+            lsp_types::DocumentSymbolParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.code,
+            jsonrpc::ErrorCode::ServerError(crate::error::SESSION_SHUT_DOWN_ERROR_CODE)
+        );
+        let _ = state.shutdown_server().await;
+    }
+
+    #[test]
+    fn workspace_files_resolve_to_their_member_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Forc.toml"),
+            "[workspace]\nmembers = [\"a\", \"b\"]\n",
+        )
+        .unwrap();
+        for member in ["a", "b"] {
+            let src = dir.path().join(member).join("src");
+            std::fs::create_dir_all(&src).unwrap();
+            std::fs::write(
+                dir.path().join(member).join("Forc.toml"),
+                format!("[project]\nname = \"{member}\"\nentry = \"main.sw\"\nlicense = \"Apache-2.0\"\n"),
+            )
+            .unwrap();
+            std::fs::write(src.join("main.sw"), "library;\n").unwrap();
+        }
+        let root = dir.path().canonicalize().unwrap();
+
+        let uri = Url::from_file_path(root.join("b").join("src").join("main.sw")).unwrap();
+        assert_eq!(manifest_dir_from_uri(&uri).unwrap(), root.join("b"));
+
+        let uri = Url::from_file_path(&root).unwrap();
+        assert_eq!(manifest_dir_from_uri(&uri).unwrap(), root.join("a"));
+        assert_eq!(
+            package_uris(&uri),
+            vec![
+                Url::from_file_path(root.join("a")).unwrap(),
+                Url::from_file_path(root.join("b")).unwrap(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn dropping_an_init_part_way_through_lets_it_start_again() {
+        let config = Arc::new(RwLock::new(Config {
+            max_parallel_init: 1,
+            ..Config::default()
+        }));
+        let sessions = Sessions::new(config);
+        let uri = get_url(&get_absolute_path(
+            "sway-lsp/tests/fixtures/diagnostics/dead_code/src/main.sw",
+        ));
+        let manifest_dir = sessions.session_dir_from_uri(&uri).unwrap();
+
+        // Holding the only permit keeps the init waiting until its request times out and is dropped.
+        let permit = sessions.init_permits.acquire().await.unwrap();
+        let init = sessions.uri_and_session_from_workspace(&uri);
+        assert!(tokio::time::timeout(Duration::from_millis(50), init)
+            .await
+            .is_err());
+        assert!(!sessions.initializing.contains_key(&manifest_dir));
+        // Nothing is left for waiters to wait on.
+        sessions.wait_for_init(&uri).await;
+
+        drop(permit);
+        let (_, session) = sessions.uri_and_session_from_workspace(&uri).await.unwrap();
+        session.shutdown();
+    }
+
+    #[test]
+    fn init_permits_follow_max_parallel_init() {
+        let config = Arc::new(RwLock::new(Config {
+            max_parallel_init: 2,
+            ..Config::default()
+        }));
+        let sessions = Sessions::new(config.clone());
+        assert_eq!(sessions.init_permits.available_permits(), 2);
+
+        config.write().max_parallel_init = 4;
+        sessions.resize_init_permits();
+        assert_eq!(sessions.init_permits.available_permits(), 4);
+
+        config.write().max_parallel_init = 0;
+        sessions.resize_init_permits();
+        assert_eq!(sessions.init_permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn changes_without_a_session_are_kept_until_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.sw");
+        std::fs::write(&path, "script;\n").unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+        let sessions = Sessions::new(Arc::new(RwLock::new(Config::default())));
+        let change = |line: u32, text: &str| TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(line, 0), Position::new(line, 0))),
+            range_length: None,
+            text: text.to_string(),
+        };
+
+        sessions
+            .buffer_changes(&uri, vec![change(1, "fn main() {}")])
+            .await;
+        sessions.buffer_changes(&uri, vec![change(1, "// ")]).await;
+        assert_eq!(
+            sessions.pending_documents.get(&path).unwrap().get_text(),
+            "script;\n// fn main() {}"
+        );
+
+        sessions.discard_pending_document(&uri);
+        assert!(sessions.pending_documents.is_empty());
+    }
+
+    #[test]
+    fn compile_time_messages_are_throttled() {
+        let start = Instant::now();
+        let mut last = None;
+        assert!(throttle_compile_time_message(&mut last, start));
+        assert!(!throttle_compile_time_message(
+            &mut last,
+            start + Duration::from_secs(1)
+        ));
+        assert!(throttle_compile_time_message(
+            &mut last,
+            start + COMPILE_TIME_MESSAGE_INTERVAL
+        ));
+        assert_eq!(last, Some(start + COMPILE_TIME_MESSAGE_INTERVAL));
+    }
+
+    #[test]
+    fn only_filesystem_errors_are_retried() {
+        assert_eq!(compile_retry_backoff(1), COMPILE_RETRY_MIN_BACKOFF);
+        assert_eq!(compile_retry_backoff(3), COMPILE_RETRY_MIN_BACKOFF * 4);
+
+        let io_error = anyhow::Error::from(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "locked",
+        ))
+        .context("failed to read Forc.lock");
+        assert!(LanguageServerError::BuildPlanFailed(io_error).is_transient());
+        assert!(
+            !LanguageServerError::FailedToCompile(anyhow::anyhow!("type mismatch")).is_transient()
+        );
+        assert!(!LanguageServerError::ProgramsIsNone.is_transient());
+    }
+
+    #[tokio::test]
+    async fn batch_progress_reaches_100_percent() {
+        let mut progress = BatchProgress::begin(None, "sway/test", "Compiling", 3).await;
+        assert_eq!(
+            (progress.percentage(), progress.message()),
+            (0, "0/3 projects".to_string())
+        );
+        progress.complete(1).await;
+        assert_eq!(progress.percentage(), 33);
+        // Completing more than the total doesn't go past 100%.
+        progress.complete(5).await;
+        assert_eq!(
+            (progress.percentage(), progress.message()),
+            (100, "3/3 projects".to_string())
+        );
+
+        let empty = BatchProgress::begin(None, "sway/test", "Compiling", 0).await;
+        assert_eq!(empty.percentage(), 100);
+    }
+
+    #[test]
+    fn compile_watchdog_cancels_compilations_that_take_too_long() {
+        let session = Arc::new(Session::new());
+        let uri = get_url("/project/src/main.sw");
+        assert!(session.compilation_cancellation.start(&uri, Some(1)));
+        let watchdog = CompileWatchdog::start(session.clone(), 10);
+        thread::sleep(Duration::from_millis(200));
+        assert!(session.compilation_cancellation.is_cancelled());
+        assert_eq!(watchdog.finish(), Some(10));
+        session.compilation_cancellation.finish();
+
+        assert!(session.compilation_cancellation.start(&uri, Some(2)));
+        let watchdog = CompileWatchdog::start(session.clone(), 60_000);
+        assert_eq!(watchdog.finish(), None);
+        assert!(!session.compilation_cancellation.is_cancelled());
+    }
+
+    #[test]
+    fn internal_compiler_errors_are_detected() {
+        let errors = vec![
+            CompileError::UnknownVariable {
+                var_name: Ident::new_no_span("x".into()),
+                span: Span::dummy(),
+            },
+            CompileError::Internal("Unexpected type", Span::dummy()),
+        ];
+        assert!(internal_compiler_error(&errors)
+            .unwrap()
+            .contains("Unexpected type"));
+        assert_eq!(internal_compiler_error(&errors[..1]), None);
+
+        let payload = panic::catch_unwind(|| panic!("index out of bounds")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "index out of bounds");
+        let payload = panic::catch_unwind(|| std::panic::panic_any(1)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "unknown panic");
+
+        let message =
+            internal_compiler_error_message(&get_url("/project/src/main.sw"), Some(3), "oops");
+        assert!(message.contains(ISSUE_TRACKER_URL));
+        assert!(message.contains("file:///project/src/main.sw, version 3"));
+    }
+
+    #[test]
+    fn session_init_backoff_doubles_up_to_max() {
+        assert_eq!(session_init_backoff(1), SESSION_INIT_MIN_BACKOFF);
+        assert_eq!(session_init_backoff(2), SESSION_INIT_MIN_BACKOFF * 2);
+        assert_eq!(session_init_backoff(3), SESSION_INIT_MIN_BACKOFF * 4);
+        assert_eq!(session_init_backoff(100), SESSION_INIT_MAX_BACKOFF);
     }
 }
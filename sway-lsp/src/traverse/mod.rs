@@ -1,4 +1,7 @@
-use crate::core::{token::TokenIdent, token_map::TokenMap};
+use crate::{
+    core::{token::TokenIdent, token_map::TokenMap},
+    utils::position_encoding::PositionEncoding,
+};
 use sway_core::{namespace::Module, Engines};
 
 pub(crate) mod dependency;
@@ -10,19 +13,26 @@ pub struct ParseContext<'a> {
     tokens: &'a TokenMap,
     engines: &'a Engines,
     namespace: &'a Module,
+    encoding: PositionEncoding,
 }
 
 impl<'a> ParseContext<'a> {
-    pub fn new(tokens: &'a TokenMap, engines: &'a Engines, namespace: &'a Module) -> Self {
+    pub fn new(
+        tokens: &'a TokenMap,
+        engines: &'a Engines,
+        namespace: &'a Module,
+        encoding: PositionEncoding,
+    ) -> Self {
         Self {
             tokens,
             engines,
             namespace,
+            encoding,
         }
     }
 
     pub fn ident(&self, ident: &sway_types::Ident) -> TokenIdent {
-        TokenIdent::new(ident, self.engines.se())
+        TokenIdent::new(ident, self.engines.se(), self.encoding)
     }
 }
 
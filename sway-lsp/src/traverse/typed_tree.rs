@@ -344,10 +344,11 @@ impl Parse for ty::TyExpression {
                     {
                         token.typed = Some(TypedAstToken::TypedExpression(field.value.clone()));
 
-                        if let Some(struct_decl) = &ctx
-                            .tokens
-                            .struct_declaration_of_type_id(ctx.engines, &self.return_type)
-                        {
+                        if let Some(struct_decl) = &ctx.tokens.struct_declaration_of_type_id(
+                            ctx.engines,
+                            &self.return_type,
+                            ctx.encoding,
+                        ) {
                             struct_decl.fields.iter().for_each(|decl_field| {
                                 if decl_field.name == field.name {
                                     token.type_def =
@@ -370,6 +371,7 @@ impl Parse for ty::TyExpression {
                 desugared,
                 scrutinees,
             } => {
+                collect_branching_expression(ctx, self, "match");
                 // Order is important here, the expression must be processed first otherwise the
                 // scrutinee information will get overwritten by processing the underlying tree of
                 // conditions
@@ -381,6 +383,7 @@ impl Parse for ty::TyExpression {
                 then,
                 r#else,
             } => {
+                collect_branching_expression(ctx, self, "if");
                 condition.parse(ctx);
                 then.parse(ctx);
                 if let Some(r#else) = r#else {
@@ -1100,10 +1103,11 @@ impl Parse for ty::TyReassignment {
             if let ty::ProjectionKind::StructField { name } = proj_kind {
                 if let Some(mut token) = ctx.tokens.try_get_mut_with_retry(&ctx.ident(name)) {
                     token.typed = Some(TypedAstToken::TypedReassignment(self.clone()));
-                    if let Some(struct_decl) = &ctx
-                        .tokens
-                        .struct_declaration_of_type_id(ctx.engines, &self.lhs_type)
-                    {
+                    if let Some(struct_decl) = &ctx.tokens.struct_declaration_of_type_id(
+                        ctx.engines,
+                        &self.lhs_type,
+                        ctx.encoding,
+                    ) {
                         struct_decl.fields.iter().for_each(|decl_field| {
                             if &decl_field.name == name {
                                 token.type_def =
@@ -1220,6 +1224,31 @@ fn collect_call_path_prefixes(ctx: &ParseContext, prefixes: &[Ident]) {
     }
 }
 
+/// Assigns a `match` or `if` expression to the token of its keyword.
+///
+/// The type of these expressions is unified from their branches and can't be found from any
+/// of their idents, so this is what resolves methods called on them, e.g. `match x { ... }.foo()`.
+fn collect_branching_expression(ctx: &ParseContext, expr: &ty::TyExpression, keyword: &str) {
+    // Desugared expressions reuse the span of the expression they were desugared from.
+    if !expr.span.as_str().starts_with(keyword) {
+        return;
+    }
+    let Some(keyword_span) = Span::new(
+        expr.span.src().clone(),
+        expr.span.start(),
+        expr.span.start() + keyword.len(),
+        expr.span.source_id().cloned(),
+    ) else {
+        return;
+    };
+    if let Some(mut token) = ctx
+        .tokens
+        .try_get_mut_with_retry(&ctx.ident(&Ident::new(keyword_span)))
+    {
+        token.typed = Some(TypedAstToken::TypedExpression(expr.clone()));
+    }
+}
+
 fn collect_const_decl(ctx: &ParseContext, const_decl: &ty::TyConstantDecl, ident: Option<&Ident>) {
     let key = ctx.ident(ident.unwrap_or(const_decl.name()));
 
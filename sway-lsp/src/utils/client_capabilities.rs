@@ -0,0 +1,138 @@
+//! The optional features of the protocol that the client advertised in `initialize`.
+//!
+//! Clients vary widely in what they support, and one that is sent something it didn't ask for may drop it,
+//! show an error or misbehave. Handlers consult these before using an optional feature or field.
+
+use lsp_types::{Diagnostic, DiagnosticTag};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientCapabilities {
+    /// Whether the client can insert completion items that are snippets.
+    pub snippet_support: bool,
+    /// Whether the client can show document symbols as a tree rather than as a flat list.
+    pub hierarchical_document_symbols: bool,
+    /// Whether the client lets us register the files it should watch for us.
+    pub watched_files_registration: bool,
+    /// Whether the client pulls diagnostics with `textDocument/diagnostic` requests, in which case none are pushed.
+    pub pull_diagnostics: bool,
+    /// Whether the client accepts `$/progress` notifications for work done progress that the server creates.
+    pub work_done_progress: bool,
+    /// Whether the client can show the related information of published diagnostics.
+    pub diagnostic_related_information: bool,
+    /// Whether the client interprets the document version of published diagnostics.
+    pub diagnostic_version: bool,
+    /// The tags of published diagnostics that the client can show.
+    pub diagnostic_tags: Vec<DiagnosticTag>,
+}
+
+impl ClientCapabilities {
+    pub fn new(capabilities: &lsp_types::ClientCapabilities) -> Self {
+        let text_document = capabilities.text_document.as_ref();
+        let publish_diagnostics =
+            text_document.and_then(|text_document| text_document.publish_diagnostics.as_ref());
+        Self {
+            snippet_support: text_document
+                .and_then(|text_document| text_document.completion.as_ref())
+                .and_then(|completion| completion.completion_item.as_ref())
+                .and_then(|completion_item| completion_item.snippet_support)
+                .unwrap_or(false),
+            hierarchical_document_symbols: text_document
+                .and_then(|text_document| text_document.document_symbol.as_ref())
+                .and_then(|document_symbol| document_symbol.hierarchical_document_symbol_support)
+                .unwrap_or(false),
+            watched_files_registration: capabilities
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+                .and_then(|watched_files| watched_files.dynamic_registration)
+                .unwrap_or(false),
+            pull_diagnostics: text_document
+                .is_some_and(|text_document| text_document.diagnostic.is_some()),
+            work_done_progress: capabilities
+                .window
+                .as_ref()
+                .and_then(|window| window.work_done_progress)
+                .unwrap_or(false),
+            diagnostic_related_information: publish_diagnostics
+                .and_then(|publish_diagnostics| publish_diagnostics.related_information)
+                .unwrap_or(false),
+            diagnostic_version: publish_diagnostics
+                .and_then(|publish_diagnostics| publish_diagnostics.version_support)
+                .unwrap_or(false),
+            diagnostic_tags: publish_diagnostics
+                .and_then(|publish_diagnostics| publish_diagnostics.tag_support.as_ref())
+                .map(|tag_support| tag_support.value_set.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Removes the fields of the diagnostics that are about to be published that the client doesn't support.
+    pub fn strip_diagnostics(&self, diagnostics: &mut [Diagnostic]) {
+        for diagnostic in diagnostics {
+            if !self.diagnostic_related_information {
+                diagnostic.related_information = None;
+            }
+            if let Some(tags) = &mut diagnostic.tags {
+                tags.retain(|tag| self.diagnostic_tags.contains(tag));
+                if tags.is_empty() {
+                    diagnostic.tags = None;
+                }
+            }
+        }
+    }
+
+    /// Returns the document version to publish diagnostics with, if the client interprets it.
+    pub fn diagnostics_version(&self, version: Option<i32>) -> Option<i32> {
+        version.filter(|_| self.diagnostic_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{DiagnosticRelatedInformation, Location, Range, Url};
+
+    #[test]
+    fn missing_capabilities_are_unsupported() {
+        let capabilities = ClientCapabilities::new(&lsp_types::ClientCapabilities::default());
+        assert_eq!(capabilities, ClientCapabilities::default());
+    }
+
+    #[test]
+    fn unsupported_diagnostic_fields_are_stripped() {
+        let capabilities: lsp_types::ClientCapabilities =
+            serde_json::from_value(serde_json::json!({
+                "textDocument": {
+                    "publishDiagnostics": { "tagSupport": { "valueSet": [1] } },
+                },
+                "window": { "workDoneProgress": true },
+            }))
+            .unwrap();
+        let capabilities = ClientCapabilities::new(&capabilities);
+        assert!(capabilities.work_done_progress);
+        assert_eq!(capabilities.diagnostics_version(Some(1)), None);
+
+        let related_information = DiagnosticRelatedInformation {
+            location: Location {
+                uri: Url::parse("file:///main.sw").unwrap(),
+                range: Range::default(),
+            },
+            message: "declared here".to_string(),
+        };
+        let mut diagnostics = vec![
+            Diagnostic {
+                related_information: Some(vec![related_information]),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY, DiagnosticTag::DEPRECATED]),
+                ..Default::default()
+            },
+            Diagnostic {
+                tags: Some(vec![DiagnosticTag::DEPRECATED]),
+                ..Default::default()
+            },
+        ];
+        capabilities.strip_diagnostics(&mut diagnostics);
+        assert_eq!(diagnostics[0].related_information, None);
+        assert_eq!(diagnostics[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+        assert_eq!(diagnostics[1].tags, None);
+    }
+}
@@ -1,10 +1,19 @@
 #![allow(dead_code)]
-use crate::core::token::{Token, TokenIdent};
+use crate::{
+    config::CollectedTokensFilter,
+    core::token::{Token, TokenIdent},
+};
 use lsp_types::{Diagnostic, DiagnosticSeverity};
+use serde_json::Value;
+use std::path::Path;
 use sway_core::{
     decl_engine::DeclEngine,
-    language::{ty, Literal},
+    language::{
+        lexed::{LexedModule, LexedProgram},
+        ty, Literal,
+    },
 };
+use sway_types::{SourceEngine, Spanned};
 
 pub(crate) fn generate_warnings_non_typed_tokens<I>(tokens: I) -> Vec<Diagnostic>
 where
@@ -15,29 +24,75 @@ where
         .collect()
 }
 
-pub(crate) fn generate_warnings_for_parsed_tokens<I>(tokens: I) -> Vec<Diagnostic>
+/// The source of the warnings that show the collected tokens, so that they can be told apart from real diagnostics.
+pub(crate) const TOKENS_DIAGNOSTIC_SOURCE: &str = "sway-tokens";
+
+pub(crate) fn generate_warnings_for_parsed_tokens<I>(
+    tokens: I,
+    filter: &CollectedTokensFilter,
+) -> Vec<Diagnostic>
 where
     I: Iterator<Item = (TokenIdent, Token)>,
 {
     tokens
+        .filter(|(ident, token)| is_token_shown(filter, ident, token))
         .map(|(ident, _)| warning_from_ident(&ident))
         .collect()
 }
 
-pub(crate) fn generate_warnings_for_typed_tokens<I>(tokens: I) -> Vec<Diagnostic>
+pub(crate) fn generate_warnings_for_typed_tokens<I>(
+    tokens: I,
+    filter: &CollectedTokensFilter,
+) -> Vec<Diagnostic>
 where
     I: Iterator<Item = (TokenIdent, Token)>,
 {
     tokens
-        .filter_map(|(ident, token)| token.typed.is_some().then_some(warning_from_ident(&ident)))
+        .filter(|(ident, token)| token.typed.is_some() && is_token_shown(filter, ident, token))
+        .map(|(ident, _)| warning_from_ident(&ident))
         .collect()
 }
 
+fn is_token_shown(filter: &CollectedTokensFilter, ident: &TokenIdent, token: &Token) -> bool {
+    filter.matches(&ident.name, &format!("{:?}", token.kind))
+}
+
+/// Returns the syntax tree of the module at `path` as JSON, or `None` if it isn't part of the program.
+///
+/// Each node is tagged with its kind, e.g. `{"Fn": {...}}`, and spans are `[start, end]` byte offsets.
+pub(crate) fn syntax_tree(
+    lexed: &LexedProgram,
+    path: &Path,
+    source_engine: &SourceEngine,
+) -> Option<Value> {
+    fn find<'a>(
+        module: &'a LexedModule,
+        path: &Path,
+        source_engine: &SourceEngine,
+    ) -> Option<&'a LexedModule> {
+        let module_path = module
+            .tree
+            .span()
+            .source_id()
+            .map(|source_id| source_engine.get_path(source_id));
+        if module_path.as_deref() == Some(path) {
+            return Some(module);
+        }
+        module
+            .submodules
+            .iter()
+            .find_map(|(_, submodule)| find(&submodule.module, path, source_engine))
+    }
+    let module = find(&lexed.root, path, source_engine)?;
+    serde_json::to_value(&module.tree).ok()
+}
+
 fn warning_from_ident(ident: &TokenIdent) -> Diagnostic {
     Diagnostic {
         range: ident.range,
         severity: Some(DiagnosticSeverity::WARNING),
         message: "".to_string(),
+        source: Some(TOKENS_DIAGNOSTIC_SOURCE.to_string()),
         ..Default::default()
     }
 }
@@ -0,0 +1,122 @@
+//! Resolves the tree of a project's dependencies from its manifests.
+
+use crate::lsp_ext::DependencyNode;
+use forc_pkg::{manifest::Dependency, PackageManifestFile};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Returns the dependencies of the package of `manifest`, resolved transitively through the
+/// manifests of its path dependencies.
+///
+/// Other dependencies are fetched by `forc` when the project is built, so their own dependencies
+/// aren't known from the manifests. A dependency that is one of its own ancestors isn't expanded again.
+pub(crate) fn dependency_tree(manifest: &PackageManifestFile) -> Vec<DependencyNode> {
+    dependencies(manifest, &mut vec![manifest.dir().to_path_buf()])
+}
+
+fn dependencies(
+    manifest: &PackageManifestFile,
+    ancestors: &mut Vec<PathBuf>,
+) -> Vec<DependencyNode> {
+    manifest
+        .deps()
+        .map(|(name, dependency)| {
+            let path = manifest.dep_path(name);
+            let mut node = dependency_node(name, dependency, path.clone());
+            let manifest = path
+                .filter(|path| !ancestors.contains(path))
+                .and_then(|path| Some((PackageManifestFile::from_dir(&path).ok()?, path)));
+            if let Some((manifest, path)) = manifest {
+                ancestors.push(path);
+                node.dependencies = dependencies(&manifest, ancestors);
+                ancestors.pop();
+            }
+            node
+        })
+        .collect()
+}
+
+/// Returns the node of the dependency `name`, without its own dependencies.
+///
+/// The details of a dependency aren't public, so they're read from its serialized form,
+/// which uses the keys of `Forc.toml`.
+fn dependency_node(name: &str, dependency: &Dependency, path: Option<PathBuf>) -> DependencyNode {
+    let details = serde_json::to_value(dependency).unwrap_or_default();
+    let detail = |key: &str| details.get(key).and_then(Value::as_str).map(str::to_string);
+    let (source, location) = if let Some(git) = detail("git") {
+        let reference = [("branch", "branch"), ("tag", "tag"), ("rev", "rev")]
+            .into_iter()
+            .find_map(|(key, label)| detail(key).map(|value| format!("{label}={value}")));
+        let location = match reference {
+            Some(reference) => format!("{git}?{reference}"),
+            None => git,
+        };
+        ("git", Some(location))
+    } else if let Some(ipfs) = detail("ipfs") {
+        ("ipfs", Some(ipfs))
+    } else if let Some(path) = detail("path") {
+        ("path", Some(path))
+    } else {
+        ("registry", None)
+    };
+    DependencyNode {
+        name: name.to_string(),
+        package: dependency.package().map(str::to_string),
+        version: details.as_str().map(str::to_string).or(detail("version")),
+        source: source.to_string(),
+        location,
+        path,
+        dependencies: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_package(dir: &std::path::Path, name: &str, dependencies: &str) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.sw"), "library;\n").unwrap();
+        fs::write(
+            dir.join("Forc.toml"),
+            format!(
+                "[project]\nauthors = [\"Fuel Labs <contact@fuel.sh>\"]\nentry = \"lib.sw\"\n\
+                license = \"Apache-2.0\"\nname = \"{name}\"\nimplicit-std = false\n\n\
+                [dependencies]\n{dependencies}"
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn dependency_tree_follows_path_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_package(
+            &dir.path().join("app"),
+            "app",
+            "lib = { path = \"../lib\" }\nremote = { git = \"https://github.com/FuelLabs/remote\", tag = \"v1\" }\n",
+        );
+        // The cycle back to `app` isn't followed.
+        write_package(
+            &dir.path().join("lib"),
+            "lib",
+            "app = { path = \"../app\" }\n",
+        );
+
+        let manifest = PackageManifestFile::from_dir(dir.path().join("app")).unwrap();
+        let tree = dependency_tree(&manifest);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].name, "lib");
+        assert_eq!(tree[0].source, "path");
+        assert_eq!(tree[0].dependencies.len(), 1);
+        assert_eq!(tree[0].dependencies[0].name, "app");
+        assert!(tree[0].dependencies[0].dependencies.is_empty());
+        assert_eq!(tree[1].source, "git");
+        assert_eq!(
+            tree[1].location.as_deref(),
+            Some("https://github.com/FuelLabs/remote?tag=v1")
+        );
+        assert!(tree[1].path.is_none());
+    }
+}
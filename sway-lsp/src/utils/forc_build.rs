@@ -0,0 +1,192 @@
+//! Runs `forc build` for a project, so that the diagnostics of a clean build can be compared with the server's.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// The `forc` executable, which is looked up in the `PATH`.
+const FORC: &str = "forc";
+
+/// The source of the diagnostics that are parsed from the output of `forc build`.
+pub(crate) const BUILD_DIAGNOSTIC_SOURCE: &str = "forc build";
+
+/// The line that `forc` prints after each diagnostic.
+const DIAGNOSTIC_END: &str = "____";
+
+/// Spawns `forc build` for the project in `manifest_dir`.
+///
+/// Each line of its output is sent to `lines` without colors as soon as it's printed.
+/// The channel is closed once the process has closed its stdout and stderr.
+pub(crate) fn spawn_build(
+    manifest_dir: &Path,
+    lines: UnboundedSender<String>,
+) -> io::Result<Child> {
+    let mut child = Command::new(FORC)
+        .arg("build")
+        .arg("--path")
+        .arg(manifest_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, lines.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, lines);
+    }
+    Ok(child)
+}
+
+/// Forwards the lines of `output` to `lines` from a thread of their own.
+fn forward_lines(output: impl Read + Send + 'static, lines: UnboundedSender<String>) {
+    thread::spawn(move || {
+        let colors = Regex::new(r"\x1b\[[0-9;]*m").expect("valid regex");
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            if lines
+                .send(colors.replace_all(&line, "").into_owned())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// A diagnostic whose title has been parsed, but maybe not its location yet.
+struct PendingDiagnostic {
+    severity: DiagnosticSeverity,
+    message: String,
+    location: Option<(PathBuf, Position)>,
+}
+
+/// Parses the diagnostics in the output of `forc build`, by the file they're in.
+///
+/// Each diagnostic starts with an `error` or `warning` title and is located at the first
+/// `--> path:line:column` after it. Diagnostics whose title has no message, like most of the
+/// compiler's errors, take the label of their first annotation. Diagnostics without a location are left out.
+pub(crate) fn parse_build_diagnostics(output: &[String]) -> HashMap<PathBuf, Vec<Diagnostic>> {
+    let title = Regex::new(r"^(error|warning)(?:: (.*))?$").expect("valid regex");
+    let location = Regex::new(r"^\s*--> (.+):(\d+):(\d+)$").expect("valid regex");
+    let label = Regex::new(r"^\s*\d*\s*\|[\s|]*[\^-]+\s+(\S.*)$").expect("valid regex");
+
+    let mut diagnostics: HashMap<PathBuf, Vec<Diagnostic>> = HashMap::new();
+    let mut finish = |pending: Option<PendingDiagnostic>| {
+        let Some(PendingDiagnostic {
+            severity,
+            message,
+            location: Some((path, position)),
+        }) = pending
+        else {
+            return;
+        };
+        if message.is_empty() {
+            return;
+        }
+        diagnostics.entry(path).or_default().push(Diagnostic {
+            range: Range::new(position, position),
+            severity: Some(severity),
+            source: Some(BUILD_DIAGNOSTIC_SOURCE.to_string()),
+            message,
+            ..Default::default()
+        });
+    };
+
+    let mut pending: Option<PendingDiagnostic> = None;
+    for line in output {
+        if let Some(captures) = title.captures(line) {
+            finish(pending.take());
+            pending = Some(PendingDiagnostic {
+                severity: if &captures[1] == "error" {
+                    DiagnosticSeverity::ERROR
+                } else {
+                    DiagnosticSeverity::WARNING
+                },
+                message: captures
+                    .get(2)
+                    .map_or("", |message| message.as_str())
+                    .trim()
+                    .to_string(),
+                location: None,
+            });
+        } else if line.trim() == DIAGNOSTIC_END {
+            finish(pending.take());
+        } else if let Some(pending) = pending.as_mut() {
+            if let Some(captures) = location.captures(line) {
+                if pending.location.is_none() {
+                    let line = captures[2].parse::<u32>().unwrap_or(1);
+                    let column = captures[3].parse::<u32>().unwrap_or(1);
+                    pending.location = Some((
+                        PathBuf::from(&captures[1]),
+                        Position::new(line.saturating_sub(1), column.saturating_sub(1)),
+                    ));
+                }
+            } else if let Some(captures) = label.captures(line) {
+                if pending.message.is_empty() {
+                    pending.message = captures[1].trim().to_string();
+                }
+            }
+        }
+    }
+    finish(pending);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(output: &str) -> Vec<String> {
+        output.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parse_build_diagnostics_reads_titles_and_locations() {
+        let output = lines(
+            "  Compiling library core (/sway/sway-lib-core)
+warning
+  --> /project/src/main.sw:4:9
+  |
+2 |
+3 | fn main() {
+4 |     let x = 1;
+  |         - This declaration is never used.
+5 | }
+  |
+____
+
+error: Ambiguous item
+  --> /project/src/lib.sw:2:5
+  |
+2 | use foo::*;
+  |     ^^^ Ambiguous
+  |
+____
+
+  Aborting due to 1 error.",
+        );
+        let diagnostics = parse_build_diagnostics(&output);
+        let main = &diagnostics[Path::new("/project/src/main.sw")];
+        assert_eq!(main.len(), 1);
+        assert_eq!(main[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(main[0].message, "This declaration is never used.");
+        assert_eq!(main[0].range.start, Position::new(3, 8));
+
+        let lib = &diagnostics[Path::new("/project/src/lib.sw")];
+        assert_eq!(lib[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(lib[0].message, "Ambiguous item");
+        assert_eq!(lib[0].source.as_deref(), Some(BUILD_DIAGNOSTIC_SOURCE));
+    }
+
+    #[test]
+    fn parse_build_diagnostics_skips_diagnostics_without_location() {
+        let output = lines("error: Failed to compile project\n____\n");
+        assert!(parse_build_diagnostics(&output).is_empty());
+    }
+}
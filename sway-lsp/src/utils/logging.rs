@@ -0,0 +1,37 @@
+//! Tracing output of the language server, with a level that can be changed at runtime.
+
+use forc_tracing::{TracingFilterHandle, TracingSubscriberOptions, TracingWriterMode};
+use std::sync::OnceLock;
+use tracing::metadata::LevelFilter;
+use tracing_subscriber::{reload, EnvFilter};
+
+static TRACING_FILTER_HANDLE: OnceLock<TracingFilterHandle> = OnceLock::new();
+
+/// Installs the global tracing subscriber if it hasn't been installed yet and sets its level.
+///
+/// The subscriber is installed at most once per process, so every server in the process
+/// shares the returned handle. Output is written to stderr as stdout carries LSP messages.
+pub(crate) fn init_tracing_subscriber(
+    level: LevelFilter,
+) -> Result<TracingFilterHandle, reload::Error> {
+    let handle = TRACING_FILTER_HANDLE.get_or_init(|| {
+        forc_tracing::init_tracing_subscriber(TracingSubscriberOptions {
+            log_level: Some(level),
+            writer_mode: Some(TracingWriterMode::Stderr),
+            ..Default::default()
+        })
+    });
+    set_tracing_level(handle, level)?;
+    Ok(handle.clone())
+}
+
+/// Replaces the filter of the subscriber behind `handle` with one that enables `level`.
+///
+/// The filter is swapped under the handle's lock, so events emitted concurrently from
+/// other threads are either filtered by the old level or by the new one.
+pub(crate) fn set_tracing_level(
+    handle: &TracingFilterHandle,
+    level: LevelFilter,
+) -> Result<(), reload::Error> {
+    handle.reload(EnvFilter::default().add_directive(level.into()))
+}
@@ -4,15 +4,16 @@
 //! markdown for this purpose.
 //! Modified from rust-analyzer.
 use crate::{
-    capabilities::hover::hover_link_contents::RelatedType, core::token::get_range_from_span,
-    utils::document::get_url_from_span,
+    capabilities::hover::hover_link_contents::RelatedType,
+    core::token::get_range_from_span,
+    utils::{document::get_url_from_span, position_encoding::PositionEncoding},
 };
 use serde_json::{json, Value};
 use std::fmt::{self};
 use sway_types::{SourceEngine, Span};
 use urlencoding::encode;
 
-const GO_TO_COMMAND: &str = "sway.goToLocation";
+pub(crate) const GO_TO_COMMAND: &str = "sway.goToLocation";
 const PEEK_COMMAND: &str = "sway.peekLocations";
 
 /// A handy wrapper around `String` for constructing markdown documents.
@@ -71,6 +72,7 @@ impl Markup {
         source_engine: &SourceEngine,
         related_types: Vec<RelatedType>,
         implementations: Vec<Span>,
+        encoding: PositionEncoding,
     ) -> Self {
         if !related_types.is_empty() {
             let links_string = related_types
@@ -92,7 +94,7 @@ impl Markup {
                 .iter()
                 .flat_map(|span| {
                     if let Ok(uri) = get_url_from_span(source_engine, span) {
-                        let range = get_range_from_span(span);
+                        let range = get_range_from_span(span, encoding);
                         Some(json!({ "uri": uri, "range": range }))
                     } else {
                         None
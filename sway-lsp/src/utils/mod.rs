@@ -1,6 +1,12 @@
 pub(crate) mod attributes;
+pub mod client_capabilities;
 pub mod debug;
+pub(crate) mod dependency_graph;
 pub(crate) mod document;
+pub(crate) mod forc_build;
 pub mod keyword_docs;
+pub(crate) mod logging;
 pub mod markdown;
 pub(crate) mod markup;
+pub mod position_encoding;
+pub mod position_kind;
@@ -0,0 +1,142 @@
+//! The encoding of the `character` offsets of the positions that are exchanged with the client.
+//!
+//! The encoding is negotiated in `initialize` and kept by the server state, which hands it to each session
+//! so that the ranges computed from spans while collecting tokens and diagnostics use it too. A client only
+//! offers the encodings it supports with `general.positionEncodings`, and only UTF-16 is supported by all of them.
+
+use lsp_types::{Position, PositionEncodingKind, Range};
+use serde::{Deserialize, Serialize};
+use sway_types::Span;
+
+/// The unit that the `character` offsets of positions are counted in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PositionEncoding {
+    /// Bytes, which is what the compiler's spans are counted in.
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// UTF-16 code units, which every client supports.
+    #[default]
+    #[serde(rename = "utf-16")]
+    Utf16,
+}
+
+impl PositionEncoding {
+    /// Returns `preferred` if the client supports it, and UTF-16 otherwise.
+    pub fn negotiate(preferred: Self, supported: &[PositionEncodingKind]) -> Self {
+        if supported.contains(&preferred.kind()) {
+            preferred
+        } else {
+            PositionEncoding::Utf16
+        }
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+
+    /// Returns the length of `text` in code units.
+    pub fn len(self, text: &str) -> u32 {
+        match self {
+            PositionEncoding::Utf8 => text.len() as u32,
+            PositionEncoding::Utf16 => text.encode_utf16().count() as u32,
+        }
+    }
+
+    /// Returns the [Range] of `span`, whose columns are counted in bytes by the compiler.
+    pub fn range_of(self, span: &Span) -> Range {
+        let src = span.src();
+        let position = |offset: usize| {
+            let line_start = src[..offset].rfind('\n').map_or(0, |i| i + 1);
+            Position::new(
+                src[..line_start].matches('\n').count() as u32,
+                self.len(&src[line_start..offset]),
+            )
+        };
+        Range::new(position(span.start()), position(span.end()))
+    }
+
+    /// Returns the byte offset of the `character` offset in `line`, clamped to the end of the line.
+    ///
+    /// An offset in the middle of a character is rounded down to the start of the character.
+    pub fn byte_offset(self, line: &str, character: u32) -> usize {
+        let character = character as usize;
+        match self {
+            PositionEncoding::Utf8 => {
+                let mut offset = character.min(line.len());
+                while !line.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                offset
+            }
+            PositionEncoding::Utf16 => {
+                let mut utf16_offset = 0;
+                line.char_indices()
+                    .find(|(_, c)| {
+                        utf16_offset += c.len_utf16();
+                        utf16_offset > character
+                    })
+                    .map_or(line.len(), |(i, _)| i)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths_are_counted_in_code_units() {
+        // `é` is 2 bytes and 1 UTF-16 code unit, `🦀` is 4 bytes and 2 UTF-16 code units.
+        let text = "let é = \"🦀\";";
+        assert_eq!(PositionEncoding::Utf8.len(text), 16);
+        assert_eq!(PositionEncoding::Utf16.len(text), 13);
+    }
+
+    #[test]
+    fn byte_offsets_round_down_to_characters() {
+        let line = "é🦀x";
+        assert_eq!(PositionEncoding::Utf8.byte_offset(line, 2), 2);
+        assert_eq!(PositionEncoding::Utf8.byte_offset(line, 4), 2);
+        assert_eq!(PositionEncoding::Utf8.byte_offset(line, 6), 6);
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 1), 2);
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 2), 2);
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 3), 6);
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 10), line.len());
+    }
+
+    #[test]
+    fn ranges_of_spans_are_counted_in_code_units() {
+        let src = "fn main() {\n    let é = \"🦀\";\n}\n";
+        let start = src.find("\"🦀").unwrap();
+        let span = Span::new(src.into(), start, start + "\"🦀\"".len(), None).unwrap();
+        assert_eq!(
+            PositionEncoding::Utf8.range_of(&span),
+            Range::new(Position::new(1, 13), Position::new(1, 19))
+        );
+        assert_eq!(
+            PositionEncoding::Utf16.range_of(&span),
+            Range::new(Position::new(1, 12), Position::new(1, 16))
+        );
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_utf16() {
+        let utf8 = [PositionEncodingKind::UTF8, PositionEncodingKind::UTF16];
+        assert_eq!(
+            PositionEncoding::negotiate(PositionEncoding::Utf8, &utf8),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(PositionEncoding::Utf8, &[PositionEncodingKind::UTF16]),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(PositionEncoding::Utf16, &utf8),
+            PositionEncoding::Utf16
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Tells whether a position of a document is inside a comment or a string literal.
+
+use crate::{capabilities::formatting::offset_at, utils::position_encoding::PositionEncoding};
+use lsp_types::Position;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use sway_ast::token::{CommentedTokenStream, CommentedTokenTree, GenericTokenTree};
+use sway_error::{error::CompileError, handler::Handler, lex_error::LexErrorKind};
+use sway_types::Span;
+
+/// What the text at a position of a document is part of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PositionKind {
+    /// A line, block or doc comment.
+    Comment,
+    /// A string literal, between its quotes.
+    String,
+    /// Anything else.
+    Code,
+}
+
+/// Returns what the text at `position` of `text` is part of.
+///
+/// Only the text before the position is lexed, so that a comment or string that's still being typed,
+/// and isn't closed yet, is recognized too.
+pub fn position_kind(text: &str, position: Position, encoding: PositionEncoding) -> PositionKind {
+    let src: Arc<str> = Arc::from(text);
+    let offset = offset_at(text, position, encoding);
+    let handler = Handler::default();
+    let stream = sway_parse::lex_commented(&handler, &src, 0, offset, &None);
+    let (errors, _) = handler.consume();
+    for error in &errors {
+        if let CompileError::Lex { error } = error {
+            match error.kind {
+                LexErrorKind::UnclosedStringLiteral { .. } => return PositionKind::String,
+                LexErrorKind::UnclosedMultilineComment { .. } => return PositionKind::Comment,
+                _ => {}
+            }
+        }
+    }
+    match stream {
+        Ok(stream) if ends_with_line_comment(&stream, offset) => PositionKind::Comment,
+        _ => PositionKind::Code,
+    }
+}
+
+/// Returns true if a line comment of `stream` runs until `offset`, the end of the lexed text.
+fn ends_with_line_comment(stream: &CommentedTokenStream, offset: usize) -> bool {
+    let is_line_comment = |span: &Span| span.end() == offset && span.as_str().starts_with("//");
+    stream.token_trees.iter().any(|tree| match tree {
+        CommentedTokenTree::Comment(comment) => is_line_comment(&comment.span),
+        CommentedTokenTree::Tree(GenericTokenTree::DocComment(doc_comment)) => {
+            is_line_comment(&doc_comment.span)
+        }
+        CommentedTokenTree::Tree(GenericTokenTree::Group(group)) => {
+            ends_with_line_comment(&group.token_stream, offset)
+        }
+        CommentedTokenTree::Tree(_) => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "script;\n\n/// Docs\nfn main() {\n    let s = \"a b\"; // note\n    /* block */ let x = 1;\n}\n";
+
+    fn kind(line: u32, character: u32) -> PositionKind {
+        position_kind(SRC, Position::new(line, character), PositionEncoding::Utf16)
+    }
+
+    #[test]
+    fn comments_and_strings_are_recognized() {
+        assert_eq!(kind(2, 5), PositionKind::Comment);
+        assert_eq!(kind(4, 14), PositionKind::String);
+        assert_eq!(kind(4, 22), PositionKind::Comment);
+        assert_eq!(kind(4, 27), PositionKind::Comment);
+        assert_eq!(kind(5, 8), PositionKind::Comment);
+        assert_eq!(kind(5, 20), PositionKind::Code);
+        assert_eq!(kind(4, 8), PositionKind::Code);
+        // Right after the closing quote of the string.
+        assert_eq!(kind(4, 17), PositionKind::Code);
+    }
+
+    #[test]
+    fn unclosed_comments_and_strings_are_recognized() {
+        let src = "fn main() {\n    let s = \"abc";
+        assert_eq!(
+            position_kind(src, Position::new(1, 15), PositionEncoding::Utf16),
+            PositionKind::String
+        );
+        let src = "fn main() {\n    /* todo";
+        assert_eq!(
+            position_kind(src, Position::new(1, 10), PositionEncoding::Utf16),
+            PositionKind::Comment
+        );
+    }
+}
@@ -0,0 +1,8 @@
+script;
+
+fn main() {}
+
+#[test]
+fn test_type_error() {
+    let _x: u64 = true;
+}
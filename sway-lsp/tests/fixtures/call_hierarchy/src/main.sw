@@ -0,0 +1,14 @@
+script;
+
+fn leaf() -> u64 {
+    1
+}
+
+fn middle() -> u64 {
+    let _a = leaf();
+    leaf()
+}
+
+fn main() -> u64 {
+    middle()
+}
@@ -0,0 +1,32 @@
+script;
+
+struct A {}
+struct B {}
+struct C {}
+
+impl A {
+    fn to_b(self) -> B {
+        B {}
+    }
+}
+
+impl B {
+    fn to_c(self) -> C {
+        C {}
+    }
+}
+
+impl C {
+    fn done(self) -> u64 {
+        0
+    }
+}
+
+fn main() {
+    let a = A {};
+    let _ = a.to_b().to_c().done();
+    let _ = a
+        .to_b()
+        .to_c()
+        .done();
+}
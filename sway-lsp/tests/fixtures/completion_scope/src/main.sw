@@ -0,0 +1,31 @@
+script;
+
+enum Color {
+    Red: (),
+    Blue: u64,
+}
+
+impl Color {
+    fn default() -> Self {
+        Color::Red
+    }
+
+    fn value(self) -> u64 {
+        0
+    }
+}
+
+const LIMIT: u64 = 10;
+
+fn add(a: u64, b: u64) -> u64 {
+    a + b
+}
+
+fn main() {
+    let first = 1;
+    if true {
+        let inner = 2;
+    }
+    let color = Color::Blue(3);
+    let second = add(first, LIMIT);
+}
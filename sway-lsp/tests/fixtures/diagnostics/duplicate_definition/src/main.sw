@@ -0,0 +1,8 @@
+script;
+
+fn main() {
+    let x = (1u64, 2u64);
+    match x {
+        (a, a) => {},
+    }
+}
@@ -0,0 +1,6 @@
+script;
+
+fn main() {
+    let x = 1;
+    x = 2;
+}
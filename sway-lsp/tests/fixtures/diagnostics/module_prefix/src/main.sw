@@ -0,0 +1,7 @@
+script;
+
+mod utils;
+
+fn main() {
+    let _ = utils::one();
+}
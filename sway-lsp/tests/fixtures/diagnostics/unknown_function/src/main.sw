@@ -0,0 +1,6 @@
+script;
+
+fn main() {
+    let amount = 5u64;
+    let total: u64 = add_fee(amount, true);
+}
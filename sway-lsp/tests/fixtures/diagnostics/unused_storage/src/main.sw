@@ -0,0 +1,16 @@
+contract;
+
+abi MyContract {
+    fn get_key();
+}
+
+storage {
+    used: u64 = 0,
+    unused: u64 = 0,
+}
+
+impl MyContract for Contract {
+    fn get_key() {
+        let _key = storage.used;
+    }
+}
@@ -0,0 +1,7 @@
+script;
+
+fn main() -> u64 {
+    let unused = 1;
+    let used = 2;
+    used
+}
@@ -0,0 +1,5 @@
+library;
+
+pub fn double(a: u64) -> u64 {
+    a
+}
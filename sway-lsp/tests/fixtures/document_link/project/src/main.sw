@@ -0,0 +1,11 @@
+script;
+
+mod utils;
+
+use dependency::helpers::double;
+use utils::math::add;
+use ::utils::math::sub;
+
+fn main() {
+    let _ = sub(add(1, 2), double(3));
+}
@@ -0,0 +1,9 @@
+script;
+
+fn identity<T>(value: T) -> T {
+    value
+}
+
+fn main() {
+    let a = identity(42u64);
+}
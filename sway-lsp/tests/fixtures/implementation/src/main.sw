@@ -0,0 +1,25 @@
+contract;
+
+struct Point {
+    x: u64,
+}
+
+trait Shape {
+    fn area(self) -> u64;
+}
+
+impl Shape for Point {
+    fn area(self) -> u64 {
+        self.x
+    }
+}
+
+abi Counter {
+    fn count() -> u64;
+}
+
+impl Counter for Contract {
+    fn count() -> u64 {
+        0
+    }
+}
\ No newline at end of file
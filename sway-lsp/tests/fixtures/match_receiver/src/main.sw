@@ -0,0 +1,19 @@
+script;
+
+struct Point {
+    x: u64,
+}
+
+impl Point {
+    fn x_value(self) -> u64 {
+        self.x
+    }
+}
+
+fn main() {
+    let flag = true;
+    let _ = match flag {
+        true => Point { x: 1 },
+        false => Point { x: 2 },
+    }.x_value();
+}
@@ -0,0 +1,15 @@
+library;
+
+pub struct Point {
+    x: u64,
+}
+
+pub struct Unused {}
+
+pub enum Color {
+    Red: (),
+}
+
+pub fn make() -> Point {
+    Point { x: 0 }
+}
@@ -0,0 +1,12 @@
+script;
+
+mod items;
+
+use items::Unused;
+use items::{Point, make};
+use items::Color;
+
+fn main() {
+    let _point: Point = make();
+    let _color = Color::Red;
+}
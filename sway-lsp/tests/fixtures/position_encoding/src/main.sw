@@ -0,0 +1,7 @@
+script;
+
+fn main() -> u64 {
+    let _s = "é🦀"; let unused = 1;
+    let _t = "é🦀"; let used = 2;
+    used
+}
@@ -0,0 +1,8 @@
+script;
+
+mod utils;
+
+fn main() {
+    utils::helper();
+    utils::helper();
+}
@@ -0,0 +1,21 @@
+script;
+
+struct Point {
+    x: u64,
+}
+
+impl Point {
+    fn shift(self, dx: u64, dy: u64) -> u64 {
+        dy
+    }
+}
+
+fn add(a: u64, b: u64) -> u64 {
+    b
+}
+
+fn main() {
+    let point = Point { x: 1 };
+    let sum = add(1, add(2, 3));
+    let _ = point.shift(sum, 4);
+}
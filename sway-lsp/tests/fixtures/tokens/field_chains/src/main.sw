@@ -0,0 +1,26 @@
+script;
+
+struct Inner {
+    value: u64,
+}
+
+struct Middle {
+    inner: Inner,
+}
+
+struct Outer {
+    middle: Middle,
+}
+
+fn main() -> u64 {
+    let outer = Outer {
+        middle: Middle {
+            inner: Inner { value: 1 },
+        },
+    };
+    outer.middle.inner.value
+}
+
+fn unresolved() {
+    let _ = missing.middle.inner;
+}
@@ -33,3 +33,7 @@ pub fn fun() {
     let _ = ::deep_mod::deeper_mod::DeepStruct::<u64> { field: 0 };
     let _ = deep_mod::deeper_mod::DeepStruct::<u64> { field: 0 };
 }
+
+fn call_alias() {
+    dfun();
+}
@@ -0,0 +1,14 @@
+script;
+
+struct Point {
+    x: u64,
+}
+
+fn origin() -> Point {
+    Point { x: 0 }
+}
+
+fn main() {
+    let p = origin();
+    let _x = p.x;
+}
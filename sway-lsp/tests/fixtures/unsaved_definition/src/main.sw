@@ -0,0 +1,7 @@
+script;
+
+mod utils;
+
+fn main() {
+    utils::helper();
+}
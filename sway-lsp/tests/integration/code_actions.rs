@@ -7,7 +7,9 @@ use pretty_assertions::assert_eq;
 use serde_json::json;
 use std::collections::HashMap;
 use sway_lsp::{
-    capabilities::diagnostic::DiagnosticData, handlers::request, server_state::ServerState,
+    capabilities::diagnostic::{DiagnosticData, UnusedStorageField},
+    handlers::request,
+    server_state::ServerState,
 };
 
 fn create_code_action(
@@ -507,6 +509,7 @@ pub(crate) async fn code_action_auto_import_struct_request(server: &ServerState,
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("EvmAddress".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -550,6 +553,7 @@ pub(crate) async fn code_action_auto_import_struct_request(server: &ServerState,
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("DeepStruct".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -597,6 +601,7 @@ pub(crate) async fn code_action_auto_import_enum_request(server: &ServerState, u
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("AuthError".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -640,6 +645,7 @@ pub(crate) async fn code_action_auto_import_enum_request(server: &ServerState, u
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("DeepEnum".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -688,6 +694,7 @@ pub(crate) async fn code_action_auto_import_function_request(server: &ServerStat
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("deep_fun".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -736,6 +743,7 @@ pub(crate) async fn code_action_auto_import_constant_request(server: &ServerStat
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("TEST_CONST".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -781,6 +789,7 @@ pub(crate) async fn code_action_auto_import_trait_request(server: &ServerState,
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("TryFrom".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -824,6 +833,7 @@ pub(crate) async fn code_action_auto_import_trait_request(server: &ServerState,
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("DeepTrait".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -870,6 +880,7 @@ pub(crate) async fn code_action_auto_import_alias_request(server: &ServerState,
             range,
             DiagnosticData {
                 unknown_symbol_name: Some("A".to_string()),
+                ..Default::default()
             },
         ),
     );
@@ -909,3 +920,374 @@ pub(crate) async fn code_action_auto_import_alias_request(server: &ServerState,
     let actual = send_request(server, &params).await;
     assert_eq!(expected, actual);
 }
+
+pub(crate) async fn code_action_go_to_related_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 5,
+            character: 12,
+        },
+        end: Position {
+            line: 5,
+            character: 13,
+        },
+    };
+    let related_location = |line, start, end| Location {
+        uri: uri.clone(),
+        range: Range {
+            start: Position {
+                line,
+                character: start,
+            },
+            end: Position {
+                line,
+                character: end,
+            },
+        },
+    };
+    let diagnostic = Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        message: "Variable \"a\" is already defined in match arm.".to_string(),
+        related_information: Some(vec![
+            DiagnosticRelatedInformation {
+                location: related_location(5, 9, 10),
+                message: "This is the first definition of the variable \"a\".".to_string(),
+            },
+            DiagnosticRelatedInformation {
+                location: related_location(4, 10, 11),
+                message: "The expression to match on is of type \"(u64, u64)\".".to_string(),
+            },
+        ]),
+        ..Default::default()
+    };
+    let params = create_code_action_params(uri.clone(), range, Some(vec![diagnostic.clone()]));
+
+    let expected = [(5, 9, 10, "main.sw:6"), (4, 10, 11, "main.sw:5")]
+        .into_iter()
+        .map(|(line, start, end, file_line)| {
+            let title = format!("Go to definition ({})", file_line);
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.clone(),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                command: Some(Command {
+                    title,
+                    command: "sway.goToLocation".to_string(),
+                    arguments: Some(vec![json!({
+                        "uri": uri,
+                        "range": related_location(line, start, end).range,
+                    })]),
+                }),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_make_mutable_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 4,
+            character: 4,
+        },
+        end: Position {
+            line: 4,
+            character: 9,
+        },
+    };
+    let params = create_code_action_params(
+        uri.clone(),
+        range,
+        create_diagnostic_from_data(
+            range,
+            DiagnosticData {
+                immutable_variable_name: Some("x".to_string()),
+                ..Default::default()
+            },
+        ),
+    );
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Make mutable `x`".to_string(),
+        create_changes_map(
+            uri,
+            Range {
+                start: Position {
+                    line: 3,
+                    character: 8,
+                },
+                end: Position {
+                    line: 3,
+                    character: 8,
+                },
+            },
+            "mut ",
+        ),
+        None,
+        Some(CodeActionKind::QUICKFIX),
+    )];
+
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_create_function_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 4,
+            character: 21,
+        },
+        end: Position {
+            line: 4,
+            character: 28,
+        },
+    };
+    let params = create_code_action_params(
+        uri.clone(),
+        range,
+        create_diagnostic_from_data(
+            range,
+            DiagnosticData {
+                unknown_symbol_name: Some("add_fee".to_string()),
+                ..Default::default()
+            },
+        ),
+    );
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Create function `add_fee`".to_string(),
+        create_changes_map(
+            uri,
+            Range {
+                start: Position {
+                    line: 6,
+                    character: 0,
+                },
+                end: Position {
+                    line: 6,
+                    character: 0,
+                },
+            },
+            "\nfn add_fee(amount: u64, arg1: bool) -> u64 {\n    // TODO: implement `add_fee`.\n    revert(0)\n}\n",
+        ),
+        None,
+        Some(CodeActionKind::QUICKFIX),
+    )];
+
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_remove_storage_field_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 8,
+            character: 4,
+        },
+        end: Position {
+            line: 8,
+            character: 10,
+        },
+    };
+    let removal_range = Range {
+        start: Position {
+            line: 8,
+            character: 0,
+        },
+        end: Position {
+            line: 9,
+            character: 0,
+        },
+    };
+    let params = create_code_action_params(
+        uri.clone(),
+        range,
+        create_diagnostic_from_data(
+            range,
+            DiagnosticData {
+                unused_storage_field: Some(UnusedStorageField {
+                    name: "unused".to_string(),
+                    removal_range,
+                }),
+                ..Default::default()
+            },
+        ),
+    );
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Remove unused storage field `unused`".to_string(),
+        create_changes_map(uri, removal_range, ""),
+        None,
+        Some(CodeActionKind::QUICKFIX),
+    )];
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_remove_unused_variable_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 3,
+            character: 8,
+        },
+        end: Position {
+            line: 3,
+            character: 14,
+        },
+    };
+    let params = create_code_action_params(
+        uri.clone(),
+        range,
+        Some(vec![Diagnostic {
+            range,
+            code: Some(NumberOrString::String("dead_code".to_string())),
+            ..Default::default()
+        }]),
+    );
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Remove unused variable `unused`".to_string(),
+        create_changes_map(
+            uri,
+            Range {
+                start: Position {
+                    line: 3,
+                    character: 0,
+                },
+                end: Position {
+                    line: 4,
+                    character: 0,
+                },
+            },
+            "",
+        ),
+        None,
+        Some(CodeActionKind::QUICKFIX),
+    )];
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_fix_all_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 0,
+            character: 0,
+        },
+        end: Position {
+            line: 0,
+            character: 0,
+        },
+    };
+    // The fixes come from the diagnostics of the file, not from those of the request.
+    let mut params = create_code_action_params(uri.clone(), range, None);
+    params.context.only = Some(vec![CodeActionKind::SOURCE_FIX_ALL]);
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Fix all auto-fixable problems".to_string(),
+        create_changes_map(
+            uri,
+            Range {
+                start: Position {
+                    line: 3,
+                    character: 0,
+                },
+                end: Position {
+                    line: 4,
+                    character: 0,
+                },
+            },
+            "",
+        ),
+        None,
+        Some(CodeActionKind::SOURCE_FIX_ALL),
+    )];
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_organize_imports_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 1,
+            character: 0,
+        },
+        end: Position {
+            line: 1,
+            character: 0,
+        },
+    };
+    let mut params = create_code_action_params(uri.clone(), range, None);
+    params.context.only = Some(vec![CodeActionKind::SOURCE_ORGANIZE_IMPORTS]);
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Organize imports".to_string(),
+        create_changes_map(
+            uri,
+            Range {
+                start: Position {
+                    line: 4,
+                    character: 0,
+                },
+                end: Position {
+                    line: 6,
+                    character: 17,
+                },
+            },
+            "use items::{Color, Point, make};",
+        ),
+        None,
+        Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+    )];
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
+
+pub(crate) async fn code_action_fully_qualify_request(server: &ServerState, uri: &Url) {
+    let range = Range {
+        start: Position {
+            line: 9,
+            character: 24,
+        },
+        end: Position {
+            line: 9,
+            character: 28,
+        },
+    };
+    let mut params = create_code_action_params(uri.clone(), range, None);
+    params.context.only = Some(vec![CodeActionKind::REFACTOR]);
+    let changes = HashMap::from([(
+        uri.clone(),
+        vec![
+            TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 5,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: 5,
+                        character: 25,
+                    },
+                },
+                new_text: "use items::Point;".to_string(),
+            },
+            TextEdit {
+                range,
+                new_text: "items::make".to_string(),
+            },
+        ],
+    )]);
+    let expected = vec![create_code_action(
+        uri.clone(),
+        "Use the fully-qualified path `items::make`".to_string(),
+        changes,
+        None,
+        Some(CodeActionKind::REFACTOR),
+    )];
+    let actual = send_request(server, &params).await;
+    assert_eq!(expected, actual);
+}
@@ -6,11 +6,15 @@ use crate::{GotoDefinition, HoverDocumentation, Rename};
 use assert_json_diff::assert_json_eq;
 use regex::Regex;
 use serde_json::json;
-use std::{borrow::Cow, path::Path};
+use std::{borrow::Cow, path::Path, sync::atomic::Ordering};
 use sway_lsp::{
-    handlers::request,
-    lsp_ext::{ShowAstParams, VisualizeParams},
-    server_state::ServerState,
+    capabilities::diagnostic::DiagnosticsSnapshot,
+    handlers::{notification, request},
+    lsp_ext::{
+        BenchmarkParams, ExportDiagnosticsParams, SemanticTokensRangesParams, ServerInfoParams,
+        ShowAstParams, VisualizeParams, WaitForCompilationParams,
+    },
+    server_state::{LastCompilationState, ServerState},
 };
 use sway_utils::PerformanceData;
 use tower::{Service, ServiceExt};
@@ -35,8 +39,21 @@ pub(crate) async fn call_request(
     service.ready().await?.call(req).await
 }
 
+/// The capabilities of the client, which are what the published diagnostics are checked against.
+fn client_capabilities() -> serde_json::Value {
+    json!({
+        "textDocument": {
+            "publishDiagnostics": {
+                "relatedInformation": true,
+                "versionSupport": true,
+                "tagSupport": { "valueSet": [1, 2] },
+            },
+        },
+    })
+}
+
 pub(crate) async fn initialize_request(service: &mut LspService<ServerState>) -> Request {
-    let params = json!({ "capabilities": sway_lsp::server_capabilities() });
+    let params = json!({ "capabilities": client_capabilities() });
     let initialize = build_request_with_id("initialize", params, 1);
     let response = call_request(service, initialize.clone()).await;
     let expected = Response::from_ok(
@@ -47,6 +64,33 @@ pub(crate) async fn initialize_request(service: &mut LspService<ServerState>) ->
     initialize
 }
 
+pub(crate) async fn initialize_with_options_request(
+    service: &mut LspService<ServerState>,
+    initialization_options: serde_json::Value,
+) {
+    let params = json!({
+        "capabilities": client_capabilities(),
+        "initializationOptions": initialization_options,
+    });
+    let initialize = build_request_with_id("initialize", params, 1);
+    let response = call_request(service, initialize).await;
+    assert!(response.unwrap().unwrap().is_ok());
+}
+
+/// Initializes the server with a client that supports the `position_encodings`, and returns the result.
+pub(crate) async fn initialize_with_position_encodings_request(
+    service: &mut LspService<ServerState>,
+    position_encodings: Vec<PositionEncodingKind>,
+) -> InitializeResult {
+    let params = json!({
+        "capabilities": { "general": { "positionEncodings": position_encodings } },
+    });
+    let initialize = build_request_with_id("initialize", params, 1);
+    let response = call_request(service, initialize).await.unwrap().unwrap();
+    let (_, result) = response.into_parts();
+    serde_json::from_value(result.unwrap()).unwrap()
+}
+
 pub(crate) async fn initialized_notification(service: &mut LspService<ServerState>) {
     let initialized = Request::build("initialized").finish();
     let response = call_request(service, initialized).await;
@@ -128,6 +172,77 @@ pub(crate) async fn did_change_request(
     did_change
 }
 
+pub(crate) async fn did_change_notification(
+    server: &ServerState,
+    uri: &Url,
+    version: i32,
+    text: &str,
+) {
+    let params = DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier {
+            uri: uri.clone(),
+            version,
+        },
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position {
+                    line: 1,
+                    character: 0,
+                },
+                end: Position {
+                    line: 1,
+                    character: 0,
+                },
+            }),
+            range_length: Some(0),
+            text: text.to_string(),
+        }],
+    };
+    let res = notification::handle_did_change_text_document(server, params).await;
+    assert!(res.is_ok());
+    // make sure to set is_compiling to true so the wait_for_parsing method can properly synchnonize
+    server
+        .is_compiling
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+pub(crate) async fn did_save_notification(service: &mut LspService<ServerState>, uri: &Url) {
+    let params = json!({
+        "textDocument": {
+            "uri": uri,
+        },
+    });
+    let did_save = Request::build("textDocument/didSave")
+        .params(params)
+        .finish();
+    let response = call_request(service, did_save).await;
+    assert_eq!(response, Ok(None));
+}
+
+pub(crate) async fn did_change_configuration_notification(
+    service: &mut LspService<ServerState>,
+    settings: serde_json::Value,
+) {
+    let params = json!({ "settings": settings });
+    let did_change_configuration = Request::build("workspace/didChangeConfiguration")
+        .params(params)
+        .finish();
+    let response = call_request(service, did_change_configuration).await;
+    assert_eq!(response, Ok(None));
+}
+
+pub(crate) async fn did_change_watched_files_notification(
+    service: &mut LspService<ServerState>,
+    changes: Vec<FileEvent>,
+) {
+    let params = json!({ "changes": changes });
+    let did_change_watched_files = Request::build("workspace/didChangeWatchedFiles")
+        .params(params)
+        .finish();
+    let response = call_request(service, did_change_watched_files).await;
+    assert_eq!(response, Ok(None));
+}
+
 pub(crate) async fn show_ast_request(
     server: &ServerState,
     uri: &Url,
@@ -169,6 +284,83 @@ pub(crate) async fn visualize_request(server: &ServerState, uri: &Url, graph_kin
     assert!(!re.find(response.as_str()).unwrap().is_empty());
 }
 
+pub(crate) async fn benchmark_request(server: &ServerState, uri: &Url, iterations: usize) {
+    let params = BenchmarkParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        iterations,
+        clear_cache: false,
+    };
+    let result = request::handle_benchmark(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.samples.len(), iterations);
+    assert_eq!(result.phases.len(), iterations);
+    assert!(result.min > 0.0);
+    assert!(result.min <= result.median && result.median <= result.max);
+    assert!(result.min <= result.mean && result.mean <= result.max);
+}
+
+pub(crate) async fn recompile_workspace_request(server: &ServerState, num_projects: usize) {
+    let result = request::handle_recompile_workspace(server).await.unwrap();
+    assert_eq!(result.compiled + result.already_compiling, num_projects);
+}
+
+pub(crate) async fn export_diagnostics_request(
+    server: &ServerState,
+    uri: &Url,
+) -> Option<DiagnosticsSnapshot> {
+    let params = ExportDiagnosticsParams { uri: uri.clone() };
+    request::handle_export_diagnostics(server, params)
+        .await
+        .unwrap()
+}
+
+pub(crate) async fn wait_for_compilation_request(
+    server: &ServerState,
+    uri: Option<&Url>,
+) -> LastCompilationState {
+    let params = WaitForCompilationParams {
+        uri: uri.cloned(),
+        timeout_ms: Some(60_000),
+    };
+    let result = request::handle_wait_for_compilation(server, params)
+        .await
+        .unwrap();
+    assert!(!server.is_compiling.load(Ordering::SeqCst));
+    result.last_compilation_state
+}
+
+pub(crate) fn health_check_request(server: &ServerState) {
+    let result = request::handle_health_check(server).unwrap();
+    assert!(result.compilation_thread_alive);
+    assert_eq!(result.queue_length, 0);
+    assert!(!result.is_compiling);
+}
+
+pub(crate) fn server_info_request(uri: &Url, project_name: &str) {
+    let params = ServerInfoParams {
+        text_document: Some(TextDocumentIdentifier { uri: uri.clone() }),
+    };
+    let result = request::handle_server_info(params).unwrap();
+    assert_eq!(result.server_version, env!("CARGO_PKG_VERSION"));
+    assert!(!result.compiler_version.is_empty());
+    let project = result.project.unwrap();
+    assert_eq!(project.name, project_name);
+    assert!(project.manifest_path.ends_with("Forc.toml"));
+    assert_eq!(project.forc_version, None);
+    assert!(project.compatible);
+}
+
+pub(crate) async fn set_trace_level_request(
+    service: &mut LspService<ServerState>,
+    level: &str,
+) -> Response {
+    let params = json!({ "level": level });
+    let request = build_request_with_id("sway/setTraceLevel", params, 1);
+    call_request(service, request).await.unwrap().unwrap()
+}
+
 pub(crate) async fn metrics_request(
     service: &mut LspService<ServerState>,
     uri: &Url,
@@ -183,7 +375,7 @@ pub(crate) async fn metrics_request(
         .await
         .unwrap()
         .unwrap();
-    let value = result.result().unwrap().as_array();
+    let value = result.result().unwrap()["modules"].as_array();
     let mut res = vec![];
     for v in value.unwrap().iter() {
         let path = v.get(0).unwrap().as_str().unwrap();
@@ -207,6 +399,118 @@ pub(crate) async fn semantic_tokens_request(server: &ServerState, uri: &Url) {
     }
 }
 
+pub(crate) async fn semantic_tokens_full_request(
+    server: &ServerState,
+    uri: &Url,
+) -> SemanticTokens {
+    let params = SemanticTokensParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    match request::handle_semantic_tokens_full(server, params)
+        .await
+        .unwrap()
+    {
+        Some(SemanticTokensResult::Tokens(tokens)) => tokens,
+        response => panic!("Expected semantic tokens, got {response:?}"),
+    }
+}
+
+pub(crate) async fn semantic_tokens_full_delta_request(
+    server: &ServerState,
+    uri: &Url,
+    previous_result_id: &str,
+) -> SemanticTokensFullDeltaResult {
+    let params = SemanticTokensDeltaParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        previous_result_id: previous_result_id.to_string(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    request::handle_semantic_tokens_full_delta(server, params)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+/// Requests the semantic tokens of `range` and returns them with their absolute positions,
+/// along with the names of their types and modifiers.
+pub(crate) async fn semantic_tokens_range_request(
+    server: &ServerState,
+    uri: &Url,
+    range: Range,
+) -> Vec<(Position, SemanticTokenType, Vec<SemanticTokenModifier>)> {
+    let params = SemanticTokensRangeParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let response = request::handle_semantic_tokens_range(server, params)
+        .await
+        .unwrap();
+    let Some(SemanticTokensRangeResult::Tokens(tokens)) = response else {
+        panic!("Expected semantic tokens, got {response:?}");
+    };
+    decode_semantic_tokens(&tokens)
+}
+
+/// Requests the semantic tokens of several `ranges` with `sway/semanticTokensRanges` and returns them like
+/// [semantic_tokens_range_request].
+pub(crate) async fn semantic_tokens_ranges_request(
+    server: &ServerState,
+    uri: &Url,
+    ranges: Vec<Range>,
+) -> Vec<(Position, SemanticTokenType, Vec<SemanticTokenModifier>)> {
+    let params = SemanticTokensRangesParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        ranges,
+    };
+    let tokens = request::handle_semantic_tokens_ranges(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    decode_semantic_tokens(&tokens)
+}
+
+/// Returns the semantic tokens with their absolute positions, along with the names of their types and modifiers.
+fn decode_semantic_tokens(
+    tokens: &SemanticTokens,
+) -> Vec<(Position, SemanticTokenType, Vec<SemanticTokenModifier>)> {
+    let Some(SemanticTokensServerCapabilities::SemanticTokensOptions(options)) =
+        sway_lsp::server_capabilities().semantic_tokens_provider
+    else {
+        panic!("Expected the semantic tokens legend");
+    };
+    let legend = options.legend;
+    let mut position = Position::default();
+    tokens
+        .data
+        .iter()
+        .map(|token| {
+            if token.delta_line == 0 {
+                position.character += token.delta_start;
+            } else {
+                position.line += token.delta_line;
+                position.character = token.delta_start;
+            }
+            let modifiers = legend
+                .token_modifiers
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| token.token_modifiers_bitset & (1 << i) != 0)
+                .map(|(_, modifier)| modifier.clone())
+                .collect();
+            (
+                position,
+                legend.token_types[token.token_type as usize].clone(),
+                modifiers,
+            )
+        })
+        .collect()
+}
+
 pub(crate) async fn document_symbol_request(server: &ServerState, uri: &Url) {
     let params = DocumentSymbolParams {
         text_document: TextDocumentIdentifier { uri: uri.clone() },
@@ -222,6 +526,8 @@ pub(crate) async fn document_symbol_request(server: &ServerState, uri: &Url) {
 }
 
 pub(crate) async fn format_request(server: &ServerState, uri: &Url) {
+    // Add blank lines after the first line, which the formatter collapses into one.
+    did_change_notification(server, uri, 1, "\n\n\n").await;
     let params = DocumentFormattingParams {
         text_document: TextDocumentIdentifier { uri: uri.clone() },
         options: FormattingOptions {
@@ -231,8 +537,203 @@ pub(crate) async fn format_request(server: &ServerState, uri: &Url) {
         },
         work_done_progress_params: Default::default(),
     };
-    let response = request::handle_formatting(server, params).await.unwrap();
-    assert!(!response.unwrap().is_empty());
+    let edits = request::handle_formatting(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    // Only the added lines are edited.
+    assert_eq!(edits.len(), 1);
+    assert!(edits[0].range.start.line >= 1 && edits[0].range.end.line <= 5);
+    assert!(edits[0].new_text.trim().is_empty());
+}
+
+pub(crate) async fn range_format_request(
+    server: &ServerState,
+    uri: &Url,
+    range: Range,
+) -> Vec<TextEdit> {
+    let params = DocumentRangeFormattingParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        options: FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+    };
+    request::handle_range_formatting(server, params)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+pub(crate) async fn monomorphized_type_request(server: &ServerState, uri: &Url) {
+    // The call to `identity` in `let a = identity(42u64);`.
+    let params = TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        position: Position {
+            line: 7,
+            character: 14,
+        },
+    };
+    let result = request::handle_monomorphized_type(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.function, "identity");
+    assert_eq!(result.substitutions.len(), 1);
+    assert_eq!(result.substitutions[0].type_parameter, "T");
+    assert_eq!(result.substitutions[0].concrete_type, "u64");
+}
+
+pub(crate) async fn call_hierarchy_request(server: &ServerState, uri: &Url) {
+    let range = |line, start, end| Range {
+        start: Position {
+            line,
+            character: start,
+        },
+        end: Position {
+            line,
+            character: end,
+        },
+    };
+
+    // Prepare from the call site of `leaf` in `middle`.
+    let params = CallHierarchyPrepareParams {
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position: Position {
+                line: 8,
+                character: 5,
+            },
+        },
+        work_done_progress_params: Default::default(),
+    };
+    let items = request::handle_prepare_call_hierarchy(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    let leaf = items[0].clone();
+    assert_eq!(leaf.name, "leaf");
+    assert_eq!(leaf.kind, SymbolKind::FUNCTION);
+    assert_eq!(&leaf.uri, uri);
+    assert_eq!(leaf.selection_range, range(2, 3, 7));
+
+    let params = CallHierarchyIncomingCallsParams {
+        item: leaf.clone(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let incoming = request::handle_incoming_calls(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(incoming.len(), 1);
+    let middle = incoming[0].from.clone();
+    assert_eq!(middle.name, "middle");
+    assert_eq!(middle.selection_range, range(6, 3, 9));
+    assert_eq!(
+        incoming[0].from_ranges,
+        vec![range(7, 13, 17), range(8, 4, 8)]
+    );
+
+    let params = CallHierarchyOutgoingCallsParams {
+        item: middle.clone(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let outgoing = request::handle_outgoing_calls(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(outgoing.len(), 1);
+    assert_eq!(outgoing[0].to, leaf);
+    assert_eq!(
+        outgoing[0].from_ranges,
+        vec![range(7, 13, 17), range(8, 4, 8)]
+    );
+
+    let params = CallHierarchyIncomingCallsParams {
+        item: middle,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let incoming = request::handle_incoming_calls(server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(incoming.len(), 1);
+    assert_eq!(incoming[0].from.name, "main");
+    assert_eq!(incoming[0].from_ranges, vec![range(12, 4, 10)]);
+}
+
+pub(crate) async fn references_request(
+    server: &ServerState,
+    uri: &Url,
+    position: Position,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let params = ReferenceParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position,
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: ReferenceContext {
+            include_declaration,
+        },
+    };
+    request::handle_references(server, params)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+pub(crate) async fn signature_help_request(
+    server: &ServerState,
+    uri: &Url,
+    line: u32,
+    character: u32,
+) -> Option<SignatureHelp> {
+    let params = SignatureHelpParams {
+        context: None,
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position: Position { line, character },
+        },
+        work_done_progress_params: Default::default(),
+    };
+    request::handle_signature_help(server, params)
+        .await
+        .unwrap()
+}
+
+pub(crate) async fn document_link_request(
+    server: &ServerState,
+    uri: &Url,
+) -> Option<Vec<DocumentLink>> {
+    let params = DocumentLinkParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    request::handle_document_link(server, params).await.unwrap()
+}
+
+pub(crate) async fn inlay_hints_request(
+    server: &ServerState,
+    uri: &Url,
+    range: Range,
+) -> Option<Vec<InlayHint>> {
+    let params = InlayHintParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        work_done_progress_params: Default::default(),
+    };
+    request::handle_inlay_hints(server, params).await.unwrap()
 }
 
 pub(crate) async fn highlight_request(server: &ServerState, uri: &Url) {
@@ -361,6 +862,50 @@ pub(crate) async fn code_lens_request(server: &ServerState, uri: &Url) {
     assert_eq!(expected, response.unwrap());
 }
 
+pub(crate) async fn completion_labels_request(
+    server: &ServerState,
+    uri: &Url,
+    position: Position,
+) -> Vec<String> {
+    completion_items_request(server, uri, position)
+        .await
+        .into_iter()
+        .map(|item| item.label)
+        .collect()
+}
+
+pub(crate) async fn completion_items_request(
+    server: &ServerState,
+    uri: &Url,
+    position: Position,
+) -> Vec<CompletionItem> {
+    let params = CompletionParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position,
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: Some(CompletionContext {
+            trigger_kind: CompletionTriggerKind::INVOKED,
+            trigger_character: None,
+        }),
+    };
+    match request::handle_completion(server, params).await.unwrap() {
+        Some(CompletionResponse::Array(items)) => items,
+        res => panic!("Unexpected completion response: {res:?}"),
+    }
+}
+
+pub(crate) async fn completion_resolve_request(
+    server: &ServerState,
+    item: CompletionItem,
+) -> CompletionItem {
+    request::handle_completion_resolve(server, item)
+        .await
+        .unwrap()
+}
+
 pub(crate) async fn completion_request(server: &ServerState, uri: &Url) {
     let params = CompletionParams {
         text_document_position: TextDocumentPositionParams {
@@ -377,11 +922,25 @@ pub(crate) async fn completion_request(server: &ServerState, uri: &Url) {
             trigger_character: Some(".".to_string()),
         }),
     };
-    let res = request::handle_completion(server, params).await.unwrap();
-    let expected = CompletionResponse::Array(vec![
+    let Some(CompletionResponse::Array(mut items)) =
+        request::handle_completion(server, params).await.unwrap()
+    else {
+        panic!("Expected an array of completion items");
+    };
+    // The signature of the method is only filled in once it's resolved.
+    let get = completion_resolve_request(server, items[1].clone()).await;
+    assert_eq!(
+        get.detail,
+        Some("fn get(self, foo: Self) -> Self".to_string())
+    );
+    for item in &mut items {
+        assert!(item.data.take().is_some());
+    }
+    let expected = vec![
         CompletionItem {
             label: "a".to_string(),
             kind: Some(CompletionItemKind::FIELD),
+            detail: Some("bool".to_string()),
             label_details: Some(CompletionItemLabelDetails {
                 detail: None,
                 description: Some("bool".to_string()),
@@ -389,11 +948,11 @@ pub(crate) async fn completion_request(server: &ServerState, uri: &Url) {
             ..Default::default()
         },
         CompletionItem {
-            label: "get(…)".to_string(),
+            label: "get".to_string(),
             kind: Some(CompletionItemKind::METHOD),
             label_details: Some(CompletionItemLabelDetails {
-                detail: None,
-                description: Some("fn(self, MyStruct) -> MyStruct".to_string()),
+                detail: Some("(foo: MyStruct)".to_string()),
+                description: Some("MyStruct".to_string()),
             }),
             text_edit: Some(CompletionTextEdit::Edit(TextEdit {
                 range: Range {
@@ -410,8 +969,93 @@ pub(crate) async fn completion_request(server: &ServerState, uri: &Url) {
             })),
             ..Default::default()
         },
-    ]);
-    assert_eq!(expected, res.unwrap());
+    ];
+    assert_eq!(expected, items);
+}
+
+pub(crate) async fn type_definition_request(server: &ServerState, uri: &Url) {
+    let type_definition = |line, character| {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        request::handle_goto_type_definition(server, params)
+    };
+
+    // The type of `p` in `p.x` is `Point`.
+    let expected = GotoDefinitionResponse::Scalar(Location {
+        uri: uri.clone(),
+        range: Range {
+            start: Position {
+                line: 2,
+                character: 7,
+            },
+            end: Position {
+                line: 2,
+                character: 12,
+            },
+        },
+    });
+    let res = type_definition(12, 13).await.unwrap();
+    assert_eq!(res, Some(expected));
+
+    // The type of `_x` is `u64`, which has no declaration.
+    let res = type_definition(12, 9).await.unwrap();
+    assert_eq!(res, None);
+}
+
+pub(crate) async fn implementation_request(server: &ServerState, uri: &Url) {
+    let implementation = |line, character| {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        request::handle_goto_implementation(server, params)
+    };
+    let location = |line, start, end| Location {
+        uri: uri.clone(),
+        range: Range::new(Position::new(line, start), Position::new(line, end)),
+    };
+
+    // The trait `Shape` is implemented for `Point`.
+    let res = implementation(6, 8).await.unwrap();
+    assert_eq!(
+        res,
+        Some(GotoDefinitionResponse::Array(vec![location(10, 5, 10)]))
+    );
+
+    // The method `area` of `Shape` is implemented in `impl Shape for Point`.
+    let res = implementation(7, 8).await.unwrap();
+    assert_eq!(
+        res,
+        Some(GotoDefinitionResponse::Array(vec![location(11, 7, 11)]))
+    );
+
+    // The method `area` of the impl goes to the method of the trait it implements.
+    let res = implementation(11, 8).await.unwrap();
+    assert_eq!(
+        res,
+        Some(GotoDefinitionResponse::Array(vec![location(7, 7, 11)]))
+    );
+
+    // The abi `Counter` is implemented for `Contract`.
+    let res = implementation(16, 6).await.unwrap();
+    assert_eq!(
+        res,
+        Some(GotoDefinitionResponse::Array(vec![location(20, 5, 12)]))
+    );
+
+    // Structs have no implementations to go to.
+    let res = implementation(2, 8).await.unwrap();
+    assert_eq!(res, None);
 }
 
 pub(crate) async fn definition_check<'a>(server: &ServerState, go_to: &'a GotoDefinition<'a>) {
@@ -466,6 +1110,47 @@ pub(crate) async fn definition_check<'a>(server: &ServerState, go_to: &'a GotoDe
     }
 }
 
+/// Checks that going to the definition of `go_to` lands on its declaration first, and also offers the
+/// `use` statement of the requested file that aliases it, at `alias_range`.
+pub(crate) async fn definition_through_alias_check<'a>(
+    server: &ServerState,
+    go_to: &'a GotoDefinition<'a>,
+    alias_range: Range,
+) {
+    let params = GotoDefinitionParams {
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: go_to.req_uri.clone(),
+            },
+            position: Position::new(go_to.req_line, go_to.req_char),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let res = request::handle_goto_definition(server, params)
+        .await
+        .unwrap();
+    let Some(GotoDefinitionResponse::Array(locations)) = res else {
+        panic!("Expected GotoDefinitionResponse::Array, got {res:?}");
+    };
+    assert_eq!(locations.len(), 2, "{locations:?}");
+    assert!(
+        locations[0].uri.as_str().ends_with(go_to.def_path),
+        "{} doesn't end with {}",
+        locations[0].uri,
+        go_to.def_path,
+    );
+    assert_eq!(
+        locations[0].range,
+        Range::new(
+            Position::new(go_to.def_line, go_to.def_start_char),
+            Position::new(go_to.def_line, go_to.def_end_char),
+        )
+    );
+    assert_eq!(locations[1].uri, *go_to.req_uri);
+    assert_eq!(locations[1].range, alias_range);
+}
+
 pub(crate) async fn definition_check_with_req_offset(
     server: &ServerState,
     go_to: &mut GotoDefinition<'_>,
@@ -537,7 +1222,26 @@ pub(crate) async fn rename_request<'a>(
     server: &ServerState,
     rename: &'a Rename<'a>,
 ) -> WorkspaceEdit {
-    let params = RenameParams {
+    let worspace_edit = request::handle_rename(server, rename_params(rename))
+        .await
+        .unwrap();
+    worspace_edit.unwrap()
+}
+
+/// Sends a rename request that is expected to be rejected, returning the error message.
+pub(crate) async fn rename_request_error<'a>(
+    server: &ServerState,
+    rename: &'a Rename<'a>,
+) -> String {
+    request::handle_rename(server, rename_params(rename))
+        .await
+        .unwrap_err()
+        .message
+        .to_string()
+}
+
+fn rename_params(rename: &Rename) -> RenameParams {
+    RenameParams {
         text_document_position: TextDocumentPositionParams {
             text_document: TextDocumentIdentifier {
                 uri: rename.req_uri.clone(),
@@ -549,7 +1253,5 @@ pub(crate) async fn rename_request<'a>(
         },
         new_name: rename.new_name.to_string(),
         work_done_progress_params: Default::default(),
-    };
-    let worspace_edit = request::handle_rename(server, params).await.unwrap();
-    worspace_edit.unwrap()
+    }
 }
@@ -1,11 +1,19 @@
 pub mod integration;
 
 use crate::integration::{code_actions, lsp};
+use futures::StreamExt;
 use lsp_types::*;
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
 use sway_lsp::{
+    capabilities::diagnostic::DiagnosticsSnapshot,
     handlers::{notification, request},
-    server_state::ServerState,
+    lsp_ext,
+    server_state::{LastCompilationState, ServerState},
+    utils::position_encoding::PositionEncoding,
 };
 use sway_lsp_test_utils::{
     assert_server_requests, dir_contains_forc_manifest, doc_comments_dir, e2e_language_dir,
@@ -80,6 +88,38 @@ async fn initialize() {
     let _ = request::handle_initialize(&server, params);
 }
 
+#[tokio::test]
+async fn initialize_with_disabled_features() {
+    let server = ServerState::default();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "features": { "hover": false, "inlayHint": false },
+            "format": { "enabled": false }
+        })),
+        ..Default::default()
+    };
+    let capabilities = request::handle_initialize(&server, params)
+        .unwrap()
+        .capabilities;
+    assert!(capabilities.hover_provider.is_none());
+    assert!(capabilities.inlay_hint_provider.is_none());
+    assert!(capabilities.document_formatting_provider.is_none());
+    assert!(capabilities.completion_provider.is_some());
+
+    // Disabled features are answered with an empty response if they're requested anyway.
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    let params = HoverParams {
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(0, 0),
+        },
+        work_done_progress_params: Default::default(),
+    };
+    let hover = request::handle_hover(&server, params).await.unwrap();
+    assert!(hover.is_none());
+    let _ = server.shutdown_server().await;
+}
+
 #[tokio::test]
 async fn did_open() {
     let server = ServerState::default();
@@ -87,6 +127,25 @@ async fn did_open() {
     let _ = server.shutdown_server().await;
 }
 
+#[tokio::test]
+async fn did_open_ignores_other_file_extensions() {
+    let server = ServerState::default();
+    let uri = Url::from_file_path(e2e_test_dir().join("src/main.sw.tera")).unwrap();
+    let params = DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri,
+            language_id: "sway".to_string(),
+            version: 1,
+            text: String::new(),
+        },
+    };
+    let res = notification::handle_did_open_text_document(&server, params).await;
+    assert!(res.is_ok());
+    let sessions = request::handle_list_sessions(&server).unwrap().sessions;
+    assert!(sessions.is_empty());
+    let _ = server.shutdown_server().await;
+}
+
 #[tokio::test]
 async fn did_change() {
     let (mut service, _) = LspService::new(ServerState::new);
@@ -175,8 +234,564 @@ async fn visualize() {
     let _ = server.shutdown_server().await;
 }
 
+#[tokio::test]
+async fn benchmark() {
+    let server = ServerState::default();
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    lsp::benchmark_request(&server, &uri, 3).await;
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn recompile_workspace() {
+    let server = ServerState::default();
+    let _ = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    let _ = open(&server, doc_comments_dir().join("src/main.sw")).await;
+    lsp::recompile_workspace_request(&server, 2).await;
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn reset_session() {
+    let server = ServerState::default();
+    let uri = open(&server, doc_comments_dir().join("src/main.sw")).await;
+    let _ = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    server.wait_for_parsing().await;
+    let params = lsp_ext::ResetSessionParams { uri };
+    let result = request::handle_reset_session(&server, params)
+        .await
+        .unwrap();
+    assert!(result.reset);
+    let sessions = request::handle_list_sessions(&server).unwrap().sessions;
+    assert_eq!(sessions.len(), 2);
+    for session in sessions {
+        assert_eq!(
+            session.last_compilation_state,
+            LastCompilationState::Success
+        );
+    }
+
+    // Projects without a session have nothing to reset.
+    let uri = Url::from_file_path(generic_impl_self_dir().join("src/main.sw")).unwrap();
+    let params = lsp_ext::ResetSessionParams { uri };
+    let result = request::handle_reset_session(&server, params)
+        .await
+        .unwrap();
+    assert!(!result.reset);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn code_lens_reference_counts() {
+    let server = ServerState::default();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({ "codeLens": { "references": true } })),
+        ..Default::default()
+    };
+    let _ = request::handle_initialize(&server, params);
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    let params = CodeLensParams {
+        text_document: TextDocumentIdentifier { uri },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let lenses = request::handle_code_lens(&server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut titles = vec![];
+    for lens in lenses.into_iter().filter(|lens| lens.data.is_some()) {
+        // The references are only counted once the lens is resolved.
+        assert!(lens.command.is_none());
+        let line = lens.range.start.line;
+        let lens = request::handle_code_lens_resolve(&server, lens)
+            .await
+            .unwrap();
+        let command = lens.command.unwrap();
+        assert_eq!(command.command, "sway.showReferences");
+        titles.push((line, command.title));
+    }
+    // `main`, `Data` and `ret_struct`.
+    assert_eq!(
+        titles,
+        vec![
+            (4, "0 references".to_string()),
+            (15, "3 references".to_string()),
+            (19, "1 reference".to_string()),
+        ]
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn keyword_doc() {
+    let server = ServerState::default();
+    let params = lsp_ext::KeywordDocParams {
+        keyword: "pub".to_string(),
+    };
+    let result = request::handle_keyword_doc(&server, params).unwrap();
+    assert!(result
+        .documentation
+        .contains("Make an item visible to others."));
+
+    let params = lsp_ext::KeywordDocParams {
+        keyword: "publ".to_string(),
+    };
+    let err = request::handle_keyword_doc(&server, params).unwrap_err();
+    assert_eq!(
+        err.code,
+        tower_lsp::jsonrpc::ErrorCode::ServerError(sway_lsp::error::KEYWORD_NOT_FOUND_ERROR_CODE)
+    );
+}
+
+#[test]
+fn manifest_for_file() {
+    let uri = Url::from_file_path(e2e_test_dir().join("src/main.sw")).unwrap();
+    let result = request::handle_manifest_for_file(lsp_ext::ManifestForFileParams { uri }).unwrap();
+    assert_eq!(result.manifest_dir, e2e_test_dir());
+    assert_eq!(result.project_name, "struct_field_access");
+
+    let dir = tempfile::tempdir().unwrap();
+    let uri = Url::from_file_path(dir.path().join("main.sw")).unwrap();
+    let err =
+        request::handle_manifest_for_file(lsp_ext::ManifestForFileParams { uri }).unwrap_err();
+    assert_eq!(
+        err.code,
+        tower_lsp::jsonrpc::ErrorCode::ServerError(sway_lsp::error::MANIFEST_NOT_FOUND_ERROR_CODE)
+    );
+}
+
+#[tokio::test]
+async fn temp_workspace_path() {
+    let server = ServerState::default();
+    let uri = Url::from_file_path(e2e_test_dir().join("src/main.sw")).unwrap();
+    let params = || lsp_ext::TempWorkspacePathParams { uri: uri.clone() };
+    // The session doesn't exist until a file of the project is opened.
+    let err = request::handle_temp_workspace_path(&server, params()).unwrap_err();
+    assert_eq!(
+        err.code,
+        tower_lsp::jsonrpc::ErrorCode::ServerError(sway_lsp::error::SESSION_NOT_FOUND_ERROR_CODE)
+    );
+
+    open(&server, e2e_test_dir().join("src/main.sw")).await;
+    let result = request::handle_temp_workspace_path(&server, params()).unwrap();
+    assert_eq!(result.manifest_dir, e2e_test_dir());
+    assert_ne!(result.temp_dir, result.manifest_dir);
+    assert!(result.temp_dir.join("src/main.sw").is_file());
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn collect_garbage() {
+    let server = ServerState::default();
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    server.wait_for_parsing().await;
+    let generation =
+        || request::handle_list_sessions(&server).unwrap().sessions[0].engines_generation;
+    let before = generation();
+    let result = request::handle_collect_garbage(
+        &server,
+        lsp_ext::CollectGarbageParams {
+            uri: Some(uri.clone()),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.collected, 1);
+    // The project was recompiled with the collected engines.
+    assert_ne!(generation(), before);
+    assert_eq!(
+        server.last_compilation_state(),
+        LastCompilationState::Success
+    );
+
+    let result =
+        request::handle_collect_garbage(&server, lsp_ext::CollectGarbageParams { uri: None })
+            .await
+            .unwrap();
+    assert_eq!(result.collected, 1);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn file_diagnostics() {
+    let dir = test_fixtures_dir().join("diagnostics/dead_code");
+    let server = ServerState::default();
+    let uri = Url::from_file_path(dir.join("src/main.sw")).unwrap();
+    let params = || lsp_ext::FileDiagnosticsParams { uri: uri.clone() };
+    let err = request::handle_file_diagnostics(&server, params()).unwrap_err();
+    assert_eq!(
+        err.code,
+        tower_lsp::jsonrpc::ErrorCode::ServerError(sway_lsp::error::SESSION_NOT_FOUND_ERROR_CODE)
+    );
+
+    open(&server, dir.join("src/main.sw")).await;
+    server.wait_for_parsing().await;
+    let result = request::handle_file_diagnostics(&server, params()).unwrap();
+    assert_eq!(result.last_compilation_state, LastCompilationState::Success);
+    assert!(!result.diagnostics.is_empty());
+    assert!(result
+        .diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::WARNING)));
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn locate_symbol() {
+    let server = ServerState::default();
+    let uri = open(&server, test_fixtures_dir().join("tokens/abi/src/main.sw")).await;
+    let locate = |name: &str, unique: bool| {
+        request::handle_locate_symbol(
+            &server,
+            lsp_ext::LocateSymbolParams {
+                uri: uri.clone(),
+                name: name.to_string(),
+                unique,
+            },
+        )
+    };
+    let range = |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+    let ranges = |result: lsp_ext::LocateSymbolResult| {
+        assert!(result.locations.iter().all(|location| location.uri == uri));
+        result
+            .locations
+            .into_iter()
+            .map(|location| location.range)
+            .collect::<Vec<_>>()
+    };
+
+    let result = locate("Contract::test_function", true).await.unwrap();
+    assert_eq!(ranges(result), vec![range(10, 7, 20)]);
+    let result = locate("MyContract::test_function", false).await.unwrap();
+    assert_eq!(ranges(result), vec![range(6, 7, 20), range(10, 7, 20)]);
+    let result = locate("abi::Empty", true).await.unwrap();
+    assert_eq!(ranges(result), vec![range(2, 7, 12)]);
+
+    let err = locate("abi::missing", false).await.unwrap_err();
+    assert_eq!(
+        err.code,
+        tower_lsp::jsonrpc::ErrorCode::ServerError(sway_lsp::error::SYMBOL_NOT_FOUND_ERROR_CODE)
+    );
+    let err = locate("test_function", true).await.unwrap_err();
+    assert_eq!(
+        err.code,
+        tower_lsp::jsonrpc::ErrorCode::ServerError(sway_lsp::error::AMBIGUOUS_SYMBOL_ERROR_CODE)
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn peek_signature() {
+    let server = ServerState::default();
+    let uri = open(&server, test_fixtures_dir().join("tokens/abi/src/main.sw")).await;
+    let peek = |line, character| {
+        request::handle_peek_signature(
+            &server,
+            lsp_ext::PeekSignatureParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position::new(line, character),
+            },
+        )
+    };
+
+    let result = peek(10, 9).await.unwrap().unwrap();
+    assert_eq!(result.signature, "fn test_function() -> Empty");
+    assert_eq!(result.documentation, None);
+    assert_eq!(
+        result.range,
+        Range::new(Position::new(10, 7), Position::new(10, 20))
+    );
+    let result = peek(5, 6).await.unwrap().unwrap();
+    assert_eq!(result.signature, "abi MyContract");
+    assert_eq!(
+        result.documentation.as_deref().map(str::trim),
+        Some("Docs for MyContract")
+    );
+    assert!(result.contents.value.contains("abi MyContract"));
+    // The parameter `address` has no signature of its own.
+    assert!(peek(16, 11).await.unwrap().is_none());
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn symbols_by_kind() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/variables/src/main.sw"),
+    )
+    .await;
+    let params = |kind| lsp_ext::SymbolsByKindParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        kind,
+    };
+    let structs = request::handle_symbols_by_kind(&server, params(SymbolKind::STRUCT))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        structs,
+        vec![lsp_ext::KindSymbol {
+            name: "ExampleStruct".to_string(),
+            location: Location::new(
+                uri.clone(),
+                Range::new(Position::new(2, 7), Position::new(2, 20))
+            ),
+        }]
+    );
+    // Only the declarations, not the call of `example_function` in `main`.
+    let functions = request::handle_symbols_by_kind(&server, params(SymbolKind::FUNCTION))
+        .await
+        .unwrap()
+        .unwrap();
+    let names: Vec<_> = functions
+        .iter()
+        .map(|symbol| symbol.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["example_function", "main"]);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn health_check() {
+    let server = ServerState::default();
+    let _ = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    server.wait_for_parsing().await;
+    lsp::health_check_request(&server);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn list_sessions() {
+    let server = ServerState::default();
+    let dir = test_fixtures_dir().join("diagnostics/dead_code");
+    let _ = open(&server, dir.join("src/main.sw")).await;
+    server.wait_for_parsing().await;
+    let result = request::handle_list_sessions(&server).unwrap();
+    assert_eq!(result.sessions.len(), 1);
+    let session = &result.sessions[0];
+    assert_eq!(session.manifest_dir, dir);
+    assert_eq!(session.project_name.as_deref(), Some("dead_code"));
+    assert!(!session.is_compiling);
+    assert_eq!(
+        session.last_compilation_state,
+        LastCompilationState::Success
+    );
+    assert_eq!(session.files_with_diagnostics, 1);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn server_info() {
+    let server = ServerState::default();
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    lsp::server_info_request(&uri, "struct_field_access");
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn set_trace_level() {
+    let (mut service, _) = LspService::build(ServerState::new)
+        .custom_method("sway/setTraceLevel", ServerState::set_trace_level)
+        .finish();
+    let _ = lsp::initialize_request(&mut service).await;
+    lsp::initialized_notification(&mut service).await;
+    for level in ["debug", "trace", "off"] {
+        let response = lsp::set_trace_level_request(&mut service, level).await;
+        assert_eq!(response.result(), Some(&serde_json::Value::Null));
+    }
+    let response = lsp::set_trace_level_request(&mut service, "verbose").await;
+    assert!(response.error().is_some());
+    shutdown_and_exit(&mut service).await;
+}
+
 //------------------- GO TO DEFINITION -------------------//
 
+#[tokio::test]
+async fn go_to_definition_across_files_with_unsaved_changes() {
+    let server = ServerState::default();
+    let dir = test_fixtures_dir().join("unsaved_definition/src");
+    let uri = open(&server, dir.join("main.sw")).await;
+    let utils_uri = Url::from_file_path(dir.join("utils.sw")).unwrap();
+    // Shift `helper` down by two lines without saving the file.
+    lsp::did_change_notification(&server, &utils_uri, 2, "\n\n").await;
+    let go_to = GotoDefinition {
+        req_uri: &uri,
+        req_line: 5,
+        req_char: 12,
+        def_line: 4,
+        def_start_char: 7,
+        def_end_char: 13,
+        def_path: utils_uri.as_str(),
+    };
+    lsp::definition_check(&server, &go_to).await;
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn references_across_files() {
+    let server = ServerState::default();
+    let dir = test_fixtures_dir().join("references/src");
+    let uri = open(&server, dir.join("main.sw")).await;
+    let utils_uri = Url::from_file_path(dir.join("utils.sw")).unwrap();
+    let location = |uri: &Url, line, start_char| {
+        Location::new(
+            uri.clone(),
+            Range::new(
+                Position::new(line, start_char),
+                Position::new(line, start_char + 6),
+            ),
+        )
+    };
+    let calls = vec![location(&uri, 5, 11), location(&uri, 6, 11)];
+    let position = Position::new(5, 13);
+    assert_eq!(
+        lsp::references_request(&server, &uri, position, false).await,
+        calls
+    );
+    let mut calls_and_declaration = calls;
+    calls_and_declaration.push(location(&utils_uri, 2, 7));
+    assert_eq!(
+        lsp::references_request(&server, &uri, position, true).await,
+        calls_and_declaration
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn signature_help() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("signature_help/src/main.sw"),
+    )
+    .await;
+    let add = "fn add(a: u64, b: u64) -> u64";
+    let cases = [
+        // `add(|1, add(2, 3))`
+        (18, 18, add, 0),
+        // `add(1, add(2, |3))`
+        (18, 28, add, 1),
+        // `add(1, add(2, 3)|)`
+        (18, 30, add, 1),
+        // `point.shift(sum, |4)`
+        (19, 29, "fn shift(dx: u64, dy: u64) -> u64", 1),
+    ];
+    for (line, character, label, active_parameter) in cases {
+        let help = lsp::signature_help_request(&server, &uri, line, character)
+            .await
+            .unwrap();
+        assert_eq!(help.signatures.len(), 1);
+        assert_eq!(help.signatures[0].label, label);
+        assert_eq!(help.signatures[0].parameters.as_ref().unwrap().len(), 2);
+        assert_eq!(help.active_parameter, Some(active_parameter));
+    }
+    // `Point { x: |1 }` isn't a call.
+    assert!(lsp::signature_help_request(&server, &uri, 17, 27)
+        .await
+        .is_none());
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn document_link() {
+    let server = ServerState::default();
+    let dir = test_fixtures_dir().join("document_link");
+    let uri = open(&server, dir.join("project/src/main.sw")).await;
+    let links = lsp::document_link_request(&server, &uri)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|link| {
+            let path = link.target.unwrap().to_file_path().unwrap();
+            let path = path.strip_prefix(&dir).unwrap().display().to_string();
+            (link.range.start.line, link.range.start.character, path)
+        })
+        .collect::<Vec<_>>();
+    // The imported items aren't modules, so they don't get links.
+    let expected = [
+        // `mod utils;`
+        (2, 4, "project/src/utils.sw"),
+        // `use dependency::helpers::double;`
+        (4, 4, "dependency/src/lib.sw"),
+        (4, 16, "dependency/src/helpers.sw"),
+        // `use utils::math::add;`
+        (5, 4, "project/src/utils.sw"),
+        (5, 11, "project/src/utils/math.sw"),
+        // `use ::utils::math::sub;`
+        (6, 6, "project/src/utils.sw"),
+        (6, 13, "project/src/utils/math.sw"),
+    ]
+    .map(|(line, character, path)| (line, character, path.to_string()));
+    assert_eq!(links, expected);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn chaining_inlay_hints() {
+    let (mut service, _) = LspService::new(ServerState::new);
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({
+            "inlayHints": { "renderColons": true, "typeHints": false, "chainingHints": true },
+        }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("chaining_hints/src/main.sw"));
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    let range = Range::new(Position::new(0, 0), Position::new(32, 0));
+    let mut hints = lsp::inlay_hints_request(service.inner(), &uri, range)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|hint| match hint.label {
+            InlayHintLabel::String(label) => (hint.position.line, hint.position.character, label),
+            InlayHintLabel::LabelParts(_) => panic!("expected a string label"),
+        })
+        .collect::<Vec<_>>();
+    hints.sort();
+    // Only the calls that are continued on the next line get a hint.
+    assert_eq!(
+        hints,
+        vec![(28, 15, ": B".to_string()), (29, 15, ": C".to_string())]
+    );
+    // Hints outside of the requested range are left out.
+    let range = Range::new(Position::new(29, 0), Position::new(32, 0));
+    let hints = lsp::inlay_hints_request(service.inner(), &uri, range)
+        .await
+        .unwrap();
+    assert_eq!(hints.len(), 1);
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn go_to_definition_with_unsaved_changes_after_saving_another_file() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    tokio::spawn(socket.for_each(|_| async {}));
+    let dir = test_fixtures_dir().join("unsaved_definition/src");
+    let uri = init_and_open(&mut service, dir.join("main.sw")).await;
+    let utils_uri = Url::from_file_path(dir.join("utils.sw")).unwrap();
+    // Shift `helper` down by two lines without saving the file.
+    lsp::did_change_notification(service.inner(), &utils_uri, 2, "\n\n").await;
+    // Saving main.sw resyncs the temp workspace from the files on disk.
+    lsp::did_save_notification(&mut service, &uri).await;
+    service.inner().wait_for_parsing().await;
+    let go_to = GotoDefinition {
+        req_uri: &uri,
+        req_line: 5,
+        req_char: 12,
+        def_line: 4,
+        def_start_char: 7,
+        def_end_char: 13,
+        def_path: utils_uri.as_str(),
+    };
+    lsp::definition_check(service.inner(), &go_to).await;
+    shutdown_and_exit(&mut service).await;
+}
+
 #[tokio::test]
 async fn go_to_definition() {
     let server = ServerState::default();
@@ -194,6 +809,53 @@ async fn go_to_definition() {
     let _ = server.shutdown_server().await;
 }
 
+#[tokio::test]
+async fn std_files_are_navigated_in_the_session_of_the_project() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/fields/src/main.sw"),
+    )
+    .await;
+    let definition = |uri: &Url, line, character| GotoDefinitionParams {
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position: Position { line, character },
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let Some(GotoDefinitionResponse::Scalar(location)) =
+        request::handle_goto_definition(&server, definition(&uri, 5, 8))
+            .await
+            .unwrap()
+    else {
+        panic!("Expected a single location");
+    };
+    assert!(location.uri.path().ends_with("sway-lib-std/src/option.sw"));
+
+    // The std file is read-only, so opening it doesn't create a session that compiles std on its own.
+    let std_uri = open(&server, location.uri.to_file_path().unwrap()).await;
+    let sessions = request::handle_list_sessions(&server).unwrap().sessions;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(
+        sessions[0].manifest_dir,
+        test_fixtures_dir().join("tokens/fields")
+    );
+    let response = request::handle_goto_definition(
+        &server,
+        definition(
+            &std_uri,
+            location.range.start.line,
+            location.range.start.character,
+        ),
+    )
+    .await
+    .unwrap();
+    assert!(response.is_some());
+    let _ = server.shutdown_server().await;
+}
+
 #[tokio::test]
 async fn go_to_definition_for_fields() {
     let server = ServerState::default();
@@ -248,6 +910,52 @@ async fn go_to_definition_for_fields() {
     let _ = server.shutdown_server().await;
 }
 
+#[tokio::test]
+async fn go_to_definition_for_field_chains() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/field_chains/src/main.sw"),
+    )
+    .await;
+    let mut go_to = GotoDefinition {
+        req_uri: &uri,
+        req_line: 20,
+        req_char: 24,
+        def_line: 3,
+        def_start_char: 4,
+        def_end_char: 9,
+        def_path: "sway-lsp/tests/fixtures/tokens/field_chains/src/main.sw",
+    };
+    // value
+    lsp::definition_check(&server, &go_to).await;
+    // inner
+    go_to.def_line = 7;
+    lsp::definition_check_with_req_offset(&server, &mut go_to, 20, 18).await;
+    // middle
+    go_to.def_line = 11;
+    go_to.def_end_char = 10;
+    lsp::definition_check_with_req_offset(&server, &mut go_to, 20, 11).await;
+
+    // The type of `missing` is unknown, so neither of the fields it's followed by resolves.
+    for character in [20, 27] {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position::new(24, character),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let response = request::handle_goto_definition(&server, params)
+            .await
+            .unwrap();
+        assert_eq!(response, None);
+    }
+
+    let _ = server.shutdown_server().await;
+}
+
 #[tokio::test]
 async fn go_to_definition_inside_turbofish() {
     let server = ServerState::default();
@@ -677,6 +1385,16 @@ async fn go_to_definition_for_paths() {
     // deep_fun
     lsp::definition_check(&server, &go_to).await;
     lsp::definition_check_with_req_offset(&server, &mut go_to, 6, 28).await;
+    // dfun, which aliases deep_fun
+    lsp::definition_check_with_req_offset(&server, &mut go_to, 6, 40).await;
+    go_to.req_line = 37;
+    go_to.req_char = 6;
+    lsp::definition_through_alias_check(
+        &server,
+        &go_to,
+        Range::new(Position::new(6, 38), Position::new(6, 42)),
+    )
+    .await;
 
     let go_to = GotoDefinition {
         req_uri: &uri,
@@ -1416,18 +2134,200 @@ async fn hover_docs_for_functions() {
 }
 
 #[tokio::test]
-async fn hover_docs_for_structs() {
+async fn hover_docs_for_methods_on_match_expressions() {
     let server = ServerState::default();
     let uri = open(
         &server,
-        test_fixtures_dir().join("tokens/structs/src/main.sw"),
+        test_fixtures_dir().join("match_receiver/src/main.sw"),
     )
     .await;
-    let data_documention = "```sway\nenum Data\n```\n---\n My data enum";
-
-    let mut hover = HoverDocumentation {
+    let hover = HoverDocumentation {
         req_uri: &uri,
-        req_line: 12,
+        req_line: 17,
+        req_char: 8,
+        documentation: vec!["```sway\nfn x_value(self) -> u64\n```"],
+    };
+    lsp::hover_request(&server, &hover).await;
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn completion_on_match_expressions() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("match_receiver/src/main.sw"),
+    )
+    .await;
+    let labels = lsp::completion_labels_request(&server, &uri, Position::new(17, 6)).await;
+    assert_eq!(labels, vec!["x", "x_value"]);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn completion_of_names_in_scope() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("completion_scope/src/main.sw"),
+    )
+    .await;
+    let items = lsp::completion_items_request(&server, &uri, Position::new(29, 17)).await;
+    let (keywords, names): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .partition(|item| item.kind == Some(CompletionItemKind::KEYWORD));
+    let names = names
+        .into_iter()
+        .map(|item| (item.label, item.kind.unwrap()))
+        .collect::<Vec<_>>();
+    // `inner` is out of scope, and `second` isn't declared until the end of its statement.
+    assert_eq!(
+        names,
+        vec![
+            ("Color".to_string(), CompletionItemKind::ENUM),
+            ("LIMIT".to_string(), CompletionItemKind::CONSTANT),
+            ("add".to_string(), CompletionItemKind::FUNCTION),
+            ("main".to_string(), CompletionItemKind::FUNCTION),
+            ("first".to_string(), CompletionItemKind::VARIABLE),
+            ("color".to_string(), CompletionItemKind::VARIABLE),
+        ]
+    );
+    // The documentation of the keywords is only filled in once they're resolved.
+    let keyword = keywords.iter().find(|item| item.label == "let").unwrap();
+    assert!(keyword.documentation.is_none());
+    let keyword = lsp::completion_resolve_request(&server, keyword.clone()).await;
+    assert!(matches!(
+        keyword.documentation,
+        Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            ..
+        }))
+    ));
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn completion_of_function_calls_as_snippets() {
+    let server = ServerState::default();
+    let params = InitializeParams {
+        capabilities: ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                completion: Some(CompletionClientCapabilities {
+                    completion_item: Some(CompletionItemCapability {
+                        snippet_support: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let _ = request::handle_initialize(&server, params);
+
+    let uri = open(&server, test_fixtures_dir().join("completion/src/main.sw")).await;
+    let items = lsp::completion_items_request(&server, &uri, Position::new(19, 8)).await;
+    let get = items.iter().find(|item| item.label == "get").unwrap();
+    assert_eq!(get.insert_text_format, Some(InsertTextFormat::SNIPPET));
+    assert!(matches!(
+        &get.text_edit,
+        Some(CompletionTextEdit::Edit(edit)) if edit.new_text == "get(${1:foo})$0"
+    ));
+
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("completion_scope/src/main.sw"),
+    )
+    .await;
+    let items = lsp::completion_items_request(&server, &uri, Position::new(29, 17)).await;
+    let add = items.iter().find(|item| item.label == "add").unwrap();
+    assert_eq!(add.insert_text_format, Some(InsertTextFormat::SNIPPET));
+    assert!(matches!(
+        &add.text_edit,
+        Some(CompletionTextEdit::Edit(edit)) if edit.new_text == "add(${1:a}, ${2:b})$0"
+    ));
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn completion_of_enum_variants_and_associated_functions() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("completion_scope/src/main.sw"),
+    )
+    .await;
+    let mut resolved = vec![];
+    for item in lsp::completion_items_request(&server, &uri, Position::new(28, 23)).await {
+        resolved.push(lsp::completion_resolve_request(&server, item).await);
+    }
+    let items = resolved
+        .into_iter()
+        .map(|item| (item.label, item.kind.unwrap(), item.detail))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        items,
+        vec![
+            (
+                "Red".to_string(),
+                CompletionItemKind::ENUM_MEMBER,
+                Some("()".to_string())
+            ),
+            (
+                "Blue".to_string(),
+                CompletionItemKind::ENUM_MEMBER,
+                Some("u64".to_string())
+            ),
+            (
+                "default".to_string(),
+                CompletionItemKind::FUNCTION,
+                Some("fn default() -> Self".to_string())
+            ),
+        ]
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn completion_of_names_that_are_not_imported() {
+    let server = ServerState::default();
+    let uri = open(&server, test_fixtures_dir().join("auto_import/src/main.sw")).await;
+    let items = lsp::completion_items_request(&server, &uri, Position::new(13, 8)).await;
+    let deep_fun = items.iter().find(|item| item.label == "deep_fun").unwrap();
+    assert_eq!(
+        deep_fun.label_details.as_ref().unwrap().description,
+        Some("deep_mod::deeper_mod::deep_fun".to_string())
+    );
+    assert_eq!(
+        deep_fun.additional_text_edits,
+        Some(vec![TextEdit {
+            range: Range::new(Position::new(5, 0), Position::new(5, 0)),
+            new_text: "use deep_mod::deeper_mod::deep_fun;\n".to_string(),
+        }])
+    );
+
+    // Names that are already imported don't need an edit.
+    let items = lsp::completion_items_request(&server, &uri, Position::new(12, 8)).await;
+    let test_fun = items.iter().find(|item| item.label == "test_fun").unwrap();
+    assert_eq!(test_fun.additional_text_edits, None);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn hover_docs_for_structs() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/structs/src/main.sw"),
+    )
+    .await;
+    let data_documention = "```sway\nenum Data\n```\n---\n My data enum";
+
+    let mut hover = HoverDocumentation {
+        req_uri: &uri,
+        req_line: 12,
         req_char: 10,
         documentation: vec![data_documention],
     };
@@ -1571,6 +2471,28 @@ async fn hover_docs_for_boolean_keywords() {
     let _ = server.shutdown_server().await;
 }
 
+#[tokio::test]
+async fn hover_docs_for_keywords_while_compiling() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/storage/src/main.sw"),
+    )
+    .await;
+    server.wait_for_parsing().await;
+    // Hovering over anything but a keyword would wait for this compilation to finish.
+    server.is_compiling.store(true, Ordering::SeqCst);
+    let hover = HoverDocumentation {
+        req_uri: &uri,
+        req_line: 13,
+        req_char: 36,
+        documentation: vec!["\n```sway\nfalse\n```\n\n---\n\n A value of type [`bool`] representing logical **false**."],
+    };
+    lsp::hover_request(&server, &hover).await;
+    server.is_compiling.store(false, Ordering::SeqCst);
+    let _ = server.shutdown_server().await;
+}
+
 #[tokio::test]
 async fn rename() {
     let server = ServerState::default();
@@ -1624,7 +2546,9 @@ async fn rename() {
         new_name: "better_func_name", // from test_fun
     };
     let _ = lsp::prepare_rename_request(&server, &rename).await;
-    let _ = lsp::rename_request(&server, &rename).await;
+    let result = lsp::rename_request(&server, &rename).await;
+    // Both the declaration in the module and the call are renamed.
+    assert_eq!(result.changes.unwrap().len(), 2);
 
     // Function method in ABI declaration
     let rename = Rename {
@@ -1657,6 +2581,35 @@ async fn rename() {
     let result = lsp::rename_request(&server, &rename).await;
     assert_eq!(result.changes.unwrap().values().next().unwrap().len(), 3);
 
+    // Fail to rename a variable to the name of another variable of the function
+    let rename = Rename {
+        req_uri: &uri,
+        req_line: 22,
+        req_char: 8,
+        new_name: "c", // from point
+    };
+    let error = lsp::rename_request_error(&server, &rename).await;
+    assert!(error.contains("already declared"), "{error}");
+
+    // Fail to rename a struct to the name of another struct of the module
+    let rename = Rename {
+        req_uri: &uri,
+        req_line: 11,
+        req_char: 7,
+        new_name: "MyStruct1", // from Point
+    };
+    let error = lsp::rename_request_error(&server, &rename).await;
+    assert!(error.contains("already declared"), "{error}");
+
+    // A field can share its name with a variable
+    let rename = Rename {
+        req_uri: &uri,
+        req_line: 12,
+        req_char: 4,
+        new_name: "n", // from x
+    };
+    let _ = lsp::rename_request(&server, &rename).await;
+
     // Fail to rename keyword
     let rename = Rename {
         req_uri: &uri,
@@ -1703,6 +2656,317 @@ async fn publish_diagnostics_dead_code_warning() {
     shutdown_and_exit(&mut service).await;
 }
 
+#[tokio::test]
+async fn publish_project_status() {
+    let (mut service, mut socket) = LspService::new(ServerState::new);
+    let socket_handle = tokio::spawn(async move {
+        while let Some(request) = socket.next().await {
+            if request.method() == "sway/projectStatus" {
+                let params: lsp_ext::ProjectStatusParams =
+                    serde_json::from_value(request.params().unwrap().clone()).unwrap();
+                assert!(params.manifest_dir.ends_with("dead_code"));
+                assert_eq!(params.error_count, 0);
+                assert_eq!(params.warning_count, 12);
+                assert_eq!(params.last_compilation_state, LastCompilationState::Success);
+                return;
+            }
+        }
+        panic!("The project status was never published");
+    });
+    let _ = init_and_open(
+        &mut service,
+        test_fixtures_dir().join("diagnostics/dead_code/src/main.sw"),
+    )
+    .await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn diagnostics_snapshot() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    tokio::spawn(socket.for_each(|_| async {}));
+    let dir = tempfile::tempdir().unwrap();
+    let snapshot_path = dir.path().join("diagnostics.json");
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({ "diagnostic": { "snapshot": { "path": snapshot_path } } }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("diagnostics/dead_code/src/main.sw"));
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    service.inner().wait_for_parsing().await;
+
+    let snapshot: DiagnosticsSnapshot =
+        serde_json::from_str(&fs::read_to_string(&snapshot_path).unwrap()).unwrap();
+    let main_file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("src/main.sw"))
+        .unwrap();
+    let fixture = get_fixture(test_fixtures_dir().join("diagnostics/dead_code/expected.json"));
+    assert_json_diff::assert_json_include!(
+        expected: fixture["params"]["diagnostics"],
+        actual: serde_json::to_value(&main_file.diagnostics).unwrap()
+    );
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn warn_missing_manifest_once_per_directory() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+    let expected_requests = dirs
+        .iter()
+        .map(|dir| {
+            serde_json::json!({
+                "method": "window/showMessage",
+                "params": {
+                    "type": 2,
+                    "message": format!(
+                        "No Forc.toml was found in {} or any of its parent directories. Sway language \
+                        features are only available in Forc projects, which can be created with `forc init`.",
+                        dir.path().display()
+                    ),
+                },
+            })
+        })
+        .collect();
+    let socket_handle = assert_server_requests(socket, expected_requests).await;
+    let _ = lsp::initialize_request(&mut service).await;
+    lsp::initialized_notification(&mut service).await;
+    // Only the first file of each directory is warned about.
+    for (dir, file_name) in [(&dirs[0], "a.sw"), (&dirs[0], "b.sw"), (&dirs[1], "a.sw")] {
+        let path = dir.path().join(file_name);
+        fs::write(&path, "script;\n").unwrap();
+        let (uri, sway_program) = load_sway_example(path);
+        lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    }
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn export_diagnostics() {
+    let server = ServerState::default();
+    // The project is compiled by the request, as none of its files are open.
+    let dir = test_fixtures_dir().join("diagnostics/dead_code");
+    let snapshot = lsp::export_diagnostics_request(&server, &Url::from_file_path(&dir).unwrap())
+        .await
+        .unwrap();
+    let main_file = snapshot
+        .files
+        .iter()
+        .find(|file| file.path == Path::new("src/main.sw"))
+        .unwrap();
+    let fixture = get_fixture(dir.join("expected.json"));
+    assert_json_diff::assert_json_include!(
+        expected: fixture["params"]["diagnostics"],
+        actual: serde_json::to_value(&main_file.diagnostics).unwrap()
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn wait_for_compilation() {
+    let server = ServerState::default();
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    assert_eq!(
+        lsp::wait_for_compilation_request(&server, Some(&uri)).await,
+        LastCompilationState::Success
+    );
+    assert_eq!(
+        lsp::wait_for_compilation_request(&server, None).await,
+        LastCompilationState::Success
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn dependency_files_belong_to_the_dependent_session() {
+    let server = ServerState::default();
+    let _ = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    let dependency_uri =
+        Url::from_file_path(sway_workspace_dir().join("sway-lib-core/src/ops.sw")).unwrap();
+    let params = DocumentSymbolParams {
+        text_document: TextDocumentIdentifier {
+            uri: dependency_uri.clone(),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let response = request::handle_document_symbol(&server, params)
+        .await
+        .unwrap();
+    assert!(matches!(response, Some(DocumentSymbolResponse::Flat(symbols)) if !symbols.is_empty()));
+    // Edits to the dependency are ignored rather than compiled.
+    lsp::did_change_notification(&server, &dependency_uri, 1, "\n").await;
+    server.is_compiling.store(false, Ordering::SeqCst);
+    assert_eq!(
+        request::handle_health_check(&server).unwrap().queue_length,
+        0
+    );
+    assert_eq!(
+        request::handle_list_sessions(&server)
+            .unwrap()
+            .sessions
+            .len(),
+        1
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn compile_now_shares_the_pending_compilation() {
+    let server = ServerState::default();
+    let uri = open(&server, e2e_test_dir().join("src/main.sw")).await;
+    // The project was compiled when it was opened, so there's nothing to wait for.
+    server.compile_now(&uri).await.unwrap();
+    lsp::did_change_notification(&server, &uri, 1, "\n").await;
+    // Both callers wait for the compilation that the edit requested, rather than requesting their own.
+    let (a, b) = tokio::join!(server.compile_now(&uri), server.compile_now(&uri));
+    assert!(a.is_ok() && b.is_ok());
+    assert_eq!(
+        request::handle_health_check(&server).unwrap().queue_length,
+        0
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn warnings_as_errors() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    tokio::spawn(socket.for_each(|_| async {}));
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({ "diagnostic": { "warningsAsErrors": true } }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("diagnostics/dead_code/src/main.sw"));
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    let snapshot = lsp::export_diagnostics_request(service.inner(), &uri)
+        .await
+        .unwrap();
+    let diagnostics = snapshot
+        .files
+        .iter()
+        .flat_map(|file| &file.diagnostics)
+        .collect::<Vec<_>>();
+    assert!(!diagnostics.is_empty());
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR)));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn did_change_configuration_recompiles_on_build_change() {
+    let (mut service, _) = LspService::new(ServerState::new);
+    let uri = init_and_open(
+        &mut service,
+        test_fixtures_dir().join("build_config/src/main.sw"),
+    )
+    .await;
+    let num_errors = |snapshot: DiagnosticsSnapshot| {
+        snapshot
+            .files
+            .iter()
+            .flat_map(|file| &file.diagnostics)
+            .filter(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR))
+            .count()
+    };
+    // The type error is in a test function, so it's only reported while tests are compiled.
+    let snapshot = lsp::export_diagnostics_request(service.inner(), &uri)
+        .await
+        .unwrap();
+    assert_ne!(num_errors(snapshot), 0);
+    lsp::did_change_configuration_notification(
+        &mut service,
+        serde_json::json!({ "build": { "includeTests": false } }),
+    )
+    .await;
+    let snapshot = lsp::export_diagnostics_request(service.inner(), &uri)
+        .await
+        .unwrap();
+    assert_eq!(num_errors(snapshot), 0);
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn did_change_watched_files_syncs_external_changes() {
+    let (mut service, _) = LspService::new(ServerState::new);
+    let dir = tempfile::tempdir().unwrap();
+    let manifest_path = dir.path().join("Forc.toml");
+    let other_path = dir.path().join("src/other.sw");
+    let manifest = |name: &str| {
+        format!(
+            "[project]\nauthors = [\"Fuel Labs <contact@fuel.sh>\"]\nentry = \"main.sw\"\n\
+            license = \"Apache-2.0\"\nname = \"{name}\"\nimplicit-std = false\n"
+        )
+    };
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::write(&manifest_path, manifest("before")).unwrap();
+    fs::write(
+        dir.path().join("src/main.sw"),
+        "script;\n\nmod other;\n\nfn main() {}\n",
+    )
+    .unwrap();
+    fs::write(&other_path, "library;\n\npub fn other() -> u64 { 0 }\n").unwrap();
+    let uri = init_and_open(&mut service, dir.path().join("src/main.sw")).await;
+    let num_errors = |snapshot: DiagnosticsSnapshot| {
+        snapshot
+            .files
+            .iter()
+            .flat_map(|file| &file.diagnostics)
+            .filter(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR))
+            .count()
+    };
+    let snapshot = lsp::export_diagnostics_request(service.inner(), &uri)
+        .await
+        .unwrap();
+    assert_eq!(num_errors(snapshot), 0);
+
+    // A type error is introduced outside the editor.
+    fs::write(&other_path, "library;\n\npub fn other() -> u64 { true }\n").unwrap();
+    lsp::did_change_watched_files_notification(
+        &mut service,
+        vec![FileEvent::new(
+            Url::from_file_path(&other_path).unwrap(),
+            FileChangeType::CHANGED,
+        )],
+    )
+    .await;
+    let snapshot = lsp::export_diagnostics_request(service.inner(), &uri)
+        .await
+        .unwrap();
+    assert_ne!(num_errors(snapshot), 0);
+
+    // Renaming the project in its manifest replaces the session.
+    fs::write(&manifest_path, manifest("after")).unwrap();
+    lsp::did_change_watched_files_notification(
+        &mut service,
+        vec![FileEvent::new(
+            Url::from_file_path(&manifest_path).unwrap(),
+            FileChangeType::CHANGED,
+        )],
+    )
+    .await;
+    let sessions = request::handle_list_sessions(service.inner())
+        .unwrap()
+        .sessions;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].project_name.as_deref(), Some("after"));
+    shutdown_and_exit(&mut service).await;
+}
+
 #[tokio::test]
 async fn publish_diagnostics_multi_file() {
     let (mut service, socket) = LspService::new(ServerState::new);
@@ -1720,6 +2984,297 @@ async fn publish_diagnostics_multi_file() {
     shutdown_and_exit(&mut service).await;
 }
 
+#[tokio::test]
+async fn publish_diagnostics_related_information() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let fixture =
+        get_fixture(test_fixtures_dir().join("diagnostics/duplicate_definition/expected.json"));
+    let expected_requests = vec![fixture];
+    let socket_handle = assert_server_requests(socket, expected_requests).await;
+    let _ = init_and_open(
+        &mut service,
+        test_fixtures_dir().join("diagnostics/duplicate_definition/src/main.sw"),
+    )
+    .await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn publish_diagnostics_immutable_assignment() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let fixture =
+        get_fixture(test_fixtures_dir().join("diagnostics/immutable_assignment/expected.json"));
+    let expected_requests = vec![fixture];
+    let socket_handle = assert_server_requests(socket, expected_requests).await;
+    let _ = init_and_open(
+        &mut service,
+        test_fixtures_dir().join("diagnostics/immutable_assignment/src/main.sw"),
+    )
+    .await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn publish_diagnostics_unused_storage_field() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let socket_handle = tokio::spawn(async move {
+        let requests = socket.take(1).collect::<Vec<_>>().await;
+        let params: PublishDiagnosticsParams =
+            serde_json::from_value(requests[0].params().unwrap().clone()).unwrap();
+        let unused_storage_fields = params
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String("unused_storage_field".to_string()))
+            })
+            .collect::<Vec<_>>();
+        // Only `unused` is reported, as `used` is accessed in `get_key`.
+        assert_eq!(unused_storage_fields.len(), 1);
+        assert_eq!(unused_storage_fields[0].range.start.line, 8);
+        assert_eq!(
+            unused_storage_fields[0].message,
+            "Storage field `unused` is never read or written."
+        );
+    });
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({ "diagnostic": { "unusedStorageFields": true } }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("diagnostics/unused_storage/src/main.sw"));
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn publish_diagnostics_module_prefix() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let socket_handle = tokio::spawn(async move {
+        let requests = socket.take(2).collect::<Vec<_>>().await;
+        let params = requests
+            .iter()
+            .map(|request| {
+                serde_json::from_value::<PublishDiagnosticsParams>(
+                    request.params().unwrap().clone(),
+                )
+                .unwrap()
+            })
+            .find(|params| params.uri.path().ends_with("utils.sw"))
+            .unwrap();
+        assert!(!params.diagnostics.is_empty());
+        for diagnostic in params.diagnostics {
+            assert!(diagnostic.message.starts_with("[module_prefix::utils] "));
+        }
+    });
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({ "diagnostic": { "modulePrefix": true } }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("diagnostics/module_prefix/src/main.sw"));
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn max_memory_mb() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let (uri, sway_program) = load_sway_example(test_fixtures_dir().join("benchmark/src/main.sw"));
+    let expected_requests = vec![serde_json::json!({
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": uri,
+            "diagnostics": [{ "severity": 3, "code": "memory_limit" }],
+        },
+    })];
+    let socket_handle = assert_server_requests(socket, expected_requests).await;
+    // The server uses more than 1 MB of memory, so every compilation is aborted.
+    lsp::initialize_with_options_request(&mut service, serde_json::json!({ "maxMemoryMb": 1 }))
+        .await;
+    lsp::initialized_notification(&mut service).await;
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    let result = request::handle_health_check(service.inner()).unwrap();
+    assert_eq!(result.last_compilation_state, LastCompilationState::Failed);
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn position_encoding() {
+    // `é` is 2 bytes and 1 UTF-16 code unit, and `🦀` is 4 bytes and 2 UTF-16 code units, so the
+    // identifiers that follow them on the same line start 3 characters later in UTF-8.
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("position_encoding/src/main.sw"));
+    for (encoding, shift) in [
+        (PositionEncodingKind::UTF8, 3),
+        (PositionEncodingKind::UTF16, 0),
+    ] {
+        let (mut service, socket) = LspService::new(ServerState::new);
+        let socket_handle = tokio::spawn(async move {
+            let requests = socket.take(1).collect::<Vec<_>>().await;
+            let params: PublishDiagnosticsParams =
+                serde_json::from_value(requests[0].params().unwrap().clone()).unwrap();
+            params
+                .diagnostics
+                .into_iter()
+                .find(|diagnostic| diagnostic.range.start.line == 3)
+                .expect("expected a warning for `unused`")
+                .range
+        });
+        let result =
+            lsp::initialize_with_position_encodings_request(&mut service, vec![encoding.clone()])
+                .await;
+        assert_eq!(
+            result.capabilities.position_encoding,
+            Some(encoding.clone())
+        );
+        lsp::initialized_notification(&mut service).await;
+        lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+
+        let unused_range = socket_handle
+            .await
+            .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+        assert_eq!(
+            unused_range,
+            Range::new(Position::new(3, 24 + shift), Position::new(3, 30 + shift)),
+            "{encoding:?}"
+        );
+
+        let hover = request::handle_hover(
+            service.inner(),
+            HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                    position: Position::new(4, 25 + shift),
+                },
+                work_done_progress_params: Default::default(),
+            },
+        )
+        .await
+        .unwrap()
+        .expect("expected a hover for `used`");
+        assert_eq!(
+            hover.range,
+            Some(Range::new(
+                Position::new(4, 24 + shift),
+                Position::new(4, 28 + shift)
+            )),
+            "{encoding:?}"
+        );
+        shutdown_and_exit(&mut service).await;
+    }
+
+    // A client that doesn't list any encodings gets UTF-16, whatever another server negotiated.
+    let (mut utf8_service, _) = LspService::new(ServerState::new);
+    lsp::initialize_with_position_encodings_request(
+        &mut utf8_service,
+        vec![PositionEncodingKind::UTF8],
+    )
+    .await;
+    let (mut service, _) = LspService::new(ServerState::new);
+    lsp::initialize_request(&mut service).await;
+    assert_eq!(service.inner().position_encoding(), PositionEncoding::Utf16);
+    assert_eq!(
+        utf8_service.inner().position_encoding(),
+        PositionEncoding::Utf8
+    );
+    shutdown_and_exit(&mut service).await;
+    shutdown_and_exit(&mut utf8_service).await;
+}
+
+#[tokio::test]
+async fn publish_diagnostics_streaming() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let (uri, sway_program) =
+        load_sway_example(test_fixtures_dir().join("diagnostics/parse_error/src/main.sw"));
+    let parse_error = serde_json::json!({
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": uri,
+            "diagnostics": [{ "severity": 1 }],
+        },
+    });
+    // The parse errors are published before the compilation has finished, and are then replaced
+    // by the full diagnostics once it has.
+    let expected_requests = vec![parse_error.clone(), parse_error];
+    let socket_handle = assert_server_requests(socket, expected_requests).await;
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({ "experimental": { "streamingDiagnostics": true } }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
+#[tokio::test]
+async fn publish_diagnostics_active_file_first() {
+    let (mut service, socket) = LspService::new(ServerState::new);
+    let dir = test_fixtures_dir().join("diagnostics/multi_file/src");
+    let file_uri = |name: &str| Url::from_file_path(dir.join(name)).unwrap();
+    let publish = |name: &str| {
+        serde_json::json!({
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": file_uri(name) },
+        })
+    };
+    let sorted = [
+        "adt_tests.sw",
+        "complex_tests.sw",
+        "main.sw",
+        "or_patterns.sw",
+        "primitive_tests.sw",
+    ];
+    // Opening main.sw publishes it first, followed by the rest of the files.
+    let mut expected_requests = vec![publish("main.sw")];
+    expected_requests.extend(
+        sorted
+            .iter()
+            .filter(|f| **f != "main.sw")
+            .map(|f| publish(f)),
+    );
+    // After editing or_patterns.sw, it's published before the saved file and the rest of the files.
+    expected_requests.extend([publish("or_patterns.sw"), publish("main.sw")]);
+    expected_requests.extend(
+        sorted
+            .iter()
+            .filter(|f| **f != "main.sw" && **f != "or_patterns.sw")
+            .map(|f| publish(f)),
+    );
+    let socket_handle = assert_server_requests(socket, expected_requests).await;
+
+    let main_uri = init_and_open(&mut service, dir.join("main.sw")).await;
+    let _ = lsp::did_change_request(&mut service, &file_uri("or_patterns.sw"), 1).await;
+    service.inner().wait_for_parsing().await;
+    lsp::did_save_notification(&mut service, &main_uri).await;
+    socket_handle
+        .await
+        .unwrap_or_else(|e| panic!("Test failed: {e:?}"));
+    shutdown_and_exit(&mut service).await;
+}
+
 // This macro allows us to spin up a server / client for testing
 // It initializes and performs the necessary handshake and then loads
 // the sway example that was passed into `example_dir`.
@@ -1750,6 +3305,225 @@ lsp_capability_test!(
     lsp::semantic_tokens_request,
     doc_comments_dir().join("src/main.sw")
 );
+
+#[tokio::test]
+async fn range_format() {
+    let server = ServerState::default();
+    let uri = open(&server, doc_comments_dir().join("src/main.sw")).await;
+    // Add an unformatted function right after `contract;`.
+    lsp::did_change_notification(&server, &uri, 1, "fn unformatted() {\nlet x = 1;\n}\n").await;
+
+    // Only the function under the cursor is formatted, not the missing blank line before it.
+    let range = Range::new(Position::new(2, 3), Position::new(2, 3));
+    let edits = lsp::range_format_request(&server, &uri, range).await;
+    assert_eq!(
+        edits,
+        vec![TextEdit {
+            range: Range::new(Position::new(2, 0), Position::new(2, 0)),
+            new_text: "    ".to_string(),
+        }]
+    );
+
+    // The items that are already formatted aren't edited.
+    let range = Range::new(Position::new(8, 0), Position::new(12, 0));
+    assert!(lsp::range_format_request(&server, &uri, range)
+        .await
+        .is_empty());
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn on_type_format() {
+    let server = ServerState::default();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({ "format": { "onType": true } })),
+        ..Default::default()
+    };
+    let capabilities = request::handle_initialize(&server, params)
+        .unwrap()
+        .capabilities;
+    assert!(capabilities.document_on_type_formatting_provider.is_some());
+
+    let uri = open(&server, doc_comments_dir().join("src/main.sw")).await;
+    lsp::did_change_notification(&server, &uri, 1, "    fn f() {}\n").await;
+    let params = DocumentOnTypeFormattingParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(1, 4),
+        },
+        ch: "\n".to_string(),
+        options: FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        },
+    };
+    // The item is moved back to the top level.
+    let edits = request::handle_on_type_formatting(&server, params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        edits,
+        vec![TextEdit {
+            range: Range::new(Position::new(1, 0), Position::new(1, 4)),
+            new_text: String::new(),
+        }]
+    );
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn semantic_tokens_range() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/variables/src/main.sw"),
+    )
+    .await;
+    // `let variable1 = 10;` through `let variable3 = false;`
+    let range = Range::new(Position::new(19, 0), Position::new(21, 26));
+    let tokens = lsp::semantic_tokens_range_request(&server, &uri, range).await;
+    assert!(tokens
+        .iter()
+        .all(|(position, _, _)| (19..=21).contains(&position.line)));
+
+    let modifiers_at = |line, character| {
+        tokens
+            .iter()
+            .find(|(position, ty, _)| {
+                *position == Position::new(line, character) && *ty == SemanticTokenType::VARIABLE
+            })
+            .map(|(_, _, modifiers)| modifiers.clone())
+            .unwrap()
+    };
+    // The declaration of `variable1`
+    assert_eq!(
+        modifiers_at(19, 8),
+        vec![
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::READONLY
+        ]
+    );
+    // The use of `variable1` in the declaration of `variable2`
+    assert_eq!(modifiers_at(20, 32), vec![SemanticTokenModifier::READONLY]);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn semantic_tokens_ranges() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/variables/src/main.sw"),
+    )
+    .await;
+    // The declarations of `variable1` and `variable3`, but not the one of `variable2` in between.
+    let first = Range::new(Position::new(19, 0), Position::new(19, 26));
+    let second = Range::new(Position::new(21, 0), Position::new(21, 26));
+    let tokens = lsp::semantic_tokens_ranges_request(&server, &uri, vec![second, first]).await;
+    let mut expected = lsp::semantic_tokens_range_request(&server, &uri, first).await;
+    expected.extend(lsp::semantic_tokens_range_request(&server, &uri, second).await);
+    assert!(!expected.is_empty());
+    // Positions are only correct after the gap if the tokens are encoded relative to each other across it.
+    assert_eq!(tokens, expected);
+
+    // Ranges that cover most of the file get all of its tokens.
+    let everything = Range::new(Position::new(0, 0), Position::new(1000, 0));
+    let all = lsp::semantic_tokens_range_request(&server, &uri, everything).await;
+    let most = Range::new(Position::new(2, 0), Position::new(1000, 0));
+    let tokens = lsp::semantic_tokens_ranges_request(&server, &uri, vec![first, most]).await;
+    assert_eq!(tokens, all);
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn semantic_tokens_full_delta() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/variables/src/main.sw"),
+    )
+    .await;
+    let previous = lsp::semantic_tokens_full_request(&server, &uri).await;
+    let previous_id = previous.result_id.clone().unwrap();
+
+    // Nothing has changed since the last request.
+    let SemanticTokensFullDeltaResult::TokensDelta(delta) =
+        lsp::semantic_tokens_full_delta_request(&server, &uri, &previous_id).await
+    else {
+        panic!("Expected a delta");
+    };
+    assert!(delta.edits.is_empty());
+    assert_ne!(delta.result_id, Some(previous_id.clone()));
+
+    // A result id that wasn't the last one sent gets all of the tokens.
+    let result = lsp::semantic_tokens_full_delta_request(&server, &uri, &previous_id).await;
+    assert!(matches!(
+        result,
+        SemanticTokensFullDeltaResult::Tokens(tokens) if tokens.data == previous.data
+    ));
+
+    // Shift every token after the first line down by two lines.
+    let previous = lsp::semantic_tokens_full_request(&server, &uri).await;
+    lsp::did_change_notification(&server, &uri, 1, "\n\n").await;
+    let SemanticTokensFullDeltaResult::TokensDelta(delta) =
+        lsp::semantic_tokens_full_delta_request(&server, &uri, &previous.result_id.unwrap()).await
+    else {
+        panic!("Expected a delta");
+    };
+    // Apply the edits to the integers the tokens are encoded as.
+    let encode = |tokens: &[SemanticToken]| -> Vec<u32> {
+        tokens
+            .iter()
+            .flat_map(|token| {
+                [
+                    token.delta_line,
+                    token.delta_start,
+                    token.length,
+                    token.token_type,
+                    token.token_modifiers_bitset,
+                ]
+            })
+            .collect()
+    };
+    assert!(!delta.edits.is_empty());
+    let mut data = encode(&previous.data);
+    for edit in delta.edits.iter().rev() {
+        let start = edit.start as usize;
+        let inserted = encode(edit.data.as_deref().unwrap_or_default());
+        data.splice(start..start + edit.delete_count as usize, inserted);
+    }
+    let current = lsp::semantic_tokens_full_request(&server, &uri).await;
+    assert_eq!(data, encode(&current.data));
+    let _ = server.shutdown_server().await;
+}
+
+#[tokio::test]
+async fn semantic_tokens_are_recomputed_after_the_engines_are_swapped() {
+    let server = ServerState::default();
+    let uri = open(
+        &server,
+        test_fixtures_dir().join("tokens/variables/src/main.sw"),
+    )
+    .await;
+    let engines_generation =
+        || request::handle_list_sessions(&server).unwrap().sessions[0].engines_generation;
+    let generation = engines_generation();
+    let tokens = lsp::semantic_tokens_full_request(&server, &uri).await;
+
+    // Nothing was compiled since, so the tokens are reused with a new result id.
+    let reused = lsp::semantic_tokens_full_request(&server, &uri).await;
+    assert_eq!(reused.data, tokens.data);
+    assert_ne!(reused.result_id, tokens.result_id);
+    assert_eq!(engines_generation(), generation);
+
+    lsp::did_change_notification(&server, &uri, 1, "\n\n").await;
+    let shifted = lsp::semantic_tokens_full_request(&server, &uri).await;
+    assert_ne!(engines_generation(), generation);
+    assert_ne!(shifted.data, tokens.data);
+    let _ = server.shutdown_server().await;
+}
 lsp_capability_test!(
     document_symbol,
     lsp::document_symbol_request,
@@ -1760,6 +3534,75 @@ lsp_capability_test!(
     lsp::format_request,
     doc_comments_dir().join("src/main.sw")
 );
+lsp_capability_test!(
+    call_hierarchy,
+    lsp::call_hierarchy_request,
+    test_fixtures_dir().join("call_hierarchy/src/main.sw")
+);
+lsp_capability_test!(
+    type_definition,
+    lsp::type_definition_request,
+    test_fixtures_dir().join("type_definition/src/main.sw")
+);
+lsp_capability_test!(
+    implementation,
+    lsp::implementation_request,
+    test_fixtures_dir().join("implementation/src/main.sw")
+);
+lsp_capability_test!(
+    monomorphized_type,
+    lsp::monomorphized_type_request,
+    test_fixtures_dir().join("generics/src/main.sw")
+);
+
+#[tokio::test]
+async fn type_of_expression() {
+    let (mut service, _) = LspService::new(ServerState::new);
+    lsp::initialize_with_options_request(
+        &mut service,
+        serde_json::json!({ "experimental": { "typeOfExpression": true } }),
+    )
+    .await;
+    lsp::initialized_notification(&mut service).await;
+    let (uri, sway_program) = load_sway_example(test_fixtures_dir().join("generics/src/main.sw"));
+    lsp::did_open_notification(&mut service, &uri, &sway_program).await;
+    service.inner().wait_for_parsing().await;
+    let type_of = |position, expression: &str| {
+        request::handle_type_of_expression(
+            service.inner(),
+            lsp_ext::TypeOfExpressionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+                expression: expression.to_string(),
+            },
+        )
+    };
+
+    // After `let a = identity(42u64);`, so `a` is in scope.
+    let result = type_of(Position::new(8, 0), "identity(a)")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.type_name.as_deref(), Some("u64"));
+    assert!(result.errors.is_empty());
+
+    let result = type_of(Position::new(8, 0), "a + true")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.type_name, None);
+    assert!(!result.errors.is_empty());
+
+    // Outside of any function.
+    let result = type_of(Position::new(1, 0), "42u64")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.type_name, None);
+    assert_eq!(result.errors.len(), 1);
+    shutdown_and_exit(&mut service).await;
+}
+
 lsp_capability_test!(
     highlight,
     lsp::highlight_request,
@@ -1825,6 +3668,46 @@ lsp_capability_test!(
     code_actions::code_action_auto_import_alias_request,
     test_fixtures_dir().join("auto_import/src/main.sw")
 );
+lsp_capability_test!(
+    code_action_make_mutable,
+    code_actions::code_action_make_mutable_request,
+    test_fixtures_dir().join("diagnostics/immutable_assignment/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_create_function,
+    code_actions::code_action_create_function_request,
+    test_fixtures_dir().join("diagnostics/unknown_function/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_remove_storage_field,
+    code_actions::code_action_remove_storage_field_request,
+    test_fixtures_dir().join("diagnostics/unused_storage/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_remove_unused_variable,
+    code_actions::code_action_remove_unused_variable_request,
+    test_fixtures_dir().join("diagnostics/unused_variable/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_fix_all,
+    code_actions::code_action_fix_all_request,
+    test_fixtures_dir().join("diagnostics/unused_variable/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_organize_imports,
+    code_actions::code_action_organize_imports_request,
+    test_fixtures_dir().join("organize_imports/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_fully_qualify,
+    code_actions::code_action_fully_qualify_request,
+    test_fixtures_dir().join("organize_imports/src/main.sw")
+);
+lsp_capability_test!(
+    code_action_go_to_related,
+    code_actions::code_action_go_to_related_request,
+    test_fixtures_dir().join("diagnostics/duplicate_definition/src/main.sw")
+);
 lsp_capability_test!(
     code_lens,
     lsp::code_lens_request,
@@ -1,8 +1,9 @@
 use crate::{ModuleId, SourceId};
 use std::{
     collections::{BTreeSet, HashMap},
-    path::PathBuf,
-    sync::RwLock,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
 };
 
 /// The Source Engine manages a relationship between file paths and their corresponding
@@ -22,6 +23,7 @@ pub struct SourceEngine {
     next_module_id: RwLock<u16>,
     path_to_module_map: RwLock<HashMap<PathBuf, ModuleId>>,
     module_to_sources_map: RwLock<HashMap<ModuleId, BTreeSet<SourceId>>>,
+    source_overrides: RwLock<HashMap<PathBuf, Arc<str>>>,
 }
 
 impl Clone for SourceEngine {
@@ -33,6 +35,7 @@ impl Clone for SourceEngine {
             next_module_id: RwLock::new(*self.next_module_id.read().unwrap()),
             path_to_module_map: RwLock::new(self.path_to_module_map.read().unwrap().clone()),
             module_to_sources_map: RwLock::new(self.module_to_sources_map.read().unwrap().clone()),
+            source_overrides: RwLock::new(self.source_overrides.read().unwrap().clone()),
         }
     }
 }
@@ -87,6 +90,11 @@ impl SourceEngine {
             .clone()
     }
 
+    /// This function provides the number of source files that have been assigned an ID.
+    pub fn source_count(&self) -> usize {
+        self.source_to_path_map.read().unwrap().len()
+    }
+
     /// This function provides the module ID corresponding to a specified file path.
     pub fn get_module_id(&self, path: &PathBuf) -> Option<ModuleId> {
         self.path_to_module_map.read().unwrap().get(path).cloned()
@@ -100,4 +108,29 @@ impl SourceEngine {
             .map(|file_name| file_name.to_string_lossy())
             .map(|file_name| file_name.to_string())
     }
+
+    /// This function makes [SourceEngine::read_source] return `src` for the file at `path`
+    /// instead of the file's contents on disk, e.g. to compile the unsaved contents of an editor.
+    pub fn set_source_override(&self, path: PathBuf, src: Arc<str>) {
+        self.source_overrides.write().unwrap().insert(path, src);
+    }
+
+    /// This function removes all of the overrides that were set with [SourceEngine::set_source_override].
+    pub fn clear_source_overrides(&self) {
+        self.source_overrides.write().unwrap().clear();
+    }
+
+    /// This function returns whether the contents of the file at `path` are overridden.
+    pub fn has_source_override(&self, path: &Path) -> bool {
+        self.source_overrides.read().unwrap().contains_key(path)
+    }
+
+    /// This function provides the source code of the file at `path`, which is its override
+    /// if one is set, or its contents on disk otherwise.
+    pub fn read_source(&self, path: &Path) -> io::Result<Arc<str>> {
+        if let Some(src) = self.source_overrides.read().unwrap().get(path) {
+            return Ok(src.clone());
+        }
+        std::fs::read_to_string(path).map(Arc::from)
+    }
 }